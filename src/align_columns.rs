@@ -1,4 +1,15 @@
 // Implements http://rosettacode.org/wiki/Align_columns
+//
+// Besides the `$`-separated input of the task, the aligner reads quoted CSV and TSV, pads cells
+// by their display width (so CJK text and combining marks line up) and renders the result as a
+// plain, box-drawing or Markdown table. Cells may span several lines, and long columns can be
+// wrapped with the line breaking of `word_wrap`.
+use std::cmp;
+use std::fmt;
+
+use word_wrap::{display_width, fill, Options};
+mod word_wrap;
+
 const TEST_STR: &'static str =
     "Given$a$text$file$of$many$lines,$where$fields$within$a$line$\nare$delineated\
     $by$a$single$'dollar'$character,$write$a$program\nthat$aligns$each$column$of\
@@ -6,79 +17,459 @@ const TEST_STR: &'static str =
     $space.\nFurther,$allow$for$each$word$in$a$column$to$be$either$left$\n\
     justified,$right$justified,$or$center$justified$within$its$column.\n";
 
-#[cfg(not(test))]
-fn main() {
-    let (chunks, max_lengths) = align_columns(TEST_STR);
-    print_aligned_columns(&chunks, &max_lengths);
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Alignment {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Style {
+    // Columns separated by a single space
+    Plain,
+    // Unicode box-drawing borders
+    Box,
+    // GitHub flavoured Markdown, the first row is the header
+    Markdown,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum ParseError {
+    // A quoted field was still open at the end of the input, started on the given line
+    UnterminatedQuote(usize),
+    // Something other than a delimiter or a newline followed a closing quote
+    TextAfterQuote(usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::UnterminatedQuote(line) =>
+                write!(f, "unterminated quoted field starting on line {}", line),
+            ParseError::TextAfterQuote(line) =>
+                write!(f, "unexpected text after a closing quote on line {}", line),
+        }
+    }
+}
+
+// Splits the input of the original task: one row per line, fields separated by `$`.
+pub fn parse_dollar(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(|line| line.split('$').map(|s| s.to_string()).collect())
+        .collect()
+}
+
+// Splits tab separated values. TSV has no quoting, a tab inside a field cannot be represented.
+pub fn parse_tsv(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(|line| line.split('\t').map(|s| s.to_string()).collect())
+        .collect()
 }
 
-fn align_columns(text: &str) -> (Vec<Vec<String>>, Vec<usize>) {
-    let lines: Vec<String> = text.split('\n').map(|s| s.to_string()).collect();
-    let mut max_lengths: Vec<usize> = Vec::new();
-    let mut chunks: Vec<Vec<String>> = Vec::new();
+// Parses RFC 4180 style CSV: fields may be enclosed in double quotes, in which case they can
+// contain the delimiter, line breaks and doubled quotes (`""` stands for a single `"`).
+pub fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, ParseError> {
+    parse_delimited(text, ',')
+}
 
-    for i in (0..lines.len()) {
-        let ref input = lines[i];
-        let split_input: Vec<String> = input[..].split('$').map(|s| s.to_string()).collect();
-        chunks.push(split_input.clone());
-        let v: Vec<usize> = split_input.iter().map(|chunk| chunk.len() ).collect();
+pub fn parse_delimited(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, ParseError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    // `quoted` is set while inside a quoted field, `was_quoted` once it has been closed
+    let mut quoted = false;
+    let mut was_quoted = false;
+    let mut quote_line = 0;
 
-        for i in (0..v.len()) {
-            if i < max_lengths.len() {
-                max_lengths[i] = std::cmp::max(max_lengths[i], v[i]);
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                    was_quoted = true;
+                }
             } else {
-                max_lengths.push(v[i]);
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+            continue;
+        }
+
+        if c == delimiter {
+            row.push(field);
+            field = String::new();
+            was_quoted = false;
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
             }
+            row.push(field);
+            rows.push(row);
+            row = Vec::new();
+            field = String::new();
+            was_quoted = false;
+            line += 1;
+        } else if was_quoted {
+            return Err(ParseError::TextAfterQuote(line));
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+            quote_line = line;
+        } else {
+            field.push(c);
         }
     }
 
-    (chunks, max_lengths)
+    if quoted {
+        return Err(ParseError::UnterminatedQuote(quote_line));
+    }
+    // A final line without a trailing newline
+    if !field.is_empty() || was_quoted || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+// Width of the widest line of a cell
+fn cell_width(cell: &str) -> usize {
+    cell.lines().map(display_width).max().unwrap_or(0)
 }
 
-fn print_aligned_columns(chunks: &Vec<Vec<String>>, max_lengths: &Vec<usize>) {
-    // left aligned
-    for i in (0..chunks.len()) {
-        for j in (0..chunks[i].len()) {
-            print!("{0:<1$}", chunks[i][j], 1 + max_lengths[j]);
+// Pads `s` with spaces so that it is `width` columns wide.
+pub fn pad(s: &str, width: usize, alignment: Alignment) -> String {
+    let spaces = width - cmp::min(width, display_width(s));
+    let (left, right) = match alignment {
+        Alignment::Left => (0, spaces),
+        Alignment::Right => (spaces, 0),
+        Alignment::Center => (spaces / 2, spaces - spaces / 2),
+    };
+    let mut padded = String::with_capacity(s.len() + spaces);
+    for _ in 0..left {
+        padded.push(' ');
+    }
+    padded.push_str(s);
+    for _ in 0..right {
+        padded.push(' ');
+    }
+    padded
+}
+
+pub struct Table {
+    rows: Vec<Vec<String>>,
+    widths: Vec<usize>,
+    alignments: Vec<Alignment>,
+    header: bool,
+}
+
+impl Table {
+    // Builds a table from rows of cells. Short rows are padded with empty cells so that every
+    // row has as many cells as the longest one.
+    pub fn new(mut rows: Vec<Vec<String>>) -> Table {
+        let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        for row in rows.iter_mut() {
+            while row.len() < columns {
+                row.push(String::new());
+            }
         }
-        println!("");
+        let mut table = Table {
+            rows: rows,
+            widths: vec![0; columns],
+            alignments: vec![Alignment::Left; columns],
+            header: false,
+        };
+        table.update_widths();
+        table
     }
-    println!("");
-    // right aligned
-    for i in (0..chunks.len()) {
-        for j in (0..chunks[i].len()) {
-            print!("{0:>1$}", chunks[i][j], 1 + max_lengths[j]);
+
+    fn update_widths(&mut self) {
+        for width in self.widths.iter_mut() {
+            *width = 0;
         }
-        println!("");
-    }
-    println!("");
-    // center aligned
-    for i in (0..chunks.len()) {
-        for j in (0..chunks[i].len()) {
-            let ref string: String = chunks[i][j];
-            let spaces: usize = 1 + max_lengths[j] - string.len();
-            for _ in (0..spaces>>1) {
-                print!(" ");
+        for row in &self.rows {
+            for (width, cell) in self.widths.iter_mut().zip(row.iter()) {
+                *width = cmp::max(*width, cell_width(cell));
             }
-            print!("{}", string);
-            for _ in (0..spaces - (spaces>>1)) {
-                print!(" ");
+        }
+    }
+
+    // Treats the first row as a header: box tables draw a rule beneath it. Markdown tables
+    // always have a header.
+    pub fn with_header(mut self) -> Table {
+        self.header = true;
+        self
+    }
+
+    // Sets the alignment of a single column, out of range columns are ignored.
+    pub fn align(mut self, column: usize, alignment: Alignment) -> Table {
+        if column < self.alignments.len() {
+            self.alignments[column] = alignment;
+        }
+        self
+    }
+
+    // Sets the same alignment for every column.
+    pub fn align_all(mut self, alignment: Alignment) -> Table {
+        for a in self.alignments.iter_mut() {
+            *a = alignment;
+        }
+        self
+    }
+
+    // Word wraps every cell of a column to at most `width` columns. Words longer than that are
+    // left whole, so the column can still end up wider.
+    pub fn wrap_column(mut self, column: usize, width: usize) -> Table {
+        let options = Options::new(width);
+        for row in self.rows.iter_mut() {
+            if column < row.len() {
+                row[column] = fill(&row[column], &options);
             }
         }
-        println!("");
+        self.update_widths();
+        self
+    }
+
+    pub fn widths(&self) -> &[usize] {
+        &self.widths
+    }
+
+    pub fn rows(&self) -> &[Vec<String>] {
+        &self.rows
+    }
+
+    pub fn render(&self, style: Style) -> String {
+        match style {
+            Style::Plain => self.render_plain(),
+            Style::Box => self.render_box(),
+            Style::Markdown => self.render_markdown(),
+        }
     }
+
+    // Pads the cells of a row, which takes as many lines as its tallest cell. Returns the padded
+    // cells of each line.
+    fn padded_lines(&self, row: &[String]) -> Vec<Vec<String>> {
+        let cells: Vec<Vec<&str>> = row.iter().map(|cell| cell.lines().collect()).collect();
+        let height = cells.iter().map(|lines| lines.len()).max().unwrap_or(0);
+        (0..cmp::max(height, 1)).map(|k| {
+            cells.iter()
+                 .zip(self.widths.iter().zip(self.alignments.iter()))
+                 .map(|(lines, (&width, &alignment))| {
+                     pad(lines.get(k).map_or("", |line| *line), width, alignment)
+                 })
+                 .collect()
+        }).collect()
+    }
+
+    fn render_plain(&self) -> String {
+        let mut out = String::new();
+        for row in &self.rows {
+            for cells in self.padded_lines(row) {
+                out.push_str(cells.connect(" ").trim_right());
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    fn render_box(&self) -> String {
+        let rule = |left: &str, middle: &str, right: &str| {
+            let segments: Vec<String> = self.widths.iter()
+                .map(|&w| ::std::iter::repeat("─").take(w + 2).collect())
+                .collect();
+            format!("{}{}{}\n", left, segments.connect(middle), right)
+        };
+
+        let mut out = rule("┌", "┬", "┐");
+        for (i, row) in self.rows.iter().enumerate() {
+            for cells in self.padded_lines(row) {
+                out.push_str(&format!("│ {} │\n", cells.connect(" │ ")));
+            }
+            if i == 0 && self.header && self.rows.len() > 1 {
+                out.push_str(&rule("├", "┼", "┤"));
+            }
+        }
+        out.push_str(&rule("└", "┴", "┘"));
+        out
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = String::new();
+        let escape = |row: &[String]| -> Vec<String> {
+            row.iter().map(|cell| cell.replace("|", "\\|").replace("\n", "<br>")).collect()
+        };
+        // Widths have to be recomputed since escaping changes the cell contents, and a separator
+        // needs at least three dashes
+        let rows: Vec<Vec<String>> = self.rows.iter().map(|row| escape(row)).collect();
+        let mut widths = vec![3; self.widths.len()];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = cmp::max(*width, display_width(cell));
+            }
+        }
+
+        let render_row = |row: &[String]| -> String {
+            let cells: Vec<String> = row.iter()
+                .zip(widths.iter().zip(self.alignments.iter()))
+                .map(|(cell, (&width, &alignment))| pad(cell, width, alignment))
+                .collect();
+            format!("| {} |\n", cells.connect(" | "))
+        };
+
+        for (i, row) in rows.iter().enumerate() {
+            out.push_str(&render_row(row));
+            if i == 0 {
+                let separators: Vec<String> = widths.iter().zip(self.alignments.iter())
+                    .map(|(&width, &alignment)| {
+                        let dashes: String = ::std::iter::repeat('-').take(width - 2).collect();
+                        match alignment {
+                            Alignment::Left => format!(":{}-", dashes),
+                            Alignment::Right => format!("-{}:", dashes),
+                            Alignment::Center => format!(":{}:", dashes),
+                        }
+                    })
+                    .collect();
+                out.push_str(&format!("| {} |\n", separators.connect(" | ")));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let table = Table::new(parse_dollar(TEST_STR));
+    for &alignment in &[Alignment::Left, Alignment::Right, Alignment::Center] {
+        let table = Table::new(table.rows().to_vec()).align_all(alignment);
+        println!("{}", table.render(Style::Plain));
+    }
+
+    let csv = "city,country,population\n\
+               \"Tokyo, 東京\",Japan,13960000\n\
+               \"São Paulo\",Brazil,12330000\n\
+               Zürich,Switzerland,415367\n";
+    let table = Table::new(parse_csv(csv).unwrap())
+        .with_header()
+        .align(2, Alignment::Right);
+    println!("{}", table.render(Style::Box));
+    println!("{}", table.render(Style::Markdown));
+
+    let rows = vec![vec!["task".to_string(), "description".to_string()],
+                    vec!["Align columns".to_string(), TEST_STR.replace("$", " ")]];
+    let table = Table::new(rows).with_header().wrap_column(1, 40);
+    println!("{}", table.render(Style::Box));
 }
 
-#[test]
-fn test_result() {
-    let (chunks, max_lengths) = align_columns(TEST_STR);
-    for chunkset in &chunks {
-        // the number of words in a chunkset is <= the number of values in max_lengths
-        assert!(chunkset.len() <= max_lengths.len());
-        for j in (0..chunkset.len()) {
-            // a word in a chunkset cannot be longer than max_lengths
-            assert!(chunkset[j].len() <= max_lengths[j]);
+#[cfg(test)]
+mod test {
+    use super::{TEST_STR, Alignment, ParseError, Style, Table};
+    use super::{pad, parse_csv, parse_dollar, parse_tsv};
+    use word_wrap::display_width;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|s| s.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn test_result() {
+        let table = Table::new(parse_dollar(TEST_STR));
+        for row in table.rows() {
+            // every row has been padded to the same number of cells
+            assert_eq!(row.len(), table.widths().len());
+            for (cell, &width) in row.iter().zip(table.widths().iter()) {
+                // a word in a column cannot be wider than the column
+                assert!(display_width(cell) <= width);
+            }
         }
+        let rendered = table.render(Style::Plain);
+        assert!(rendered.starts_with("Given      a          text       file"));
+    }
+
+    #[test]
+    fn test_csv_quotes() {
+        let parsed = parse_csv("a,\"b,c\",\"say \"\"hi\"\"\"\n\"multi\nline\",,x").unwrap();
+        assert_eq!(parsed, rows(&[&["a", "b,c", "say \"hi\""], &["multi\nline", "", "x"]]));
+    }
+
+    #[test]
+    fn test_csv_errors() {
+        assert_eq!(parse_csv("a,b\nc,\"d"), Err(ParseError::UnterminatedQuote(2)));
+        assert_eq!(parse_csv("\"a\"b,c"), Err(ParseError::TextAfterQuote(1)));
+    }
+
+    #[test]
+    fn test_csv_crlf() {
+        assert_eq!(parse_csv("a,b\r\nc,d\r\n").unwrap(), rows(&[&["a", "b"], &["c", "d"]]));
+    }
+
+    #[test]
+    fn test_tsv() {
+        assert_eq!(parse_tsv("a\t\"b\"\nc"), rows(&[&["a", "\"b\""], &["c"]]));
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("東京"), 4);
+        // 'e' followed by a combining acute accent
+        assert_eq!(display_width("e\u{301}t\u{301}e\u{301}"), 3);
+        assert_eq!(display_width("Zürich"), 6);
+    }
+
+    #[test]
+    fn test_pad() {
+        assert_eq!(pad("ab", 6, Alignment::Left), "ab    ");
+        assert_eq!(pad("ab", 6, Alignment::Right), "    ab");
+        assert_eq!(pad("ab", 5, Alignment::Center), " ab  ");
+        assert_eq!(pad("東", 4, Alignment::Right), "  東");
+    }
+
+    #[test]
+    fn test_plain_unicode() {
+        let table = Table::new(rows(&[&["東京", "x"], &["ab", "y"]]));
+        assert_eq!(table.render(Style::Plain), "東京 x\nab   y\n");
+    }
+
+    #[test]
+    fn test_box() {
+        let table = Table::new(rows(&[&["id", "name"], &["1", "東"]]))
+            .with_header()
+            .align(0, Alignment::Right);
+        assert_eq!(table.render(Style::Box),
+                   "┌────┬──────┐\n\
+                    │ id │ name │\n\
+                    ├────┼──────┤\n\
+                    │  1 │ 東   │\n\
+                    └────┴──────┘\n");
+    }
+
+    #[test]
+    fn test_wrap_column() {
+        let table = Table::new(rows(&[&["1", "a few words to wrap"], &["2", "short"]]))
+            .wrap_column(1, 8)
+            .align(0, Alignment::Right);
+        assert_eq!(table.widths(), &[1, 8]);
+        assert_eq!(table.render(Style::Plain), "1 a few\n  words to\n  wrap\n2 short\n");
+        assert_eq!(table.render(Style::Markdown),
+                   "|   1 | a few<br>words to<br>wrap |\n\
+                    | --: | :------------------------ |\n\
+                    |   2 | short                     |\n");
+    }
+
+    #[test]
+    fn test_markdown() {
+        let table = Table::new(rows(&[&["a", "b", "c"], &["x|y", "long cell", "z"]]))
+            .align(1, Alignment::Center)
+            .align(2, Alignment::Right);
+        assert_eq!(table.render(Style::Markdown),
+                   "| a    |     b     |   c |\n\
+                    | :--- | :-------: | --: |\n\
+                    | x\\|y | long cell |   z |\n");
     }
-    print_aligned_columns(&chunks, &max_lengths);
 }
@@ -1,6 +1,12 @@
 // Implements http://rosettacode.org/wiki/Anagrams
+// and http://rosettacode.org/wiki/Anagrams/Deranged_anagrams
+//
+// `WordIndex` answers the queries we use for puzzle generation over a word list: multi-word
+// anagrams of a phrase, deranged anagrams, words that can be built from a rack of letters and
+// wildcard patterns.
 #[cfg(not(test))] use std::fs::File;
 #[cfg(not(test))] use std::io::{BufReader, BufRead};
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 
@@ -44,6 +50,211 @@ fn largest_groups(groups: &HashMap<String, HashSet<String>>)
     }).collect()
 }
 
+// Number of occurrences of each letter from a to z
+type Letters = [u8; 26];
+
+// Counts the letters of a word, `None` if it has anything but lowercase ASCII letters
+fn letter_counts(word: &str) -> Option<Letters> {
+    let mut counts = [0; 26];
+    for b in word.bytes() {
+        if b < b'a' || b > b'z' {
+            return None;
+        }
+        counts[(b - b'a') as usize] += 1;
+    }
+    Some(counts)
+}
+
+// Whether the letters of `part` can all be taken from `whole`
+fn fits(part: &Letters, whole: &Letters) -> bool {
+    part.iter().zip(whole.iter()).all(|(p, w)| p <= w)
+}
+
+// Orders longer strings first, and strings of the same length alphabetically
+fn longest_first(a: &str, b: &str) -> Ordering {
+    match b.len().cmp(&a.len()) {
+        Ordering::Equal => a.cmp(b),
+        ordering => ordering,
+    }
+}
+
+fn is_deranged(a: &str, b: &str) -> bool {
+    a.bytes().zip(b.bytes()).all(|(x, y)| x != y)
+}
+
+// Matches `?` against any single character and `*` against any sequence of characters
+fn matches_pattern(pattern: &[char], word: &[char]) -> bool {
+    match pattern.first() {
+        None => word.is_empty(),
+        Some(&'*') => (0..word.len() + 1).any(|i| matches_pattern(&pattern[1..], &word[i..])),
+        Some(&p) => match word.first() {
+            Some(&c) if p == '?' || p == c => matches_pattern(&pattern[1..], &word[1..]),
+            _ => false,
+        },
+    }
+}
+
+pub struct WordIndex {
+    words: Vec<String>,
+    // Indices of the words sharing the same sorted letters
+    groups: HashMap<String, Vec<usize>>,
+    // Distinct letter sets of the words made of letters only, with their sorted key
+    letter_sets: Vec<(Letters, usize, String)>,
+}
+
+impl WordIndex {
+    pub fn new<T: Iterator<Item=String>>(lines: T) -> WordIndex {
+        let mut words = Vec::new();
+        let mut groups = HashMap::new();
+        let mut letter_sets = Vec::new();
+        for line in lines {
+            let word = line.trim().to_string();
+            if word.is_empty() {
+                continue;
+            }
+            let sorted = sorted_characters(&word);
+            let group = match groups.entry(sorted.clone()) {
+                Vacant(entry) => {
+                    if let Some(counts) = letter_counts(&word) {
+                        letter_sets.push((counts, word.len(), sorted));
+                    }
+                    entry.insert(Vec::new())
+                }
+                Occupied(entry) => entry.into_mut(),
+            };
+            group.push(words.len());
+            words.push(word);
+        }
+        // Longest words first, they use up a phrase the quickest
+        letter_sets.sort_by(|a, b| longest_first(&a.2, &b.2));
+        WordIndex { words: words, groups: groups, letter_sets: letter_sets }
+    }
+
+    fn group(&self, sorted: &str) -> Vec<&str> {
+        self.groups[sorted].iter().map(|&i| &self.words[i][..]).collect()
+    }
+
+    // Pairs of anagrams with no letter in the same position, the longest first
+    pub fn deranged_anagrams(&self) -> Vec<(&str, &str)> {
+        let mut pairs = Vec::new();
+        for group in self.groups.values() {
+            for (n, &i) in group.iter().enumerate() {
+                for &j in &group[n + 1..] {
+                    let (a, b) = (&self.words[i][..], &self.words[j][..]);
+                    if is_deranged(a, b) {
+                        pairs.push((a, b));
+                    }
+                }
+            }
+        }
+        pairs.sort_by(|a, b| match longest_first(a.0, b.0) {
+            Ordering::Equal => a.1.cmp(b.1),
+            ordering => ordering,
+        });
+        pairs
+    }
+
+    // Words that can be spelled with the letters of the rack, each `?` being a blank that stands
+    // for any letter. Longest words first.
+    pub fn from_rack(&self, rack: &str) -> Vec<&str> {
+        let blanks = rack.chars().filter(|&c| c == '?').count();
+        let letters: String = rack.chars().filter(|&c| c != '?').collect();
+        let rack = match letter_counts(&letters.to_lowercase()) {
+            Some(rack) => rack,
+            None => return Vec::new(),
+        };
+        let mut found = Vec::new();
+        for &(ref counts, _, ref sorted) in &self.letter_sets {
+            let missing = counts.iter().zip(rack.iter())
+                                .fold(0, |acc, (&c, &r)| acc + if c > r { (c - r) as usize }
+                                                               else { 0 });
+            if missing <= blanks {
+                found.extend(self.group(sorted).into_iter());
+            }
+        }
+        found.sort_by(|a, b| longest_first(a, b));
+        found
+    }
+
+    // Words matching a wildcard pattern, in the order of the word list
+    pub fn matching(&self, pattern: &str) -> Vec<&str> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        self.words.iter()
+            .filter(|word| matches_pattern(&pattern, &word.chars().collect::<Vec<char>>()))
+            .map(|word| &word[..])
+            .collect()
+    }
+
+    // Combinations of at most `max_words` words of at least `min_len` letters using exactly the
+    // letters of the phrase, ignoring anything but letters. Returns up to `limit` of them.
+    pub fn multi_word_anagrams(&self, phrase: &str, max_words: usize, min_len: usize,
+                               limit: usize) -> Vec<Vec<&str>> {
+        let letters: String = phrase.to_lowercase().chars().filter(|c| c.is_alphabetic()).collect();
+        let target = match letter_counts(&letters) {
+            Some(target) => target,
+            None => return Vec::new(),
+        };
+        let candidates: Vec<usize> = (0..self.letter_sets.len())
+            .filter(|&i| self.letter_sets[i].1 >= min_len && fits(&self.letter_sets[i].0, &target))
+            .collect();
+
+        let mut combinations = Vec::new();
+        self.search(&candidates, 0, target, letters.len(), max_words, &mut Vec::new(),
+                    &mut combinations, limit);
+
+        // Every letter set may stand for several words
+        let mut results = Vec::new();
+        for combination in combinations {
+            let mut partial: Vec<Vec<&str>> = vec![Vec::new()];
+            for &set in &combination {
+                let words = self.group(&self.letter_sets[set].2);
+                partial = partial.iter().flat_map(|prefix| words.iter().map(move |word| {
+                    let mut extended = prefix.clone();
+                    extended.push(*word);
+                    extended
+                })).collect();
+            }
+            for result in partial {
+                if results.len() == limit {
+                    return results;
+                }
+                results.push(result);
+            }
+        }
+        results
+    }
+
+    // Depth first search over the candidate letter sets, in order, so that each combination is
+    // only found once
+    fn search(&self, candidates: &[usize], start: usize, remaining: Letters, left: usize,
+              max_words: usize, chosen: &mut Vec<usize>, found: &mut Vec<Vec<usize>>,
+              limit: usize) {
+        if left == 0 {
+            found.push(chosen.clone());
+            return;
+        }
+        if chosen.len() == max_words {
+            return;
+        }
+        for (n, &i) in candidates.iter().enumerate().skip(start) {
+            if found.len() == limit {
+                return;
+            }
+            let (ref counts, length, _) = self.letter_sets[i];
+            if length > left || !fits(counts, &remaining) {
+                continue;
+            }
+            let mut rest = remaining;
+            for (r, c) in rest.iter_mut().zip(counts.iter()) {
+                *r -= *c;
+            }
+            chosen.push(i);
+            self.search(candidates, n, rest, left - length, max_words, chosen, found, limit);
+            chosen.pop();
+        }
+    }
+}
+
 #[cfg(not(test))]
 fn main () {
     let reader = BufReader::new(File::open("src/resources/unixdict.txt").unwrap());
@@ -59,6 +270,18 @@ fn main () {
         }
         println!("")
     }
+
+    let reader = BufReader::new(File::open("src/resources/unixdict.txt").unwrap());
+    let index = WordIndex::new(reader.lines().map(|l| l.unwrap()));
+    let (a, b) = index.deranged_anagrams()[0];
+    println!("\nLongest deranged anagrams: {} {}", a, b);
+    println!("Longest words from the rack \"retains?\": {:?}",
+             &index.from_rack("retains?")[..5]);
+    println!("Words matching \"*zz?e\": {:?}", index.matching("*zz?e"));
+    println!("Two word anagrams of \"rosetta code\":");
+    for words in index.multi_word_anagrams("rosetta code", 2, 3, 10) {
+        println!("  {}", words.connect(" "));
+    }
 }
 
 #[test]
@@ -94,3 +317,57 @@ fn basic_test() {
     assert_has_value(largest_groups, group2);
     assert!(largest_groups.values().all(|group| group != group3));
 }
+
+#[cfg(test)]
+fn small_index() -> WordIndex {
+    let words = ["listen", "silent", "enlist", "tinsel", "inlets", "google", "dog", "god",
+                 "act", "cat", "tac", "a", "i", "in", "it", "cart", "trace", "crate", "o'neil",
+                 "excitation", "intoxicate"];
+    WordIndex::new(words.iter().map(|w| w.to_string()))
+}
+
+#[test]
+fn test_deranged_anagrams() {
+    let index = small_index();
+    let pairs = index.deranged_anagrams();
+    assert_eq!(pairs[0], ("excitation", "intoxicate"));
+    assert!(pairs.contains(&("listen", "enlist")));
+    // "listen" and "silent" share the 'i'
+    assert!(!pairs.contains(&("listen", "silent")));
+    // "crate" and "trace" share the 'e' at the end
+    assert!(!pairs.iter().any(|&p| p == ("crate", "trace") || p == ("trace", "crate")));
+    assert!(pairs.iter().all(|&(a, b)| a.bytes().zip(b.bytes()).all(|(x, y)| x != y)));
+}
+
+#[test]
+fn test_from_rack() {
+    let index = small_index();
+    assert_eq!(index.from_rack("tacr"), vec!["cart", "act", "cat", "tac", "a"]);
+    // The blank stands for the missing 'e'
+    assert_eq!(&index.from_rack("ract?")[..3], &["crate", "trace", "cart"]);
+    assert_eq!(index.from_rack("1"), Vec::<&str>::new());
+}
+
+#[test]
+fn test_matching() {
+    let index = small_index();
+    assert_eq!(index.matching("?og"), vec!["dog"]);
+    assert_eq!(index.matching("*ent"), vec!["silent"]);
+    assert_eq!(index.matching("c*t"), vec!["cat", "cart"]);
+    assert_eq!(index.matching("*'*"), vec!["o'neil"]);
+}
+
+#[test]
+fn test_multi_word_anagrams() {
+    let index = small_index();
+    let mut found = index.multi_word_anagrams("Cat, god!", 2, 3, 100);
+    for words in found.iter_mut() {
+        words.sort();
+    }
+    found.sort();
+    assert_eq!(found.len(), 6);
+    assert_eq!(found[0], vec!["act", "dog"]);
+    assert!(index.multi_word_anagrams("catgod", 1, 1, 100).is_empty());
+    assert_eq!(index.multi_word_anagrams("cat god", 2, 3, 4).len(), 4);
+    assert_eq!(index.multi_word_anagrams("ai", 2, 1, 10), vec![vec!["a", "i"]]);
+}
@@ -1,10 +1,31 @@
 // Implements http://rosettacode.org/wiki/Basic_bitmap_storage
+// and the drawing and transforming tasks built on it:
+// http://rosettacode.org/wiki/Bitmap/Bresenham%27s_line_algorithm
+// http://rosettacode.org/wiki/Xiaolin_Wu%27s_line_algorithm
+// http://rosettacode.org/wiki/Bitmap/Midpoint_circle_algorithm
+// http://rosettacode.org/wiki/Bitmap/B%C3%A9zier_curves/Quadratic
+// http://rosettacode.org/wiki/Bitmap/B%C3%A9zier_curves/Cubic
+// http://rosettacode.org/wiki/Bitmap/Flood_fill
+// http://rosettacode.org/wiki/Bilinear_interpolation
 
-
+use std::collections::{HashMap, VecDeque};
 use std::default::Default;
-use std::io::{Write, BufWriter, Error};
-use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
 use std::ops::{Index, IndexMut};
+use self::netpbm::{Netpbm, NetpbmError, Kind, Encoding};
+use self::png::{Png, PngError, ColorType, Compression};
+use self::gif::GifWriter;
+use self::processing::GrayImage;
+
+// Reading and writing images; path attributes let every program using this module get them too
+#[path = "netpbm.rs"]
+pub mod netpbm;
+#[path = "png.rs"]
+pub mod png;
+#[path = "gif.rs"]
+pub mod gif;
+#[path = "image_processing.rs"]
+pub mod processing;
 
 #[derive(Copy, Clone, Default, PartialEq, Debug)]
 pub struct Color {
@@ -35,17 +56,704 @@ impl Image {
         }
     }
 
+    /// Writes the image as a raw (P6) PPM file.
     pub fn write_ppm(&self, filename: &str) -> Result<(), Error> {
-        let file = try!(File::create(filename));
-        let mut writer = BufWriter::new(file);
-        try!(writeln!(&mut writer,"P6"));
-        try!(write!(&mut writer, "{} {} {}\n", self.width, self.height, 255));
-        for color in &(self.data) {
-            for channel in &[color.red, color.green, color.blue] {
-                try!(write!(&mut writer, "{}", *channel as u8));
+        self.to_netpbm().save(filename, Encoding::Raw)
+    }
+
+    /// Reads any PBM, PGM or PPM file.
+    pub fn read_ppm(filename: &str) -> Result<Image, NetpbmError> {
+        Netpbm::load(filename).map(|image| Image::from_netpbm(&image))
+    }
+
+    /// Writes the image as an RGB PNG file.
+    pub fn write_png(&self, filename: &str) -> Result<(), Error> {
+        self.to_png().save(filename, Compression::Fixed)
+    }
+
+    /// Reads an 8-bit PNG file. Any alpha channel is dropped.
+    pub fn read_png(filename: &str) -> Result<Image, PngError> {
+        Png::load(filename).map(|image| Image::from_png(&image))
+    }
+
+    /// Adds the image to an animated GIF. It may have at most 256 colors.
+    pub fn write_gif_frame<W: Write>(&self, gif: &mut GifWriter<W>) -> Result<(), Error> {
+        let (palette, indices) = try!(self.to_indexed());
+        gif.write_frame(&palette, &indices)
+    }
+
+    /// The image's colors and, for each pixel, its color's index.
+    pub fn to_indexed(&self) -> Result<(Vec<[u8; 3]>, Vec<u8>), Error> {
+        let mut palette = Vec::new();
+        let mut lookup = HashMap::new();
+        let mut indices = Vec::with_capacity(self.data.len());
+        for c in &self.data {
+            let rgb = [c.red, c.green, c.blue];
+            let index = match lookup.get(&rgb) {
+                Some(&index) => index,
+                None => {
+                    if palette.len() == 256 {
+                        return Err(Error::new(ErrorKind::InvalidInput, "more than 256 colors"));
+                    }
+                    let index = palette.len() as u8;
+                    palette.push(rgb);
+                    lookup.insert(rgb, index);
+                    index
+                }
+            };
+            indices.push(index);
+        }
+        Ok((palette, indices))
+    }
+
+    pub fn to_png(&self) -> Png {
+        Png {
+            width: self.width,
+            height: self.height,
+            color: ColorType::Rgb,
+            pixels: self.data.iter().flat_map(|c| vec![c.red, c.green, c.blue]).collect(),
+        }
+    }
+
+    pub fn from_png(image: &Png) -> Image {
+        let channels = image.color.channels();
+        Image {
+            width: image.width,
+            height: image.height,
+            data: image.pixels.chunks(channels).map(|p| {
+                if channels < 3 {
+                    Color { red: p[0], green: p[0], blue: p[0] }
+                } else {
+                    Color { red: p[0], green: p[1], blue: p[2] }
+                }
+            }).collect(),
+        }
+    }
+
+    /// The brightness of each pixel, weighting the colors by their luminance (ITU-R BT.709).
+    pub fn to_gray(&self) -> GrayImage {
+        GrayImage {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(|c| {
+                (0.2126 * c.red as f64 + 0.7152 * c.green as f64 + 0.0722 * c.blue as f64)
+                    .round() as u8
+            }).collect(),
+        }
+    }
+
+    pub fn from_gray(image: &GrayImage) -> Image {
+        Image {
+            width: image.width,
+            height: image.height,
+            data: image.data.iter().map(|&v| Color { red: v, green: v, blue: v }).collect(),
+        }
+    }
+
+    /// The red, green and blue channels as separate images.
+    pub fn channels(&self) -> [GrayImage; 3] {
+        let channel = |f: &Fn(&Color) -> u8| GrayImage {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(|c| f(c)).collect(),
+        };
+        [channel(&|c| c.red), channel(&|c| c.green), channel(&|c| c.blue)]
+    }
+
+    pub fn from_channels(red: &GrayImage, green: &GrayImage, blue: &GrayImage) -> Image {
+        assert!(red.width == green.width && green.width == blue.width);
+        assert!(red.height == green.height && green.height == blue.height);
+        Image {
+            width: red.width,
+            height: red.height,
+            data: (0..red.data.len()).map(|i| {
+                Color { red: red.data[i], green: green.data[i], blue: blue.data[i] }
+            }).collect(),
+        }
+    }
+
+    /// Applies a grayscale operation, such as a blur, to each channel.
+    pub fn map_channels<F: Fn(&GrayImage) -> GrayImage>(&self, f: F) -> Image {
+        let channels = self.channels();
+        Image::from_channels(&f(&channels[0]), &f(&channels[1]), &f(&channels[2]))
+    }
+
+    /// The image as an 8-bit pixmap.
+    pub fn to_netpbm(&self) -> Netpbm {
+        let mut image = Netpbm::new(Kind::Pixmap, self.width, self.height, 255);
+        image.samples = self.data.iter()
+            .flat_map(|c| vec![c.red as u16, c.green as u16, c.blue as u16])
+            .collect();
+        image
+    }
+
+    /// Converts any Netpbm image, scaling its samples to 8 bits.
+    pub fn from_netpbm(image: &Netpbm) -> Image {
+        Image {
+            width: image.width,
+            height: image.height,
+            data: image.rgb8().into_iter()
+                .map(|(r, g, b)| Color { red: r, green: g, blue: b }).collect(),
+        }
+    }
+}
+
+impl Color {
+    /// This color with `over` laid on top of it at the given opacity, from 0 to 1.
+    pub fn blend(self, over: Color, alpha: f64) -> Color {
+        let alpha = alpha.max(0.0).min(1.0);
+        let mix = |under: u8, over: u8| {
+            (under as f64 + (over as f64 - under as f64) * alpha).round() as u8
+        };
+        Color {
+            red: mix(self.red, over.red),
+            green: mix(self.green, over.green),
+            blue: mix(self.blue, over.blue),
+        }
+    }
+}
+
+// Drawing. Shapes may extend past the edges of the image; only the pixels inside are drawn.
+impl Image {
+    /// Sets a pixel, if it lies inside the image.
+    pub fn plot(&mut self, x: i64, y: i64, color: Color) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self[(x as usize, y as usize)] = color;
+        }
+    }
+
+    /// Lays a color over a pixel at the given opacity, if the pixel lies inside the image.
+    pub fn blend_pixel(&mut self, x: i64, y: i64, color: Color, alpha: f64) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            let pixel = &mut self[(x as usize, y as usize)];
+            *pixel = pixel.blend(color, alpha);
+        }
+    }
+
+    /// A line by Bresenham's algorithm, including both ends. The line is clipped to the image
+    /// first, so a line reaching far outside costs no more than the pixels it draws.
+    pub fn draw_line(&mut self, from: (i64, i64), to: (i64, i64), color: Color) {
+        let (dx, dy) = ((to.0 - from.0).abs(), (to.1 - from.1).abs());
+        let (step_x, step_y) = (if from.0 < to.0 { 1 } else { -1 },
+                                if from.1 < to.1 { 1 } else { -1 });
+        let (width, height) = (self.width as i64, self.height as i64);
+        // Step k of the line moves k pixels along its longer axis, and (2 k short + long) /
+        // (2 long) along the shorter, which is where the error term of the usual formulation
+        // would have taken it. The products stay exact for lines shorter than about 2^31.
+        let x_major = dx >= dy;
+        let (long, short) = if x_major { (dx, dy) } else { (dy, dx) };
+        let ((major, major_step, major_size), (minor, minor_step, minor_size)) = if x_major {
+            ((from.0, step_x, width), (from.1, step_y, height))
+        } else {
+            ((from.1, step_y, height), (from.0, step_x, width))
+        };
+
+        // Clip the steps to those inside the image along the longer axis, then to those
+        // whose shift along the shorter axis keeps them inside
+        let (mut first, mut last) = steps_inside(major, major_step, major_size, long);
+        let (low, high) = steps_inside(minor, minor_step, minor_size, short);
+        if low > high {
+            return;
+        }
+        if short > 0 {
+            if low > 0 {
+                first = first.max((2 * long * low - long + 2 * short - 1) / (2 * short));
+            }
+            last = last.min((2 * long * (high + 1) - long - 1) / (2 * short));
+        }
+        for k in first..last + 1 {
+            let shift = if long == 0 { 0 } else { (2 * k * short + long) / (2 * long) };
+            let (a, b) = (major + major_step * k, minor + minor_step * shift);
+            if x_major {
+                self.plot(a, b, color);
+            } else {
+                self.plot(b, a, color);
+            }
+        }
+    }
+
+    /// An antialiased line by Xiaolin Wu's algorithm, blended over the image. Pixel centers
+    /// are at whole coordinates.
+    pub fn draw_line_antialiased(&mut self, from: (f64, f64), to: (f64, f64), color: Color) {
+        let ((mut x0, mut y0), (mut x1, mut y1)) = (from, to);
+        if !(x0.is_finite() && y0.is_finite() && x1.is_finite() && y1.is_finite()) {
+            return;
+        }
+        // Step along the longer axis, left to right
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            ::std::mem::swap(&mut x0, &mut y0);
+            ::std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            ::std::mem::swap(&mut x0, &mut x1);
+            ::std::mem::swap(&mut y0, &mut y1);
+        }
+        let gradient = if x1 == x0 { 1.0 } else { (y1 - y0) / (x1 - x0) };
+        let plot = |image: &mut Image, x: f64, y: f64, alpha: f64| {
+            if steep {
+                image.blend_pixel(y as i64, x as i64, color, alpha);
+            } else {
+                image.blend_pixel(x as i64, y as i64, color, alpha);
+            }
+        };
+        let fraction = |v: f64| v - v.floor();
+
+        // Each end covers part of its pixel column, by how far it is past the column's middle
+        let mut ends = [(0.0, 0.0); 2];
+        for (i, &(x, y)) in [(x0, y0), (x1, y1)].iter().enumerate() {
+            let x_end = (x + 0.5).floor();
+            let y_end = y + gradient * (x_end - x);
+            let gap = if i == 0 { 1.0 - fraction(x + 0.5) } else { fraction(x + 0.5) };
+            plot(self, x_end, y_end.floor(), (1.0 - fraction(y_end)) * gap);
+            plot(self, x_end, y_end.floor() + 1.0, fraction(y_end) * gap);
+            ends[i] = (x_end, y_end);
+        }
+
+        // Step k is at column start + k. Only the steps in columns of the image, and where the
+        // line is within a pixel of it, are walked, with a step to spare for rounding.
+        let (major_size, minor_size) = if steep {
+            (self.height as i64, self.width as f64)
+        } else {
+            (self.width as i64, self.height as f64)
+        };
+        let start = (ends[0].0 + 1.0, ends[0].1 + gradient);
+        let count = ends[1].0 - start.0;
+        if count < 1.0 {
+            return;
+        }
+        let (mut first, mut last) = steps_inside(start.0 as i64, 1, major_size, count as i64 - 1);
+        if gradient == 0.0 {
+            if start.1 < -1.0 || start.1 >= minor_size {
+                return;
+            }
+        } else {
+            let (a, b) = ((-1.0 - start.1) / gradient, (minor_size - start.1) / gradient);
+            first = first.max((a.min(b).floor() - 1.0).max(0.0).min(count) as i64);
+            last = last.min((a.max(b).ceil() + 1.0).max(-1.0).min(count) as i64);
+        }
+        for k in first..last + 1 {
+            let (x, y) = (start.0 + k as f64, start.1 + gradient * k as f64);
+            plot(self, x, y.floor(), 1.0 - fraction(y));
+            plot(self, x, y.floor() + 1.0, fraction(y));
+        }
+    }
+
+    /// A circle by the midpoint algorithm, which draws all eight octants from one.
+    pub fn draw_circle(&mut self, (cx, cy): (i64, i64), radius: i64, color: Color) {
+        let (mut x, mut y) = (0, radius);
+        // Whether the midpoint between the next two candidate pixels is outside the circle
+        let mut decision = 1 - radius;
+        while x <= y {
+            for &(dx, dy) in &[(x, y), (y, x), (-x, y), (-y, x), (x, -y), (y, -x), (-x, -y),
+                               (-y, -x)] {
+                self.plot(cx + dx, cy + dy, color);
+            }
+            x += 1;
+            if decision < 0 {
+                decision += 2 * x + 1;
+            } else {
+                y -= 1;
+                decision += 2 * (x - y) + 1;
+            }
+        }
+    }
+
+    // Joins points along a Bézier curve with lines, using enough of them that each covers a few
+    // pixels. `point` gives the curve with the control points `control`.
+    fn draw_curve<F: Fn(f64) -> (f64, f64)>(&mut self, control: &[(f64, f64)], point: F,
+                                            color: Color) {
+        self.draw_curve_part(control.to_vec(), 0.0, 1.0, &point, color);
+    }
+
+    // Draws the curve from t0 to t1, whose control points are `control`. The curve stays
+    // inside their bounding box, so pieces away from the image are skipped, and long pieces
+    // are halved until the ones left near the image are short.
+    fn draw_curve_part<F: Fn(f64) -> (f64, f64)>(&mut self, control: Vec<(f64, f64)>, t0: f64,
+                                                 t1: f64, point: &F, color: Color) {
+        let (mut min, mut max) = (control[0], control[0]);
+        for &(x, y) in &control {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        if max.0 < -1.0 || max.1 < -1.0 || min.0 > self.width as f64 ||
+           min.1 > self.height as f64 {
+            return;
+        }
+        let length = control.windows(2).fold(0.0, |sum, pair| {
+            sum + (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1)
+        });
+        if length > 4.0 * (self.width + self.height) as f64 && t1 - t0 > 1e-12 {
+            let (left, right) = split_bezier(&control);
+            let middle = (t0 + t1) / 2.0;
+            self.draw_curve_part(left, t0, middle, point, color);
+            self.draw_curve_part(right, middle, t1, point, color);
+            return;
+        }
+
+        let segments = (length / 4.0).ceil().max(1.0) as usize;
+        let round = |(x, y): (f64, f64)| (x.round() as i64, y.round() as i64);
+        let mut previous = round(point(t0));
+        for i in 1..segments + 1 {
+            let next = round(point(t0 + (t1 - t0) * i as f64 / segments as f64));
+            self.draw_line(previous, next, color);
+            previous = next;
+        }
+    }
+
+    /// A quadratic Bézier curve from p0 to p2, pulled towards p1.
+    pub fn draw_quadratic_bezier(&mut self, p0: (f64, f64), p1: (f64, f64), p2: (f64, f64),
+                                 color: Color) {
+        self.draw_curve(&[p0, p1, p2], |t| {
+            let (a, b, c) = ((1.0 - t) * (1.0 - t), 2.0 * t * (1.0 - t), t * t);
+            (a * p0.0 + b * p1.0 + c * p2.0, a * p0.1 + b * p1.1 + c * p2.1)
+        }, color);
+    }
+
+    /// A cubic Bézier curve from p0 to p3, pulled towards p1 and p2.
+    pub fn draw_cubic_bezier(&mut self, p0: (f64, f64), p1: (f64, f64), p2: (f64, f64),
+                             p3: (f64, f64), color: Color) {
+        self.draw_curve(&[p0, p1, p2, p3], |t| {
+            let s = 1.0 - t;
+            let (a, b, c, d) = (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+            (a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0, a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1)
+        }, color);
+    }
+
+    /// Fills a polygon scanline by scanline, by the even-odd rule: a pixel is inside when a ray
+    /// from its center crosses the outline an odd number of times. Pixel (x, y) covers the
+    /// square from (x, y) to (x + 1, y + 1).
+    pub fn fill_polygon(&mut self, polygon: &[(f64, f64)], color: Color) {
+        let n = polygon.len();
+        let top = polygon.iter().fold(::std::f64::INFINITY, |top, p| top.min(p.1));
+        let bottom = polygon.iter().fold(::std::f64::NEG_INFINITY, |bottom, p| bottom.max(p.1));
+        if n < 3 || bottom <= 0.0 || top >= self.height as f64 {
+            return;
+        }
+        let first_row = (top - 0.5).ceil().max(0.0) as usize;
+        let last_row = ((bottom - 0.5).ceil().max(0.0) as usize).min(self.height);
+        let mut crossings = Vec::new();
+        for y in first_row..last_row {
+            let center = y as f64 + 0.5;
+            crossings.clear();
+            for i in 0..n {
+                let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+                if (a.1 <= center) != (b.1 <= center) {
+                    crossings.push(a.0 + (center - a.1) * (b.0 - a.0) / (b.1 - a.1));
+                }
+            }
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            for span in crossings.chunks(2) {
+                // The pixels whose centers fall in the span
+                let start = (span[0] - 0.5).ceil().max(0.0) as usize;
+                let end = ((span[1] - 0.5).ceil().max(0.0) as usize).min(self.width);
+                for x in start..end {
+                    self[(x, y)] = color;
+                }
+            }
+        }
+    }
+
+    /// Repaints the region of pixels with the same color as (x, y) that is connected to it
+    /// horizontally and vertically. Returns the number of pixels painted, none if (x, y) is
+    /// outside the image.
+    pub fn flood_fill(&mut self, x: usize, y: usize, color: Color) -> usize {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        let target = self[(x, y)];
+        if target == color {
+            return 0;
+        }
+        // Pixels are painted as they join the queue, so none joins twice
+        let mut queue = VecDeque::new();
+        self[(x, y)] = color;
+        queue.push_back((x, y));
+        let mut painted = 0;
+        while let Some((x, y)) = queue.pop_front() {
+            painted += 1;
+            let mut neighbors = Vec::with_capacity(4);
+            if x > 0 { neighbors.push((x - 1, y)); }
+            if x + 1 < self.width { neighbors.push((x + 1, y)); }
+            if y > 0 { neighbors.push((x, y - 1)); }
+            if y + 1 < self.height { neighbors.push((x, y + 1)); }
+            for neighbor in neighbors {
+                if self[neighbor] == target {
+                    self[neighbor] = color;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        painted
+    }
+}
+
+// The control points of the two halves of a Bézier curve, by de Casteljau's algorithm.
+fn split_bezier(control: &[(f64, f64)]) -> (Vec<(f64, f64)>, Vec<(f64, f64)>) {
+    let mut points = control.to_vec();
+    let (mut left, mut right) = (vec![points[0]], vec![points[points.len() - 1]]);
+    while points.len() > 1 {
+        points = points.windows(2)
+            .map(|p| ((p[0].0 + p[1].0) / 2.0, (p[0].1 + p[1].1) / 2.0))
+            .collect();
+        left.push(points[0]);
+        right.push(points[points.len() - 1]);
+    }
+    right.reverse();
+    (left, right)
+}
+
+// The range of n from 0 to `count` for which start + step * n lies from 0 to size - 1, `step`
+// being 1 or -1. The range is empty, with its first after its last, if there are none.
+fn steps_inside(start: i64, step: i64, size: i64, count: i64) -> (i64, i64) {
+    let (first, last) = if step > 0 {
+        (-start, size - 1 - start)
+    } else {
+        (start - size + 1, start)
+    };
+    (first.max(0), last.min(count))
+}
+
+/// How to work out the colors between the pixels of an image when resampling it.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Filter {
+    /// The nearest pixel
+    Nearest,
+    /// Linear interpolation between the nearest 2x2 pixels
+    Bilinear,
+    /// Catmull-Rom cubic interpolation between the nearest 4x4 pixels
+    Bicubic,
+    /// Lanczos windowed sinc over the nearest 6x6 pixels: the sharpest, but it can ring
+    /// around hard edges
+    Lanczos,
+    /// The average of the pixels each new pixel covers, weighted by how much of them it
+    /// covers. Meant for shrinking; when sampling single points it works like Bilinear.
+    Area,
+}
+
+impl Filter {
+    // How many pixels the kernel reaches either side of a point, at the source's scale
+    fn support(self) -> f64 {
+        match self {
+            Filter::Nearest | Filter::Area => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+            Filter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            Filter::Nearest | Filter::Area => if x < 0.5 { 1.0 } else { 0.0 },
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Bicubic => {
+                if x < 1.0 {
+                    (1.5 * x - 2.5) * x * x + 1.0
+                } else if x < 2.0 {
+                    ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0
+                } else {
+                    0.0
+                }
             }
+            Filter::Lanczos => {
+                if x < 1e-9 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = ::std::f64::consts::PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    // The pixels along an axis of `size` that the kernel, widened by `stretch`, covers around
+    // a position in pixel coordinates, with weights adding up to one. Pixels past the ends
+    // repeat the ones at the ends.
+    fn taps(self, center: f64, stretch: f64, size: usize) -> Vec<(usize, f64)> {
+        let radius = self.support() * stretch;
+        let mut taps: Vec<(usize, f64)> =
+            ((center - radius).ceil() as isize..(center + radius).floor() as isize + 1)
+                .map(|j| {
+                    let clamped = j.max(0).min(size as isize - 1) as usize;
+                    (clamped, self.weight((j as f64 - center) / stretch))
+                })
+                .filter(|&(_, weight)| weight != 0.0)
+                .collect();
+        let total = taps.iter().fold(0.0, |sum, &(_, weight)| sum + weight);
+        for tap in taps.iter_mut() {
+            tap.1 /= total;
+        }
+        taps
+    }
+
+    // For each of `to` pixels spread over an axis of `from` pixels, which of those it is made
+    // of and how much of each
+    fn axis_weights(self, from: usize, to: usize) -> Vec<Vec<(usize, f64)>> {
+        let scale = from as f64 / to as f64;
+        (0..to).map(|i| {
+            match self {
+                Filter::Nearest => {
+                    vec![((((i as f64 + 0.5) * scale) as usize).min(from - 1), 1.0)]
+                }
+                Filter::Area => {
+                    let (start, end) = (i as f64 * scale, (i + 1) as f64 * scale);
+                    (start.floor() as usize..(end.ceil() as usize).min(from)).map(|j| {
+                        (j, (end.min(j as f64 + 1.0) - start.max(j as f64)) / scale)
+                    }).filter(|&(_, weight)| weight > 0.0).collect()
+                }
+                // Shrinking widens the kernel so that every source pixel counts
+                _ => self.taps((i as f64 + 0.5) * scale - 0.5, scale.max(1.0), from),
+            }
+        }).collect()
+    }
+}
+
+// Rounds and clamps the sums of weighted channels to a color
+fn to_color(sum: [f64; 3]) -> Color {
+    let channel = |v: f64| v.round().max(0.0).min(255.0) as u8;
+    Color { red: channel(sum[0]), green: channel(sum[1]), blue: channel(sum[2]) }
+}
+
+// Geometric transforms. These all make a new image and leave this one as it was.
+impl Image {
+    // An image of the given size taking each pixel from the one here that `source` picks
+    fn remap<F: Fn(usize, usize) -> (usize, usize)>(&self, width: usize, height: usize,
+                                                       source: F) -> Image {
+        let mut output = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                output[(x, y)] = self[source(x, y)];
+            }
+        }
+        output
+    }
+
+    /// The rectangle of the image with its top left corner at (x, y). It must lie inside the
+    /// image.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Image {
+        assert!(x + width <= self.width && y + height <= self.height,
+                "crop outside the image");
+        self.remap(width, height, |cx, cy| (x + cx, y + cy))
+    }
+
+    /// The image mirrored left to right.
+    pub fn flip_horizontal(&self) -> Image {
+        self.remap(self.width, self.height, |x, y| (self.width - 1 - x, y))
+    }
+
+    /// The image mirrored top to bottom.
+    pub fn flip_vertical(&self) -> Image {
+        self.remap(self.width, self.height, |x, y| (x, self.height - 1 - y))
+    }
+
+    /// The image turned a quarter turn clockwise.
+    pub fn rotate_90(&self) -> Image {
+        self.remap(self.height, self.width, |x, y| (y, self.height - 1 - x))
+    }
+
+    pub fn rotate_180(&self) -> Image {
+        self.remap(self.width, self.height,
+                   |x, y| (self.width - 1 - x, self.height - 1 - y))
+    }
+
+    /// The image turned a quarter turn counterclockwise.
+    pub fn rotate_270(&self) -> Image {
+        self.remap(self.height, self.width, |x, y| (self.width - 1 - y, x))
+    }
+
+    /// The color at a point, where pixel (x, y) covers the square from (x, y) to
+    /// (x + 1, y + 1), or None if the point is outside the image.
+    pub fn sample(&self, x: f64, y: f64, filter: Filter) -> Option<Color> {
+        if !(x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64) {
+            return None;
         }
-        Ok(())
+        let filter = match filter {
+            Filter::Nearest => return Some(self[(x as usize, y as usize)]),
+            Filter::Area => Filter::Bilinear,
+            filter => filter,
+        };
+        let columns = filter.taps(x - 0.5, 1.0, self.width);
+        let mut sum = [0.0; 3];
+        for &(row, row_weight) in &filter.taps(y - 0.5, 1.0, self.height) {
+            for &(column, column_weight) in &columns {
+                let (c, weight) = (self[(column, row)], row_weight * column_weight);
+                sum[0] += weight * c.red as f64;
+                sum[1] += weight * c.green as f64;
+                sum[2] += weight * c.blue as f64;
+            }
+        }
+        Some(to_color(sum))
+    }
+
+    /// The image turned clockwise by an angle in radians about its center, on a canvas just
+    /// big enough to hold it. The corners it leaves uncovered are filled with the background.
+    pub fn rotate(&self, angle: f64, filter: Filter, background: Color) -> Image {
+        let (sin, cos) = angle.sin_cos();
+        let (w, h) = (self.width as f64, self.height as f64);
+        // Shave off rounding error so that quarter turns keep their exact sizes
+        let width = (w * cos.abs() + h * sin.abs() - 1e-9).ceil().max(0.0) as usize;
+        let height = (w * sin.abs() + h * cos.abs() - 1e-9).ceil().max(0.0) as usize;
+        let mut output = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                // Turn the pixel's center back to where it came from
+                let dx = x as f64 + 0.5 - width as f64 / 2.0;
+                let dy = y as f64 + 0.5 - height as f64 / 2.0;
+                output[(x, y)] = self.sample(w / 2.0 + dx * cos + dy * sin,
+                                             h / 2.0 - dx * sin + dy * cos, filter)
+                                     .unwrap_or(background);
+            }
+        }
+        output
+    }
+
+    /// The image stretched or shrunk to a new size, one axis at a time. The image must not be
+    /// empty.
+    pub fn resize(&self, width: usize, height: usize, filter: Filter) -> Image {
+        assert!(self.width > 0 && self.height > 0, "resizing an empty image");
+        let columns = filter.axis_weights(self.width, width);
+        let rows = filter.axis_weights(self.height, height);
+
+        // Across first, keeping the sums unrounded for the second pass
+        let mut across = vec![[0.0; 3]; width * self.height];
+        for y in 0..self.height {
+            for (x, weights) in columns.iter().enumerate() {
+                let sum = &mut across[x + y * width];
+                for &(j, weight) in weights {
+                    let c = self[(j, y)];
+                    sum[0] += weight * c.red as f64;
+                    sum[1] += weight * c.green as f64;
+                    sum[2] += weight * c.blue as f64;
+                }
+            }
+        }
+
+        let mut output = Image::new(width, height);
+        for (y, weights) in rows.iter().enumerate() {
+            for x in 0..width {
+                let mut sum = [0.0; 3];
+                for &(j, weight) in weights {
+                    let partial = across[x + j * width];
+                    for k in 0..3 {
+                        sum[k] += weight * partial[k];
+                    }
+                }
+                output[(x, y)] = to_color(sum);
+            }
+        }
+        output
+    }
+
+    /// The image shrunk by area averaging to fit in the given size, keeping its proportions.
+    /// Images that already fit come back the same size.
+    pub fn thumbnail(&self, max_width: usize, max_height: usize) -> Image {
+        let scale = (max_width as f64 / self.width as f64)
+            .min(max_height as f64 / self.height as f64).min(1.0);
+        let fit = |size: usize| ((size as f64 * scale).round() as usize).max(1);
+        self.resize(fit(self.width), fit(self.height), Filter::Area)
     }
 }
 
@@ -76,7 +784,7 @@ pub fn main() {
 
     for y in 0..10 {
         for x in 0..10 {
-            if image[(x,y)].red + image[(x,y)].green + image[(x,y)].blue == 0 {
+            if image[(x,y)] == Default::default() {
                 print!("#");
             } else {
                 print!(".");
@@ -84,11 +792,42 @@ pub fn main() {
         }
         println!("");
     }
+
+    // Some of everything there is to draw
+    let mut image = Image::new(200, 200);
+    image.fill(Color { red: 255, green: 255, blue: 255 });
+    let black = Color { red: 0, green: 0, blue: 0 };
+    image.fill_polygon(&[(20.0, 180.0), (100.0, 110.0), (180.0, 180.0)],
+                       Color { red: 250, green: 200, blue: 40 });
+    image.draw_circle((60, 60), 40, black);
+    image.flood_fill(60, 60, Color { red: 120, green: 160, blue: 230 });
+    image.draw_line((110, 20), (190, 90), black);
+    image.draw_line_antialiased((110.0, 30.0), (190.0, 100.0), black);
+    image.draw_quadratic_bezier((10.0, 190.0), (100.0, 60.0), (190.0, 190.0),
+                                Color { red: 200, green: 0, blue: 0 });
+    image.draw_cubic_bezier((110.0, 100.0), (140.0, 0.0), (160.0, 200.0), (190.0, 100.0),
+                            Color { red: 0, green: 130, blue: 0 });
+    match image.write_png("drawing.png") {
+        Ok(()) => println!("Wrote drawing.png"),
+        Err(e) => println!("Can't write drawing.png: {}", e),
+    }
+
+    // And a thumbnail of it turned on its corner
+    let tilted = image.rotate(::std::f64::consts::PI / 4.0, Filter::Bicubic,
+                              Color { red: 255, green: 255, blue: 255 });
+    match tilted.thumbnail(96, 96).write_png("drawing-thumbnail.png") {
+        Ok(()) => println!("Wrote drawing-thumbnail.png"),
+        Err(e) => println!("Can't write drawing-thumbnail.png: {}", e),
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Color, Image};
+    use super::{Color, Image, Filter};
+    use super::netpbm::{Netpbm, Kind};
+    use super::png::{Png, ColorType};
+    use super::gif::GifWriter;
+    use super::processing::box_blur;
     use std::default::Default;
 
     #[test]
@@ -128,4 +867,313 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn netpbm_conversions() {
+        let mut image = Image::new(2, 1);
+        image[(1, 0)] = Color { red: 10, green: 20, blue: 30 };
+        let pixmap = image.to_netpbm();
+        assert_eq!(pixmap.samples, vec![0, 0, 0, 10, 20, 30]);
+        let back = Image::from_netpbm(&pixmap);
+        assert_eq!(back.data, image.data);
+
+        let mut bitmap = Netpbm::new(Kind::Bitmap, 2, 1, 1);
+        bitmap.samples[0] = 0;
+        assert_eq!(Image::from_netpbm(&bitmap).data,
+                   vec![Color { red: 255, green: 255, blue: 255 }, Default::default()]);
+    }
+
+    #[test]
+    fn png_conversions() {
+        let mut image = Image::new(2, 2);
+        image[(0, 1)] = Color { red: 10, green: 20, blue: 30 };
+        let png = image.to_png();
+        assert_eq!(png.pixels[6..9].to_vec(), vec![10, 20, 30]);
+        assert_eq!(Image::from_png(&png).data, image.data);
+
+        let gray = Png { width: 1, height: 1, color: ColorType::GrayAlpha, pixels: vec![7, 0] };
+        assert_eq!(Image::from_png(&gray).data, vec![Color { red: 7, green: 7, blue: 7 }]);
+    }
+
+    #[test]
+    fn indexed_colors() {
+        let mut image = Image::new(3, 1);
+        image[(1, 0)] = Color { red: 10, green: 20, blue: 30 };
+        let (palette, indices) = image.to_indexed().unwrap();
+        assert_eq!(palette, vec![[0, 0, 0], [10, 20, 30]]);
+        assert_eq!(indices, vec![0, 1, 0]);
+
+        let mut gif = GifWriter::new(Vec::new(), 3, 1, 0).unwrap();
+        image.write_gif_frame(&mut gif).unwrap();
+        assert!(Image::new(2, 2).write_gif_frame(&mut gif).is_err());
+
+        let mut colorful = Image::new(256, 2);
+        for x in 0..256 {
+            colorful[(x, 0)] = Color { red: x as u8, green: 0, blue: 0 };
+        }
+        assert_eq!(colorful.to_indexed().unwrap().0.len(), 256);
+        colorful[(0, 1)].blue = 1;
+        assert!(colorful.to_indexed().is_err());
+    }
+
+    const WHITE: Color = Color { red: 255, green: 255, blue: 255 };
+
+    fn painted(image: &Image) -> Vec<(usize, usize)> {
+        let mut pixels = Vec::new();
+        for y in 0..image.height {
+            for x in 0..image.width {
+                if image[(x, y)] != Default::default() {
+                    pixels.push((x, y));
+                }
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn lines() {
+        let mut image = Image::new(10, 10);
+        image.draw_line((1, 2), (8, 5), WHITE);
+        let pixels = painted(&image);
+        assert_eq!(pixels.len(), 8);
+        assert!(pixels.contains(&(1, 2)) && pixels.contains(&(8, 5)));
+        // The same pixels either way round, in any octant
+        let mut reversed = Image::new(10, 10);
+        reversed.draw_line((8, 5), (1, 2), WHITE);
+        assert_eq!(painted(&reversed), pixels);
+        let mut steep = Image::new(10, 10);
+        steep.draw_line((2, 1), (5, 8), WHITE);
+        let mut transposed: Vec<(usize, usize)> = painted(&steep).iter().map(|&(x, y)| (y, x))
+                                                                  .collect();
+        transposed.sort_by(|a, b| (a.1, a.0).cmp(&(b.1, b.0)));
+        assert_eq!(transposed, pixels);
+        // Off the edges only the visible part is drawn
+        let mut clipped = Image::new(10, 10);
+        clipped.draw_line((-5, 3), (20, 3), WHITE);
+        assert_eq!(painted(&clipped).len(), 10);
+        // Clipping keeps the pixels the whole line would have had
+        for &(from, to) in &[((-50, -37), (80, 61)), ((13, -40), (-7, 45)), ((4, -3), (4, 30)),
+                             ((-9, 12), (12, -9)), ((30, 2), (-30, 8)), ((-3, -3), (-1, 5))] {
+            let mut clipped = Image::new(10, 10);
+            clipped.draw_line(from, to, WHITE);
+            let mut whole = Image::new(200, 200);
+            whole.draw_line((from.0 + 100, from.1 + 100), (to.0 + 100, to.1 + 100), WHITE);
+            assert_eq!(painted(&clipped), painted(&whole.crop(100, 100, 10, 10)));
+        }
+        // Far off ends cost nothing
+        let mut far = Image::new(10, 10);
+        far.draw_line((-1000000000, 3), (1000000000, 6), WHITE);
+        assert_eq!(painted(&far).len(), 10);
+    }
+
+    #[test]
+    fn antialiased_lines() {
+        let mut image = Image::new(10, 10);
+        image.draw_line_antialiased((1.0, 1.0), (8.0, 1.0), WHITE);
+        assert_eq!(painted(&image), (1..9).map(|x| (x, 1)).collect::<Vec<_>>());
+        // Halfway between two rows, both get half the color
+        let mut image = Image::new(10, 10);
+        image.draw_line_antialiased((1.0, 2.5), (8.0, 2.5), WHITE);
+        for x in 2..8 {
+            assert_eq!(image[(x, 2)].red, 128);
+            assert_eq!(image[(x, 3)].red, 128);
+        }
+        // Ends on pixel centers cover half their pixels
+        let mut image = Image::new(10, 10);
+        image.draw_line_antialiased((2.0, 1.0), (7.0, 6.0), WHITE);
+        for i in 3..7 {
+            assert_eq!(image[(i, i - 1)], WHITE);
+        }
+        assert_eq!(image[(2, 1)].red, 128);
+        assert_eq!(image[(7, 6)].red, 128);
+        assert_eq!(Color::default().blend(WHITE, 0.25), Color { red: 64, green: 64, blue: 64 });
+        // Clipping keeps the shades the whole line would have had
+        for &(from, to) in &[((-90.0, -79.75), (90.0, 50.75)), ((3.5, -60.0), (6.25, 70.0)),
+                             ((40.0, 2.25), (-40.0, 7.5)), ((-2.5, 3.0), (4.5, -2.0))] {
+            let mut clipped = Image::new(10, 10);
+            clipped.draw_line_antialiased(from, to, WHITE);
+            let mut whole = Image::new(200, 200);
+            whole.draw_line_antialiased((from.0 + 100.0, from.1 + 100.0),
+                                        (to.0 + 100.0, to.1 + 100.0), WHITE);
+            // Moving the line can round its shades the other way
+            let cropped = whole.crop(100, 100, 10, 10);
+            for (a, b) in clipped.data.iter().zip(cropped.data.iter()) {
+                assert!((a.red as i32 - b.red as i32).abs() <= 1);
+            }
+        }
+        // Far off ends cost nothing
+        let mut far = Image::new(10, 10);
+        far.draw_line_antialiased((-1e9, 4.5), (1e9, 4.5), WHITE);
+        for x in 0..10 {
+            assert_eq!((far[(x, 4)].red, far[(x, 5)].red), (128, 128));
+        }
+        let mut far = Image::new(10, 10);
+        far.draw_line_antialiased((-1e9, -1e9), (1e9, 1e9), WHITE);
+        assert_eq!(painted(&far), (0..10).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn circles() {
+        let mut image = Image::new(21, 21);
+        image.draw_circle((10, 10), 7, WHITE);
+        let pixels = painted(&image);
+        for &(x, y) in &pixels {
+            let distance = ((x as f64 - 10.0).powi(2) + (y as f64 - 10.0).powi(2)).sqrt();
+            assert!((distance - 7.0).abs() < 0.5);
+            assert!(pixels.contains(&(20 - x, y)) && pixels.contains(&(y, x)));
+        }
+        // The outline is closed, so filling inside leaves the outside alone
+        let filled = image.flood_fill(10, 10, WHITE);
+        let outside = image.flood_fill(0, 0, Color { red: 1, green: 0, blue: 0 });
+        assert_eq!(filled + outside + pixels.len(), 21 * 21);
+        // Roughly the area inside the outline's pixels
+        assert!((filled as f64 - ::std::f64::consts::PI * 6.5 * 6.5).abs() < 10.0);
+        assert_eq!(image.flood_fill(0, 0, Color { red: 1, green: 0, blue: 0 }), 0);
+        assert_eq!(image.flood_fill(21, 0, WHITE), 0);
+    }
+
+    #[test]
+    fn bezier_curves() {
+        let mut image = Image::new(40, 40);
+        image.draw_quadratic_bezier((2.0, 30.0), (20.0, -10.0), (38.0, 30.0), WHITE);
+        let pixels = painted(&image);
+        for pixel in &[(2, 30), (20, 10), (38, 30)] {
+            assert!(pixels.contains(pixel));
+        }
+        // Every column is reached, with no gaps
+        for x in 2..39 {
+            assert!(pixels.iter().any(|&(px, _)| px == x));
+        }
+        let mut image = Image::new(40, 40);
+        image.draw_cubic_bezier((2.0, 20.0), (10.0, 0.0), (30.0, 40.0), (38.0, 20.0), WHITE);
+        let pixels = painted(&image);
+        for pixel in &[(2, 20), (20, 20), (38, 20)] {
+            assert!(pixels.contains(pixel));
+        }
+        // A curve reaching far outside only draws what can be seen, and quickly
+        let mut image = Image::new(40, 40);
+        image.draw_quadratic_bezier((-1e9, 20.0), (20.0, 20.0), (1e9, 20.0), WHITE);
+        assert_eq!(painted(&image), (0..40).map(|x| (x, 20)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn polygons() {
+        let mut image = Image::new(10, 10);
+        image.fill_polygon(&[(2.0, 2.0), (6.0, 2.0), (6.0, 5.0), (2.0, 5.0)], WHITE);
+        assert_eq!(painted(&image), (2..5).flat_map(|y| (2..6).map(move |x| (x, y)))
+                                         .collect::<Vec<_>>());
+        // A pentagram leaves its center empty under the even-odd rule
+        let mut image = Image::new(50, 50);
+        let star: Vec<(f64, f64)> = (0..5).map(|i| {
+            let angle = i as f64 * 4.0 * ::std::f64::consts::PI / 5.0;
+            (25.0 + 20.0 * angle.sin(), 25.0 - 20.0 * angle.cos())
+        }).collect();
+        image.fill_polygon(&star, WHITE);
+        assert_eq!(image[(25, 25)], Color::default());
+        assert_eq!(image[(25, 8)], WHITE);
+        // Partly outside the image
+        let mut image = Image::new(10, 10);
+        image.fill_polygon(&[(-5.0, -5.0), (5.0, -5.0), (5.0, 5.0), (-5.0, 5.0)], WHITE);
+        assert_eq!(painted(&image).len(), 25);
+    }
+
+    #[test]
+    fn gray_and_channels() {
+        let mut image = Image::new(3, 1);
+        image[(0, 0)] = Color { red: 255, green: 0, blue: 0 };
+        image[(1, 0)] = Color { red: 0, green: 255, blue: 0 };
+        image[(2, 0)] = WHITE;
+        assert_eq!(image.to_gray().data, vec![54, 182, 255]);
+        assert_eq!(Image::from_gray(&image.to_gray())[(2, 0)], WHITE);
+
+        let channels = image.channels();
+        assert_eq!(channels[1].data, vec![0, 255, 255]);
+        let rebuilt = Image::from_channels(&channels[0], &channels[1], &channels[2]);
+        assert_eq!(rebuilt.data, image.data);
+        let blurred = image.map_channels(|channel| box_blur(channel, 1));
+        assert_eq!(blurred[(1, 0)], Color { red: 170, green: 170, blue: 85 });
+    }
+
+    // A 4x3 image with every pixel a different color
+    fn numbered() -> Image {
+        let mut image = Image::new(4, 3);
+        for y in 0..3 {
+            for x in 0..4 {
+                image[(x, y)] = Color { red: x as u8, green: y as u8, blue: 0 };
+            }
+        }
+        image
+    }
+
+    fn at(x: u8, y: u8) -> Color {
+        Color { red: x, green: y, blue: 0 }
+    }
+
+    #[test]
+    fn flips_rotations_and_crops() {
+        let image = numbered();
+        assert_eq!(image.flip_horizontal()[(0, 1)], at(3, 1));
+        assert_eq!(image.flip_vertical()[(0, 0)], at(0, 2));
+        let turned = image.rotate_90();
+        assert_eq!((turned.width, turned.height), (3, 4));
+        // The top left corner goes to the top right
+        assert_eq!(turned[(2, 0)], at(0, 0));
+        assert_eq!(turned[(0, 0)], at(0, 2));
+        assert_eq!(turned.rotate_270().data, image.data);
+        assert_eq!(turned.rotate_90().data, image.rotate_180().data);
+        assert_eq!(image.rotate_180()[(0, 0)], at(3, 2));
+
+        let cropped = image.crop(1, 1, 2, 2);
+        assert_eq!(cropped.data, vec![at(1, 1), at(2, 1), at(1, 2), at(2, 2)]);
+        // Quarter turns through rotate match the exact ones
+        for &filter in &[Filter::Nearest, Filter::Bilinear, Filter::Bicubic, Filter::Lanczos] {
+            let turned = image.rotate(::std::f64::consts::PI / 2.0, filter, WHITE);
+            assert_eq!(turned.data, image.rotate_90().data);
+        }
+        let tilted = image.rotate(::std::f64::consts::PI / 4.0, Filter::Nearest, WHITE);
+        assert_eq!((tilted.width, tilted.height), (5, 5));
+        assert_eq!(tilted[(0, 0)], WHITE);
+    }
+
+    #[test]
+    fn resizing() {
+        let filters = [Filter::Nearest, Filter::Bilinear, Filter::Bicubic, Filter::Lanczos,
+                       Filter::Area];
+        let mut flat = Image::new(7, 5);
+        flat.fill(Color { red: 10, green: 100, blue: 200 });
+        for &filter in &filters {
+            for &(width, height) in &[(3, 2), (7, 5), (20, 11)] {
+                let resized = flat.resize(width, height, filter);
+                assert_eq!((resized.width, resized.height), (width, height));
+                assert!(resized.data.iter().all(|&c| c == flat.data[0]));
+            }
+            // Same size changes nothing
+            assert_eq!(numbered().resize(4, 3, filter).data, numbered().data);
+        }
+
+        // Doubling by nearest copies each pixel into a 2x2 block
+        let doubled = numbered().resize(8, 6, Filter::Nearest);
+        assert_eq!(doubled[(5, 3)], at(2, 1));
+        // Area averaging halves a checkerboard to a flat gray
+        let mut checkers = Image::new(6, 4);
+        for y in 0..4 {
+            for x in 0..6 {
+                if (x + y) % 2 == 0 {
+                    checkers[(x, y)] = WHITE;
+                }
+            }
+        }
+        let halved = checkers.resize(3, 2, Filter::Area);
+        assert!(halved.data.iter().all(|&c| c == Color { red: 128, green: 128, blue: 128 }));
+        // Bilinear interpolation of a ramp stays a ramp
+        let ramp = numbered().resize(7, 3, Filter::Bilinear);
+        let reds: Vec<u8> = (0..7).map(|x| ramp[(x, 0)].red).collect();
+        assert!(reds.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(numbered().sample(2.0, 1.5, Filter::Bilinear), Some(at(2, 1)));
+        assert_eq!(numbered().sample(4.0, 1.0, Filter::Bilinear), None);
+
+        let thumbnail = checkers.thumbnail(3, 3);
+        assert_eq!((thumbnail.width, thumbnail.height), (3, 2));
+        assert_eq!(checkers.thumbnail(10, 10).data, checkers.data);
+    }
 }
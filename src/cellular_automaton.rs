@@ -0,0 +1,1004 @@
+// Implements http://rosettacode.org/wiki/Conway%27s_Game_of_Life
+// and the other automata on a grid of cells:
+// http://rosettacode.org/wiki/Wireworld
+// http://rosettacode.org/wiki/Langton%27s_ant
+// http://rosettacode.org/wiki/Elementary_cellular_automaton
+//
+// Cells hold small numbers, and a rule decides the next generation of the whole grid from the
+// last one. Patterns load from the plain text format of the Wireworld task, or from RLE as
+// written by Golly and used by most pattern collections.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::{Index, IndexMut};
+use self::bitmap::{Color, Image};
+use self::bitmap::gif::GifWriter;
+use self::hashlife::Hashlife;
+
+// Drawing the generations, and running Life far into the future
+#[path = "bitmap.rs"]
+pub mod bitmap;
+#[path = "hashlife.rs"]
+pub mod hashlife;
+
+/// The states of Wireworld cells, numbered as in Golly
+pub const EMPTY: u8 = 0;
+pub const HEAD: u8 = 1;
+pub const TAIL: u8 = 2;
+pub const CONDUCTOR: u8 = 3;
+
+/// The states of cells in Life, Langton's ant and the elementary automata
+pub const DEAD: u8 = 0;
+pub const ALIVE: u8 = 1;
+
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    // The line number of a header that isn't "x = <width>, y = <height>, ..."
+    BadHeader(usize),
+    BadRule(String),
+    BadCell(char),
+    // A cell past the size given in the header
+    OutOfBounds,
+}
+
+impl From<io::Error> for PatternError {
+    fn from(err: io::Error) -> PatternError {
+        PatternError::Io(err)
+    }
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatternError::Io(ref err) => write!(f, "{}", err),
+            PatternError::BadHeader(line) => write!(f, "bad RLE header on line {}", line),
+            PatternError::BadRule(ref rule) => write!(f, "can't read the rule {:?}", rule),
+            PatternError::BadCell(c) => write!(f, "unexpected {:?} in the pattern", c),
+            PatternError::OutOfBounds => write!(f, "the pattern is bigger than its header says"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<u8>,
+    /// Whether the edges join up with the opposite ones. If not, everything past them stays
+    /// at state 0.
+    pub wrap: bool,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Grid {
+        Grid { width: width, height: height, cells: vec![0; width * height], wrap: false }
+    }
+
+    /// The state of a cell, which may be past the edges.
+    pub fn get(&self, x: isize, y: isize) -> u8 {
+        let (width, height) = (self.width as isize, self.height as isize);
+        if self.wrap && width > 0 && height > 0 {
+            let (x, y) = ((x % width + width) % width, (y % height + height) % height);
+            self[(x as usize, y as usize)]
+        } else if x >= 0 && y >= 0 && x < width && y < height {
+            self[(x as usize, y as usize)]
+        } else {
+            0
+        }
+    }
+
+    /// The states of the eight cells around this one, row by row.
+    pub fn neighborhood(&self, x: usize, y: usize) -> [u8; 8] {
+        let (x, y) = (x as isize, y as isize);
+        [self.get(x - 1, y - 1), self.get(x, y - 1), self.get(x + 1, y - 1),
+         self.get(x - 1, y), self.get(x + 1, y),
+         self.get(x - 1, y + 1), self.get(x, y + 1), self.get(x + 1, y + 1)]
+    }
+
+    /// A new grid made by working out each cell from this one.
+    pub fn map<F: Fn(&Grid, usize, usize) -> u8>(&self, f: F) -> Grid {
+        let mut next = Grid { cells: Vec::with_capacity(self.cells.len()), ..self.clone() };
+        for y in 0..self.height {
+            for x in 0..self.width {
+                next.cells.push(f(self, x, y));
+            }
+        }
+        next
+    }
+
+    /// Reads a Wireworld grid drawn in text: `H` for heads, `t` for tails, `.` for conductors
+    /// and anything else for empty cells. Short lines are padded with empty cells.
+    pub fn parse_wireworld(text: &str) -> Grid {
+        let lines: Vec<&str> = text.lines().collect();
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+        let mut grid = Grid::new(width, lines.len());
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                grid[(x, y)] = match c {
+                    'H' => HEAD,
+                    't' => TAIL,
+                    '.' => CONDUCTOR,
+                    _ => EMPTY,
+                };
+            }
+        }
+        grid
+    }
+
+    /// The grid in the text format of parse_wireworld, with spaces for empty cells.
+    pub fn to_wireworld(&self) -> String {
+        if self.width == 0 {
+            return ::std::iter::repeat('\n').take(self.height).collect();
+        }
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+        for row in self.cells.chunks(self.width) {
+            for &cell in row {
+                text.push(match cell {
+                    HEAD => 'H',
+                    TAIL => 't',
+                    CONDUCTOR => '.',
+                    _ => ' ',
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl Index<(usize, usize)> for Grid {
+    type Output = u8;
+
+    fn index(&self, (x, y): (usize, usize)) -> &u8 {
+        &self.cells[x + y * self.width]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Grid {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut u8 {
+        &mut self.cells[x + y * self.width]
+    }
+}
+
+/// A grid as read from a file, along with the rule the file asks for, if any.
+pub struct Pattern {
+    pub grid: Grid,
+    pub rule: Option<String>,
+}
+
+impl Pattern {
+    /// Reads a pattern in run length encoding. Cells are `b` or `.` for state 0, `o` for state
+    /// 1, or `A` to `X` for states 1 to 24, each after an optional count; `$` ends a row and
+    /// `!` the pattern. Lines starting with `#` are comments.
+    pub fn parse_rle(text: &str) -> Result<Pattern, PatternError> {
+        let mut lines = text.lines().enumerate()
+            .filter(|&(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+        let (width, height, rule) = match lines.next() {
+            Some((number, header)) => try!(Pattern::parse_header(number + 1, header)),
+            None => return Err(PatternError::BadHeader(1)),
+        };
+
+        let mut grid = Grid::new(width, height);
+        let (mut x, mut y, mut count) = (0usize, 0usize, 0usize);
+        'body: for (_, line) in lines {
+            for c in line.chars() {
+                let state = match c {
+                    '0'...'9' => {
+                        // A count too big to hold can't fit in the grid either
+                        let digit = c as usize - '0' as usize;
+                        count = try!(count.checked_mul(10).and_then(|n| n.checked_add(digit))
+                                          .ok_or(PatternError::OutOfBounds));
+                        continue;
+                    }
+                    '!' => break 'body,
+                    '$' => {
+                        y = try!(y.checked_add(if count == 0 { 1 } else { count })
+                                  .ok_or(PatternError::OutOfBounds));
+                        x = 0;
+                        count = 0;
+                        continue;
+                    }
+                    'b' | '.' => DEAD,
+                    'o' => ALIVE,
+                    'A'...'X' => c as u8 - b'A' + 1,
+                    c if c.is_whitespace() => continue,
+                    c => return Err(PatternError::BadCell(c)),
+                };
+                let run = if count == 0 { 1 } else { count };
+                let end = try!(x.checked_add(run).ok_or(PatternError::OutOfBounds));
+                if state != 0 {
+                    if end > width || y >= height {
+                        return Err(PatternError::OutOfBounds);
+                    }
+                    for i in x..end {
+                        grid[(i, y)] = state;
+                    }
+                }
+                x = end;
+                count = 0;
+            }
+        }
+        Ok(Pattern { grid: grid, rule: rule })
+    }
+
+    fn parse_header(number: usize, header: &str)
+                    -> Result<(usize, usize, Option<String>), PatternError> {
+        let (mut width, mut height, mut rule) = (None, None, None);
+        for field in header.split(',') {
+            let mut parts = field.splitn(2, '=').map(|part| part.trim());
+            match (parts.next(), parts.next()) {
+                (Some("x"), Some(value)) => width = value.parse().ok(),
+                (Some("y"), Some(value)) => height = value.parse().ok(),
+                (Some("rule"), Some(value)) => rule = Some(value.to_string()),
+                _ => return Err(PatternError::BadHeader(number)),
+            }
+        }
+        match (width, height) {
+            (Some(width), Some(height)) => Ok((width, height, rule)),
+            _ => Err(PatternError::BadHeader(number)),
+        }
+    }
+
+    /// Reads RLE from files ending in `.rle` and the Wireworld text format from any others.
+    pub fn load(filename: &str) -> Result<Pattern, PatternError> {
+        let mut text = String::new();
+        try!(try!(File::open(filename)).read_to_string(&mut text));
+        if filename.ends_with(".rle") {
+            Pattern::parse_rle(&text)
+        } else {
+            Ok(Pattern { grid: Grid::parse_wireworld(&text), rule: Some("WireWorld".to_string()) })
+        }
+    }
+}
+
+/// How an automaton gets from one generation to the next.
+pub trait Rule {
+    fn step(&mut self, grid: &Grid) -> Grid;
+
+    fn color(&self, state: u8) -> Color;
+
+    /// Whatever the rule keeps besides the cells that affects what happens next, so that
+    /// repeats can be told apart
+    fn state(&self) -> Vec<isize> {
+        Vec::new()
+    }
+}
+
+/// Rules where a cell's next state depends only on its own and its eight neighbours', which
+/// is what SparseGrid needs. A cell in state 0 with nothing but state 0 around it has to stay
+/// that way.
+pub trait LocalRule {
+    fn next_cell(&self, state: u8, neighborhood: &[u8; 8]) -> u8;
+}
+
+// How many cells of a neighbourhood are in a state
+fn count(neighborhood: &[u8; 8], state: u8) -> usize {
+    neighborhood.iter().filter(|&&cell| cell == state).count()
+}
+
+pub struct Wireworld;
+
+impl LocalRule for Wireworld {
+    fn next_cell(&self, state: u8, neighborhood: &[u8; 8]) -> u8 {
+        match state {
+            HEAD => TAIL,
+            TAIL => CONDUCTOR,
+            CONDUCTOR => {
+                let heads = count(neighborhood, HEAD);
+                if heads == 1 || heads == 2 { HEAD } else { CONDUCTOR }
+            }
+            other => other,
+        }
+    }
+}
+
+impl Rule for Wireworld {
+    fn step(&mut self, grid: &Grid) -> Grid {
+        grid.map(|grid, x, y| self.next_cell(grid[(x, y)], &grid.neighborhood(x, y)))
+    }
+
+    fn color(&self, state: u8) -> Color {
+        match state {
+            HEAD => Color { red: 40, green: 90, blue: 255 },
+            TAIL => Color { red: 255, green: 60, blue: 20 },
+            CONDUCTOR => Color { red: 255, green: 200, blue: 0 },
+            _ => Color { red: 0, green: 0, blue: 0 },
+        }
+    }
+}
+
+/// Life-like rules: a dead cell comes alive with one of the `birth` numbers of live
+/// neighbours, and a live one stays alive with one of the `survival` numbers.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Life {
+    pub birth: [bool; 9],
+    pub survival: [bool; 9],
+}
+
+impl Life {
+    /// Conway's Game of Life, B3/S23
+    pub fn conway() -> Life {
+        Life::parse("B3/S23").unwrap()
+    }
+
+    /// Reads a rule written as "B3/S23", in either order and either case, or in the older
+    /// survival/birth form "23/3".
+    pub fn parse(rule: &str) -> Result<Life, PatternError> {
+        let bad = || PatternError::BadRule(rule.to_string());
+        let parts: Vec<&str> = rule.trim().split('/').collect();
+        if parts.len() != 2 {
+            return Err(bad());
+        }
+        let mut life = Life { birth: [false; 9], survival: [false; 9] };
+        for (i, part) in parts.iter().enumerate() {
+            let (is_birth, digits) = match part.chars().next() {
+                Some('B') | Some('b') => (true, &part[1..]),
+                Some('S') | Some('s') => (false, &part[1..]),
+                // Without letters, survival comes first
+                _ => (i == 1, *part),
+            };
+            for digit in digits.chars() {
+                let n = try!(digit.to_digit(10).ok_or_else(&bad)) as usize;
+                if n > 8 {
+                    return Err(bad());
+                }
+                if is_birth { life.birth[n] = true } else { life.survival[n] = true }
+            }
+        }
+        Ok(life)
+    }
+
+    /// A Hashlife universe for this rule holding the live cells of a grid, which must not
+    /// wrap.
+    pub fn hashlife(&self, grid: &Grid) -> Hashlife {
+        let mut universe = Hashlife::new(self.birth, self.survival);
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                if grid[(x, y)] == ALIVE {
+                    universe.set(x as i64, y as i64, true).unwrap();
+                }
+            }
+        }
+        universe
+    }
+}
+
+impl LocalRule for Life {
+    fn next_cell(&self, state: u8, neighborhood: &[u8; 8]) -> u8 {
+        let neighbors = count(neighborhood, ALIVE);
+        let alive = if state == ALIVE { self.survival[neighbors] } else { self.birth[neighbors] };
+        if alive { ALIVE } else { DEAD }
+    }
+}
+
+impl Rule for Life {
+    fn step(&mut self, grid: &Grid) -> Grid {
+        grid.map(|grid, x, y| self.next_cell(grid[(x, y)], &grid.neighborhood(x, y)))
+    }
+
+    fn color(&self, state: u8) -> Color {
+        if state == ALIVE {
+            Color { red: 255, green: 255, blue: 255 }
+        } else {
+            Color { red: 0, green: 0, blue: 0 }
+        }
+    }
+}
+
+/// Langton's ant turns right on dead (white) cells and left on live (black) ones, flips the
+/// cell it is on and moves forward. It stops for good if it walks off a grid that doesn't
+/// wrap.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LangtonsAnt {
+    pub position: Option<(usize, usize)>,
+    /// Up, right, down and left are 0 to 3
+    pub direction: usize,
+}
+
+impl LangtonsAnt {
+    /// An ant facing up
+    pub fn new(x: usize, y: usize) -> LangtonsAnt {
+        LangtonsAnt { position: Some((x, y)), direction: 0 }
+    }
+}
+
+impl Rule for LangtonsAnt {
+    fn step(&mut self, grid: &Grid) -> Grid {
+        let mut next = grid.clone();
+        let (x, y) = match self.position {
+            Some(position) => position,
+            None => return next,
+        };
+        self.direction = if grid[(x, y)] == DEAD {
+            (self.direction + 1) % 4
+        } else {
+            (self.direction + 3) % 4
+        };
+        next[(x, y)] = if grid[(x, y)] == DEAD { ALIVE } else { DEAD };
+
+        let (dx, dy) = [(0, -1), (1, 0), (0, 1), (-1, 0)][self.direction];
+        let (nx, ny) = (x as isize + dx, y as isize + dy);
+        let (width, height) = (grid.width as isize, grid.height as isize);
+        self.position = if grid.wrap {
+            Some((((nx + width) % width) as usize, ((ny + height) % height) as usize))
+        } else if nx >= 0 && ny >= 0 && nx < width && ny < height {
+            Some((nx as usize, ny as usize))
+        } else {
+            None
+        };
+        next
+    }
+
+    fn color(&self, state: u8) -> Color {
+        if state == ALIVE {
+            Color { red: 0, green: 0, blue: 0 }
+        } else {
+            Color { red: 255, green: 255, blue: 255 }
+        }
+    }
+
+    fn state(&self) -> Vec<isize> {
+        match self.position {
+            Some((x, y)) => vec![x as isize, y as isize, self.direction as isize],
+            None => vec![-1],
+        }
+    }
+}
+
+/// One of the 256 one-dimensional rules with two states and three cells in each
+/// neighbourhood, numbered as Wolfram does. Every row of the grid is a separate line of cells.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Elementary(pub u8);
+
+impl Elementary {
+    /// A picture of the rule's history: the first row is `cells` and each row below is the
+    /// generation after the one above.
+    pub fn history(&self, cells: &[u8], generations: usize, wrap: bool) -> Grid {
+        let mut row = Grid { width: cells.len(), height: 1, cells: cells.to_vec(), wrap: wrap };
+        let mut history = Grid { width: cells.len(), height: 0, cells: Vec::new(), wrap: wrap };
+        let mut rule = *self;
+        for _ in 0..generations {
+            history.cells.extend(row.cells.iter().cloned());
+            history.height += 1;
+            row = rule.step(&row);
+        }
+        history
+    }
+}
+
+impl Rule for Elementary {
+    fn step(&mut self, grid: &Grid) -> Grid {
+        let Elementary(rule) = *self;
+        grid.map(|grid, x, y| {
+            let (x, y) = (x as isize, y as isize);
+            let index = (grid.get(x - 1, y) << 2) | (grid.get(x, y) << 1) | grid.get(x + 1, y);
+            (rule >> index) & 1
+        })
+    }
+
+    fn color(&self, state: u8) -> Color {
+        if state == ALIVE {
+            Color { red: 0, green: 0, blue: 0 }
+        } else {
+            Color { red: 255, green: 255, blue: 255 }
+        }
+    }
+}
+
+/// A repeat found by Automaton::find_cycle: the generation at `start` comes back every
+/// `period` generations.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+pub struct Automaton<R> {
+    pub rule: R,
+    pub grid: Grid,
+    pub generation: usize,
+    // The generation each configuration was first seen in, once find_cycle starts looking
+    seen: HashMap<(Vec<u8>, Vec<isize>), usize>,
+}
+
+impl<R: Rule> Automaton<R> {
+    pub fn new(rule: R, grid: Grid) -> Automaton<R> {
+        Automaton { rule: rule, grid: grid, generation: 0, seen: HashMap::new() }
+    }
+
+    pub fn step(&mut self) {
+        self.grid = self.rule.step(&self.grid);
+        self.generation += 1;
+    }
+
+    /// Runs until the grid and rule repeat a configuration seen before, or until `limit`
+    /// more generations have gone by without one. Every configuration is kept, so this is
+    /// for small grids.
+    pub fn find_cycle(&mut self, limit: usize) -> Option<Cycle> {
+        for i in 0..limit + 1 {
+            let key = (self.grid.cells.clone(), self.rule.state());
+            if let Some(&start) = self.seen.get(&key) {
+                return Some(Cycle { start: start, period: self.generation - start });
+            }
+            self.seen.insert(key, self.generation);
+            if i < limit {
+                self.step();
+            }
+        }
+        None
+    }
+
+    /// The grid with each cell drawn as a square `scale` pixels across.
+    pub fn render(&self, scale: usize) -> Image {
+        let mut image = Image::new(self.grid.width * scale, self.grid.height * scale);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                image[(x, y)] = self.rule.color(self.grid[(x / scale, y / scale)]);
+            }
+        }
+        image
+    }
+
+    /// Writes this generation and the ones after it as PPM files named after the generation,
+    /// like `prefix0000.ppm`, leaving the automaton at the generation after the last one.
+    pub fn write_frames(&mut self, prefix: &str, frames: usize, scale: usize)
+                        -> io::Result<()> {
+        for _ in 0..frames {
+            try!(self.render(scale).write_ppm(&format!("{}{:04}.ppm", prefix, self.generation)));
+            self.step();
+        }
+        Ok(())
+    }
+
+    /// Writes this generation and the ones after it as an animated GIF, showing each for
+    /// `delay` hundredths of a second, and leaves the automaton as write_frames does.
+    pub fn write_gif(&mut self, filename: &str, frames: usize, scale: usize, delay: u16)
+                     -> io::Result<()> {
+        let (width, height) = (self.grid.width * scale, self.grid.height * scale);
+        let mut gif = try!(GifWriter::create(filename, width, height, delay));
+        for _ in 0..frames {
+            try!(self.render(scale).write_gif_frame(&mut gif));
+            self.step();
+        }
+        gif.finish().map(|_| ())
+    }
+}
+
+// The width and height of the chunks of a SparseGrid
+const CHUNK: i64 = 32;
+
+/// An unbounded grid kept as square chunks, leaving out the ones with only state 0 in them.
+/// Each step only works out the chunks that changed in the one before and the chunks around
+/// them, so the quiet parts of a pattern, like Wireworld wires with no electrons on them, cost
+/// nothing.
+pub struct SparseGrid<R> {
+    pub rule: R,
+    pub generation: usize,
+    chunks: HashMap<(i64, i64), Vec<u8>>,
+    // The chunks changed by the last step or set since
+    active: HashSet<(i64, i64)>,
+}
+
+// The chunk holding a cell and the cell's index within it
+fn chunk_of(x: i64, y: i64) -> ((i64, i64), usize) {
+    // Rounding down rather than towards zero
+    let floor = |v: i64| if v >= 0 { v / CHUNK } else { (v + 1) / CHUNK - 1 };
+    let (cx, cy) = (floor(x), floor(y));
+    ((cx, cy), ((x - cx * CHUNK) + (y - cy * CHUNK) * CHUNK) as usize)
+}
+
+impl<R: LocalRule> SparseGrid<R> {
+    pub fn new(rule: R) -> SparseGrid<R> {
+        assert!(rule.next_cell(0, &[0; 8]) == 0,
+                "rules that change empty cells can't be run on a sparse grid");
+        SparseGrid { rule: rule, generation: 0, chunks: HashMap::new(), active: HashSet::new() }
+    }
+
+    /// The cells of a grid, with its top left corner at the origin. Whether the grid wraps is
+    /// ignored.
+    pub fn from_grid(rule: R, grid: &Grid) -> SparseGrid<R> {
+        let mut sparse = SparseGrid::new(rule);
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                if grid[(x, y)] != 0 {
+                    sparse.set(x as i64, y as i64, grid[(x, y)]);
+                }
+            }
+        }
+        sparse
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> u8 {
+        let (chunk, i) = chunk_of(x, y);
+        self.chunks.get(&chunk).map_or(0, |cells| cells[i])
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, state: u8) {
+        let (chunk, i) = chunk_of(x, y);
+        if state == 0 && !self.chunks.contains_key(&chunk) {
+            return;
+        }
+        self.chunks.entry(chunk).or_insert_with(|| vec![0; (CHUNK * CHUNK) as usize])[i] = state;
+        self.active.insert(chunk);
+    }
+
+    /// How many chunks are stored, and how many of them the next step will look at, along
+    /// with their neighbours.
+    pub fn chunks(&self) -> (usize, usize) {
+        (self.chunks.len(), self.active.len())
+    }
+
+    /// How many cells are in a state other than 0.
+    pub fn population(&self) -> usize {
+        self.chunks.values().fold(0, |sum, cells| sum + cells.iter().filter(|&&c| c != 0).count())
+    }
+
+    /// The top left corner and the size of the smallest rectangle holding every cell not in
+    /// state 0, or None if there are none.
+    pub fn bounds(&self) -> Option<((i64, i64), (usize, usize))> {
+        let mut bounds: Option<(i64, i64, i64, i64)> = None;
+        for (&(cx, cy), cells) in &self.chunks {
+            for (i, _) in cells.iter().enumerate().filter(|&(_, &c)| c != 0) {
+                let (x, y) = (cx * CHUNK + i as i64 % CHUNK, cy * CHUNK + i as i64 / CHUNK);
+                bounds = Some(match bounds {
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                    None => (x, y, x, y),
+                });
+            }
+        }
+        bounds.map(|(x0, y0, x1, y1)| ((x0, y0), ((x1 - x0 + 1) as usize, (y1 - y0 + 1) as usize)))
+    }
+
+    /// The rectangle with its top left corner at (x, y), as an ordinary grid.
+    pub fn window(&self, x: i64, y: i64, width: usize, height: usize) -> Grid {
+        let mut grid = Grid::new(width, height);
+        for wy in 0..height {
+            for wx in 0..width {
+                grid[(wx, wy)] = self.get(x + wx as i64, y + wy as i64);
+            }
+        }
+        grid
+    }
+
+    // A chunk with a border of one cell taken from the chunks around it
+    fn padded(&self, (cx, cy): (i64, i64)) -> Vec<u8> {
+        let side = CHUNK + 2;
+        let mut cells = vec![0; (side * side) as usize];
+        for dy in -1..2 {
+            for dx in -1..2 {
+                let chunk = match self.chunks.get(&(cx + dx, cy + dy)) {
+                    Some(chunk) => chunk,
+                    None => continue,
+                };
+                // The part of the neighbouring chunk that falls inside the border
+                let part = |d: i64| {
+                    if d < 0 { CHUNK - 1..CHUNK } else if d > 0 { 0..1 } else { 0..CHUNK }
+                };
+                for y in part(dy) {
+                    for x in part(dx) {
+                        let (px, py) = (x + 1 + dx * CHUNK, y + 1 + dy * CHUNK);
+                        cells[(px + py * side) as usize] = chunk[(x + y * CHUNK) as usize];
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    pub fn step(&mut self) {
+        let mut candidates = HashSet::new();
+        for &(cx, cy) in &self.active {
+            for dy in -1..2 {
+                for dx in -1..2 {
+                    candidates.insert((cx + dx, cy + dy));
+                }
+            }
+        }
+
+        let side = CHUNK + 2;
+        let mut changes = Vec::new();
+        for chunk in candidates {
+            let padded = self.padded(chunk);
+            if !self.chunks.contains_key(&chunk) && padded.iter().all(|&c| c == 0) {
+                continue;
+            }
+            let mut next = Vec::with_capacity((CHUNK * CHUNK) as usize);
+            for y in 1..CHUNK + 1 {
+                for x in 1..CHUNK + 1 {
+                    let at = |dx: i64, dy: i64| padded[(x + dx + (y + dy) * side) as usize];
+                    let neighborhood = [at(-1, -1), at(0, -1), at(1, -1), at(-1, 0), at(1, 0),
+                                        at(-1, 1), at(0, 1), at(1, 1)];
+                    next.push(self.rule.next_cell(at(0, 0), &neighborhood));
+                }
+            }
+            let changed = match self.chunks.get(&chunk) {
+                Some(cells) => *cells != next,
+                None => next.iter().any(|&c| c != 0),
+            };
+            if changed {
+                changes.push((chunk, next));
+            }
+        }
+
+        self.active.clear();
+        for (chunk, cells) in changes {
+            self.active.insert(chunk);
+            if cells.iter().all(|&c| c == 0) {
+                self.chunks.remove(&chunk);
+            } else {
+                self.chunks.insert(chunk, cells);
+            }
+        }
+        self.generation += 1;
+    }
+}
+
+impl<R: LocalRule + Rule> SparseGrid<R> {
+    /// The rectangle with its top left corner at (x, y), each cell drawn as a square `scale`
+    /// pixels across.
+    pub fn render(&self, x: i64, y: i64, width: usize, height: usize, scale: usize) -> Image {
+        let grid = self.window(x, y, width, height);
+        let mut image = Image::new(width * scale, height * scale);
+        for py in 0..image.height {
+            for px in 0..image.width {
+                image[(px, py)] = self.rule.color(grid[(px / scale, py / scale)]);
+            }
+        }
+        image
+    }
+}
+
+#[cfg(not(test))]
+#[allow(dead_code)]
+pub fn main() {
+    // A glider gun, as it appears in most collections
+    let gun = "#N Gosper glider gun
+x = 36, y = 9, rule = B3/S23
+24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4bobo$
+10bo5bo7bo$11bo3bo$12b2o!";
+    let pattern = Pattern::parse_rle(gun).unwrap();
+    let mut grid = Grid::new(60, 40);
+    for y in 0..pattern.grid.height {
+        for x in 0..pattern.grid.width {
+            grid[(x + 2, y + 2)] = pattern.grid[(x, y)];
+        }
+    }
+    let rule = Life::parse(&pattern.rule.unwrap()).unwrap();
+    let mut life = Automaton::new(rule, grid.clone());
+    match life.write_frames("life", 60, 6) {
+        Ok(()) => println!("Wrote life0000.ppm to life0059.ppm"),
+        Err(e) => println!("Can't write the frames: {}", e),
+    }
+    match Automaton::new(rule, grid).write_gif("life.gif", 60, 6, 10) {
+        Ok(()) => println!("Wrote life.gif"),
+        Err(e) => println!("Can't write life.gif: {}", e),
+    }
+
+    let mut ant = Automaton::new(LangtonsAnt::new(50, 50), Grid::new(100, 100));
+    while ant.rule.position.is_some() {
+        ant.step();
+    }
+    println!("Langton's ant left the grid after {} steps", ant.generation);
+
+    let mut cells = vec![DEAD; 79];
+    cells[39] = ALIVE;
+    let history = Elementary(90).history(&cells, 40, false);
+    for row in history.cells.chunks(history.width) {
+        println!("{}", row.iter().map(|&c| if c == ALIVE { '#' } else { ' ' })
+                            .collect::<String>());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Automaton, Cycle, Elementary, Grid, LangtonsAnt, Life, Pattern, PatternError,
+                Rule, SparseGrid, Wireworld, ALIVE, CONDUCTOR, DEAD, HEAD};
+
+    #[test]
+    fn life() {
+        let blinker = Pattern::parse_rle("x = 5, y = 5\n$b3o!").unwrap();
+        assert_eq!(blinker.grid.cells.iter().filter(|&&c| c == ALIVE).count(), 3);
+        assert_eq!(blinker.rule, None);
+        let mut life = Automaton::new(Life::conway(), blinker.grid);
+        assert_eq!(life.find_cycle(10), Some(Cycle { start: 0, period: 2 }));
+
+        // A glider crosses an 8x8 torus in 32 generations
+        let mut grid = Pattern::parse_rle("#C A glider\nx = 8, y = 8, rule = B3/S23\n\
+                                           bo$2bo$3o!").unwrap().grid;
+        grid.wrap = true;
+        let mut glider = Automaton::new(Life::conway(), grid);
+        assert_eq!(glider.find_cycle(100), Some(Cycle { start: 0, period: 32 }));
+
+        // Life without survival leaves nothing behind a single cell
+        let mut grid = Grid::new(3, 3);
+        grid[(1, 1)] = ALIVE;
+        let mut seeds = Automaton::new(Life::parse("b2/s").unwrap(), grid);
+        assert_eq!(seeds.find_cycle(5), Some(Cycle { start: 1, period: 1 }));
+
+        assert_eq!(Life::parse("23/36").unwrap(), Life::parse("B36/S23").unwrap());
+        for bad in &["B3", "B9/S23", "B3/Sx", ""] {
+            match Life::parse(bad) {
+                Err(PatternError::BadRule(_)) => {}
+                _ => panic!("{:?} should be a bad rule", bad),
+            }
+        }
+    }
+
+    #[test]
+    fn wireworld() {
+        let text = "tH.........\n.   .\n   ...\n.   .\nHt.. ......\n";
+        let mut wires = Automaton::new(Wireworld, Grid::parse_wireworld(text));
+        for _ in 0..10 {
+            wires.step();
+        }
+        assert_eq!(wires.grid.to_wireworld(),
+                   ".tH.tH.tH.t\nH   t      \n   HHH     \nH   .      \nt.tH ......\n");
+
+        // An electron going round a loop of ten cells
+        let mut clock = Automaton::new(Wireworld, Grid::parse_wireworld(" tH..\n.    .\n ...."));
+        assert_eq!(clock.find_cycle(20), Some(Cycle { start: 0, period: 10 }));
+        let image = clock.render(2);
+        assert_eq!((image.width, image.height), (12, 6));
+        assert_eq!(image[(1, 1)], Wireworld.color(clock.grid[(0, 0)]));
+
+        let rle = Pattern::parse_rle("x = 4, y = 1, rule = WireWorld\n.CAB!").unwrap();
+        assert_eq!(rle.grid.cells, vec![0, 3, 1, 2]);
+        assert_eq!(rle.rule, Some("WireWorld".to_string()));
+        assert_eq!(rle.grid[(2, 0)], HEAD);
+
+        // Blank lines make a grid with no width
+        assert_eq!(Grid::parse_wireworld("\n\n").to_wireworld(), "\n\n");
+    }
+
+    #[test]
+    fn rle_errors() {
+        match Pattern::parse_rle("#C nothing here") {
+            Err(PatternError::BadHeader(1)) => {}
+            _ => panic!(),
+        }
+        match Pattern::parse_rle("x = 2\n2o!") {
+            Err(PatternError::BadHeader(1)) => {}
+            _ => panic!(),
+        }
+        match Pattern::parse_rle("x = 2, y = 1\n3o!") {
+            Err(PatternError::OutOfBounds) => {}
+            _ => panic!(),
+        }
+        match Pattern::parse_rle("x = 2, y = 1\noz!") {
+            Err(PatternError::BadCell('z')) => {}
+            _ => panic!(),
+        }
+        // Runs of dead cells may go past the edge
+        assert!(Pattern::parse_rle("x = 2, y = 1\no5b!").is_ok());
+        // but not past what a count can hold
+        for bad in &["99999999999999999999999o!", "18446744073709551615b2b!",
+                     "$18446744073709551615$!"] {
+            match Pattern::parse_rle(&format!("x = 2, y = 1\n{}", bad)) {
+                Err(PatternError::OutOfBounds) => {}
+                _ => panic!("{:?} should be out of bounds", bad),
+            }
+        }
+    }
+
+    #[test]
+    fn langtons_ant() {
+        // At first the ant walks round a square and comes back to where it started
+        let mut ant = Automaton::new(LangtonsAnt::new(5, 5), Grid::new(10, 10));
+        for _ in 0..4 {
+            ant.step();
+        }
+        assert_eq!(ant.rule.position, Some((5, 5)));
+        assert_eq!(ant.grid.cells.iter().filter(|&&c| c == ALIVE).count(), 4);
+        // Once it walks off the grid nothing changes again
+        let cycle = ant.find_cycle(1000).unwrap();
+        assert_eq!((ant.rule.position, cycle.period), (None, 1));
+
+        // After its chaotic start the ant builds a highway, leaving any finite grid
+        let mut ant = Automaton::new(LangtonsAnt::new(40, 40), Grid::new(80, 80));
+        while ant.rule.position.is_some() {
+            ant.step();
+        }
+        assert!(ant.generation > 10000);
+    }
+
+    #[test]
+    fn elementary() {
+        let mut cells = vec![DEAD; 31];
+        cells[15] = ALIVE;
+        // Rule 90 draws Pascal's triangle mod 2, so row n has 2^(number of ones in n) cells
+        let history = Elementary(90).history(&cells, 16, false);
+        for (n, row) in history.cells.chunks(31).enumerate() {
+            let alive = row.iter().filter(|&&c| c == ALIVE).count();
+            assert_eq!(alive, 1 << (n as u32).count_ones());
+        }
+        let rule30 = Elementary(30).history(&cells, 3, false);
+        let row: Vec<u8> = rule30.cells[2 * 31 + 13..2 * 31 + 18].to_vec();
+        assert_eq!(row, vec![1, 1, 0, 0, 1]);
+        // On a ring, rule 204 changes nothing
+        let mut ring = Automaton::new(Elementary(204), Elementary(0).history(&cells, 1, true));
+        assert_eq!(ring.find_cycle(3), Some(Cycle { start: 0, period: 1 }));
+    }
+
+    #[test]
+    fn sparse_grids() {
+        let text = "tH.........\n.   .\n   ...\n.   .\nHt.. ......\n";
+        let mut dense = Automaton::new(Wireworld, Grid::parse_wireworld(text));
+        let mut sparse = SparseGrid::from_grid(Wireworld, &dense.grid);
+        for _ in 0..20 {
+            dense.step();
+            sparse.step();
+            assert_eq!(sparse.window(0, 0, 11, 5), dense.grid);
+        }
+        assert_eq!(sparse.bounds(), Some(((0, 0), (11, 5))));
+
+        // A long wire with no electrons on it settles down at once
+        let mut wire = SparseGrid::new(Wireworld);
+        for x in -1000..1000 {
+            wire.set(x, -3, CONDUCTOR);
+        }
+        assert_eq!(wire.chunks(), (64, 64));
+        wire.step();
+        assert_eq!(wire.chunks(), (64, 0));
+        wire.set(-1000, -3, HEAD);
+        wire.step();
+        assert_eq!(wire.get(-999, -3), HEAD);
+        assert_eq!(wire.chunks(), (64, 1));
+
+        // Gliders crossing the edges of chunks, at negative coordinates too
+        let glider = Pattern::parse_rle("x = 3, y = 3\nbo$2bo$3o!").unwrap().grid;
+        let mut sparse = SparseGrid::new(Life::conway());
+        let mut dense = Automaton::new(Life::conway(), Grid::new(120, 120));
+        for &(x, y) in &[(-40, -40), (-2, 25), (29, 29)] {
+            for gy in 0..3 {
+                for gx in 0..3 {
+                    sparse.set(x + gx, y + gy, glider[(gx as usize, gy as usize)]);
+                    dense.grid[((x + gx + 50) as usize, (y + gy + 50) as usize)] =
+                        glider[(gx as usize, gy as usize)];
+                }
+            }
+        }
+        for _ in 0..100 {
+            sparse.step();
+            dense.step();
+        }
+        assert_eq!(sparse.window(-50, -50, 120, 120), dense.grid);
+        assert_eq!(sparse.population(), 15);
+        sparse.set(0, 0, DEAD);
+        assert_eq!(sparse.get(0, 0), DEAD);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sparse_birth_on_zero() {
+        // Every empty cell would be born, out to infinity
+        SparseGrid::new(Life::parse("B0/S").unwrap());
+    }
+
+    #[test]
+    fn hashlife() {
+        let mut grid = Grid::new(10, 10);
+        for &(x, y) in &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            grid[(x, y)] = ALIVE;
+        }
+        let mut life = Automaton::new(Life::conway(), grid.clone());
+        let mut universe = Life::conway().hashlife(&grid);
+        for _ in 0..24 {
+            life.step();
+        }
+        universe.advance(24).unwrap();
+        let mut cells: Vec<(i64, i64)> = Vec::new();
+        for y in 0..10 {
+            for x in 0..10 {
+                if life.grid[(x, y)] == ALIVE {
+                    cells.push((x as i64, y as i64));
+                }
+            }
+        }
+        let mut found = universe.cells();
+        found.sort_by(|a, b| (a.1, a.0).cmp(&(b.1, b.0)));
+        assert_eq!(found, cells);
+    }
+}
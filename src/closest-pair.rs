@@ -1,19 +1,18 @@
 // Implements http://rosettacode.org/wiki/Closest-pair_problem
 
-// We interpret complex numbers as points in the Cartesian plane, here.
-// We also use the sweepline/plane sweep closest pairs algorithm
+// Points come from the geometry module.
+// We use the sweepline/plane sweep closest pairs algorithm
 // (http://www.cs.mcgill.ca/~cs251/ClosestPair/ClosestPairPS.html) instead
 // of the divide-and-conquer algorithm, since it's (arguably)
 // easier to implement, and an efficient implementation does not require
 // use of unsafe.
-extern crate num;
-
 use std::cmp::{PartialOrd, Ordering};
-use num::complex::Complex;
 use std::collections::BTreeSet;
-type Point = Complex<f32>;
+use geometry::Point;
+#[allow(dead_code)]
+mod geometry;
 
-// Wrapper around Point (i.e. Complex<f32>) so that we can use a TreeSet
+// Wrapper around Point so that we can use a TreeSet
 #[derive(PartialEq)]
 struct YSortedPoint {
     point: Point
@@ -21,7 +20,7 @@ struct YSortedPoint {
 
 impl PartialOrd for YSortedPoint {
     fn partial_cmp(&self, other: &YSortedPoint) -> Option<Ordering> {
-        (self.point.im, self.point.re).partial_cmp(&(other.point.im, other.point.re))
+        (self.point.y, self.point.x).partial_cmp(&(other.point.y, other.point.x))
     }
 }
 
@@ -38,7 +37,7 @@ fn closest_pair(points: &mut [Point]) -> Option<(Point, Point)> {
         return None
     }
 
-    points.sort_by(|a, b| (a.re, a.im).partial_cmp(&(b.re, b.im)).unwrap());
+    points.sort_by(|a, b| a.cmp_xy(b));
 
     let mut closest_pair = (points[0], points[1]);
     let mut closest_distance_sqr = (points[0] - points[1]).norm_sqr();
@@ -58,7 +57,7 @@ fn closest_pair(points: &mut [Point]) -> Option<(Point, Point)> {
         // along the x-axis
         while leftmost_idx < idx {
             let leftmost_point = &points[leftmost_idx];
-            if (leftmost_point.re - point.re).powi(2) < closest_distance_sqr {
+            if (leftmost_point.x - point.x).powi(2) < closest_distance_sqr {
                 break;
             }
             strip.remove(&YSortedPoint { point: leftmost_point.clone() });
@@ -68,7 +67,7 @@ fn closest_pair(points: &mut [Point]) -> Option<(Point, Point)> {
         // Compare to points in bounding box
         {
             let low_bound = YSortedPoint {
-                point: Point { re: ::std::f32::INFINITY, im: point.im - closest_distance }
+                point: Point { x: ::std::f64::INFINITY, y: point.y - closest_distance }
             };
             let mut strip_iter = strip.iter().skip_while(|&p| p < &low_bound);
             loop {
@@ -76,7 +75,7 @@ fn closest_pair(points: &mut [Point]) -> Option<(Point, Point)> {
                     None => break,
                     Some(p) => p.point
                 };
-                if point2.im - point.im >= closest_distance {
+                if point2.y - point.y >= closest_distance {
                     // we've reached the end of the box
                     break;
                 }
@@ -99,46 +98,46 @@ fn closest_pair(points: &mut [Point]) -> Option<(Point, Point)> {
 #[cfg(not(test))]
 pub fn main() {
     let mut test_data = [
-        Complex::new(0.654682, 0.925557),
-        Complex::new(0.409382, 0.619391),
-        Complex::new(0.891663, 0.888594),
-        Complex::new(0.716629, 0.996200),
-        Complex::new(0.477721, 0.946355),
-        Complex::new(0.925092, 0.818220),
-        Complex::new(0.624291, 0.142924),
-        Complex::new(0.211332, 0.221507),
-        Complex::new(0.293786, 0.691701),
-        Complex::new(0.839186, 0.728260)
+        Point::new(0.654682, 0.925557),
+        Point::new(0.409382, 0.619391),
+        Point::new(0.891663, 0.888594),
+        Point::new(0.716629, 0.996200),
+        Point::new(0.477721, 0.946355),
+        Point::new(0.925092, 0.818220),
+        Point::new(0.624291, 0.142924),
+        Point::new(0.211332, 0.221507),
+        Point::new(0.293786, 0.691701),
+        Point::new(0.839186, 0.728260)
     ];
     let (p1, p2) = closest_pair(&mut test_data[..]).unwrap();
     println!("Closest pair: {} and {}", p1, p2);
-    println!("Distance: {}", (p1 - p2).norm_sqr().sqrt());
+    println!("Distance: {}", (p1 - p2).norm());
 }
 
 #[cfg(test)]
 mod test {
     use super::closest_pair;
-    use num::complex::Complex;
+    use geometry::Point;
 
     #[test]
     fn random_floats() {
         let mut test_data = [
-            Complex::new(0.654682, 0.925557),
-            Complex::new(0.409382, 0.619391),
-            Complex::new(0.891663, 0.888594),
-            Complex::new(0.716629, 0.996200),
-            Complex::new(0.477721, 0.946355),
-            Complex::new(0.925092, 0.818220),
-            Complex::new(0.624291, 0.142924),
-            Complex::new(0.211332, 0.221507),
-            Complex::new(0.293786, 0.691701),
-            Complex::new(0.839186, 0.728260)
+            Point::new(0.654682, 0.925557),
+            Point::new(0.409382, 0.619391),
+            Point::new(0.891663, 0.888594),
+            Point::new(0.716629, 0.996200),
+            Point::new(0.477721, 0.946355),
+            Point::new(0.925092, 0.818220),
+            Point::new(0.624291, 0.142924),
+            Point::new(0.211332, 0.221507),
+            Point::new(0.293786, 0.691701),
+            Point::new(0.839186, 0.728260)
         ];
         let (p1, p2) = closest_pair(&mut test_data[..]).unwrap();
-        assert!((p1.re - 0.891663).abs() < 1e-6f32);
-        assert!((p1.im - 0.888594).abs() < 1e-6f32);
-        assert!((p2.re - 0.925092).abs() < 1e-6f32);
-        assert!((p2.im - 0.818220).abs() < 1e-6f32);
-        assert!(((p1 - p2).norm_sqr() - 0.0779102f32.powi(2)).abs() < 1e-6f32);
+        assert!((p1.x - 0.891663).abs() < 1e-6);
+        assert!((p1.y - 0.888594).abs() < 1e-6);
+        assert!((p2.x - 0.925092).abs() < 1e-6);
+        assert!((p2.y - 0.818220).abs() < 1e-6);
+        assert!(((p1 - p2).norm_sqr() - 0.0779102f64.powi(2)).abs() < 1e-6);
     }
 }
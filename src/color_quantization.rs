@@ -0,0 +1,434 @@
+// Implements http://rosettacode.org/wiki/Color_quantization
+//
+// Picking a small palette for an image, by median cut or with an octree, and drawing the image
+// in a palette, either plainly, with error diffusion (Floyd-Steinberg or Atkinson) or with an
+// ordered (Bayer) dither. Two colors, black and white, give 1-bit PBM files.
+
+use std::io;
+use self::bitmap::{Color, Image};
+use self::bitmap::netpbm::{Netpbm, Kind, Encoding};
+
+// The images to quantize
+#[path = "bitmap.rs"]
+pub mod bitmap;
+
+fn channel(color: Color, i: usize) -> u8 {
+    match i {
+        0 => color.red,
+        1 => color.green,
+        _ => color.blue,
+    }
+}
+
+// The average of some colors, given the sums of their channels
+fn average(sums: [u64; 3], count: u64) -> Color {
+    let mean = |sum: u64| ((sum + count / 2) / count) as u8;
+    Color { red: mean(sums[0]), green: mean(sums[1]), blue: mean(sums[2]) }
+}
+
+/// A palette of at most `colors` colors by median cut: start with a box around every pixel's
+/// color, and keep splitting the box that is widest along one channel at the median pixel
+/// along that channel. Each box gives the average of its pixels.
+pub fn median_cut(image: &Image, colors: usize) -> Vec<Color> {
+    let mut boxes = vec![image.data.clone()];
+    while boxes.len() < colors {
+        // The widest box, the channel it is widest along, and how wide
+        let mut widest = None;
+        for (i, pixels) in boxes.iter().enumerate() {
+            for c in 0..3 {
+                let values = pixels.iter().map(|&p| channel(p, c));
+                let (min, max) = values.fold((255, 0), |(min, max), v| {
+                    (if v < min { v } else { min }, if v > max { v } else { max })
+                });
+                let range = if max > min { max - min } else { 0 };
+                if range > 0 && widest.map_or(true, |(_, _, widest)| range > widest) {
+                    widest = Some((i, c, range));
+                }
+            }
+        }
+        let (i, c) = match widest {
+            Some((i, c, _)) => (i, c),
+            // Every box holds a single color
+            None => break,
+        };
+        let mut pixels = boxes.swap_remove(i);
+        pixels.sort_by(|a, b| channel(*a, c).cmp(&channel(*b, c)));
+        // Split at the median, but between two different values so neither half is empty
+        let mut middle = pixels.len() / 2;
+        while middle > 0 && channel(pixels[middle - 1], c) == channel(pixels[middle], c) {
+            middle -= 1;
+        }
+        if middle == 0 {
+            middle = pixels.len() / 2;
+            while channel(pixels[middle - 1], c) == channel(pixels[middle], c) {
+                middle += 1;
+            }
+        }
+        let upper = pixels.split_off(middle);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+    boxes.iter().filter(|pixels| !pixels.is_empty()).map(|pixels| {
+        let sums = pixels.iter().fold([0u64; 3], |mut sums, p| {
+            sums[0] += p.red as u64;
+            sums[1] += p.green as u64;
+            sums[2] += p.blue as u64;
+            sums
+        });
+        average(sums, pixels.len() as u64)
+    }).collect()
+}
+
+struct OctreeNode {
+    // Indices into the tree; 0, the root, means there is no child
+    children: [usize; 8],
+    sums: [u64; 3],
+    count: u64,
+    level: usize,
+    leaf: bool,
+}
+
+/// A palette of at most `colors` colors from an octree: every pixel's color goes down eight
+/// levels, one bit of each channel at a time, and then the least used branches are folded
+/// into their parents, deepest first, until few enough leaves are left. Each leaf gives the
+/// average of the pixels in it.
+pub fn octree(image: &Image, colors: usize) -> Vec<Color> {
+    let node = |level| OctreeNode {
+        children: [0; 8], sums: [0; 3], count: 0, level: level, leaf: level == 8
+    };
+    let mut tree = vec![node(0)];
+    let mut leaves = 0;
+    for &pixel in &image.data {
+        let mut current = 0;
+        for level in 0..8 {
+            let bit = |value: u8| ((value >> (7 - level)) & 1) as usize;
+            let index = bit(pixel.red) << 2 | bit(pixel.green) << 1 | bit(pixel.blue);
+            if tree[current].children[index] == 0 {
+                tree.push(node(level + 1));
+                let child = tree.len() - 1;
+                tree[current].children[index] = child;
+                if level == 7 {
+                    leaves += 1;
+                }
+            }
+            current = tree[current].children[index];
+        }
+        let leaf = &mut tree[current];
+        leaf.sums[0] += pixel.red as u64;
+        leaf.sums[1] += pixel.green as u64;
+        leaf.sums[2] += pixel.blue as u64;
+        leaf.count += 1;
+    }
+
+    // Fold nodes whose children are all leaves, deepest and least used first
+    let colors = if colors == 0 { 1 } else { colors };
+    for level in (0..8).rev() {
+        if leaves <= colors {
+            break;
+        }
+        // Everything below this level has been folded already, so the children are all leaves
+        let mut nodes: Vec<usize> = (0..tree.len()).filter(|&i| tree[i].level == level).collect();
+        for &i in &nodes {
+            let count = tree[i].children.iter().filter(|&&c| c != 0)
+                .fold(0, |n, &c| n + tree[c].count);
+            tree[i].count = count;
+        }
+        nodes.sort_by(|&a, &b| tree[a].count.cmp(&tree[b].count));
+        for i in nodes {
+            if leaves <= colors {
+                break;
+            }
+            let children = tree[i].children;
+            let mut sums = [0; 3];
+            let mut merged = 0;
+            for &c in children.iter().filter(|&&c| c != 0) {
+                for k in 0..3 {
+                    sums[k] += tree[c].sums[k];
+                }
+                merged += 1;
+            }
+            tree[i].sums = sums;
+            tree[i].children = [0; 8];
+            tree[i].leaf = true;
+            leaves = leaves + 1 - merged;
+        }
+    }
+
+    // The leaves still hanging from the root
+    let mut palette = Vec::new();
+    let mut stack = vec![0];
+    while let Some(i) = stack.pop() {
+        if tree[i].leaf {
+            if tree[i].count > 0 {
+                palette.push(average(tree[i].sums, tree[i].count));
+            }
+        } else {
+            stack.extend(tree[i].children.iter().cloned().filter(|&c| c != 0));
+        }
+    }
+    palette
+}
+
+/// The index of the palette color closest to a color, by distance in RGB space.
+pub fn nearest(palette: &[Color], color: [f64; 3]) -> usize {
+    let distance = |p: &Color| {
+        let (r, g, b) = (p.red as f64 - color[0], p.green as f64 - color[1],
+                         p.blue as f64 - color[2]);
+        r * r + g * g + b * b
+    };
+    let mut best = 0;
+    for (i, p) in palette.iter().enumerate() {
+        if distance(p) < distance(&palette[best]) {
+            best = i;
+        }
+    }
+    best
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Dither {
+    /// Each pixel becomes the closest palette color
+    None,
+    /// The error at each pixel is passed on to the four neighbours not done yet
+    FloydSteinberg,
+    /// The error is passed on six neighbours, three quarters of it, which keeps more contrast
+    Atkinson,
+    /// Pixels are pushed up or down by a threshold map of the given size, a power of two
+    Bayer(usize),
+}
+
+// Where error diffusion sends each pixel's error, and what part of it
+const FLOYD_STEINBERG: [(isize, isize, f64); 4] =
+    [(1, 0, 7.0 / 16.0), (-1, 1, 3.0 / 16.0), (0, 1, 5.0 / 16.0), (1, 1, 1.0 / 16.0)];
+const ATKINSON: [(isize, isize, f64); 6] =
+    [(1, 0, 0.125), (2, 0, 0.125), (-1, 1, 0.125), (0, 1, 0.125), (1, 1, 0.125), (0, 2, 0.125)];
+
+/// The Bayer threshold map of a size that is a power of two, each number from 0 to size^2 - 1
+/// appearing once.
+pub fn bayer_matrix(size: usize) -> Vec<Vec<usize>> {
+    let mut matrix = vec![vec![0]];
+    while matrix.len() < size {
+        let n = matrix.len();
+        let mut bigger = vec![vec![0; 2 * n]; 2 * n];
+        for y in 0..n {
+            for x in 0..n {
+                let v = 4 * matrix[y][x];
+                bigger[y][x] = v;
+                bigger[y][x + n] = v + 2;
+                bigger[y + n][x] = v + 3;
+                bigger[y + n][x + n] = v + 1;
+            }
+        }
+        matrix = bigger;
+    }
+    matrix
+}
+
+/// The image drawn in the colors of a palette, which must not be empty.
+pub fn quantize(image: &Image, palette: &[Color], dither: Dither) -> Image {
+    assert!(!palette.is_empty(), "quantizing to an empty palette");
+    let mut values: Vec<[f64; 3]> = image.data.iter()
+        .map(|c| [c.red as f64, c.green as f64, c.blue as f64]).collect();
+    let mut output = Image::new(image.width, image.height);
+
+    let (diffusion, matrix): (&[(isize, isize, f64)], _) = match dither {
+        Dither::None => (&[], None),
+        Dither::FloydSteinberg => (&FLOYD_STEINBERG, None),
+        Dither::Atkinson => (&ATKINSON, None),
+        Dither::Bayer(size) => (&[], Some(bayer_matrix(size))),
+    };
+    // Ordered dithering moves each channel by up to half the largest gap between neighbouring
+    // palette colors, measured along the channel that differs most
+    let spread = palette.iter().map(|p| {
+        palette.iter().filter(|q| q != &p).map(|q| {
+            (0..3).map(|c| (channel(*p, c) as f64 - channel(*q, c) as f64).abs())
+                  .fold(0.0, f64::max)
+        }).fold(::std::f64::INFINITY, f64::min)
+    }).filter(|gap| gap.is_finite()).fold(0.0, f64::max);
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let i = x + y * image.width;
+            let mut value = values[i];
+            if let Some(ref matrix) = matrix {
+                let n = matrix.len();
+                let threshold = (matrix[y % n][x % n] as f64 + 0.5) / (n * n) as f64 - 0.5;
+                for c in 0..3 {
+                    value[c] += threshold * spread;
+                }
+            }
+            for c in 0..3 {
+                value[c] = value[c].max(0.0).min(255.0);
+            }
+            let chosen = palette[nearest(palette, value)];
+            output.data[i] = chosen;
+
+            for &(dx, dy, share) in diffusion {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                if nx < 0 || nx as usize >= image.width || ny as usize >= image.height {
+                    continue;
+                }
+                let target = &mut values[nx as usize + ny as usize * image.width];
+                for c in 0..3 {
+                    target[c] += (value[c] - channel(chosen, c) as f64) * share;
+                }
+            }
+        }
+    }
+    output
+}
+
+/// The image in black and white as a PBM image, dithered by its brightness.
+pub fn to_pbm(image: &Image, dither: Dither) -> Netpbm {
+    let black = Color { red: 0, green: 0, blue: 0 };
+    let white = Color { red: 255, green: 255, blue: 255 };
+    let gray = Image::from_gray(&image.to_gray());
+    let mut pbm = Netpbm::new(Kind::Bitmap, image.width, image.height, 1);
+    for (sample, &pixel) in pbm.samples.iter_mut()
+                               .zip(quantize(&gray, &[black, white], dither).data.iter()) {
+        *sample = if pixel == black { 1 } else { 0 };
+    }
+    pbm
+}
+
+/// Writes the image as a raw 1-bit PBM (P4) file, dithered by its brightness.
+pub fn write_pbm(image: &Image, filename: &str, dither: Dither) -> io::Result<()> {
+    to_pbm(image, dither).save(filename, Encoding::Raw)
+}
+
+#[cfg(not(test))]
+#[allow(dead_code)]
+pub fn main() {
+    use std::env;
+    use std::f64::consts::PI;
+
+    // color_quantization [image.ppm]: without an image, a color wheel
+    let image = match env::args().nth(1) {
+        Some(filename) => match Image::read_ppm(&filename) {
+            Ok(image) => image,
+            Err(e) => {
+                println!("Can't read {}: {}", filename, e);
+                return;
+            }
+        },
+        None => {
+            let mut wheel = Image::new(256, 256);
+            for y in 0..256 {
+                for x in 0..256 {
+                    let (dx, dy) = (x as f64 - 127.5, y as f64 - 127.5);
+                    let angle = dy.atan2(dx);
+                    let light = 1.0 - (dx * dx + dy * dy).sqrt() / 182.0;
+                    let wave = |offset: f64| {
+                        ((0.5 + 0.5 * (angle + offset).cos()) * 255.0 * light) as u8
+                    };
+                    wheel[(x, y)] = Color {
+                        red: wave(0.0), green: wave(2.0 * PI / 3.0), blue: wave(4.0 * PI / 3.0)
+                    };
+                }
+            }
+            wheel
+        }
+    };
+
+    // A thumbnail for an e-ink display, in sixteen colors and in black and white
+    let thumbnail = image.thumbnail(200, 200);
+    let palette = median_cut(&thumbnail, 16);
+    let outputs = vec![
+        ("quantized-median-cut.ppm", quantize(&thumbnail, &palette, Dither::FloydSteinberg)),
+        ("quantized-octree.ppm",
+         quantize(&thumbnail, &octree(&thumbnail, 16), Dither::Atkinson)),
+        ("quantized-bayer.ppm", quantize(&thumbnail, &palette, Dither::Bayer(8))),
+    ];
+    for (filename, output) in outputs {
+        match output.write_ppm(filename) {
+            Ok(()) => println!("Wrote {}", filename),
+            Err(e) => println!("Can't write {}: {}", filename, e),
+        }
+    }
+    match write_pbm(&thumbnail, "quantized.pbm", Dither::FloydSteinberg) {
+        Ok(()) => println!("Wrote quantized.pbm"),
+        Err(e) => println!("Can't write quantized.pbm: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{median_cut, octree, quantize, to_pbm, bayer_matrix, Dither};
+    use super::bitmap::{Color, Image};
+
+    const BLACK: Color = Color { red: 0, green: 0, blue: 0 };
+    const WHITE: Color = Color { red: 255, green: 255, blue: 255 };
+
+    fn sorted(colors: Vec<Color>) -> Vec<(u8, u8, u8)> {
+        let mut triples: Vec<(u8, u8, u8)> = colors.into_iter()
+            .map(|c| (c.red, c.green, c.blue)).collect();
+        triples.sort();
+        triples
+    }
+
+    fn gray(level: u8, width: usize, height: usize) -> Image {
+        let mut image = Image::new(width, height);
+        image.fill(Color { red: level, green: level, blue: level });
+        image
+    }
+
+    #[test]
+    fn palettes() {
+        let colors = vec![Color { red: 200, green: 10, blue: 10 },
+                          Color { red: 10, green: 200, blue: 10 },
+                          Color { red: 10, green: 10, blue: 200 },
+                          Color { red: 250, green: 250, blue: 0 }];
+        let mut image = Image::new(20, 20);
+        for (i, pixel) in image.data.iter_mut().enumerate() {
+            *pixel = colors[(i + i / 20) % 4];
+        }
+        // Asking for as many colors as there are, or more, gives them back
+        for palette in vec![median_cut(&image, 4), octree(&image, 4), median_cut(&image, 10),
+                            octree(&image, 10)] {
+            assert_eq!(sorted(palette), sorted(colors.clone()));
+        }
+        // Asking for fewer colors merges the closest
+        let mut shades = Image::new(4, 1);
+        shades.data = vec![BLACK, Color { red: 10, green: 10, blue: 10 }, WHITE,
+                           Color { red: 250, green: 250, blue: 250 }];
+        assert_eq!(sorted(median_cut(&shades, 2)), vec![(5, 5, 5), (253, 253, 253)]);
+        assert_eq!(sorted(octree(&shades, 2)), vec![(5, 5, 5), (253, 253, 253)]);
+        assert_eq!(octree(&shades, 1).len(), 1);
+    }
+
+    #[test]
+    fn dithering() {
+        let palette = [BLACK, WHITE];
+        let whites = |image: &Image| image.data.iter().filter(|&&c| c == WHITE).count();
+        let image = gray(128, 32, 32);
+        assert_eq!(whites(&quantize(&image, &palette, Dither::None)), 1024);
+        for &(dither, low, high) in &[(Dither::FloydSteinberg, 500, 530),
+                                      (Dither::Atkinson, 480, 560),
+                                      (Dither::Bayer(4), 512, 512)] {
+            let output = quantize(&image, &palette, dither);
+            assert!(output.data.iter().all(|&c| c == BLACK || c == WHITE));
+            let count = whites(&output);
+            assert!(count >= low && count <= high, "{:?} gave {} white pixels", dither, count);
+        }
+        // A quarter gray comes out a quarter white with ordered dithering
+        assert_eq!(whites(&quantize(&gray(64, 32, 32), &palette, Dither::Bayer(8))), 256);
+
+        assert_eq!(bayer_matrix(2), vec![vec![0, 2], vec![3, 1]]);
+        let mut values: Vec<usize> = bayer_matrix(8).into_iter().flat_map(|row| row).collect();
+        values.sort();
+        assert_eq!(values, (0..64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pbm() {
+        let mut image = gray(255, 10, 2);
+        image[(3, 1)] = BLACK;
+        image[(4, 1)] = Color { red: 255, green: 0, blue: 0 };
+        let pbm = to_pbm(&image, Dither::None);
+        assert_eq!(pbm.samples.iter().filter(|&&s| s == 1).count(), 2);
+        assert_eq!((pbm.samples[13], pbm.samples[14], pbm.samples[15]), (1, 1, 0));
+        let mut bytes = Vec::new();
+        pbm.write(&mut bytes, super::Encoding::Raw).unwrap();
+        assert_eq!(bytes, b"P4\n10 2\n\x00\x00\x18\x00".to_vec());
+    }
+}
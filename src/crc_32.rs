@@ -1,12 +1,13 @@
 // http://rosettacode.org/wiki/CRC-32
 
 #[cfg(not(test))]
-fn main() {
+#[allow(dead_code)]
+pub fn main() {
     let s = "The quick brown fox jumps over the lazy dog";
     println!("{:X}", crc(s.as_bytes()));
 }
 
-fn crc(bytes: &[u8]) -> u32 {
+pub fn crc(bytes: &[u8]) -> u32 {
     // Store the CRC of all possible 256 one byte values in table
     let mut table: [u32; 256] = [0; 256];
     for i in (0..table.len()) {
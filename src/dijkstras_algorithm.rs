@@ -1,145 +1,1346 @@
 // Implements http://rosettacode.org/wiki/Dijkstra's_algorithm
+//
+// The graph is generic over the vertex names and the edge weights, and can be directed or
+// undirected. Besides Dijkstra it provides traversals, topological sorting, strongly connected
+// components, Bellman-Ford, A* and Floyd-Warshall, minimum spanning trees by Kruskal and Prim,
+// and maximum flows with minimum cuts by Edmonds-Karp and Dinic. Graphs can be read from edge
+// lists, CSV and Graphviz DOT, written to CSV and DOT, and serialized to JSON.
+extern crate rustc_serialize;
 
-use std::collections::{HashMap, BinaryHeap, LinkedList};
-use std::collections::hash_map::Entry::{Occupied, Vacant};
-use std::iter::repeat;
-use std::cmp::Ordering;
-use std::usize;
+use rustc_serialize::{json, Decodable, Encodable};
+use std::borrow::Borrow;
+use std::cmp::{min, Ordering};
+use std::collections::{HashMap, BinaryHeap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufRead, BufReader};
+use std::ops::{Add, Sub};
+use std::str::FromStr;
 
 type Node = usize;
-type Cost = usize;
-type Edge = (Node, Node);
 
+/// Edge weights. `one` is the weight of edges loaded without one.
+pub trait Weight: Copy + PartialOrd + Add<Output=Self> + Sub<Output=Self> + fmt::Debug {
+    fn zero() -> Self;
+    fn one() -> Self;
+}
+
+macro_rules! impl_weight {
+    ($($t:ty),*) => {
+        $(impl Weight for $t {
+            fn zero() -> $t { 0 as $t }
+            fn one() -> $t { 1 as $t }
+        })*
+    }
+}
+
+impl_weight!(usize, u32, u64, isize, i32, i64, f32, f64);
 
 /// The DistPair struct is for the Priority Queue.
-#[derive(Eq, PartialEq, PartialOrd)]
-struct DistPair(Node, Cost);
-impl Ord for DistPair {
-    fn cmp(&self, other:&DistPair) -> Ordering {
-        let DistPair(_, dist_a) = *self;
-        let DistPair(_, dist_b) = *other;
-        dist_b.cmp(&dist_a) //Intentionally reversed
+struct DistPair<W>(Node, W);
+
+impl<W: PartialOrd> Ord for DistPair<W> {
+    fn cmp(&self, other: &DistPair<W>) -> Ordering {
+        let DistPair(_, ref dist_a) = *self;
+        let DistPair(_, ref dist_b) = *other;
+        // Intentionally reversed. Incomparable weights (NaN) count as equal.
+        dist_b.partial_cmp(dist_a).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl<W: PartialOrd> PartialOrd for DistPair<W> {
+    fn partial_cmp(&self, other: &DistPair<W>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<W: PartialOrd> PartialEq for DistPair<W> {
+    fn eq(&self, other: &DistPair<W>) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<W: PartialOrd> Eq for DistPair<W> {}
+
+/// Graph structure, represented as an Adjacency List. Every vertex is identified by its index
+/// into `vertices`; `index` maps the vertices back to their indices.
+pub struct Graph<V, W> {
+    vertices: Vec<V>,
+    index: HashMap<V, Node>,
+    adj_list: Vec<Vec<(Node, W)>>,
+    directed: bool,
+}
+
+/// Returned when a negative cycle makes shortest paths meaningless.
+#[derive(Debug, PartialEq)]
+pub struct NegativeCycle;
+
+/// Distances and paths from a single source, as found by Dijkstra or Bellman-Ford.
+pub struct ShortestPaths<'g, V: 'g, W: 'g> {
+    graph: &'g Graph<V, W>,
+    dist: Vec<Option<W>>,
+    prev: Vec<Option<Node>>,
+}
+
+impl<'g, V, W> ShortestPaths<'g, V, W> where V: Hash + Eq + Clone, W: Weight {
+    /// The length of the shortest path to the target, or None if it is unreachable.
+    pub fn distance<Q: ?Sized>(&self, target: &Q) -> Option<W> where V: Borrow<Q>, Q: Hash + Eq {
+        self.graph.vertex_index(target).and_then(|t| self.dist[t])
+    }
+
+    /// The vertices along the shortest path to the target, or an empty vector if it is
+    /// unreachable.
+    pub fn path<Q: ?Sized>(&self, target: &Q) -> Vec<V> where V: Borrow<Q>, Q: Hash + Eq {
+        match self.graph.vertex_index(target) {
+            Some(t) if self.dist[t].is_some() => self.graph.trace_path(&self.prev, t),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mark {
+    Unvisited,
+    Active,
+    Done,
+}
+
+/// Bookkeeping of Tarjan's strongly connected components algorithm.
+struct Tarjan {
+    index: Vec<Option<usize>>,
+    low: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<Node>,
+    counter: usize,
+    components: Vec<Vec<Node>>,
+}
+
+impl Tarjan {
+    fn enter(&mut self, u: Node) {
+        self.index[u] = Some(self.counter);
+        self.low[u] = self.counter;
+        self.counter += 1;
+        self.stack.push(u);
+        self.on_stack[u] = true;
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    // The number of a line that could not be parsed
+    Parse(usize),
+    Json(json::DecoderError),
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<json::DecoderError> for LoadError {
+    fn from(err: json::DecoderError) -> LoadError {
+        LoadError::Json(err)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err) => write!(f, "{}", err),
+            LoadError::Parse(line) => write!(f, "syntax error on line {}", line),
+            LoadError::Json(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum DotToken {
+    // An identifier, and whether it was quoted
+    Id(String, bool),
+    Arrow,
+    Line,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Equals,
+    Semicolon,
+    Comma,
+}
+
+/// Splits DOT source into tokens, each with its line number. Comments are dropped.
+fn dot_tokens(text: &str) -> Result<Vec<(DotToken, usize)>, LoadError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let next = chars.get(i + 1).cloned();
+        let token = match chars[i] {
+            '\n' => { line += 1; i += 1; continue; }
+            c if c.is_whitespace() => { i += 1; continue; }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' { i += 1; }
+                continue;
+            }
+            '/' if next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' { i += 1; }
+                continue;
+            }
+            '/' if next == Some('*') => {
+                i += 2;
+                while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                    if chars[i] == '\n' { line += 1; }
+                    i += 1;
+                }
+                if i + 1 >= chars.len() { return Err(LoadError::Parse(line)); }
+                i += 2;
+                continue;
+            }
+            '-' if next == Some('>') => { i += 2; DotToken::Arrow }
+            '-' if next == Some('-') => { i += 2; DotToken::Line }
+            '{' => { i += 1; DotToken::LBrace }
+            '}' => { i += 1; DotToken::RBrace }
+            '[' => { i += 1; DotToken::LBracket }
+            ']' => { i += 1; DotToken::RBracket }
+            '=' => { i += 1; DotToken::Equals }
+            ';' => { i += 1; DotToken::Semicolon }
+            ',' => { i += 1; DotToken::Comma }
+            '"' => {
+                let start_line = line;
+                let mut id = String::new();
+                i += 1;
+                loop {
+                    match (chars.get(i).cloned(), chars.get(i + 1).cloned()) {
+                        (None, _) => return Err(LoadError::Parse(start_line)),
+                        (Some('"'), _) => { i += 1; break; }
+                        (Some('\\'), Some(c)) if c == '"' || c == '\\' => { id.push(c); i += 2; }
+                        // A backslash before a newline continues the line
+                        (Some('\\'), Some('\n')) => { line += 1; i += 2; }
+                        (Some(c), _) => {
+                            if c == '\n' { line += 1; }
+                            id.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                DotToken::Id(id, true)
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let mut id = c.to_string();
+                i += 1;
+                while i < chars.len()
+                      && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                    id.push(chars[i]);
+                    i += 1;
+                }
+                DotToken::Id(id, false)
+            }
+            _ => return Err(LoadError::Parse(line)),
+        };
+        tokens.push((token, line));
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens of a DOT graph. Subgraphs and ports are not
+/// supported.
+struct DotParser {
+    tokens: Vec<(DotToken, usize)>,
+    pos: usize,
+}
+
+impl DotParser {
+    fn peek(&self) -> Option<DotToken> {
+        self.tokens.get(self.pos).map(|&(ref token, _)| token.clone())
+    }
+
+    fn next(&mut self) -> Option<DotToken> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    /// The line of the current token, or of the last one at the end of the input.
+    fn line(&self) -> usize {
+        let pos = min(self.pos, self.tokens.len().saturating_sub(1));
+        self.tokens.get(pos).map_or(1, |&(_, line)| line)
+    }
+
+    fn error(&self) -> LoadError {
+        LoadError::Parse(self.line())
+    }
+
+    fn eat(&mut self, token: DotToken) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: DotToken) -> Result<(), LoadError> {
+        if self.eat(token) { Ok(()) } else { Err(self.error()) }
+    }
+
+    /// Consumes an unquoted keyword, which DOT matches case-insensitively.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(DotToken::Id(ref id, false)) if id.to_lowercase() == keyword => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn id(&mut self) -> Result<String, LoadError> {
+        match self.peek() {
+            Some(DotToken::Id(id, _)) => { self.pos += 1; Ok(id) }
+            _ => Err(self.error()),
+        }
+    }
+
+    /// Any number of attribute lists, `[name=value, ...]`.
+    fn attributes(&mut self) -> Result<Vec<(String, String)>, LoadError> {
+        let mut attributes = Vec::new();
+        while self.eat(DotToken::LBracket) {
+            loop {
+                match self.next() {
+                    Some(DotToken::RBracket) => break,
+                    Some(DotToken::Comma) | Some(DotToken::Semicolon) => {}
+                    Some(DotToken::Id(name, _)) => {
+                        try!(self.expect(DotToken::Equals));
+                        attributes.push((name, try!(self.id())));
+                    }
+                    _ => { self.pos -= 1; return Err(self.error()); }
+                }
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+/// The weight of an edge: its `weight` attribute, or else its `label` if that is a number, or
+/// else one.
+fn dot_weight<W: Weight + FromStr>(attributes: &[(String, String)], line: usize)
+                                   -> Result<W, LoadError> {
+    if let Some(&(_, ref weight)) = attributes.iter().find(|&&(ref name, _)| name == "weight") {
+        return weight.parse().map_err(|_| LoadError::Parse(line));
+    }
+    Ok(attributes.iter()
+       .find(|&&(ref name, _)| name == "label")
+       .and_then(|&(_, ref label)| label.parse().ok())
+       .unwrap_or(W::one()))
+}
+
+fn dot_quote(id: &str) -> String {
+    format!("\"{}\"", id.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+/// Splits a CSV line into fields, which may be quoted with doubled quotes inside. Unquoted fields
+/// are trimmed. Returns None for an unterminated quote, or text after a closing quote.
+fn csv_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        while chars.peek() == Some(&' ') { chars.next(); }
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return None,
+                }
+            }
+            while chars.peek() == Some(&' ') { chars.next(); }
+            if chars.peek().map_or(false, |&c| c != ',') {
+                return None;
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == ',' { break; }
+                field.push(c);
+                chars.next();
+            }
+            field = field.trim().to_string();
+        }
+        fields.push(field);
+        if chars.next().is_none() {
+            return Some(fields);
+        }
+    }
+}
+
+fn csv_quote(field: &str) -> String {
+    if field.is_empty() || field.contains(',') || field.contains('"') || field.trim() != field {
+        format!("\"{}\"", field.replace("\"", "\"\""))
+    } else {
+        field.to_string()
     }
 }
 
-/// Graph structure, represented as an Adjancency List.
-struct Graph<'a> {
-    vertices: Vec<&'a str>,
-    adj_list: Vec<Vec<Node>>,
-    costs: HashMap<Edge, Cost>,
+/// The JSON form of a graph: the vertices, and for each of them the edges leaving it as
+/// [target index, weight].
+#[derive(RustcEncodable, RustcDecodable)]
+struct JsonGraph<V, W> {
+    directed: bool,
+    vertices: Vec<V>,
+    adjacency: Vec<Vec<(Node, W)>>,
 }
 
-impl<'a> Graph<'a> {
-    fn new() -> Graph<'a> {
-        let vertices:Vec<&str> = Vec::new();
-        let adj_list:Vec<Vec<Node>> = Vec::new();
-        let costs:HashMap<Edge, Cost> = HashMap::new();
+impl<V, W> Graph<V, W> where V: Hash + Eq + Clone, W: Weight {
+    /// Creates an empty directed graph.
+    pub fn new() -> Graph<V, W> {
+        Graph { vertices: Vec::new(), index: HashMap::new(), adj_list: Vec::new(), directed: true }
+    }
+
+    /// Creates an empty undirected graph, where every edge can be followed both ways.
+    pub fn undirected() -> Graph<V, W> {
+        Graph { directed: false, ..Graph::new() }
+    }
+
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    pub fn vertices(&self) -> &[V] {
+        &self.vertices
+    }
+
+    /// The edges leaving the given vertex, as (target, weight).
+    pub fn neighbors(&self, vertex: Node) -> &[(Node, W)] {
+        &self.adj_list[vertex]
+    }
 
-        Graph{vertices: vertices, adj_list: adj_list, costs: costs}
+    /// All edges as (from, to, weight). Undirected edges are listed once, from the lower index.
+    pub fn edges(&self) -> Vec<(Node, Node, W)> {
+        let mut edges = Vec::new();
+        for (u, adj) in self.adj_list.iter().enumerate() {
+            for &(v, weight) in adj {
+                if self.directed || u <= v {
+                    edges.push((u, v, weight));
+                }
+            }
+        }
+        edges
     }
 
     /// Returns the index of the vertex, or None if vertex
     /// not found.
-    fn vertex_index(&self, vertex: &str) -> Option<Node> {
-        for (idx, &v) in self.vertices.iter().enumerate(){
-            if v == vertex{return Some(idx)}
-        }
-        return None
+    pub fn vertex_index<Q: ?Sized>(&self, vertex: &Q) -> Option<Node>
+        where V: Borrow<Q>, Q: Hash + Eq
+    {
+        self.index.get(vertex).cloned()
     }
 
     /// Returns the index of the vertex. If vertex is not found, inserts
     /// the vertex.
-    fn get_or_insert_vertex(&mut self, vertex: &'a str) -> Node {
-        self.vertex_index(vertex)
-            .unwrap_or_else( || {
-                self.adj_list.push(Vec::new());
-                self.vertices.push(vertex);
-                self.vertices.len()-1
-            })
+    pub fn add_vertex(&mut self, vertex: V) -> Node {
+        if let Some(&idx) = self.index.get(&vertex) {
+            return idx;
+        }
+        let idx = self.vertices.len();
+        self.vertices.push(vertex.clone());
+        self.adj_list.push(Vec::new());
+        self.index.insert(vertex, idx);
+        idx
     }
 
-    /// Adds the given edge to the graph.
-    fn add_edge(&mut self, from: &'a str, to: &'a str, cost: usize) {
-        let from_idx = self.get_or_insert_vertex(from);
-        let to_idx = self.get_or_insert_vertex(to);
+    /// Adds the given edge to the graph, or updates its weight if it exists.
+    pub fn add_edge(&mut self, from: V, to: V, cost: W) {
+        let from_idx = self.add_vertex(from);
+        let to_idx = self.add_vertex(to);
+        self.set_arc(from_idx, to_idx, cost);
+        if !self.directed && from_idx != to_idx {
+            self.set_arc(to_idx, from_idx, cost);
+        }
+    }
 
-        match self.costs.entry((from_idx, to_idx)) {
-            Vacant(entry)   => {
-                self.adj_list[from_idx].push(to_idx);
-                entry.insert(cost);
-            },
-            Occupied(entry) => {
-                *entry.into_mut() = cost;
+    fn set_arc(&mut self, from: Node, to: Node, cost: W) {
+        match self.adj_list[from].iter().position(|&(v, _)| v == to) {
+            Some(i) => self.adj_list[from][i].1 = cost,
+            None => self.adj_list[from].push((to, cost)),
+        }
+    }
+
+    /// Follows `prev` back from the target to the start of the path.
+    fn trace_path(&self, prev: &[Option<Node>], target: Node) -> Vec<V> {
+        let mut path = vec![self.vertices[target].clone()];
+        let mut curr = target;
+        while let Some(parent) = prev[curr] {
+            curr = parent;
+            path.push(self.vertices[curr].clone());
+        }
+        path.reverse();
+        path
+    }
+
+    /// Vertices in the order a breadth first search from the start reaches them.
+    pub fn bfs<Q: ?Sized>(&self, start: &Q) -> Vec<V> where V: Borrow<Q>, Q: Hash + Eq {
+        let mut order = Vec::new();
+        let start = match self.vertex_index(start) {
+            Some(idx) => idx,
+            None => return order,
+        };
+        let mut seen = vec![false; self.vertices.len()];
+        let mut queue = VecDeque::new();
+        seen[start] = true;
+        queue.push_back(start);
+        while let Some(u) = queue.pop_front() {
+            order.push(self.vertices[u].clone());
+            for &(v, _) in &self.adj_list[u] {
+                if !seen[v] {
+                    seen[v] = true;
+                    queue.push_back(v);
+                }
+            }
+        }
+        order
+    }
+
+    /// Vertices in the order a depth first search from the start reaches them.
+    pub fn dfs<Q: ?Sized>(&self, start: &Q) -> Vec<V> where V: Borrow<Q>, Q: Hash + Eq {
+        let mut order = Vec::new();
+        let start = match self.vertex_index(start) {
+            Some(idx) => idx,
+            None => return order,
+        };
+        let mut seen = vec![false; self.vertices.len()];
+        let mut stack = vec![start];
+        while let Some(u) = stack.pop() {
+            if seen[u] {
+                continue;
+            }
+            seen[u] = true;
+            order.push(self.vertices[u].clone());
+            // Pushed in reverse, so the first neighbor is visited first
+            for &(v, _) in self.adj_list[u].iter().rev() {
+                if !seen[v] {
+                    stack.push(v);
+                }
             }
+        }
+        order
+    }
+
+    /// Orders the vertices so that every edge points forward, e.g. dependencies before their
+    /// dependents. If there is no such order, returns a cycle, starting and ending with the same
+    /// vertex.
+    pub fn topological_sort(&self) -> Result<Vec<V>, Vec<V>> {
+        let mut marks = vec![Mark::Unvisited; self.vertices.len()];
+        let mut order = Vec::with_capacity(self.vertices.len());
+        for u in 0..self.vertices.len() {
+            if marks[u] == Mark::Unvisited {
+                if let Err(cycle) = self.topological_visit(u, &mut marks, &mut order) {
+                    return Err(cycle.into_iter().map(|v| self.vertices[v].clone()).collect());
+                }
+            }
+        }
+        order.reverse();
+        Ok(order.into_iter().map(|v| self.vertices[v].clone()).collect())
+    }
+
+    /// Depth first search from `root`, adding vertices to `order` as they are finished. The
+    /// stack holds the current path, with the next edge to follow from each of its vertices.
+    fn topological_visit(&self, root: Node, marks: &mut Vec<Mark>, order: &mut Vec<Node>)
+                         -> Result<(), Vec<Node>> {
+        let mut path = vec![(root, 0)];
+        marks[root] = Mark::Active;
+        while !path.is_empty() {
+            let top = path.len() - 1;
+            let (u, i) = path[top];
+            if i == self.adj_list[u].len() {
+                path.pop();
+                marks[u] = Mark::Done;
+                order.push(u);
+                continue;
+            }
+            path[top].1 += 1;
+            let v = self.adj_list[u][i].0;
+            match marks[v] {
+                Mark::Unvisited => {
+                    marks[v] = Mark::Active;
+                    path.push((v, 0));
+                }
+                Mark::Active => {
+                    // v is on the current path, so the path from v back to v is a cycle
+                    let start = path.iter().position(|&(w, _)| w == v).unwrap();
+                    let mut cycle: Vec<Node> = path[start..].iter().map(|&(w, _)| w).collect();
+                    cycle.push(v);
+                    return Err(cycle);
+                }
+                Mark::Done => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Tarjan's algorithm. The components come in reverse topological order: no edge leads from
+    /// a component to one listed after it.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<V>> {
+        let n = self.vertices.len();
+        let mut state = Tarjan {
+            index: vec![None; n],
+            low: vec![0; n],
+            on_stack: vec![false; n],
+            stack: Vec::new(),
+            counter: 0,
+            components: Vec::new(),
         };
+        for u in 0..n {
+            if state.index[u].is_none() {
+                self.tarjan_visit(u, &mut state);
+            }
+        }
+        state.components.into_iter()
+            .map(|component| component.into_iter().map(|v| self.vertices[v].clone()).collect())
+            .collect()
+    }
+
+    /// The depth first search of Tarjan's algorithm from `root`, with an explicit stack of the
+    /// vertices being visited and the next edge to follow from each.
+    fn tarjan_visit(&self, root: Node, state: &mut Tarjan) {
+        let mut calls = vec![(root, 0)];
+        state.enter(root);
+        while !calls.is_empty() {
+            let top = calls.len() - 1;
+            let (u, i) = calls[top];
+            if i < self.adj_list[u].len() {
+                calls[top].1 += 1;
+                let v = self.adj_list[u][i].0;
+                match state.index[v] {
+                    None => {
+                        state.enter(v);
+                        calls.push((v, 0));
+                    }
+                    Some(idx) if state.on_stack[v] => state.low[u] = min(state.low[u], idx),
+                    _ => {}
+                }
+                continue;
+            }
+
+            // Every edge of u has been followed: return to the vertex that reached it
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                state.low[parent] = min(state.low[parent], state.low[u]);
+            }
+            // u is the root of a component, which is everything above it on the stack
+            if state.index[u] == Some(state.low[u]) {
+                let mut component = Vec::new();
+                loop {
+                    let v = state.stack.pop().unwrap();
+                    state.on_stack[v] = false;
+                    component.push(v);
+                    if v == u { break; }
+                }
+                component.reverse();
+                state.components.push(component);
+            }
+        }
     }
 
     /// Implements Dijkstra's Algorithm. This uses a Priority Queue to
     /// determine which vertex to visit first. Terminates on discovering
-    /// the target vertex.
-    ///
+    /// the target vertex, if any. Weights must not be negative.
+    fn dijkstra_search(&self, source: Node, target: Option<Node>) -> ShortestPaths<V, W> {
+        let num_vert = self.vertices.len();
+        let mut dist: Vec<Option<W>> = vec![None; num_vert];
+        let mut prev: Vec<Option<Node>> = vec![None; num_vert];
+        let mut done = vec![false; num_vert];
+        let mut queue: BinaryHeap<DistPair<W>> = BinaryHeap::new();
+
+        dist[source] = Some(W::zero());
+        queue.push(DistPair(source, W::zero()));
+
+        while let Some(DistPair(u, dist_u)) = queue.pop() {
+            // Stale entries are left in the queue when a shorter path is found
+            if done[u] { continue; }
+            done[u] = true;
+            if Some(u) == target { break; }
+
+            for &(v, cost_uv) in &self.adj_list[u] {
+                let alt = dist_u + cost_uv;
+                if dist[v].map_or(true, |dist_v| alt < dist_v) {
+                    dist[v] = Some(alt);
+                    prev[v] = Some(u);
+                    queue.push(DistPair(v, alt));
+                }
+            }
+        }
+
+        ShortestPaths { graph: self, dist: dist, prev: prev }
+    }
+
     /// Returns vector of vertices representing the path, or an empty vector
     /// if there's no path, or if the source or target is not in the graph.
-    fn dijkstra(&'a self, source: &str, target: &str) -> Vec<&str> {
-        let num_vert = self.vertices.len();
-        let mut dist:Vec<usize> = repeat(usize::MAX).take(num_vert)
-            .collect(); //Close enough to infinity
-        let mut prev:HashMap<Node, Node> = HashMap::new();
-        let mut queue:BinaryHeap<DistPair> = BinaryHeap::new();
+    pub fn dijkstra<Q: ?Sized>(&self, source: &Q, target: &Q) -> Vec<V>
+        where V: Borrow<Q>, Q: Hash + Eq
+    {
+        match (self.vertex_index(source), self.vertex_index(target)) {
+            (Some(s), Some(t)) => self.dijkstra_search(s, Some(t)).path(target),
+            _ => Vec::new(),
+        }
+    }
 
-        let source_idx = match self.vertex_index(source) {
-            Some(idx) => idx,
-            None      => return Vec::new() // Source not in graph, return empty path.
-        };
+    /// Shortest paths from the source to every vertex, by Dijkstra's Algorithm.
+    pub fn shortest_paths<Q: ?Sized>(&self, source: &Q) -> ShortestPaths<V, W>
+        where V: Borrow<Q>, Q: Hash + Eq
+    {
+        match self.vertex_index(source) {
+            Some(s) => self.dijkstra_search(s, None),
+            None => ShortestPaths {
+                graph: self,
+                dist: vec![None; self.vertices.len()],
+                prev: vec![None; self.vertices.len()],
+            },
+        }
+    }
 
-        let target_idx = match self.vertex_index(target) {
-            Some(idx) => idx,
-            None      => return Vec::new() // Target not in graph, return empty path.
+    /// Shortest paths from the source, allowing negative weights. Fails if a negative cycle is
+    /// reachable from the source. In an undirected graph any negative edge is such a cycle.
+    pub fn bellman_ford<Q: ?Sized>(&self, source: &Q) -> Result<ShortestPaths<V, W>, NegativeCycle>
+        where V: Borrow<Q>, Q: Hash + Eq
+    {
+        let n = self.vertices.len();
+        let mut dist: Vec<Option<W>> = vec![None; n];
+        let mut prev: Vec<Option<Node>> = vec![None; n];
+        if let Some(s) = self.vertex_index(source) {
+            dist[s] = Some(W::zero());
+        }
+
+        // Every shortest path has fewer than n edges, so n - 1 rounds of relaxing all edges find
+        // them; a change in round n means a negative cycle.
+        for round in 0..n {
+            let mut changed = false;
+            for u in 0..n {
+                let dist_u = match dist[u] {
+                    Some(d) => d,
+                    None => continue,
+                };
+                for &(v, cost_uv) in &self.adj_list[u] {
+                    let alt = dist_u + cost_uv;
+                    if dist[v].map_or(true, |dist_v| alt < dist_v) {
+                        dist[v] = Some(alt);
+                        prev[v] = Some(u);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed { break; }
+            if round == n - 1 { return Err(NegativeCycle); }
+        }
+
+        Ok(ShortestPaths { graph: self, dist: dist, prev: prev })
+    }
+
+    /// A* search, guided by a heuristic estimating the remaining distance to the target. If the
+    /// heuristic never overestimates and is consistent, the path found is a shortest one.
+    /// Returns the length and the vertices of the path.
+    pub fn a_star<Q: ?Sized, H>(&self, source: &Q, target: &Q, heuristic: H) -> Option<(W, Vec<V>)>
+        where V: Borrow<Q>, Q: Hash + Eq, H: Fn(&V) -> W
+    {
+        let (source, target) = match (self.vertex_index(source), self.vertex_index(target)) {
+            (Some(s), Some(t)) => (s, t),
+            _ => return None,
         };
+        let n = self.vertices.len();
+        let mut dist: Vec<Option<W>> = vec![None; n];
+        let mut prev: Vec<Option<Node>> = vec![None; n];
+        let mut done = vec![false; n];
+        let mut queue = BinaryHeap::new();
+
+        dist[source] = Some(W::zero());
+        queue.push(DistPair(source, heuristic(&self.vertices[source])));
 
-        dist[source_idx] = 0;
-        queue.push(DistPair(source_idx, dist[source_idx]));
-
-        loop{
-            match queue.pop() {
-                None     => break,
-                Some(DistPair(u, dist_u)) => {
-                    for &v in &(self.adj_list[u]) {
-                        let cost_uv = match self.costs.get(&(u, v)) {
-                            Some(&x) => x,
-                            None     => usize::MAX,
-                        };
-                        let alt = dist_u + cost_uv;
-                        if alt < dist[v] {
-                            match prev.entry(v) {
-                                Vacant(entry) => {entry.insert(u);},
-                                Occupied(entry) => {
-                                    *entry.into_mut() = u;
-                                }
-                            }
-                            dist[v] = alt;
-                            queue.push(DistPair(v, dist[v]));
+        while let Some(DistPair(u, _)) = queue.pop() {
+            if done[u] { continue; }
+            done[u] = true;
+            let dist_u = dist[u].unwrap();
+            if u == target {
+                return Some((dist_u, self.trace_path(&prev, u)));
+            }
+
+            for &(v, cost_uv) in &self.adj_list[u] {
+                let alt = dist_u + cost_uv;
+                if !done[v] && dist[v].map_or(true, |dist_v| alt < dist_v) {
+                    dist[v] = Some(alt);
+                    prev[v] = Some(u);
+                    queue.push(DistPair(v, alt + heuristic(&self.vertices[v])));
+                }
+            }
+        }
+        None
+    }
+
+    /// Distances between all pairs of vertices, indexed by vertex index; None where there is no
+    /// path. A negative distance from a vertex to itself means it lies on a negative cycle.
+    pub fn floyd_warshall(&self) -> Vec<Vec<Option<W>>> {
+        let n = self.vertices.len();
+        let mut dist: Vec<Vec<Option<W>>> = vec![vec![None; n]; n];
+        for u in 0..n {
+            dist[u][u] = Some(W::zero());
+            for &(v, cost_uv) in &self.adj_list[u] {
+                if dist[u][v].map_or(true, |d| cost_uv < d) {
+                    dist[u][v] = Some(cost_uv);
+                }
+            }
+        }
+        for k in 0..n {
+            for i in 0..n {
+                let dist_ik = match dist[i][k] {
+                    Some(d) => d,
+                    None => continue,
+                };
+                for j in 0..n {
+                    if let Some(dist_kj) = dist[k][j] {
+                        let alt = dist_ik + dist_kj;
+                        if dist[i][j].map_or(true, |d| alt < d) {
+                            dist[i][j] = Some(alt);
                         }
-                        if v == target_idx { break; }
                     }
                 }
-            };
+            }
         }
+        dist
+    }
+}
 
-        let mut temp_path:LinkedList<&str> = LinkedList::new();
-        let mut curr = target_idx;
-        temp_path.push_front(self.vertices[curr]);
-        loop{
-            match prev.get(&curr){
-                Some(&parent) => {
-                    curr = parent;
-                    temp_path.push_front(self.vertices[curr]);
-                    if curr == source_idx { break; }
-                },
-                None => return Vec::new(),
+impl<W> Graph<String, W> where W: Weight + FromStr {
+    /// Reads a graph from lines of "from to [weight]", separated by whitespace. Edges without a
+    /// weight weigh one; a line with a single name adds a lone vertex. Blank lines and lines
+    /// starting with '#' are skipped.
+    pub fn from_edge_list<R: BufRead>(reader: R, directed: bool)
+                                      -> Result<Graph<String, W>, LoadError> {
+        let mut graph = if directed { Graph::new() } else { Graph::undirected() };
+        for (i, line) in reader.lines().enumerate() {
+            let line = try!(line);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.len() {
+                1 => { graph.add_vertex(fields[0].to_string()); }
+                2 | 3 => {
+                    let weight = match fields.get(2) {
+                        Some(field) => try!(field.parse().map_err(|_| LoadError::Parse(i + 1))),
+                        None => W::one(),
+                    };
+                    graph.add_edge(fields[0].to_string(), fields[1].to_string(), weight);
+                }
+                _ => return Err(LoadError::Parse(i + 1)),
             }
         }
-        return temp_path.into_iter().collect();
+        Ok(graph)
+    }
+
+    pub fn load(path: &str, directed: bool) -> Result<Graph<String, W>, LoadError> {
+        let file = try!(File::open(path));
+        Graph::from_edge_list(BufReader::new(file), directed)
+    }
+
+    /// Reads a graph in the Graphviz DOT language. An edge weighs its `weight` attribute, or its
+    /// `label` if that is a number, or else one. Other attributes are ignored.
+    pub fn from_dot(text: &str) -> Result<Graph<String, W>, LoadError> {
+        let mut parser = DotParser { tokens: try!(dot_tokens(text)), pos: 0 };
+        parser.keyword("strict");
+        let directed = if parser.keyword("digraph") {
+            true
+        } else if parser.keyword("graph") {
+            false
+        } else {
+            return Err(parser.error());
+        };
+        let mut graph = if directed { Graph::new() } else { Graph::undirected() };
+        if let Some(DotToken::Id(..)) = parser.peek() {
+            parser.pos += 1;
+        }
+        try!(parser.expect(DotToken::LBrace));
+
+        loop {
+            if parser.eat(DotToken::RBrace) { break; }
+            if parser.eat(DotToken::Semicolon) { continue; }
+            // Default attributes for the graph, its nodes or its edges
+            if parser.keyword("graph") || parser.keyword("node") || parser.keyword("edge") {
+                try!(parser.attributes());
+                continue;
+            }
+            let line = parser.line();
+            let id = try!(parser.id());
+            // An attribute of the graph
+            if parser.eat(DotToken::Equals) {
+                try!(parser.id());
+                continue;
+            }
+            let mut chain = vec![id];
+            loop {
+                let edge_op = parser.peek();
+                if edge_op == Some(DotToken::Arrow) || edge_op == Some(DotToken::Line) {
+                    if (edge_op == Some(DotToken::Arrow)) != directed {
+                        return Err(parser.error());
+                    }
+                    parser.pos += 1;
+                    chain.push(try!(parser.id()));
+                } else {
+                    break;
+                }
+            }
+            let attributes = try!(parser.attributes());
+            if chain.len() == 1 {
+                graph.add_vertex(chain.pop().unwrap());
+            } else {
+                let weight = try!(dot_weight(&attributes, line));
+                for pair in chain.windows(2) {
+                    graph.add_edge(pair[0].clone(), pair[1].clone(), weight);
+                }
+            }
+        }
+
+        if parser.peek().is_some() {
+            return Err(parser.error());
+        }
+        Ok(graph)
+    }
+
+    /// Reads a graph from CSV rows of "from,to[,weight]", with an optional header row naming the
+    /// columns from/to or source/target. Edges without a weight weigh one; a row with a single
+    /// field adds a lone vertex.
+    pub fn from_csv<R: BufRead>(reader: R, directed: bool) -> Result<Graph<String, W>, LoadError> {
+        let mut graph = if directed { Graph::new() } else { Graph::undirected() };
+        let mut first = true;
+        for (i, line) in reader.lines().enumerate() {
+            let line = try!(line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut fields = try!(csv_fields(&line).ok_or(LoadError::Parse(i + 1))).into_iter();
+            let (from, to, weight) = (fields.next().unwrap(), fields.next(), fields.next());
+            if fields.next().is_some() {
+                return Err(LoadError::Parse(i + 1));
+            }
+            if first {
+                first = false;
+                let header = (from.to_lowercase(), to.as_ref().map(|to| to.to_lowercase()));
+                match (&header.0[..], header.1.as_ref().map(|to| &to[..])) {
+                    ("from", Some("to")) | ("source", Some("target")) => continue,
+                    _ => {}
+                }
+            }
+            match to {
+                None => { graph.add_vertex(from); }
+                Some(to) => {
+                    let weight = match weight {
+                        Some(weight) => try!(weight.parse().map_err(|_| LoadError::Parse(i + 1))),
+                        None => W::one(),
+                    };
+                    graph.add_edge(from, to, weight);
+                }
+            }
+        }
+        Ok(graph)
+    }
+}
+
+impl<V, W> Graph<V, W> where V: Hash + Eq + Clone + fmt::Display, W: Weight + fmt::Display {
+    /// Writes the graph in the DOT language, weights as edge labels. The vertices and edges of
+    /// the given path, e.g. one found by `dijkstra`, are drawn in red.
+    pub fn to_dot(&self, path: &[V]) -> String {
+        let on_path: Vec<Node> = path.iter().filter_map(|v| self.vertex_index(v)).collect();
+        let path_edge = |u: Node, v: Node| {
+            on_path.windows(2).any(|pair| {
+                (pair[0] == u && pair[1] == v) || (!self.directed && pair[0] == v && pair[1] == u)
+            })
+        };
+        let mut has_edges = vec![false; self.vertices.len()];
+        let edges = self.edges();
+        for &(u, v, _) in &edges {
+            has_edges[u] = true;
+            has_edges[v] = true;
+        }
+
+        let mut dot = String::new();
+        dot.push_str(if self.directed { "digraph {\n" } else { "graph {\n" });
+        // Only vertices without edges, or to highlight, need a statement of their own
+        for (u, vertex) in self.vertices.iter().enumerate() {
+            if on_path.contains(&u) {
+                dot.push_str(&format!("    {} [color=red];\n", dot_quote(&vertex.to_string())));
+            } else if !has_edges[u] {
+                dot.push_str(&format!("    {};\n", dot_quote(&vertex.to_string())));
+            }
+        }
+        let edge_op = if self.directed { "->" } else { "--" };
+        for (u, v, weight) in edges {
+            let style = if path_edge(u, v) { ", color=red, penwidth=2" } else { "" };
+            dot.push_str(&format!("    {} {} {} [label={}{}];\n",
+                                  dot_quote(&self.vertices[u].to_string()), edge_op,
+                                  dot_quote(&self.vertices[v].to_string()),
+                                  dot_quote(&weight.to_string()), style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Writes the edges as CSV rows of "from,to,weight" after a header row, and vertices without
+    /// edges as rows of their own.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "from,to,weight\n".to_string();
+        let edges = self.edges();
+        let mut connected = vec![false; self.vertices.len()];
+        for &(u, v, _) in &edges {
+            connected[u] = true;
+            connected[v] = true;
+        }
+        for (u, vertex) in self.vertices.iter().enumerate() {
+            if !connected[u] {
+                csv.push_str(&format!("{}\n", csv_quote(&vertex.to_string())));
+            }
+        }
+        for (u, v, weight) in edges {
+            csv.push_str(&format!("{},{},{}\n", csv_quote(&self.vertices[u].to_string()),
+                                  csv_quote(&self.vertices[v].to_string()), weight));
+        }
+        csv
+    }
+}
+
+impl<V, W> Graph<V, W> where V: Hash + Eq + Clone, W: Weight {
+    /// Serializes the graph as JSON: whether it is directed, the vertices, and for each vertex
+    /// the edges leaving it as [target index, weight].
+    pub fn to_json(&self) -> Result<String, json::EncoderError> where V: Encodable, W: Encodable {
+        json::encode(&JsonGraph {
+            directed: self.directed,
+            vertices: self.vertices.clone(),
+            adjacency: self.adj_list.clone(),
+        })
+    }
+
+    pub fn from_json(text: &str) -> Result<Graph<V, W>, LoadError>
+        where V: Decodable, W: Decodable
+    {
+        let decoded: JsonGraph<V, W> = try!(json::decode(text));
+        let invalid = |message: &str| {
+            LoadError::Json(json::DecoderError::ApplicationError(message.to_string()))
+        };
+        let n = decoded.vertices.len();
+        if decoded.adjacency.len() != n {
+            return Err(invalid("adjacency and vertices differ in length"));
+        }
+        let mut graph = if decoded.directed { Graph::new() } else { Graph::undirected() };
+        for vertex in decoded.vertices {
+            if graph.add_vertex(vertex) != graph.vertices.len() - 1 {
+                return Err(invalid("duplicate vertex"));
+            }
+        }
+        for (u, adj) in decoded.adjacency.into_iter().enumerate() {
+            for (v, weight) in adj {
+                if v >= n {
+                    return Err(invalid("edge to a missing vertex"));
+                }
+                graph.set_arc(u, v, weight);
+            }
+        }
+        Ok(graph)
+    }
+}
+
+/// Disjoint-set union with path halving and union by rank, for Kruskal's algorithm.
+struct DisjointSets {
+    parent: Vec<usize>,
+    rank: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(n: usize) -> DisjointSets {
+        DisjointSets { parent: (0..n).collect(), rank: vec![0; n] }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// Merges the sets of a and b. Returns false if they were already the same set.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        match self.rank[a].cmp(&self.rank[b]) {
+            Ordering::Less => self.parent[a] = b,
+            Ordering::Greater => self.parent[b] = a,
+            Ordering::Equal => {
+                self.parent[b] = a;
+                self.rank[a] += 1;
+            }
+        }
+        true
+    }
+}
+
+fn min_weight<W: Weight>(a: W, b: W) -> W {
+    if b < a { b } else { a }
+}
+
+/// The residual network of the maximum flow algorithms. Arcs are stored in pairs, so arc `a ^ 1`
+/// is the reverse of arc `a`; `capacity` is what is left of each.
+struct FlowNetwork<W> {
+    adj: Vec<Vec<usize>>,
+    head: Vec<Node>,
+    capacity: Vec<W>,
+}
+
+impl<W: Weight> FlowNetwork<W> {
+    fn push_flow(&mut self, arc: usize, flow: W) {
+        self.capacity[arc] = self.capacity[arc] - flow;
+        self.capacity[arc ^ 1] = self.capacity[arc ^ 1] + flow;
+    }
+
+    /// Breadth first search over arcs with capacity left. Returns the arc each vertex was first
+    /// reached by, and the distance of each vertex from the source.
+    fn bfs(&self, source: Node) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+        let n = self.adj.len();
+        let mut via = vec![None; n];
+        let mut level = vec![None; n];
+        let mut queue = VecDeque::new();
+        level[source] = Some(0);
+        queue.push_back(source);
+        while let Some(u) = queue.pop_front() {
+            for &arc in &self.adj[u] {
+                let v = self.head[arc];
+                if level[v].is_none() && self.capacity[arc] > W::zero() {
+                    level[v] = level[u].map(|l| l + 1);
+                    via[v] = Some(arc);
+                    queue.push_back(v);
+                }
+            }
+        }
+        (via, level)
+    }
+
+    /// Finds a path to the sink along the level graph, skipping arcs known to be dead ends, and
+    /// pushes as much flow along it as possible. Returns the flow pushed.
+    fn dinic_push(&mut self, u: Node, sink: Node, limit: W, level: &[Option<usize>],
+                  next_arc: &mut Vec<usize>) -> Option<W> {
+        if u == sink {
+            return Some(limit);
+        }
+        while next_arc[u] < self.adj[u].len() {
+            let arc = self.adj[u][next_arc[u]];
+            let v = self.head[arc];
+            if self.capacity[arc] > W::zero() && level[v] == level[u].map(|l| l + 1) {
+                let limit = min_weight(limit, self.capacity[arc]);
+                if let Some(pushed) = self.dinic_push(v, sink, limit, level, next_arc) {
+                    self.push_flow(arc, pushed);
+                    return Some(pushed);
+                }
+            }
+            next_arc[u] += 1;
+        }
+        None
+    }
+}
+
+/// A maximum flow: its value, the flow along each edge carrying any, and a minimum cut. The cut
+/// separates `source_side`, the vertices still reachable from the source in the residual network,
+/// from the rest; the capacities of the `cut` edges add up to the value of the flow.
+#[derive(Debug)]
+pub struct MaxFlow<V, W> {
+    pub value: W,
+    pub flows: Vec<(V, V, W)>,
+    pub source_side: Vec<V>,
+    pub cut: Vec<(V, V, W)>,
+}
+
+impl<V, W> Graph<V, W> where V: Hash + Eq + Clone, W: Weight {
+    /// Kruskal's algorithm: adds the edges by increasing weight, skipping those that would close
+    /// a cycle. Edges of a directed graph are taken as undirected. For a disconnected graph this
+    /// is a spanning forest. Returns the total weight and the edges.
+    pub fn kruskal(&self) -> (W, Vec<(V, V, W)>) {
+        let mut edges = self.edges();
+        edges.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal));
+        let mut sets = DisjointSets::new(self.vertices.len());
+        let mut total = W::zero();
+        let mut tree = Vec::new();
+        for (u, v, weight) in edges {
+            if sets.union(u, v) {
+                total = total + weight;
+                tree.push((self.vertices[u].clone(), self.vertices[v].clone(), weight));
+            }
+        }
+        (total, tree)
+    }
+
+    /// Prim's algorithm: grows the tree from a vertex, each time by the lightest edge leaving it,
+    /// using a Priority Queue like Dijkstra. Edges of a directed graph are taken as undirected.
+    /// For a disconnected graph it grows a tree in every component.
+    pub fn prim(&self) -> (W, Vec<(V, V, W)>) {
+        let n = self.vertices.len();
+        let mut adj_list = self.adj_list.clone();
+        if self.directed {
+            for (u, v, weight) in self.edges() {
+                adj_list[v].push((u, weight));
+            }
+        }
+
+        // best[v] is the lightest known edge joining v to the tree, as (tree vertex, weight)
+        let mut best: Vec<Option<(Node, W)>> = vec![None; n];
+        let mut in_tree = vec![false; n];
+        let mut queue: BinaryHeap<DistPair<W>> = BinaryHeap::new();
+        let mut total = W::zero();
+        let mut tree = Vec::new();
+
+        for root in 0..n {
+            if in_tree[root] { continue; }
+            queue.push(DistPair(root, W::zero()));
+            while let Some(DistPair(u, _)) = queue.pop() {
+                // Stale entries are left in the queue when a lighter edge is found
+                if in_tree[u] { continue; }
+                in_tree[u] = true;
+                if let Some((parent, weight)) = best[u] {
+                    total = total + weight;
+                    tree.push((self.vertices[parent].clone(), self.vertices[u].clone(), weight));
+                }
+                for &(v, weight) in &adj_list[u] {
+                    if !in_tree[v] && best[v].map_or(true, |(_, w)| weight < w) {
+                        best[v] = Some((u, weight));
+                        queue.push(DistPair(v, weight));
+                    }
+                }
+            }
+        }
+        (total, tree)
+    }
+
+    /// The residual network before any flow, with the weights as capacities. An undirected edge
+    /// can carry flow either way, so both arcs of its pair start with its capacity.
+    fn flow_network(&self) -> FlowNetwork<W> {
+        let mut network = FlowNetwork {
+            adj: vec![Vec::new(); self.vertices.len()],
+            head: Vec::new(),
+            capacity: Vec::new(),
+        };
+        for (u, v, capacity) in self.edges() {
+            let back = if self.directed { W::zero() } else { capacity };
+            network.adj[u].push(network.head.len());
+            network.head.push(v);
+            network.capacity.push(capacity);
+            network.adj[v].push(network.head.len());
+            network.head.push(u);
+            network.capacity.push(back);
+        }
+        network
+    }
+
+    /// Reads the flows and the minimum cut off the residual network left by a maximum flow.
+    fn max_flow_result(&self, network: &FlowNetwork<W>, source: Node, value: W) -> MaxFlow<V, W> {
+        let (_, level) = network.bfs(source);
+        let reachable: Vec<bool> = level.iter().map(|l| l.is_some()).collect();
+        let mut flows = Vec::new();
+        let mut cut = Vec::new();
+        // Edge k of edges() became the arcs 2k and 2k + 1
+        for (k, (u, v, capacity)) in self.edges().into_iter().enumerate() {
+            let (forward, backward) = (network.capacity[2 * k], network.capacity[2 * k + 1]);
+            let (from, to) = (self.vertices[u].clone(), self.vertices[v].clone());
+            if forward < capacity {
+                flows.push((from.clone(), to.clone(), capacity - forward));
+            } else if !self.directed && backward < capacity {
+                flows.push((to.clone(), from.clone(), capacity - backward));
+            }
+            if reachable[u] && !reachable[v] {
+                cut.push((from, to, capacity));
+            } else if !self.directed && reachable[v] && !reachable[u] {
+                cut.push((to, from, capacity));
+            }
+        }
+        let source_side = self.vertices.iter().zip(reachable.iter())
+            .filter(|&(_, &r)| r)
+            .map(|(v, _)| v.clone())
+            .collect();
+        MaxFlow { value: value, flows: flows, source_side: source_side, cut: cut }
+    }
+
+    fn flow_ends<Q: ?Sized>(&self, source: &Q, sink: &Q) -> Option<(Node, Node)>
+        where V: Borrow<Q>, Q: Hash + Eq
+    {
+        match (self.vertex_index(source), self.vertex_index(sink)) {
+            (Some(s), Some(t)) if s != t => Some((s, t)),
+            _ => None,
+        }
+    }
+
+    /// Edmonds-Karp: repeatedly augments along a shortest path with capacity left. Returns None
+    /// if the source or the sink is not in the graph, or they are the same vertex.
+    pub fn edmonds_karp<Q: ?Sized>(&self, source: &Q, sink: &Q) -> Option<MaxFlow<V, W>>
+        where V: Borrow<Q>, Q: Hash + Eq
+    {
+        let (source, sink) = match self.flow_ends(source, sink) {
+            Some(ends) => ends,
+            None => return None,
+        };
+        let mut network = self.flow_network();
+        let mut value = W::zero();
+        loop {
+            let (via, _) = network.bfs(source);
+            if via[sink].is_none() { break; }
+
+            let mut bottleneck = None;
+            let mut v = sink;
+            while v != source {
+                let arc = via[v].unwrap();
+                let capacity = network.capacity[arc];
+                bottleneck = Some(bottleneck.map_or(capacity, |b| min_weight(b, capacity)));
+                v = network.head[arc ^ 1];
+            }
+            let bottleneck = bottleneck.unwrap();
+
+            let mut v = sink;
+            while v != source {
+                let arc = via[v].unwrap();
+                network.push_flow(arc, bottleneck);
+                v = network.head[arc ^ 1];
+            }
+            value = value + bottleneck;
+        }
+        Some(self.max_flow_result(&network, source, value))
+    }
+
+    /// Dinic's algorithm: builds the level graph of shortest paths by breadth first search, and
+    /// saturates it with blocking flows before building the next one.
+    pub fn dinic<Q: ?Sized>(&self, source: &Q, sink: &Q) -> Option<MaxFlow<V, W>>
+        where V: Borrow<Q>, Q: Hash + Eq
+    {
+        let (source, sink) = match self.flow_ends(source, sink) {
+            Some(ends) => ends,
+            None => return None,
+        };
+        let mut network = self.flow_network();
+        // No path carries more than everything leaving the source
+        let limit = network.adj[source].iter()
+            .fold(W::zero(), |total, &arc| total + network.capacity[arc]);
+        let mut value = W::zero();
+        loop {
+            let (_, level) = network.bfs(source);
+            if level[sink].is_none() { break; }
+            let mut next_arc = vec![0; self.vertices.len()];
+            loop {
+                match network.dinic_push(source, sink, limit, &level, &mut next_arc) {
+                    Some(pushed) => value = value + pushed,
+                    None => break,
+                }
+            }
+        }
+        Some(self.max_flow_result(&network, source, value))
     }
 }
 
@@ -158,6 +1359,370 @@ fn test_dijkstras() {
     assert!(graph.dijkstra("a", "e").is_empty());
 }
 
+#[cfg(test)]
+fn example_graph(directed: bool) -> Graph<&'static str, u32> {
+    let mut graph = if directed { Graph::new() } else { Graph::undirected() };
+    for &(from, to, cost) in &[("a", "b", 7), ("a", "c", 9), ("a", "f", 14), ("b", "c", 10),
+                               ("b", "d", 15), ("c", "d", 11), ("c", "f", 2), ("d", "e", 6),
+                               ("e", "f", 9)] {
+        graph.add_edge(from, to, cost);
+    }
+    graph
+}
+
+#[test]
+fn test_undirected_and_weights() {
+    let directed = example_graph(true);
+    let undirected = example_graph(false);
+    assert_eq!(directed.dijkstra("a", "e"), vec!["a", "c", "d", "e"]);
+    assert_eq!(undirected.dijkstra("a", "e"), vec!["a", "c", "f", "e"]);
+    assert!(directed.dijkstra("e", "a").is_empty());
+    assert_eq!(undirected.shortest_paths("e").distance("a"), Some(20));
+    assert_eq!(undirected.edges().len(), 9);
+
+    // Updating an edge replaces its weight
+    let mut graph = example_graph(true);
+    graph.add_edge("c", "d", 1);
+    assert_eq!(graph.edges().len(), 9);
+    assert_eq!(graph.shortest_paths("a").distance("e"), Some(16));
+
+    // Floating point weights
+    let mut graph = Graph::new();
+    graph.add_edge("x", "y", 0.5);
+    graph.add_edge("y", "z", 0.25);
+    graph.add_edge("x", "z", 1.0);
+    assert_eq!(graph.dijkstra("x", "z"), vec!["x", "y", "z"]);
+}
+
+#[test]
+fn test_traversals() {
+    let graph = example_graph(true);
+    assert_eq!(graph.bfs("a"), vec!["a", "b", "c", "f", "d", "e"]);
+    assert_eq!(graph.dfs("a"), vec!["a", "b", "c", "d", "e", "f"]);
+    assert_eq!(graph.bfs("d"), vec!["d", "e", "f"]);
+    assert!(graph.dfs("z").is_empty());
+}
+
+#[test]
+fn test_topological_sort() {
+    let graph = example_graph(true);
+    let order = graph.topological_sort().unwrap();
+    let position = |v: &str| order.iter().position(|&w| w == v).unwrap();
+    for (u, v, _) in graph.edges() {
+        assert!(position(graph.vertices()[u]) < position(graph.vertices()[v]));
+    }
+
+    let mut graph = example_graph(true);
+    graph.add_edge("f", "b", 1);
+    assert_eq!(graph.topological_sort(), Err(vec!["b", "c", "d", "e", "f", "b"]));
+}
+
+#[test]
+fn test_strongly_connected_components() {
+    let mut graph: Graph<&str, u32> = Graph::new();
+    for &(from, to) in &[("a", "b"), ("b", "c"), ("c", "a"), ("c", "d"), ("d", "e"),
+                         ("e", "d"), ("f", "e")] {
+        graph.add_edge(from, to, 1);
+    }
+    assert_eq!(graph.strongly_connected_components(),
+               vec![vec!["d", "e"], vec!["a", "b", "c"], vec!["f"]]);
+}
+
+#[test]
+fn test_deep_graphs() {
+    // A path far longer than the call stack would allow a recursive search to follow
+    let n = 200000;
+    let mut graph: Graph<usize, u32> = Graph::new();
+    for i in 0..n - 1 {
+        graph.add_edge(i, i + 1, 1);
+    }
+    assert_eq!(graph.topological_sort(), Ok((0..n).collect()));
+    assert_eq!(graph.strongly_connected_components().len(), n);
+
+    // Closing the path into a cycle makes it a single component
+    graph.add_edge(n - 1, 0, 1);
+    assert_eq!(graph.topological_sort().unwrap_err().len(), n + 1);
+    let components = graph.strongly_connected_components();
+    assert_eq!(components.len(), 1);
+    assert_eq!(components[0], (0..n).collect::<Vec<usize>>());
+}
+
+#[test]
+fn test_bellman_ford() {
+    let mut graph = Graph::new();
+    graph.add_edge("s", "a", 4);
+    graph.add_edge("s", "b", 2);
+    graph.add_edge("a", "c", -3);
+    graph.add_edge("b", "a", 1);
+    graph.add_edge("c", "t", 2);
+    {
+        let paths = graph.bellman_ford("s").unwrap();
+        assert_eq!(paths.distance("t"), Some(2));
+        assert_eq!(paths.path("t"), vec!["s", "b", "a", "c", "t"]);
+        assert_eq!(paths.distance("x"), None);
+    }
+
+    graph.add_edge("c", "b", 1);
+    assert!(graph.bellman_ford("s").is_err());
+}
+
+#[test]
+fn test_a_star() {
+    // A grid with a wall, searched with the Manhattan distance
+    let wall = |(x, y): (i32, i32)| x == 2 && y < 4;
+    let mut graph = Graph::undirected();
+    for x in 0..5 {
+        for y in 0..5 {
+            for &next in &[(x + 1, y), (x, y + 1)] {
+                if next.0 < 5 && next.1 < 5 && !wall((x, y)) && !wall(next) {
+                    graph.add_edge((x, y), next, 1);
+                }
+            }
+        }
+    }
+    let target = (4, 0);
+    let manhattan = |&(x, y): &(i32, i32)| (target.0 - x).abs() + (target.1 - y).abs();
+    let (length, path) = graph.a_star(&(0, 0), &target, manhattan).unwrap();
+    assert_eq!(length, 12);
+    assert_eq!(path.len(), 13);
+    assert_eq!(Some(length), graph.shortest_paths(&(0, 0)).distance(&target));
+    assert!(graph.a_star(&(0, 0), &(9, 9), manhattan).is_none());
+}
+
+#[test]
+fn test_floyd_warshall() {
+    let graph = example_graph(false);
+    let dist = graph.floyd_warshall();
+    for (i, source) in graph.vertices().iter().enumerate() {
+        let paths = graph.shortest_paths(source);
+        for (j, target) in graph.vertices().iter().enumerate() {
+            assert_eq!(dist[i][j], paths.distance(target));
+        }
+    }
+
+    let mut graph = Graph::new();
+    graph.add_edge("a", "b", 1);
+    graph.add_edge("b", "a", -2);
+    graph.add_edge("c", "a", 1);
+    let dist = graph.floyd_warshall();
+    assert!(dist[0][0].unwrap() < 0);
+    assert_eq!(dist[0][2], None);
+}
+
+#[test]
+fn test_from_edge_list() {
+    let text = "# comment\na b 2.5\n\nb c\nlonely\n";
+    let graph: Graph<String, f64> = Graph::from_edge_list(text.as_bytes(), true).unwrap();
+    assert_eq!(graph.vertices(), &["a", "b", "c", "lonely"]);
+    assert_eq!(graph.shortest_paths("a").distance("c"), Some(3.5));
+
+    let result: Result<Graph<String, u32>, _> = Graph::from_edge_list("a b x".as_bytes(), true);
+    match result {
+        Err(LoadError::Parse(1)) => {}
+        _ => panic!("expected a parse error"),
+    }
+
+    let deps: Graph<String, u32> = Graph::load("src/resources/dependencies.txt", true).unwrap();
+    let order = deps.topological_sort().unwrap();
+    assert_eq!(order.len(), 7);
+    assert_eq!(order.last().unwrap(), "rust-rosetta");
+}
+
+// The vertices and edges of a graph by name, sorted, to compare graphs whose vertices were added
+// in a different order
+#[cfg(test)]
+fn by_name<V, W>(graph: &Graph<V, W>) -> (bool, Vec<String>, Vec<(String, String, W)>)
+    where V: Hash + Eq + Clone + fmt::Display, W: Weight
+{
+    let mut vertices: Vec<String> = graph.vertices().iter().map(|v| v.to_string()).collect();
+    vertices.sort();
+    let mut edges: Vec<(String, String, W)> = graph.edges().into_iter()
+        .map(|(u, v, weight)| {
+            let (from, to) = (graph.vertices()[u].to_string(), graph.vertices()[v].to_string());
+            if graph.is_directed() || from <= to { (from, to, weight) } else { (to, from, weight) }
+        })
+        .collect();
+    edges.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    (graph.is_directed(), vertices, edges)
+}
+
+#[test]
+fn test_dot_round_trip() {
+    for &directed in &[true, false] {
+        let mut graph = example_graph(directed);
+        graph.add_vertex("lonely");
+        graph.add_edge("say \"hi\"", "back\\slash", 3);
+        let dot = graph.to_dot(&[]);
+        let parsed: Graph<String, u32> = Graph::from_dot(&dot).unwrap();
+        assert_eq!(by_name(&parsed), by_name(&graph));
+        assert_eq!(parsed.to_dot(&[]), dot);
+    }
+
+    let mut graph = Graph::new();
+    graph.add_edge("x", "y", 0.125);
+    graph.add_edge("y", "x", -2.5);
+    let parsed: Graph<String, f64> = Graph::from_dot(&graph.to_dot(&[])).unwrap();
+    assert_eq!(by_name(&parsed), by_name(&graph));
+}
+
+#[test]
+fn test_dot_parsing() {
+    let dot = "/* a comment\n spanning lines */\n\
+               strict Digraph deps {\n\
+                   rankdir=LR; node [shape=box]\n\
+                   // another comment\n\
+                   a -> b -> \"c d\" [weight=4, color=blue]\n\
+                   a -> e [label=\"2\"]; e -> f [label=\"not a number\"]\n\
+                   g\n\
+               }\n";
+    let graph: Graph<String, u32> = Graph::from_dot(dot).unwrap();
+    assert!(graph.is_directed());
+    assert_eq!(graph.vertices(), &["a", "b", "c d", "e", "f", "g"]);
+    assert_eq!(graph.shortest_paths("a").distance("c d"), Some(8));
+    assert_eq!(graph.shortest_paths("a").distance("f"), Some(3));
+
+    let errors = ["digraph { a -- b }", "graph {\n a -> b }", "digraph { a -> }",
+                  "digraph { a -> b [weight=x] }", "digraph {\n\n \"a }", "digraph { a", "a -> b"];
+    let lines = [1, 2, 1, 1, 3, 1, 1];
+    for (text, &line) in errors.iter().zip(lines.iter()) {
+        match Graph::<String, u32>::from_dot(text) {
+            Err(LoadError::Parse(l)) => assert_eq!(l, line),
+            _ => panic!("expected a parse error for {}", text),
+        }
+    }
+}
+
+#[test]
+fn test_dot_highlight() {
+    let graph = example_graph(true);
+    let dot = graph.to_dot(&graph.dijkstra("a", "e"));
+    assert!(dot.starts_with("digraph {\n    \"a\" [color=red];\n"));
+    assert!(dot.contains("\n    \"a\" -> \"c\" [label=\"9\", color=red, penwidth=2];\n"));
+    assert!(dot.contains("\n    \"a\" -> \"b\" [label=\"7\"];\n"));
+    assert_eq!(dot.matches("penwidth").count(), 3);
+}
+
+#[test]
+fn test_csv() {
+    for &(csv, line) in &[("from,to\nx,y,z", 2), ("x,y,1,2", 1), ("x,\"y", 1),
+                          ("x\n\"y\"z,w", 2)] {
+        match Graph::<String, u32>::from_csv(csv.as_bytes(), true) {
+            Err(LoadError::Parse(l)) => assert_eq!(l, line),
+            _ => panic!("expected a parse error for {}", csv),
+        }
+    }
+    let csv = "Source, Target\nx, y\n\"a, b\" ,\"say \"\"hi\"\"\",4\nz\n";
+    let graph: Graph<String, u32> = Graph::from_csv(csv.as_bytes(), false).unwrap();
+    assert_eq!(graph.vertices(), &["x", "y", "a, b", "say \"hi\"", "z"]);
+    assert_eq!(graph.shortest_paths("say \"hi\"").distance("a, b"), Some(4));
+
+    let mut graph = example_graph(false);
+    graph.add_vertex(" padded ");
+    let parsed: Graph<String, u32> = Graph::from_csv(graph.to_csv().as_bytes(), false).unwrap();
+    assert_eq!(by_name(&parsed), by_name(&graph));
+    assert_eq!(parsed.to_csv(), graph.to_csv());
+}
+
+#[test]
+fn test_json_round_trip() {
+    let mut graph: Graph<String, f64> = Graph::undirected();
+    graph.add_edge("a".to_string(), "b".to_string(), 1.5);
+    graph.add_edge("b".to_string(), "c".to_string(), -0.25);
+    graph.add_vertex("d".to_string());
+    let json = graph.to_json().unwrap();
+    assert_eq!(json, "{\"directed\":false,\"vertices\":[\"a\",\"b\",\"c\",\"d\"],\
+                      \"adjacency\":[[[1,1.5]],[[0,1.5],[2,-0.25]],[[1,-0.25]],[]]}");
+    let parsed: Graph<String, f64> = Graph::from_json(&json).unwrap();
+    assert_eq!(parsed.vertices(), graph.vertices());
+    assert_eq!(parsed.edges(), graph.edges());
+    assert_eq!(parsed.to_json().unwrap(), json);
+
+    let bad = "{\"directed\":true,\"vertices\":[\"a\"],\"adjacency\":[[[1,1.0]]]}";
+    assert!(Graph::<String, f64>::from_json(bad).is_err());
+    assert!(Graph::<String, f64>::from_json("{\"directed\":true}").is_err());
+}
+
+#[test]
+fn test_spanning_trees() {
+    for &directed in &[true, false] {
+        let graph = example_graph(directed);
+        for &(total, ref tree) in &[graph.kruskal(), graph.prim()] {
+            assert_eq!(total, 33);
+            assert_eq!(tree.len(), 5);
+            assert_eq!(tree.iter().fold(0, |sum, &(_, _, w)| sum + w), 33);
+        }
+    }
+    assert_eq!(example_graph(true).kruskal().1,
+               vec![("c", "f", 2), ("d", "e", 6), ("a", "b", 7), ("a", "c", 9), ("e", "f", 9)]);
+
+    // A forest for a disconnected graph
+    let mut graph = Graph::undirected();
+    graph.add_edge("a", "b", 1.5);
+    graph.add_edge("b", "c", 0.5);
+    graph.add_edge("a", "c", 1.0);
+    graph.add_edge("x", "y", 2.0);
+    graph.add_vertex("z");
+    assert_eq!(graph.kruskal().0, 3.5);
+    assert_eq!(graph.prim(), (3.5, vec![("a", "c", 1.0), ("c", "b", 0.5), ("x", "y", 2.0)]));
+}
+
+#[cfg(test)]
+fn check_max_flow(graph: &Graph<&'static str, i32>, flow: &MaxFlow<&'static str, i32>,
+                  source: &str, sink: &str) {
+    // Flow is conserved everywhere but at the source and sink, and respects capacities
+    for &vertex in graph.vertices() {
+        let net = flow.flows.iter().fold(0, |net, &(from, to, f)| {
+            if from == vertex { net - f } else if to == vertex { net + f } else { net }
+        });
+        let expected = if vertex == source { -flow.value }
+                       else if vertex == sink { flow.value }
+                       else { 0 };
+        assert_eq!(net, expected);
+    }
+    for &(from, to, f) in &flow.flows {
+        assert!(f > 0);
+        assert!(graph.edges().iter().any(|&(u, v, c)| {
+            let (u, v) = (graph.vertices()[u], graph.vertices()[v]);
+            (u == from && v == to || !graph.is_directed() && u == to && v == from) && f <= c
+        }));
+    }
+    // The cut separates source and sink, and is as small as the flow is big
+    assert!(flow.source_side.contains(&source) && !flow.source_side.contains(&sink));
+    assert_eq!(flow.cut.iter().fold(0, |sum, &(_, _, c)| sum + c), flow.value);
+}
+
+#[test]
+fn test_max_flow() {
+    let mut graph = Graph::new();
+    for &(from, to, capacity) in &[("s", "v1", 16), ("s", "v2", 13), ("v1", "v3", 12),
+                                   ("v2", "v1", 4), ("v2", "v4", 14), ("v3", "v2", 9),
+                                   ("v3", "t", 20), ("v4", "v3", 7), ("v4", "t", 4)] {
+        graph.add_edge(from, to, capacity);
+    }
+    for flow in vec![graph.edmonds_karp("s", "t").unwrap(), graph.dinic("s", "t").unwrap()] {
+        assert_eq!(flow.value, 23);
+        check_max_flow(&graph, &flow, "s", "t");
+        let mut cut = flow.cut.clone();
+        cut.sort();
+        assert_eq!(cut, vec![("v1", "v3", 12), ("v4", "t", 4), ("v4", "v3", 7)]);
+    }
+    assert_eq!(graph.dinic("t", "s").unwrap().value, 0);
+    assert!(graph.edmonds_karp("s", "s").is_none());
+    assert!(graph.dinic("s", "x").is_none());
+
+    // Undirected edges carry flow either way
+    let mut graph = Graph::undirected();
+    for &(from, to, capacity) in &[("s", "a", 3), ("s", "b", 2), ("a", "b", 5), ("a", "t", 1),
+                                   ("b", "t", 4)] {
+        graph.add_edge(from, to, capacity);
+    }
+    for flow in vec![graph.edmonds_karp("s", "t").unwrap(), graph.dinic("s", "t").unwrap()] {
+        assert_eq!(flow.value, 5);
+        check_max_flow(&graph, &flow, "s", "t");
+    }
+    assert_eq!(graph.edmonds_karp("t", "s").unwrap().value, 5);
+}
+
 #[cfg(not(test))]
 fn main(){
     let mut graph = Graph::new();
@@ -173,4 +1738,37 @@ fn main(){
 
     let path = graph.dijkstra("a", "e");
     println!("Path is: {:?}", path);
+
+    println!("Breadth first: {:?}", graph.bfs("a"));
+    println!("Depth first: {:?}", graph.dfs("a"));
+    println!("Topological order: {:?}", graph.topological_sort());
+    println!("Distance a -> e (Bellman-Ford): {:?}",
+             graph.bellman_ford("a").ok().and_then(|paths| paths.distance("e")));
+    println!("A* without a heuristic: {:?}", graph.a_star("a", "e", |_| 0));
+
+    println!("All pairs distances:");
+    for (vertex, row) in graph.vertices().iter().zip(graph.floyd_warshall().iter()) {
+        let row: Vec<String> = row.iter()
+            .map(|d| d.map_or("-".to_string(), |d| d.to_string()))
+            .collect();
+        println!("{}: {}", vertex, row.connect(" "));
+    }
+
+    match Graph::<String, u32>::load("src/resources/dependencies.txt", true) {
+        Ok(deps) => {
+            println!("Build order: {:?}", deps.topological_sort());
+            println!("Components: {:?}", deps.strongly_connected_components());
+        }
+        Err(err) => println!("Could not load dependencies: {}", err),
+    }
+
+    println!("Minimum spanning tree: {:?}", graph.kruskal());
+    match graph.dinic("a", "e") {
+        Some(flow) => println!("Maximum flow from a to e: {}, through the cut {:?}",
+                               flow.value, flow.cut),
+        None => println!("No flow from a to e"),
+    }
+
+    println!("\nAs DOT, with the shortest path from a to e:\n{}", graph.to_dot(&path));
+    println!("As JSON: {}", graph.to_json().unwrap());
 }
@@ -1,46 +1,473 @@
 // http://rosettacode.org/wiki/FASTA_format
 // Ported and adapted from rosettacode D example
+//
+// Grown into a small library: streaming FASTA and FASTQ readers over any `BufRead`, alphabet
+// validation, writers with configurable line wrapping and a few sequence statistics.
+use std::ascii::AsciiExt;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufRead};
-
-// We use a type parameter bound `<T: Buffer>` to accept all kinds of buffers
-fn format_fasta<T: BufRead>(reader: &mut T) -> String {
-    reader.lines().map(|l| l.unwrap()).fold(String::new(), |mut out, line| {
-        // We need to trim new lines
-        let ln = line.trim();
-
-        // Lines that begin with '>' require special treatment
-        match &ln[..1] {
-            ">" => {
-                if out.len() > 0 {
-                    out.push('\n');
+use std::io::{self, BufReader, BufRead, Lines, Write};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Record {
+    // Everything after the leading '>' or '@'
+    pub header: String,
+    pub seq: String,
+    // Phred quality scores, only for FASTQ records
+    pub qual: Option<Vec<u8>>,
+}
+
+impl Record {
+    pub fn new(header: &str, seq: &str) -> Record {
+        Record { header: header.to_string(), seq: seq.to_string(), qual: None }
+    }
+
+    // The identifier is the first word of the header, the rest is a free form description
+    pub fn id(&self) -> &str {
+        self.header.split_whitespace().next().unwrap_or("")
+    }
+
+    pub fn len(&self) -> usize {
+        self.seq.len()
+    }
+
+    // Fraction of G and C among the unambiguous nucleotides, ambiguity codes like N are ignored
+    pub fn gc_content(&self) -> f64 {
+        let (gc, total) = self.seq.bytes().fold((0, 0), |(gc, total), b| {
+            match b {
+                b'G' | b'C' | b'g' | b'c' => (gc + 1, total + 1),
+                b'A' | b'T' | b'U' | b'a' | b't' | b'u' => (gc, total + 1),
+                _ => (gc, total),
+            }
+        });
+        if total == 0 { 0.0 } else { gc as f64 / total as f64 }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    // Sequence data found before the first header, on the given line
+    MissingHeader(usize),
+    // A FASTQ record whose third line doesn't start with '+'
+    MissingSeparator(usize),
+    // A FASTQ record ending before its quality line
+    Truncated(usize),
+    // Quality line of a different length than the sequence
+    QualityLength(usize),
+    // Quality character outside of the Phred+33 range
+    InvalidQuality(usize),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::MissingHeader(line) => write!(f, "line {}: sequence without a header", line),
+            Error::MissingSeparator(line) => write!(f, "line {}: expected a '+' line", line),
+            Error::Truncated(line) => write!(f, "line {}: truncated record", line),
+            Error::QualityLength(line) =>
+                write!(f, "line {}: quality and sequence lengths differ", line),
+            Error::InvalidQuality(line) => write!(f, "line {}: invalid quality score", line),
+        }
+    }
+}
+
+// Reads FASTA records one at a time. Sequences may span any number of lines, blank lines are
+// skipped.
+pub struct FastaReader<R> {
+    lines: Lines<R>,
+    line: usize,
+    // Header of the next record, already read while looking for the end of the current one
+    next_header: Option<String>,
+}
+
+impl<R: BufRead> FastaReader<R> {
+    pub fn new(reader: R) -> FastaReader<R> {
+        FastaReader { lines: reader.lines(), line: 0, next_header: None }
+    }
+}
+
+impl<R: BufRead> Iterator for FastaReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Result<Record, Error>> {
+        // Only the first record has to look for its header
+        while self.next_header.is_none() {
+            match self.lines.next() {
+                None => return None,
+                Some(Err(err)) => return Some(Err(Error::Io(err))),
+                Some(Ok(line)) => {
+                    self.line += 1;
+                    let line = line.trim();
+                    if line.starts_with('>') {
+                        self.next_header = Some(line[1..].to_string());
+                    } else if !line.is_empty() {
+                        return Some(Err(Error::MissingHeader(self.line)));
+                    }
                 }
+            }
+        }
+        let mut record = Record::new(&self.next_header.take().unwrap(), "");
+
+        while let Some(line) = self.lines.next() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(Error::Io(err))),
+            };
+            self.line += 1;
+            // We need to trim new lines
+            let line = line.trim();
+            if line.starts_with('>') {
+                self.next_header = Some(line[1..].to_string());
+                break;
+            }
+            record.seq.push_str(line);
+        }
+        Some(Ok(record))
+    }
+}
+
+// Reads four line FASTQ records with Phred+33 encoded qualities.
+pub struct FastqReader<R> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> FastqReader<R> {
+    pub fn new(reader: R) -> FastqReader<R> {
+        FastqReader { lines: reader.lines(), line: 0 }
+    }
 
-                // Push skipping the '>'
-                out.push_str(&ln[1..]);
-                out.push_str(": ");
+    fn next_line(&mut self) -> Result<Option<String>, Error> {
+        match self.lines.next() {
+            None => Ok(None),
+            Some(line) => {
+                self.line += 1;
+                Ok(Some(try!(line).trim().to_string()))
             }
-            // Other lines are just pushed
-            _ => out.push_str(ln)
         }
-        out
+    }
+
+    fn read_record(&mut self) -> Result<Option<Record>, Error> {
+        let mut header = String::new();
+        while header.is_empty() {
+            match try!(self.next_line()) {
+                None => return Ok(None),
+                Some(line) => header = line,
+            }
+        }
+        if !header.starts_with('@') {
+            return Err(Error::MissingHeader(self.line));
+        }
+        let seq = match try!(self.next_line()) {
+            Some(seq) => seq,
+            None => return Err(Error::Truncated(self.line)),
+        };
+        match try!(self.next_line()) {
+            Some(ref sep) if sep.starts_with('+') => {}
+            Some(_) => return Err(Error::MissingSeparator(self.line)),
+            None => return Err(Error::Truncated(self.line)),
+        }
+        let qual = match try!(self.next_line()) {
+            Some(qual) => qual,
+            None => return Err(Error::Truncated(self.line)),
+        };
+        if qual.len() != seq.len() {
+            return Err(Error::QualityLength(self.line));
+        }
+        if qual.bytes().any(|b| b < b'!' || b > b'~') {
+            return Err(Error::InvalidQuality(self.line));
+        }
+
+        Ok(Some(Record {
+            header: header[1..].to_string(),
+            seq: seq,
+            qual: Some(qual.bytes().map(|b| b - b'!').collect()),
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for FastqReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Result<Record, Error>> {
+        match self.read_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Alphabet {
+    Dna,
+    Rna,
+    Protein,
+}
+
+impl Alphabet {
+    // Accepted symbols, case insensitive. Nucleotides include the IUPAC ambiguity codes, proteins
+    // the 20 standard amino acids plus B, J, O, U, X, Z and the '*' stop. '-' is a gap.
+    fn symbols(&self) -> &'static str {
+        match *self {
+            Alphabet::Dna => "ACGTNRYKMSWBDHV-",
+            Alphabet::Rna => "ACGUNRYKMSWBDHV-",
+            Alphabet::Protein => "ACDEFGHIKLMNPQRSTVWYBJOUXZ*-",
+        }
+    }
+
+    pub fn contains(&self, symbol: char) -> bool {
+        self.symbols().contains(symbol.to_ascii_uppercase())
+    }
+
+    // Returns the position and value of the first symbol outside of the alphabet
+    pub fn validate(&self, seq: &str) -> Result<(), (usize, char)> {
+        match seq.chars().enumerate().find(|&(_, c)| !self.contains(c)) {
+            Some(invalid) => Err(invalid),
+            None => Ok(()),
+        }
+    }
+}
+
+// Writes FASTA records, wrapping sequences every `line_width` characters. A width of 0 writes
+// each sequence on a single line.
+pub struct FastaWriter<W> {
+    writer: W,
+    line_width: usize,
+}
+
+impl<W: Write> FastaWriter<W> {
+    pub fn new(writer: W, line_width: usize) -> FastaWriter<W> {
+        FastaWriter { writer: writer, line_width: line_width }
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        try!(writeln!(self.writer, ">{}", record.header));
+        if self.line_width == 0 {
+            return writeln!(self.writer, "{}", record.seq);
+        }
+        for chunk in record.seq.as_bytes().chunks(self.line_width) {
+            try!(self.writer.write_all(chunk));
+            try!(self.writer.write_all(b"\n"));
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+pub struct FastqWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> FastqWriter<W> {
+    pub fn new(writer: W) -> FastqWriter<W> {
+        FastqWriter { writer: writer }
+    }
+
+    // Records without qualities are written with the lowest score. Phred+33 only reaches 93
+    // ('~'), so higher scores are refused rather than wrapped around.
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let qual: String = match record.qual {
+            Some(ref qual) => {
+                if qual.iter().any(|&q| q > b'~' - b'!') {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                              "quality score above 93"));
+                }
+                qual.iter().map(|&q| (q + b'!') as char).collect()
+            }
+            None => record.seq.chars().map(|_| '!').collect(),
+        };
+        write!(self.writer, "@{}\n{}\n+\n{}\n", record.header, record.seq, qual)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+// Smallest length such that sequences at least that long cover half of the total length
+pub fn n50(lengths: &[usize]) -> usize {
+    let mut sorted = lengths.to_vec();
+    sorted.sort_by(|a, b| b.cmp(a));
+    let total = sorted.iter().fold(0, |acc, &l| acc + l);
+    let mut covered = 0;
+    for &length in &sorted {
+        covered += length;
+        if 2 * covered >= total {
+            return length;
+        }
+    }
+    0
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Stats {
+    pub records: usize,
+    pub total_length: usize,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub gc_content: f64,
+    pub n50: usize,
+}
+
+// Collects statistics over a whole file. Only the record lengths are kept in memory.
+pub fn stats<I: Iterator<Item=Result<Record, Error>>>(records: I) -> Result<Stats, Error> {
+    let mut lengths = Vec::new();
+    let (mut gc, mut at) = (0, 0);
+    for record in records {
+        let record = try!(record);
+        for b in record.seq.bytes() {
+            match b {
+                b'G' | b'C' | b'g' | b'c' => gc += 1,
+                b'A' | b'T' | b'U' | b'a' | b't' | b'u' => at += 1,
+                _ => {}
+            }
+        }
+        lengths.push(record.len());
+    }
+    Ok(Stats {
+        records: lengths.len(),
+        total_length: lengths.iter().fold(0, |acc, &l| acc + l),
+        min_length: lengths.iter().cloned().min().unwrap_or(0),
+        max_length: lengths.iter().cloned().max().unwrap_or(0),
+        gc_content: if gc + at == 0 { 0.0 } else { gc as f64 / (gc + at) as f64 },
+        n50: n50(&lengths),
     })
 }
 
+// We use a type parameter bound `<T: BufRead>` to accept all kinds of buffers
+fn format_fasta<T: BufRead>(reader: T) -> String {
+    let lines: Vec<String> = FastaReader::new(reader)
+        .map(|record| {
+            let record = record.unwrap();
+            format!("{}: {}", record.header, record.seq)
+        })
+        .collect();
+    lines.connect("\n")
+}
+
 fn read_file() -> String {
     let file = File::open("src/resources/test_data.fasta").unwrap();
-    format_fasta(&mut BufReader::new(file))
+    format_fasta(BufReader::new(file))
 }
 
 #[cfg(not(test))]
 fn main() {
     let s = read_file();
     println!("{}", s);
+
+    let file = File::open("src/resources/test_data.fasta").unwrap();
+    let s = stats(FastaReader::new(BufReader::new(file))).unwrap();
+    println!("\n{} records, {} residues, N50 {}", s.records, s.total_length, s.n50);
 }
 
-#[test]
-fn test_format_fasta() {
-    let s = read_file();
-    assert_eq!(s, "Rosetta_Example_1: THERECANBENOSPACE
+#[cfg(test)]
+mod test {
+    use super::{read_file, n50, stats, Alphabet, Error, Record};
+    use super::{FastaReader, FastaWriter, FastqReader, FastqWriter};
+    use std::io;
+
+    #[test]
+    fn test_format_fasta() {
+        let s = read_file();
+        assert_eq!(s, "Rosetta_Example_1: THERECANBENOSPACE
 Rosetta_Example_2: THERECANBESEVERALLINESBUTTHEYALLMUSTBECONCATENATED");
+    }
+
+    #[test]
+    fn test_fasta_reader() {
+        let input = "\n>seq1 first one\nACGT\nAC\n\n>seq2\n>seq3\nGG\n";
+        let records: Vec<Record> = FastaReader::new(input.as_bytes())
+            .map(|r| r.unwrap()).collect();
+        assert_eq!(records, vec![Record::new("seq1 first one", "ACGTAC"),
+                                 Record::new("seq2", ""),
+                                 Record::new("seq3", "GG")]);
+        assert_eq!(records[0].id(), "seq1");
+
+        match FastaReader::new("ACGT\n>x\n".as_bytes()).next() {
+            Some(Err(Error::MissingHeader(1))) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fastq_reader() {
+        let input = "@r1\nACGT\n+\nII#!\n@r2\nNN\n+r2\n~~\n";
+        let records: Vec<Record> = FastqReader::new(input.as_bytes())
+            .map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].qual, Some(vec![40, 40, 2, 0]));
+        assert_eq!(records[1].qual, Some(vec![93, 93]));
+
+        match FastqReader::new("@r1\nACGT\n+\nII\n".as_bytes()).next() {
+            Some(Err(Error::QualityLength(4))) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        match FastqReader::new("@r1\nACGT\n".as_bytes()).next() {
+            Some(Err(Error::Truncated(2))) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_alphabets() {
+        assert_eq!(Alphabet::Dna.validate("ACGTnryk-"), Ok(()));
+        assert_eq!(Alphabet::Dna.validate("ACGU"), Err((3, 'U')));
+        assert_eq!(Alphabet::Rna.validate("acgu"), Ok(()));
+        assert_eq!(Alphabet::Protein.validate("MKV*"), Ok(()));
+        assert_eq!(Alphabet::Protein.validate("MK1"), Err((2, '1')));
+    }
+
+    #[test]
+    fn test_writers() {
+        let record = Record::new("id desc", "ACGTACGTAC");
+        let mut writer = FastaWriter::new(Vec::new(), 4);
+        writer.write(&record).unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(),
+                   ">id desc\nACGT\nACGT\nAC\n");
+
+        let mut writer = FastaWriter::new(Vec::new(), 0);
+        writer.write(&record).unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), ">id desc\nACGTACGTAC\n");
+
+        // Writing and reading back FASTQ is lossless
+        let input = "@r1\nACGT\n+\nII#!\n";
+        let record = FastqReader::new(input.as_bytes()).next().unwrap().unwrap();
+        let mut writer = FastqWriter::new(Vec::new());
+        writer.write(&record).unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), input);
+
+        let mut record = Record::new("r2", "AC");
+        record.qual = Some(vec![93, 94]);
+        let mut writer = FastqWriter::new(Vec::new());
+        assert_eq!(writer.write(&record).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(writer.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_stats() {
+        assert_eq!(Record::new("x", "GGCCNNAT").gc_content(), 4.0 / 6.0);
+        assert_eq!(n50(&[2, 3, 4, 5, 6, 7, 8, 9, 10]), 8);
+        assert_eq!(n50(&[]), 0);
+
+        let input = ">a\nGGGG\n>b\nAT\n>c\nACGTACGT\n";
+        let s = stats(FastaReader::new(input.as_bytes())).unwrap();
+        assert_eq!(s.records, 3);
+        assert_eq!(s.total_length, 14);
+        assert_eq!(s.min_length, 2);
+        assert_eq!(s.max_length, 8);
+        assert_eq!(s.gc_content, 8.0 / 14.0);
+        assert_eq!(s.n50, 8);
+    }
 }
@@ -0,0 +1,364 @@
+// Implements http://rosettacode.org/wiki/Mandelbrot_set
+// and http://rosettacode.org/wiki/Julia_set, along with the curves drawn by L-systems:
+// http://rosettacode.org/wiki/Sierpinski_triangle/Graphical
+// http://rosettacode.org/wiki/Koch_curve
+// http://rosettacode.org/wiki/Dragon_curve
+// http://rosettacode.org/wiki/Hilbert_curve
+extern crate num;
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::mpsc::channel;
+use std::thread::spawn;
+use num::complex::Complex;
+use bitmap::{Color, Image};
+#[allow(dead_code)]
+mod bitmap;
+
+// Points escape once |z| passes this. It is much bigger than the 2 that decides escape, which
+// makes the smoothed counts smooth.
+const BAILOUT: f64 = 256.0;
+
+/// Which set to draw. The Mandelbrot set iterates z -> z^2 + c from z = 0 with c the point;
+/// a Julia set starts z at the point and keeps c fixed.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Set {
+    Mandelbrot,
+    Julia(Complex<f64>),
+}
+
+/// A view of a set: the point at the center of the image, how much of the plane a pixel
+/// covers, and how long to iterate before deciding a point is in the set.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Fractal {
+    pub set: Set,
+    pub center: Complex<f64>,
+    pub pixel_size: f64,
+    pub max_iterations: u32,
+}
+
+/// The escape count of a point, smoothed by how far past the bailout radius the last step
+/// went so that neighbouring points blend instead of falling into bands. None for points that
+/// don't escape, which are taken to be in the set.
+pub fn escape_time(set: Set, point: Complex<f64>, max_iterations: u32) -> Option<f64> {
+    let (mut z, c) = match set {
+        Set::Mandelbrot => (Complex::new(0.0, 0.0), point),
+        Set::Julia(c) => (point, c),
+    };
+    for i in 0..max_iterations {
+        let norm_sqr = z.norm_sqr();
+        if norm_sqr > BAILOUT * BAILOUT {
+            return Some(i as f64 + 1.0 - (norm_sqr.ln() / 2.0).log2());
+        }
+        z = z * z + c;
+    }
+    None
+}
+
+/// Colors spread evenly around a cycle, blended between; an escape count of `cycle`
+/// iterations goes once around.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Palette {
+    pub stops: Vec<Color>,
+    pub cycle: f64,
+    /// The color of the set itself
+    pub inside: Color,
+}
+
+impl Palette {
+    pub fn fire() -> Palette {
+        Palette {
+            stops: vec![Color { red: 0, green: 0, blue: 0 },
+                        Color { red: 160, green: 20, blue: 0 },
+                        Color { red: 255, green: 160, blue: 0 },
+                        Color { red: 255, green: 255, blue: 200 },
+                        Color { red: 200, green: 60, blue: 0 }],
+            cycle: 48.0,
+            inside: Color { red: 0, green: 0, blue: 0 },
+        }
+    }
+
+    pub fn ocean() -> Palette {
+        Palette {
+            stops: vec![Color { red: 0, green: 7, blue: 100 },
+                        Color { red: 32, green: 107, blue: 203 },
+                        Color { red: 237, green: 255, blue: 255 },
+                        Color { red: 255, green: 170, blue: 0 },
+                        Color { red: 0, green: 2, blue: 0 }],
+            cycle: 64.0,
+            inside: Color { red: 0, green: 0, blue: 0 },
+        }
+    }
+
+    pub fn grayscale() -> Palette {
+        Palette {
+            stops: vec![Color { red: 0, green: 0, blue: 0 },
+                        Color { red: 255, green: 255, blue: 255 }],
+            cycle: 32.0,
+            inside: Color { red: 0, green: 0, blue: 0 },
+        }
+    }
+
+    /// The color for a smoothed escape count, or for the set itself.
+    pub fn color(&self, escape: Option<f64>) -> Color {
+        let escape = match escape {
+            Some(escape) => escape.max(0.0),
+            None => return self.inside,
+        };
+        let position = (escape / self.cycle).fract() * self.stops.len() as f64;
+        let index = position as usize % self.stops.len();
+        let next = (index + 1) % self.stops.len();
+        self.stops[index].blend(self.stops[next], position.fract())
+    }
+}
+
+impl Fractal {
+    /// The point of the plane at the center of a pixel. The imaginary axis points up.
+    pub fn point(&self, x: usize, y: usize, width: usize, height: usize) -> Complex<f64> {
+        self.center + Complex::new((x as f64 + 0.5 - width as f64 / 2.0) * self.pixel_size,
+                                   (height as f64 / 2.0 - y as f64 - 0.5) * self.pixel_size)
+    }
+
+    /// Renders the fractal with the given number of threads, which take every so many rows
+    /// each and send them back as they finish.
+    pub fn render(&self, palette: &Palette, width: usize, height: usize, threads: usize)
+                  -> Image {
+        let threads = if threads == 0 { 1 } else { threads };
+        let (sender, receiver) = channel();
+        for thread in 0..threads {
+            let (sender, fractal, palette) = (sender.clone(), *self, palette.clone());
+            spawn(move || {
+                // Interleaving the rows gives every thread a similar share of the slow ones
+                for y in (0..height).filter(|y| y % threads == thread) {
+                    let row: Vec<Color> = (0..width).map(|x| {
+                        let point = fractal.point(x, y, width, height);
+                        palette.color(escape_time(fractal.set, point, fractal.max_iterations))
+                    }).collect();
+                    sender.send((y, row)).unwrap();
+                }
+            });
+        }
+
+        let mut image = Image::new(width, height);
+        for _ in 0..height {
+            let (y, row) = receiver.recv().unwrap();
+            for (x, color) in row.into_iter().enumerate() {
+                image[(x, y)] = color;
+            }
+        }
+        image
+    }
+}
+
+/// A Lindenmayer system: a starting string and rules that rewrite symbols into strings, all at
+/// once each generation. The turtle reads `F` and `G` as a step forward drawing a line, `+`
+/// and `-` as turns left and right by the angle, `[` and `]` as saving and going back to where
+/// it was, and skips anything else.
+pub struct LSystem {
+    pub axiom: String,
+    pub rules: HashMap<char, String>,
+    /// In degrees
+    pub angle: f64,
+}
+
+impl LSystem {
+    pub fn new(axiom: &str, rules: &[(char, &str)], angle: f64) -> LSystem {
+        LSystem {
+            axiom: axiom.to_string(),
+            rules: rules.iter().map(|&(symbol, rule)| (symbol, rule.to_string())).collect(),
+            angle: angle,
+        }
+    }
+
+    pub fn sierpinski_triangle() -> LSystem {
+        LSystem::new("F-G-G", &[('F', "F-G+F+G-F"), ('G', "GG")], 120.0)
+    }
+
+    pub fn koch_snowflake() -> LSystem {
+        LSystem::new("F--F--F", &[('F', "F+F--F+F")], 60.0)
+    }
+
+    pub fn dragon_curve() -> LSystem {
+        LSystem::new("FX", &[('X', "X+YF+"), ('Y', "-FX-Y")], 90.0)
+    }
+
+    pub fn hilbert_curve() -> LSystem {
+        LSystem::new("A", &[('A', "+BF-AFA-FB+"), ('B', "-AF+BFB+FA-")], 90.0)
+    }
+
+    /// The string after rewriting the axiom the given number of times.
+    pub fn expand(&self, generations: usize) -> String {
+        (0..generations).fold(self.axiom.clone(), |current, _| {
+            let mut next = String::with_capacity(current.len() * 2);
+            for symbol in current.chars() {
+                match self.rules.get(&symbol) {
+                    Some(rule) => next.push_str(rule),
+                    None => next.push(symbol),
+                }
+            }
+            next
+        })
+    }
+
+    /// The lines the turtle draws, taking unit steps from the origin along the x axis.
+    pub fn trace(&self, generations: usize) -> Vec<((f64, f64), (f64, f64))> {
+        let turn = self.angle * PI / 180.0;
+        let (mut position, mut heading) = ((0.0, 0.0), 0.0f64);
+        let mut saved = Vec::new();
+        let mut lines = Vec::new();
+        for symbol in self.expand(generations).chars() {
+            match symbol {
+                'F' | 'G' => {
+                    let next = (position.0 + heading.cos(), position.1 + heading.sin());
+                    lines.push((position, next));
+                    position = next;
+                }
+                '+' => heading += turn,
+                '-' => heading -= turn,
+                '[' => saved.push((position, heading)),
+                ']' => {
+                    if let Some((p, h)) = saved.pop() {
+                        position = p;
+                        heading = h;
+                    }
+                }
+                _ => {}
+            }
+        }
+        lines
+    }
+
+    /// Draws the curve on a white image, scaled to fit inside a margin of `margin` pixels.
+    pub fn render(&self, generations: usize, width: usize, height: usize, margin: f64,
+                  color: Color) -> Image {
+        let lines = self.trace(generations);
+        let mut image = Image::new(width, height);
+        image.fill(Color { red: 255, green: 255, blue: 255 });
+        if lines.is_empty() {
+            return image;
+        }
+
+        let (mut min, mut max) = ((0.0f64, 0.0f64), (0.0f64, 0.0f64));
+        for &(_, (x, y)) in &lines {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        let scale = ((width as f64 - 2.0 * margin) / (max.0 - min.0).max(1e-9))
+            .min((height as f64 - 2.0 * margin) / (max.1 - min.1).max(1e-9));
+        // Centered, with y flipped so that left turns look like left turns
+        let offset = ((width as f64 - (max.0 - min.0) * scale) / 2.0,
+                      (height as f64 - (max.1 - min.1) * scale) / 2.0);
+        let to_pixel = |(x, y): (f64, f64)| {
+            (offset.0 + (x - min.0) * scale, height as f64 - offset.1 - (y - min.1) * scale)
+        };
+        for &(from, to) in &lines {
+            image.draw_line_antialiased(to_pixel(from), to_pixel(to), color);
+        }
+        image
+    }
+}
+
+#[cfg(not(test))]
+fn main() {
+    let (width, height) = (600, 400);
+    let mandelbrot = Fractal {
+        set: Set::Mandelbrot,
+        center: Complex::new(-0.6, 0.0),
+        pixel_size: 3.0 / width as f64,
+        max_iterations: 500,
+    };
+    let julia = Fractal {
+        set: Set::Julia(Complex::new(-0.8, 0.156)),
+        center: Complex::new(0.0, 0.0),
+        pixel_size: 3.2 / width as f64,
+        max_iterations: 500,
+    };
+    let curve = |system: LSystem, generations: usize, color: Color| {
+        system.render(generations, 400, 400, 10.0, color)
+    };
+    let images = vec![
+        ("mandelbrot.png", mandelbrot.render(&Palette::ocean(), width, height, 4)),
+        ("julia.png", julia.render(&Palette::fire(), width, height, 4)),
+        ("sierpinski.png",
+         curve(LSystem::sierpinski_triangle(), 6, Color { red: 0, green: 90, blue: 0 })),
+        ("koch.png", curve(LSystem::koch_snowflake(), 4, Color { red: 0, green: 0, blue: 160 })),
+        ("dragon.png", curve(LSystem::dragon_curve(), 12, Color { red: 160, green: 0, blue: 0 })),
+        ("hilbert.png", curve(LSystem::hilbert_curve(), 5, Color { red: 0, green: 0, blue: 0 })),
+    ];
+    for (filename, image) in images {
+        match image.write_png(filename) {
+            Ok(()) => println!("Wrote {}", filename),
+            Err(e) => println!("Can't write {}: {}", filename, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{escape_time, Fractal, LSystem, Palette, Set};
+    use num::complex::Complex;
+
+    #[test]
+    fn escape_times() {
+        let inside = [Complex::new(0.0, 0.0), Complex::new(-1.0, 0.0), Complex::new(0.0, 1.0)];
+        for &point in &inside {
+            assert_eq!(escape_time(Set::Mandelbrot, point, 1000), None);
+        }
+        assert!(escape_time(Set::Mandelbrot, Complex::new(2.0, 0.0), 1000).unwrap() < 5.0);
+        // With c = 0 the Julia set is the unit circle
+        let julia = Set::Julia(Complex::new(0.0, 0.0));
+        assert_eq!(escape_time(julia, Complex::new(0.9, 0.3), 1000), None);
+        assert!(escape_time(julia, Complex::new(1.1, 0.0), 1000).is_some());
+
+        // Smoothing leaves no jumps between points close together
+        let mut previous = escape_time(Set::Mandelbrot, Complex::new(0.3, 0.0), 1000).unwrap();
+        for i in 1..100 {
+            let x = 0.3 + i as f64 * 0.001;
+            let escape = escape_time(Set::Mandelbrot, Complex::new(x, 0.0), 1000).unwrap();
+            assert!(escape <= previous && previous - escape < 1.0);
+            previous = escape;
+        }
+    }
+
+    #[test]
+    fn rendering() {
+        let fractal = Fractal {
+            set: Set::Mandelbrot,
+            center: Complex::new(-0.5, 0.0),
+            pixel_size: 0.05,
+            max_iterations: 100,
+        };
+        let palette = Palette::grayscale();
+        let image = fractal.render(&palette, 61, 41, 1);
+        assert_eq!(fractal.render(&palette, 61, 41, 4).data, image.data);
+        // The middle row runs along the real axis, from -2 to 1
+        assert_eq!(fractal.point(30, 20, 61, 41), Complex::new(-0.5, 0.0));
+        assert_eq!(image[(30, 20)], palette.inside);
+        assert!(image[(0, 0)] != palette.inside);
+        assert_eq!(palette.color(Some(16.0)), palette.stops[1]);
+        assert_eq!(palette.color(Some(32.0)), palette.stops[0]);
+    }
+
+    #[test]
+    fn l_systems() {
+        let koch = LSystem::koch_snowflake();
+        assert_eq!(koch.expand(1), "F+F--F+F--F+F--F+F--F+F--F+F");
+        assert_eq!(koch.trace(3).len(), 3 * 64);
+        assert_eq!(LSystem::sierpinski_triangle().trace(4).len(), 243);
+        assert_eq!(LSystem::dragon_curve().trace(10).len(), 1024);
+
+        // The Hilbert curve visits every point of a grid once, in unit steps
+        let lines = LSystem::hilbert_curve().trace(3);
+        assert_eq!(lines.len(), 63);
+        let mut visited: Vec<(i64, i64)> = lines.iter()
+            .flat_map(|&(from, to)| vec![from, to])
+            .map(|(x, y)| (x.round() as i64, y.round() as i64))
+            .collect();
+        visited.sort();
+        visited.dedup();
+        assert_eq!(visited.len(), 64);
+
+        let image = LSystem::hilbert_curve().render(2, 40, 40, 5.0, Default::default());
+        assert!(image.data.iter().any(|&c| c == Default::default()));
+    }
+}
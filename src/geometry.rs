@@ -0,0 +1,860 @@
+// Plane geometry shared by closest-pair.rs and friends. Implements
+// http://rosettacode.org/wiki/Convex_hull
+// http://rosettacode.org/wiki/Ray-casting_algorithm
+// http://rosettacode.org/wiki/Sutherland-Hodgman_polygon_clipping
+// http://rosettacode.org/wiki/Find_the_intersection_of_two_lines
+//
+// Every decision about which side of a line a point lies on goes through `orientation`, which is
+// exact for any finite input: a fast floating point test first, and when that is too close to
+// call, an exact evaluation with floating point expansions (after Shewchuk's "Adaptive Precision
+// Floating-Point Arithmetic and Fast Robust Geometric Predicates"). `in_circle` works the same
+// way. Constructed points, like intersections, are still rounded.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Add, Sub, Mul, Neg};
+
+/// A point in the plane, which doubles as a vector.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64
+}
+
+pub type Vector = Point;
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Point {
+        Point { x: x, y: y }
+    }
+
+    pub fn dot(self, other: Vector) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// The z component of the cross product, positive when `other` turns counterclockwise
+    /// from `self`. Rounded; see `orientation` for an exact sign.
+    pub fn cross(self, other: Vector) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn norm_sqr(self) -> f64 {
+        self.dot(self)
+    }
+
+    pub fn norm(self) -> f64 {
+        self.norm_sqr().sqrt()
+    }
+
+    /// Lexicographic order by x, then y; the order of a sweep line moving right.
+    pub fn cmp_xy(&self, other: &Point) -> Ordering {
+        (self.x, self.y).partial_cmp(&(other.x, other.y)).unwrap()
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Point {
+    type Output = Point;
+    fn mul(self, factor: f64) -> Point {
+        Point::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+    fn neg(self) -> Point {
+        Point::new(-self.x, -self.y)
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Orientation {
+    CounterClockwise,
+    Clockwise,
+    Collinear
+}
+
+// x + y exactly, as the rounded sum and the rounding error
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    let b_virtual = x - a;
+    let a_virtual = x - b_virtual;
+    (x, (a - a_virtual) + (b - b_virtual))
+}
+
+// Splits a into two halves of 26 significant bits, which multiply without rounding
+fn split(a: f64) -> (f64, f64) {
+    let c = 134217729.0 * a; // 2^27 + 1
+    let high = c - (c - a);
+    (high, a - high)
+}
+
+// a * b exactly, as the rounded product and the rounding error
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let x = a * b;
+    let (a_high, a_low) = split(a);
+    let (b_high, b_low) = split(b);
+    let error = x - a_high * b_high - a_low * b_high - a_high * b_low;
+    (x, a_low * b_low - error)
+}
+
+// Adds b to an expansion: a sum of non-overlapping components of increasing magnitude
+fn grow_expansion(expansion: &[f64], b: f64) -> Vec<f64> {
+    let mut sum = Vec::with_capacity(expansion.len() + 1);
+    let mut q = b;
+    for &e in expansion {
+        let (q_next, h) = two_sum(q, e);
+        if h != 0.0 {
+            sum.push(h);
+        }
+        q = q_next;
+    }
+    sum.push(q);
+    sum
+}
+
+/// The exact sign of a sum of products, each term given as (a, b, sign of a * b).
+fn exact_sign(terms: &[(f64, f64, f64)]) -> f64 {
+    let mut expansion = Vec::new();
+    for &(a, b, sign) in terms {
+        let (product, error) = two_product(a, b);
+        expansion = grow_expansion(&expansion, sign * error);
+        expansion = grow_expansion(&expansion, sign * product);
+    }
+    // The largest component decides the sign
+    expansion.iter().rev().cloned().find(|&e| e != 0.0).unwrap_or(0.0)
+}
+
+/// Which way the path a, b, c turns, exactly.
+pub fn orientation(a: Point, b: Point, c: Point) -> Orientation {
+    let left = (b.x - a.x) * (c.y - a.y);
+    let right = (b.y - a.y) * (c.x - a.x);
+    let det = left - right;
+    // Shewchuk's error bound for the rounded determinant
+    let bound = 3.3306690738754716e-16 * (left.abs() + right.abs());
+    let det = if det > bound || -det > bound {
+        det
+    } else {
+        // a.x b.y - a.x c.y - a.y b.x + a.y c.x + b.x c.y - b.y c.x
+        exact_sign(&[(a.x, b.y, 1.0), (a.x, c.y, -1.0), (a.y, b.x, -1.0),
+                     (a.y, c.x, 1.0), (b.x, c.y, 1.0), (b.y, c.x, -1.0)])
+    };
+    if det > 0.0 {
+        Orientation::CounterClockwise
+    } else if det < 0.0 {
+        Orientation::Clockwise
+    } else {
+        Orientation::Collinear
+    }
+}
+
+/// Whether p lies on the closed segment from a to b.
+pub fn on_segment(p: Point, a: Point, b: Point) -> bool {
+    orientation(a, b, p) == Orientation::Collinear
+        && a.x.min(b.x) <= p.x && p.x <= a.x.max(b.x)
+        && a.y.min(b.y) <= p.y && p.y <= a.y.max(b.y)
+}
+
+// a * b, exactly, for an expansion a
+fn scale_expansion(expansion: &[f64], b: f64) -> Vec<f64> {
+    let mut product = Vec::new();
+    for &e in expansion {
+        let (x, error) = two_product(e, b);
+        product = grow_expansion(&product, error);
+        product = grow_expansion(&product, x);
+    }
+    product
+}
+
+/// Where d lies relative to the circle through a, b and c, which must turn counterclockwise.
+pub fn in_circle(a: Point, b: Point, c: Point, d: Point) -> Location {
+    let (ad, bd, cd) = (a - d, b - d, c - d);
+    let (a_lift, b_lift, c_lift) = (ad.norm_sqr(), bd.norm_sqr(), cd.norm_sqr());
+    let det = a_lift * bd.cross(cd) + b_lift * cd.cross(ad) + c_lift * ad.cross(bd);
+    let permanent = ((bd.x * cd.y).abs() + (cd.x * bd.y).abs()) * a_lift
+        + ((cd.x * ad.y).abs() + (ad.x * cd.y).abs()) * b_lift
+        + ((ad.x * bd.y).abs() + (bd.x * ad.y).abs()) * c_lift;
+    let bound = 1.1102230246251577e-15 * permanent;
+    let det = if det > bound || -det > bound {
+        det
+    } else {
+        // Expand the 4x4 determinant with rows (x, y, x^2 + y^2, 1) along the lifted column:
+        // each minor is the orientation determinant of the other three points.
+        let orientation_terms = |p: Point, q: Point, r: Point| {
+            [(p.x, q.y, 1.0), (p.x, r.y, -1.0), (p.y, q.x, -1.0),
+             (p.y, r.x, 1.0), (q.x, r.y, 1.0), (q.y, r.x, -1.0)]
+        };
+        let cofactors = [(a, orientation_terms(b, c, d), 1.0),
+                         (b, orientation_terms(a, c, d), -1.0),
+                         (c, orientation_terms(a, b, d), 1.0),
+                         (d, orientation_terms(a, b, c), -1.0)];
+        let mut sum = Vec::new();
+        for &(p, ref terms, sign) in &cofactors {
+            for &lift in &[p.x, p.y] {
+                for &(u, v, term_sign) in terms.iter() {
+                    let product = scale_expansion(&scale_expansion(&[lift], lift), u);
+                    for &e in &scale_expansion(&product, v) {
+                        sum = grow_expansion(&sum, sign * term_sign * e);
+                    }
+                }
+            }
+        }
+        sum.iter().rev().cloned().find(|&e| e != 0.0).unwrap_or(0.0)
+    };
+    if det > 0.0 {
+        Location::Inside
+    } else if det < 0.0 {
+        Location::Outside
+    } else {
+        Location::Boundary
+    }
+}
+
+/// The convex hull by Andrew's monotone chain: the vertices counterclockwise, starting from the
+/// leftmost (lowest) point, without collinear points.
+pub fn convex_hull(points: &[Point]) -> Vec<Point> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.cmp_xy(b));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let mut hull: Vec<Point> = Vec::with_capacity(2 * sorted.len());
+    // The lower hull left to right, then the upper hull right to left
+    for pass in 0..2 {
+        let start = hull.len();
+        let chain: Vec<Point> = if pass == 0 {
+            sorted.clone()
+        } else {
+            sorted.iter().rev().cloned().collect()
+        };
+        for &p in &chain {
+            while hull.len() >= start + 2
+                  && orientation(hull[hull.len() - 2], hull[hull.len() - 1], p)
+                     != Orientation::CounterClockwise {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The last point of each chain is the first of the other
+        hull.pop();
+    }
+    hull
+}
+
+/// The signed area by the shoelace formula: positive for a counterclockwise polygon.
+pub fn polygon_area(polygon: &[Point]) -> f64 {
+    let n = polygon.len();
+    (0..n).fold(0.0, |sum, i| sum + polygon[i].cross(polygon[(i + 1) % n])) / 2.0
+}
+
+/// The center of mass of the polygon's area, or None if it has no area.
+pub fn centroid(polygon: &[Point]) -> Option<Point> {
+    let n = polygon.len();
+    let area = polygon_area(polygon);
+    if area == 0.0 {
+        return None;
+    }
+    let sum = (0..n).fold(Point::default(), |sum, i| {
+        let (p, q) = (polygon[i], polygon[(i + 1) % n]);
+        sum + (p + q) * p.cross(q)
+    });
+    Some(sum * (1.0 / (6.0 * area)))
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Location {
+    Inside,
+    Outside,
+    Boundary
+}
+
+/// Locates the point relative to the polygon, by its winding number. Points enclosed by a
+/// self-intersecting polygon count as inside if it winds around them at all.
+pub fn point_in_polygon(p: Point, polygon: &[Point]) -> Location {
+    let n = polygon.len();
+    let mut winding = 0;
+    for i in 0..n {
+        let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+        if on_segment(p, a, b) {
+            return Location::Boundary;
+        }
+        // Count the edges crossing the horizontal ray to the right of p, upward edges with p on
+        // their left and downward edges with p on their right
+        if a.y <= p.y {
+            if b.y > p.y && orientation(a, b, p) == Orientation::CounterClockwise {
+                winding += 1;
+            }
+        } else if b.y <= p.y && orientation(a, b, p) == Orientation::Clockwise {
+            winding -= 1;
+        }
+    }
+    if winding != 0 { Location::Inside } else { Location::Outside }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Segment {
+    pub a: Point,
+    pub b: Point
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Intersection {
+    Disjoint,
+    Point(Point),
+    // Collinear segments sharing more than a point, from one end of the overlap to the other
+    Overlap(Point, Point)
+}
+
+impl Segment {
+    pub fn new(a: Point, b: Point) -> Segment {
+        Segment { a: a, b: b }
+    }
+
+    pub fn intersects(&self, other: &Segment) -> bool {
+        let (a, b, c, d) = (self.a, self.b, other.a, other.b);
+        let (o1, o2) = (orientation(a, b, c), orientation(a, b, d));
+        let (o3, o4) = (orientation(c, d, a), orientation(c, d, b));
+        if o1 != o2 && o3 != o4
+           && o1 != Orientation::Collinear && o2 != Orientation::Collinear
+           && o3 != Orientation::Collinear && o4 != Orientation::Collinear {
+            return true;
+        }
+        on_segment(c, a, b) || on_segment(d, a, b) || on_segment(a, c, d) || on_segment(b, c, d)
+    }
+
+    pub fn intersection(&self, other: &Segment) -> Intersection {
+        if !self.intersects(other) {
+            return Intersection::Disjoint;
+        }
+        let (r, s) = (self.b - self.a, other.b - other.a);
+        let collinear = orientation(self.a, self.b, other.a) == Orientation::Collinear
+                        && orientation(self.a, self.b, other.b) == Orientation::Collinear;
+        if !collinear {
+            // The segments touch or cross at a single point; compute it from the longer one to
+            // limit rounding
+            let t = (other.a - self.a).cross(s) / r.cross(s);
+            let u = (other.a - self.a).cross(r) / r.cross(s);
+            return Intersection::Point(if r.norm_sqr() >= s.norm_sqr() {
+                self.a + r * t
+            } else {
+                other.a + s * u
+            });
+        }
+        // Collinear and touching: the overlap lies between the middle two endpoints in the
+        // direction of the segments
+        let mut ends = [self.a, self.b, other.a, other.b];
+        ends.sort_by(|p, q| p.cmp_xy(q));
+        if ends[1] == ends[2] {
+            Intersection::Point(ends[1])
+        } else {
+            Intersection::Overlap(ends[1], ends[2])
+        }
+    }
+
+    // The endpoints in sweep order
+    fn left(&self) -> Point {
+        if self.a.cmp_xy(&self.b) == Ordering::Greater { self.b } else { self.a }
+    }
+
+    fn right(&self) -> Point {
+        if self.a.cmp_xy(&self.b) == Ordering::Greater { self.a } else { self.b }
+    }
+}
+
+// Point ordered for the event queue of the sweep
+#[derive(Copy, Clone, PartialEq)]
+struct SweepPoint(Point);
+
+impl Eq for SweepPoint {}
+
+impl PartialOrd for SweepPoint {
+    fn partial_cmp(&self, other: &SweepPoint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SweepPoint {
+    fn cmp(&self, other: &SweepPoint) -> Ordering {
+        self.0.cmp_xy(&other.0)
+    }
+}
+
+// What happens at an event point: segments starting there, and segments known to cross there
+#[derive(Default)]
+struct Event {
+    starts: Vec<usize>,
+    crossings: Vec<usize>
+}
+
+// Schedules the crossing of two segments if it lies ahead of the sweep line
+fn schedule_crossing(segments: &[Segment], i: usize, j: usize, sweep: Point,
+                     events: &mut BTreeMap<SweepPoint, Event>) {
+    if let Intersection::Point(q) = segments[i].intersection(&segments[j]) {
+        if q.cmp_xy(&sweep) == Ordering::Greater {
+            let event = events.entry(SweepPoint(q)).or_insert(Event::default());
+            for &k in &[i, j] {
+                if !event.crossings.contains(&k) {
+                    event.crossings.push(k);
+                }
+            }
+        }
+    }
+}
+
+// The segments cut by the sweep line, from bottom to top, as a treap: a binary search tree kept
+// balanced by heap ordering pseudo-random node priorities. Nodes are the segment indices, and
+// there is no key; trees are split at the point where a predicate stops holding, which finds
+// places in the order in O(log n) expected time.
+struct Status {
+    children: Vec<(Option<usize>, Option<usize>)>,
+}
+
+impl Status {
+    fn priority(node: usize) -> u64 {
+        // The finalizer of splitmix64
+        let mut z = (node as u64).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // Splits the tree into the nodes for which `before` holds, which come first, and the rest
+    fn split<F: Fn(usize) -> bool>(&mut self, tree: Option<usize>, before: &F)
+                                   -> (Option<usize>, Option<usize>) {
+        match tree {
+            None => (None, None),
+            Some(node) => {
+                let (left, right) = self.children[node];
+                if before(node) {
+                    let (first, rest) = self.split(right, before);
+                    self.children[node].1 = first;
+                    (Some(node), rest)
+                } else {
+                    let (first, rest) = self.split(left, before);
+                    self.children[node].0 = rest;
+                    (first, Some(node))
+                }
+            }
+        }
+    }
+
+    // Joins two trees, every node of `a` coming before those of `b`
+    fn merge(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (None, tree) | (tree, None) => tree,
+            (Some(x), Some(y)) => {
+                if Status::priority(x) > Status::priority(y) {
+                    let right = self.children[x].1;
+                    self.children[x].1 = self.merge(right, b);
+                    a
+                } else {
+                    let left = self.children[y].0;
+                    self.children[y].0 = self.merge(a, left);
+                    b
+                }
+            }
+        }
+    }
+
+    fn first(&self, mut tree: Option<usize>) -> Option<usize> {
+        while let Some(node) = tree {
+            match self.children[node].0 {
+                Some(left) => tree = Some(left),
+                None => return Some(node),
+            }
+        }
+        None
+    }
+
+    fn last(&self, mut tree: Option<usize>) -> Option<usize> {
+        while let Some(node) = tree {
+            match self.children[node].1 {
+                Some(right) => tree = Some(right),
+                None => return Some(node),
+            }
+        }
+        None
+    }
+
+    // Appends the nodes of the tree in order
+    fn collect(&self, tree: Option<usize>, nodes: &mut Vec<usize>) {
+        if let Some(node) = tree {
+            self.collect(self.children[node].0, nodes);
+            nodes.push(node);
+            self.collect(self.children[node].1, nodes);
+        }
+    }
+}
+
+/// All intersection points of the segments by the Bentley-Ottmann sweep, in sweep order, each
+/// with the indices of the segments meeting there. A vertical line sweeps left to right, keeping
+/// the segments it cuts ordered from bottom to top; only segments that become neighbors in that
+/// order are tested against each other. Collinear overlaps are reported at their endpoints. For
+/// n segments meeting at k points this takes O((n + k) log n) expected time.
+pub fn all_intersections(segments: &[Segment]) -> Vec<(Point, Vec<usize>)> {
+    let mut events: BTreeMap<SweepPoint, Event> = BTreeMap::new();
+    for (i, segment) in segments.iter().enumerate() {
+        events.entry(SweepPoint(segment.left())).or_insert(Event::default()).starts.push(i);
+        events.entry(SweepPoint(segment.right())).or_insert(Event::default());
+    }
+
+    let mut status = Status { children: vec![(None, None); segments.len()] };
+    let mut root = None;
+    let mut result = Vec::new();
+    loop {
+        let (p, event) = match events.keys().next().cloned() {
+            Some(key) => (key.0, events.remove(&key).unwrap()),
+            None => break,
+        };
+
+        // Split the status into the segments below p, those ending at or passing through it,
+        // and those above it. Crossings count as passing through p even if rounding put p to
+        // one side of them.
+        let through = |s: usize| {
+            event.crossings.contains(&s) || on_segment(p, segments[s].a, segments[s].b)
+        };
+        let side = |s: usize| orientation(segments[s].left(), segments[s].right(), p);
+        let (below, rest) = status.split(root, &|s| {
+            !through(s) && side(s) == Orientation::CounterClockwise
+        });
+        let (at, above) = status.split(rest, &|s| through(s) || side(s) != Orientation::Clockwise);
+        let mut at_p = Vec::new();
+        status.collect(at, &mut at_p);
+
+        let (ending, mut passing): (Vec<usize>, Vec<usize>) = at_p.into_iter()
+            .partition(|&s| segments[s].right() == p);
+        let mut meeting: Vec<usize> = event.starts.iter().chain(ending.iter())
+            .chain(passing.iter()).cloned().collect();
+        meeting.sort();
+        meeting.dedup();
+        if meeting.len() > 1 {
+            result.push((p, meeting));
+        }
+
+        // Put back the segments passing through or starting at p in their order just right of
+        // p, which is the order of their directions
+        // (a segment of no length starts and ends here, so it doesn't go in)
+        passing.extend(event.starts.iter().cloned().filter(|&s| segments[s].right() != p));
+        passing.sort_by(|&s, &t| {
+            match orientation(p, segments[s].right(), segments[t].right()) {
+                Orientation::CounterClockwise => Ordering::Less,
+                Orientation::Clockwise => Ordering::Greater,
+                Orientation::Collinear => Ordering::Equal,
+            }
+        });
+        let (lower, upper) = (status.last(below), status.first(above));
+        let mut middle = None;
+        for &s in &passing {
+            status.children[s] = (None, None);
+            middle = status.merge(middle, Some(s));
+        }
+        let joined = status.merge(below, middle);
+        root = status.merge(joined, above);
+
+        // Check the new neighbors for crossings
+        match (passing.first(), passing.last()) {
+            (Some(&bottom), Some(&top)) => {
+                if let Some(lower) = lower {
+                    schedule_crossing(segments, lower, bottom, p, &mut events);
+                }
+                if let Some(upper) = upper {
+                    schedule_crossing(segments, top, upper, p, &mut events);
+                }
+            }
+            _ => {
+                if let (Some(lower), Some(upper)) = (lower, upper) {
+                    schedule_crossing(segments, lower, upper, p, &mut events);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Clips the polygon to the closed half-plane left of the directed line through a and b.
+pub fn clip_half_plane(polygon: &[Point], a: Point, b: Point) -> Vec<Point> {
+    if polygon.is_empty() {
+        return Vec::new();
+    }
+    let side = |p: Point| orientation(a, b, p);
+    // Where the edge from p to q crosses the line
+    let crossing = |p: Point, q: Point| {
+        let t = (a - p).cross(b - a) / (q - p).cross(b - a);
+        p + (q - p) * t
+    };
+    let mut output = Vec::with_capacity(polygon.len() + 1);
+    let mut prev = polygon[polygon.len() - 1];
+    for &curr in polygon {
+        // Points on the line count as inside. An edge only crosses the line if it goes
+        // strictly from one side to the other; otherwise the crossing is an endpoint.
+        match (side(prev), side(curr)) {
+            (Orientation::Clockwise, Orientation::Clockwise) => {}
+            (Orientation::Clockwise, Orientation::CounterClockwise) => {
+                output.push(crossing(prev, curr));
+                output.push(curr);
+            }
+            (Orientation::CounterClockwise, Orientation::Clockwise) => {
+                output.push(crossing(prev, curr));
+            }
+            (_, Orientation::Clockwise) => {}
+            _ => output.push(curr),
+        }
+        prev = curr;
+    }
+    output
+}
+
+/// Clips the polygon to a convex clipping polygon, by Sutherland-Hodgman: one pass per edge of
+/// the clipping polygon, keeping the part of the polygon on its inner side. The clipping
+/// polygon may be given in either direction.
+pub fn clip_polygon(subject: &[Point], clip: &[Point]) -> Vec<Point> {
+    let mut clip = clip.to_vec();
+    if polygon_area(&clip) < 0.0 {
+        clip.reverse();
+    }
+    let mut output = subject.to_vec();
+    for i in 0..clip.len() {
+        output = clip_half_plane(&output, clip[i], clip[(i + 1) % clip.len()]);
+    }
+    output
+}
+
+#[cfg(not(test))]
+#[allow(dead_code)]
+pub fn main() {
+    let points: Vec<Point> = [(16, 3), (12, 17), (0, 6), (-4, -6), (16, 6), (16, -7), (16, -3),
+                              (17, -4), (5, 19), (19, -8), (3, 16), (12, 13), (3, -4), (17, 5),
+                              (-3, 15), (-3, -9), (0, 11), (-9, -3), (-4, -2), (12, 10)]
+        .iter().map(|&(x, y)| Point::new(x as f64, y as f64)).collect();
+    let hull = convex_hull(&points);
+    let hull_strings: Vec<String> = hull.iter().map(|p| p.to_string()).collect();
+    println!("Convex hull: {}", hull_strings.connect(" "));
+    println!("Area: {}, centroid: {}", polygon_area(&hull), centroid(&hull).unwrap());
+
+    let subject: Vec<Point> = [(50, 150), (200, 50), (350, 150), (350, 300), (250, 300),
+                               (200, 250), (150, 350), (100, 250), (100, 200)]
+        .iter().map(|&(x, y)| Point::new(x as f64, y as f64)).collect();
+    let clip: Vec<Point> = [(100, 100), (300, 100), (300, 300), (100, 300)]
+        .iter().map(|&(x, y)| Point::new(x as f64, y as f64)).collect();
+    let clipped: Vec<String> = clip_polygon(&subject, &clip).iter().map(|p| p.to_string())
+                                                            .collect();
+    println!("Clipped: {}", clipped.connect(" "));
+
+    for &(x, y) in &[(5.0, 5.0), (5.0, 8.0), (-10.0, 5.0), (0.0, 5.0), (10.0, 5.0), (8.0, 5.0),
+                     (10.0, 10.0)] {
+        println!("({}, {}) in the square: {:?}", x, y,
+                 point_in_polygon(Point::new(x, y), &clip_square()));
+    }
+
+    let segments = [Segment::new(Point::new(0.0, 0.0), Point::new(4.0, 4.0)),
+                    Segment::new(Point::new(0.0, 4.0), Point::new(4.0, 0.0)),
+                    Segment::new(Point::new(0.0, 2.0), Point::new(4.0, 2.0)),
+                    Segment::new(Point::new(3.0, 0.0), Point::new(3.0, 5.0))];
+    for (point, meeting) in all_intersections(&segments) {
+        println!("Segments {:?} meet at {}", meeting, point);
+    }
+}
+
+#[cfg(not(test))]
+fn clip_square() -> Vec<Point> {
+    vec![Point::new(0.0, 0.0), Point::new(10.0, 0.0), Point::new(10.0, 10.0),
+         Point::new(0.0, 10.0)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Point, Segment, Intersection, Location, Orientation};
+    use super::{orientation, in_circle, convex_hull, polygon_area, centroid, point_in_polygon,
+                all_intersections, clip_polygon};
+
+    fn points(coords: &[(i32, i32)]) -> Vec<Point> {
+        coords.iter().map(|&(x, y)| Point::new(x as f64, y as f64)).collect()
+    }
+
+    #[test]
+    fn robust_orientation() {
+        // Nearly collinear points where the rounded determinant gets the sign wrong
+        let a = Point::new(0.5, 0.5);
+        let b = Point::new(12.0, 12.0);
+        let c = Point::new(24.0, 24.0);
+        assert_eq!(orientation(a, b, c), Orientation::Collinear);
+        let nudged = Point::new(0.5 + 2f64.powi(-53), 0.5);
+        assert_eq!(orientation(nudged, b, c), Orientation::Clockwise);
+        let nudged = Point::new(0.5, 0.5 + 2f64.powi(-53));
+        assert_eq!(orientation(nudged, b, c), Orientation::CounterClockwise);
+        // All of these lie exactly on the line y = x
+        for i in 0..64 {
+            let p = Point::new(0.5 + i as f64 * 2f64.powi(-52), 0.5 + i as f64 * 2f64.powi(-52));
+            assert_eq!(orientation(p, b, c), Orientation::Collinear);
+        }
+    }
+
+    #[test]
+    fn robust_in_circle() {
+        let (a, b, c) = (Point::new(0.0, 0.0), Point::new(1.0, 0.0), Point::new(1.0, 1.0));
+        assert_eq!(in_circle(a, b, c, Point::new(0.5, 0.5)), Location::Inside);
+        assert_eq!(in_circle(a, b, c, Point::new(2.0, 2.0)), Location::Outside);
+        assert_eq!(in_circle(a, b, c, Point::new(0.0, 1.0)), Location::Boundary);
+        // Far from the origin the rounded determinant is all noise
+        let offset = Point::new(1e8 + 0.1, 1e8 + 0.3);
+        let (a, b, c) = (a * 0.5 + offset, b * 0.5 + offset, c * 0.5 + offset);
+        assert_eq!(in_circle(a, b, c, Point::new(0.0, 0.5) + offset), Location::Boundary);
+        let ulp = 2f64.powi(-26);
+        assert_eq!(in_circle(a, b, c, Point::new(ulp, 0.5) + offset), Location::Inside);
+        assert_eq!(in_circle(a, b, c, Point::new(-ulp, 0.5) + offset), Location::Outside);
+    }
+
+    #[test]
+    fn hull() {
+        let input = points(&[(16, 3), (12, 17), (0, 6), (-4, -6), (16, 6), (16, -7), (16, -3),
+                             (17, -4), (5, 19), (19, -8), (3, 16), (12, 13), (3, -4), (17, 5),
+                             (-3, 15), (-3, -9), (0, 11), (-9, -3), (-4, -2), (12, 10)]);
+        assert_eq!(convex_hull(&input),
+                   points(&[(-9, -3), (-3, -9), (19, -8), (17, 5), (12, 17), (5, 19), (-3, 15)]));
+        // Collinear and repeated points
+        let line = points(&[(0, 0), (1, 1), (2, 2), (2, 2), (0, 0)]);
+        assert_eq!(convex_hull(&line), points(&[(0, 0), (2, 2)]));
+        let square = points(&[(0, 0), (1, 0), (2, 0), (2, 2), (0, 2), (1, 1), (0, 1)]);
+        assert_eq!(convex_hull(&square), points(&[(0, 0), (2, 0), (2, 2), (0, 2)]));
+    }
+
+    #[test]
+    fn area_and_centroid() {
+        let square = points(&[(0, 0), (4, 0), (4, 4), (0, 4)]);
+        assert_eq!(polygon_area(&square), 16.0);
+        let reversed: Vec<Point> = square.iter().rev().cloned().collect();
+        assert_eq!(polygon_area(&reversed), -16.0);
+        assert_eq!(centroid(&square), Some(Point::new(2.0, 2.0)));
+        assert_eq!(centroid(&reversed), Some(Point::new(2.0, 2.0)));
+
+        let l_shape = points(&[(0, 0), (2, 0), (2, 1), (1, 1), (1, 2), (0, 2)]);
+        assert_eq!(polygon_area(&l_shape), 3.0);
+        let c = centroid(&l_shape).unwrap();
+        assert!((c.x - 5.0 / 6.0).abs() < 1e-12 && (c.y - 5.0 / 6.0).abs() < 1e-12);
+        assert_eq!(centroid(&points(&[(0, 0), (1, 1), (2, 2)])), None);
+    }
+
+    #[test]
+    fn locate_points() {
+        // A square with a notch cut from its top
+        let polygon = points(&[(0, 0), (10, 0), (10, 10), (6, 10), (5, 5), (4, 10), (0, 10)]);
+        let cases = [((5.0, 2.0), Location::Inside), ((5.0, 8.0), Location::Outside),
+                     ((5.0, 5.0), Location::Boundary), ((10.0, 3.0), Location::Boundary),
+                     ((-1.0, 5.0), Location::Outside), ((2.0, 10.0), Location::Boundary),
+                     ((2.0, 9.9), Location::Inside), ((11.0, 0.0), Location::Outside),
+                     ((4.5, 7.5), Location::Boundary)];
+        for &((x, y), expected) in &cases {
+            assert_eq!(point_in_polygon(Point::new(x, y), &polygon), expected);
+        }
+    }
+
+    #[test]
+    fn segment_intersection() {
+        let segment = |a: (i32, i32), b: (i32, i32)| {
+            Segment::new(Point::new(a.0 as f64, a.1 as f64), Point::new(b.0 as f64, b.1 as f64))
+        };
+        assert_eq!(segment((0, 0), (4, 4)).intersection(&segment((0, 4), (4, 0))),
+                   Intersection::Point(Point::new(2.0, 2.0)));
+        assert_eq!(segment((0, 0), (4, 4)).intersection(&segment((4, 4), (6, 0))),
+                   Intersection::Point(Point::new(4.0, 4.0)));
+        assert_eq!(segment((0, 0), (4, 4)).intersection(&segment((2, 2), (6, 6))),
+                   Intersection::Overlap(Point::new(2.0, 2.0), Point::new(4.0, 4.0)));
+        assert_eq!(segment((0, 0), (2, 2)).intersection(&segment((2, 2), (6, 6))),
+                   Intersection::Point(Point::new(2.0, 2.0)));
+        assert_eq!(segment((0, 0), (2, 2)).intersection(&segment((3, 3), (6, 6))),
+                   Intersection::Disjoint);
+        assert_eq!(segment((0, 0), (4, 0)).intersection(&segment((1, 1), (3, 1))),
+                   Intersection::Disjoint);
+    }
+
+    #[test]
+    fn sweep_matches_brute_force() {
+        // Pseudo-random segments from a linear congruential generator
+        let mut state = 12345u64;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) as f64 / (1u64 << 31) as f64 * 100.0
+        };
+        let segments: Vec<Segment> = (0..60)
+            .map(|_| Segment::new(Point::new(next(), next()), Point::new(next(), next())))
+            .collect();
+        let mut brute = Vec::new();
+        for i in 0..segments.len() {
+            for j in i + 1..segments.len() {
+                if segments[i].intersects(&segments[j]) {
+                    brute.push((i, j));
+                }
+            }
+        }
+        let mut swept = Vec::new();
+        for (_, meeting) in all_intersections(&segments) {
+            assert_eq!(meeting.len(), 2);
+            swept.push((meeting[0], meeting[1]));
+        }
+        swept.sort();
+        assert!(brute.len() > 100);
+        assert_eq!(swept, brute);
+    }
+
+    #[test]
+    fn sweep_degenerate() {
+        let segment = |a: (i32, i32), b: (i32, i32)| {
+            Segment::new(Point::new(a.0 as f64, a.1 as f64), Point::new(b.0 as f64, b.1 as f64))
+        };
+        // Three segments through one point, a vertical one, and shared endpoints
+        let segments = [segment((0, 0), (4, 4)), segment((0, 4), (4, 0)), segment((0, 2), (4, 2)),
+                        segment((3, 0), (3, 5)), segment((4, 4), (6, 0)), segment((6, 0), (8, 1))];
+        let found: Vec<(Point, Vec<usize>)> = all_intersections(&segments);
+        assert_eq!(found,
+                   vec![(Point::new(2.0, 2.0), vec![0, 1, 2]), (Point::new(3.0, 1.0), vec![1, 3]),
+                        (Point::new(3.0, 2.0), vec![2, 3]), (Point::new(3.0, 3.0), vec![0, 3]),
+                        (Point::new(4.0, 4.0), vec![0, 4]), (Point::new(6.0, 0.0), vec![4, 5])]);
+    }
+
+    #[test]
+    fn clipping() {
+        let subject = points(&[(50, 150), (200, 50), (350, 150), (350, 300), (250, 300),
+                               (200, 250), (150, 350), (100, 250), (100, 200)]);
+        let clip = points(&[(100, 100), (300, 100), (300, 300), (100, 300)]);
+        let clipped = clip_polygon(&subject, &clip);
+        let expected = [(100.0, 116.66667), (125.0, 100.0), (275.0, 100.0), (300.0, 116.66667),
+                        (300.0, 300.0), (250.0, 300.0), (200.0, 250.0), (175.0, 300.0),
+                        (125.0, 300.0), (100.0, 250.0), (100.0, 200.0)];
+        assert_eq!(clipped.len(), expected.len());
+        // The same polygon, possibly starting elsewhere
+        let start = clipped.iter()
+            .position(|p| (p.x - 100.0).abs() < 1e-4 && (p.y - 116.66667).abs() < 1e-4)
+            .unwrap();
+        for (i, &(x, y)) in expected.iter().enumerate() {
+            let p = clipped[(start + i) % clipped.len()];
+            assert!((p.x - x).abs() < 1e-4 && (p.y - y).abs() < 1e-4);
+        }
+        // The direction of the clipping polygon doesn't matter
+        let reversed: Vec<Point> = clip.iter().rev().cloned().collect();
+        assert_eq!(clip_polygon(&subject, &reversed), clipped);
+        // Disjoint polygons
+        assert!(clip_polygon(&subject, &points(&[(400, 0), (500, 0), (500, 100)])).is_empty());
+    }
+}
@@ -0,0 +1,317 @@
+// GIF writing, for animations that ordinary tools can play. Frames are written one at a time
+// as they are made, each with its own color table of up to 256 colors, and compressed with the
+// variable-width LZW of the GIF89a specification. The animation loops forever.
+//
+// There is no reading, transparency or interlacing.
+
+use std::fs::File;
+use std::io::{self, Write, BufWriter};
+
+const MAX_CODES: usize = 4096;
+
+pub struct GifWriter<W: Write> {
+    writer: W,
+    width: usize,
+    height: usize,
+    /// Time each frame is shown for, in hundredths of a second
+    delay: u16,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> io::Result<()> {
+    writer.write_all(&[value as u8, (value >> 8) as u8])
+}
+
+// Packs codes of varying width into bytes, least significant bit first
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// LZW-compresses palette indices below `1 << min_size`. The code table starts over with a
+/// clear code whenever it fills up.
+fn lzw_encode(indices: &[u8], min_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_size;
+    let end = clear + 1;
+    let mut output = BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+    // The code for each string extended by each index, or 0 for none yet
+    let mut table = vec![[0u16; 256]; MAX_CODES];
+    let mut next = end + 1;
+    let mut width = min_size + 1;
+    output.write(clear, width);
+
+    let mut indices = indices.iter();
+    let mut prefix = match indices.next() {
+        Some(&index) => index as u16,
+        None => {
+            output.write(end, width);
+            return output.finish();
+        }
+    };
+    for &index in indices {
+        let code = table[prefix as usize][index as usize];
+        if code != 0 {
+            prefix = code;
+            continue;
+        }
+        output.write(prefix, width);
+        // The decoder adds its entry one code later, and widens its codes once that entry
+        // no longer fits
+        if next as usize == 1 << width && width < 12 {
+            width += 1;
+        }
+        if (next as usize) < MAX_CODES {
+            table[prefix as usize][index as usize] = next;
+            next += 1;
+        } else {
+            output.write(clear, width);
+            for entry in table.iter_mut() {
+                *entry = [0; 256];
+            }
+            next = end + 1;
+            width = min_size + 1;
+        }
+        prefix = index as u16;
+    }
+    output.write(prefix, width);
+    if next as usize == 1 << width && width < 12 {
+        width += 1;
+    }
+    output.write(end, width);
+    output.finish()
+}
+
+impl GifWriter<BufWriter<File>> {
+    pub fn create(filename: &str, width: usize, height: usize, delay: u16)
+                  -> io::Result<GifWriter<BufWriter<File>>> {
+        GifWriter::new(BufWriter::new(try!(File::create(filename))), width, height, delay)
+    }
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Writes the header of an animation whose frames show for `delay` hundredths of a second.
+    pub fn new(mut writer: W, width: usize, height: usize, delay: u16) -> io::Result<GifWriter<W>> {
+        if width > 0xffff || height > 0xffff {
+            return Err(invalid("image too large for GIF"));
+        }
+        try!(writer.write_all(b"GIF89a"));
+        try!(write_u16(&mut writer, width as u16));
+        try!(write_u16(&mut writer, height as u16));
+        // No global color table, background color 0, square pixels
+        try!(writer.write_all(&[0, 0, 0]));
+        // Loop forever
+        try!(writer.write_all(&[0x21, 0xff, 11]));
+        try!(writer.write_all(b"NETSCAPE2.0"));
+        try!(writer.write_all(&[3, 1, 0, 0, 0]));
+        Ok(GifWriter { writer: writer, width: width, height: height, delay: delay })
+    }
+
+    /// Adds a frame of indices into `palette`, row by row.
+    pub fn write_frame(&mut self, palette: &[[u8; 3]], indices: &[u8]) -> io::Result<()> {
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(invalid("GIF palettes have 1 to 256 colors"));
+        }
+        if indices.len() != self.width * self.height {
+            return Err(invalid("frame size doesn't match the GIF"));
+        }
+        if indices.iter().any(|&i| i as usize >= palette.len()) {
+            return Err(invalid("color index outside the palette"));
+        }
+        // The color table has 2^(bits + 1) entries
+        let mut bits = 0;
+        while 2 << bits < palette.len() {
+            bits += 1;
+        }
+
+        // Graphic control extension with the delay
+        try!(self.writer.write_all(&[0x21, 0xf9, 4, 0]));
+        try!(write_u16(&mut self.writer, self.delay));
+        try!(self.writer.write_all(&[0, 0]));
+
+        // Image descriptor covering the whole screen, with a local color table
+        try!(self.writer.write_all(&[0x2c, 0, 0, 0, 0]));
+        try!(write_u16(&mut self.writer, self.width as u16));
+        try!(write_u16(&mut self.writer, self.height as u16));
+        try!(self.writer.write_all(&[0x80 | bits as u8]));
+        for i in 0..2 << bits {
+            try!(self.writer.write_all(palette.get(i).unwrap_or(&[0, 0, 0])));
+        }
+
+        let min_size = ::std::cmp::max(bits + 1, 2);
+        try!(self.writer.write_all(&[min_size as u8]));
+        for block in lzw_encode(indices, min_size).chunks(255) {
+            try!(self.writer.write_all(&[block.len() as u8]));
+            try!(self.writer.write_all(block));
+        }
+        self.writer.write_all(&[0])
+    }
+
+    /// Ends the animation, giving back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        try!(self.writer.write_all(&[0x3b]));
+        try!(self.writer.flush());
+        Ok(self.writer)
+    }
+}
+
+#[cfg(not(test))]
+#[allow(dead_code)]
+pub fn main() {
+    // A spot of light going round a dark disk
+    let size = 64;
+    let palette: Vec<[u8; 3]> = (0..16).map(|i| [i * 17, i * 12, 64 + i * 12]).collect();
+    let filename = "spinner.gif";
+    let result = GifWriter::create(filename, size, size, 5).and_then(|mut gif| {
+        for frame in 0..24 {
+            let angle = frame as f64 * ::std::f64::consts::PI / 12.0;
+            let (cx, cy) = (31.5 + 20.0 * angle.cos(), 31.5 + 20.0 * angle.sin());
+            let indices: Vec<u8> = (0..size * size).map(|i| {
+                let (x, y) = ((i % size) as f64, (i / size) as f64);
+                if (x - 31.5).hypot(y - 31.5) > 30.0 {
+                    0
+                } else {
+                    (15.0 - (x - cx).hypot(y - cy) / 2.0).max(1.0) as u8
+                }
+            }).collect();
+            try!(gif.write_frame(&palette, &indices));
+        }
+        gif.finish()
+    });
+    match result {
+        Ok(_) => println!("Wrote {}", filename),
+        Err(e) => println!("Can't write {}: {}", filename, e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GifWriter, lzw_encode};
+
+    // Reads codes back and expands them, as a GIF decoder does
+    fn lzw_decode(data: &[u8], min_size: u32) -> Vec<u8> {
+        let clear = 1usize << min_size;
+        let end = clear + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut width = min_size + 1;
+        let mut previous: Option<usize> = None;
+        let mut output = Vec::new();
+        let (mut buffer, mut bits, mut position) = (0u32, 0, 0);
+        loop {
+            while bits < width {
+                buffer |= (data[position] as u32) << bits;
+                position += 1;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << width) - 1)) as usize;
+            buffer >>= width;
+            bits -= width;
+
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                width = min_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                assert_eq!(position, data.len());
+                return output;
+            }
+            let entry = match previous {
+                None => table[code].clone(),
+                Some(p) => {
+                    let entry = if code < table.len() {
+                        table[code].clone()
+                    } else {
+                        assert_eq!(code, table.len());
+                        let mut entry = table[p].clone();
+                        entry.push(table[p][0]);
+                        entry
+                    };
+                    if table.len() < 4096 {
+                        let mut added = table[p].clone();
+                        added.push(entry[0]);
+                        table.push(added);
+                    }
+                    entry
+                }
+            };
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+            output.extend(entry.iter().cloned());
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let noise: Vec<u8> = (0..100000u32).map(|i| (i.wrapping_mul(2654435761) >> 29) as u8)
+                                           .collect();
+        let runs: Vec<u8> = (0..100000u32).map(|i| (i / 1000 % 4) as u8).collect();
+        for &(ref data, min_size) in &[(vec![], 2), (vec![0], 2), (vec![1, 1, 1, 1, 1], 2),
+                                        (noise, 3), (runs, 2)] {
+            assert_eq!(lzw_decode(&lzw_encode(data, min_size), min_size), *data);
+        }
+        let all: Vec<u8> = (0..300000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+                                         .collect();
+        assert_eq!(lzw_decode(&lzw_encode(&all, 8), 8), all);
+    }
+
+    #[test]
+    fn gif_layout() {
+        let mut gif = GifWriter::new(Vec::new(), 3, 2, 10).unwrap();
+        gif.write_frame(&[[255, 0, 0], [0, 0, 255], [0, 255, 0]], &[0, 1, 2, 2, 1, 0]).unwrap();
+        gif.write_frame(&[[9, 9, 9]], &[0; 6]).unwrap();
+        let bytes = gif.finish().unwrap();
+
+        assert_eq!(&bytes[..13], b"GIF89a\x03\x00\x02\x00\x00\x00\x00");
+        assert_eq!(&bytes[13..32], b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+        // First frame: delay, descriptor, four colors, then the compressed indices
+        assert_eq!(&bytes[32..40], &[0x21, 0xf9, 4, 0, 10, 0, 0, 0]);
+        assert_eq!(&bytes[40..50], &[0x2c, 0, 0, 0, 0, 3, 0, 2, 0, 0x81]);
+        assert_eq!(&bytes[50..62], &[255, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0, 0]);
+        assert_eq!(bytes[62], 2);
+        let length = bytes[63] as usize;
+        assert_eq!(lzw_decode(&bytes[64..64 + length], 2), vec![0, 1, 2, 2, 1, 0]);
+        assert_eq!(bytes[64 + length], 0);
+        // The second frame's one color takes a table of two
+        let second = &bytes[65 + length..];
+        assert_eq!(&second[8..24], &[0x2c, 0, 0, 0, 0, 3, 0, 2, 0, 0x80, 9, 9, 9, 0, 0, 0]);
+        assert_eq!(bytes.last(), Some(&0x3b));
+    }
+
+    #[test]
+    fn bad_frames() {
+        let mut gif = GifWriter::new(Vec::new(), 2, 2, 0).unwrap();
+        assert!(gif.write_frame(&[], &[0; 4]).is_err());
+        assert!(gif.write_frame(&[[0, 0, 0]; 257], &[0; 4]).is_err());
+        assert!(gif.write_frame(&[[0, 0, 0]], &[0; 3]).is_err());
+        assert!(gif.write_frame(&[[0, 0, 0]], &[0, 0, 0, 1]).is_err());
+        assert!(GifWriter::new(Vec::new(), 70000, 1, 0).is_err());
+    }
+}
@@ -1,33 +1,368 @@
 // http://rosettacode.org/wiki/Hash_join
+//
+// The joins are generic over the row types and take a key extractor for each side. Besides the
+// in memory hash join there is a sort-merge join, mostly to compare against, and a Grace hash join
+// for inputs larger than memory: when the build side exceeds its budget, both sides are
+// partitioned by key hash into temporary files which are then joined pair by pair.
+extern crate rand;
 
 use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher, SipHasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 
 type LeftTable<'a> = Vec<(i64, &'a str)>;
 type RightTable<'a> = Vec<(&'a str, &'a str)>;
 type ResultTable<'a> = Vec<(i64, &'a str, &'a str)>;
 
-fn hash_join<'a>(s: LeftTable<'a>, r: RightTable<'a>) -> ResultTable<'a> {
-    let mut hash_map: HashMap<&str, Vec<&str>> = HashMap::new();
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum JoinKind {
+    // Pairs of matching rows
+    Inner,
+    // Also every unmatched left row, paired with `None`
+    Left,
+    // Also every unmatched right row, paired with `None`
+    Right,
+    // Unmatched rows of both sides
+    FullOuter,
+    // Left rows having at least one match, each once, without the right row
+    Semi,
+    // Left rows without any match
+    Anti,
+}
 
+impl JoinKind {
+    fn keeps_unmatched_left(&self) -> bool {
+        match *self {
+            JoinKind::Left | JoinKind::FullOuter | JoinKind::Anti => true,
+            _ => false,
+        }
+    }
+
+    fn keeps_unmatched_right(&self) -> bool {
+        *self == JoinKind::Right || *self == JoinKind::FullOuter
+    }
+}
+
+// Probes the rows of `left` against the rows of `right`, which are hashed first. Unmatched right
+// rows come last, in their original order.
+fn join_in_memory<L, R, K, I, FL, FR, F>(left: I, right: &[R], left_key: &FL, right_key: &FR,
+                                         kind: JoinKind, emit: &mut F) -> io::Result<()>
+    where K: Hash + Eq,
+          I: Iterator<Item=io::Result<L>>,
+          FL: Fn(&L) -> K,
+          FR: Fn(&R) -> K,
+          F: FnMut(Option<&L>, Option<&R>)
+{
     //hash phase
-    for (name, nemesis) in r {
-        if hash_map.contains_key(name) {
-            hash_map.get_mut(name).unwrap().push(nemesis);
+    let mut table: HashMap<K, Vec<usize>> = HashMap::new();
+    for (i, row) in right.iter().enumerate() {
+        table.entry(right_key(row)).or_insert(Vec::new()).push(i);
+    }
+    let mut matched = vec![false; right.len()];
+
+    //join phase
+    for row in left {
+        let row = try!(row);
+        match table.get(&left_key(&row)) {
+            Some(indices) => match kind {
+                JoinKind::Semi => emit(Some(&row), None),
+                JoinKind::Anti => {}
+                _ => for &i in indices {
+                    matched[i] = true;
+                    emit(Some(&row), Some(&right[i]));
+                },
+            },
+            None => if kind.keeps_unmatched_left() {
+                emit(Some(&row), None);
+            },
+        }
+    }
+
+    if kind.keeps_unmatched_right() {
+        for (row, _) in right.iter().zip(matched.iter()).filter(|&(_, &m)| !m) {
+            emit(None, Some(row));
+        }
+    }
+    Ok(())
+}
+
+pub fn hash_join_by<'a, L, R, K, FL, FR>(left: &'a [L], right: &'a [R], left_key: FL,
+                                         right_key: FR, kind: JoinKind)
+                                         -> Vec<(Option<&'a L>, Option<&'a R>)>
+    where K: Hash + Eq, FL: Fn(&L) -> K, FR: Fn(&R) -> K
+{
+    let right: Vec<&R> = right.iter().collect();
+    let mut result = Vec::new();
+    join_in_memory(left.iter().map(Ok), &right, &|l: &&L| left_key(*l), &|r: &&R| right_key(*r),
+                   kind, &mut |l: Option<&&'a L>, r: Option<&&'a R>| {
+                       result.push((l.map(|l| *l), r.map(|r| *r)))
+                   }).unwrap();
+    result
+}
+
+// Joins by sorting both sides on their key and merging them. The result is ordered by key.
+pub fn sort_merge_join<'a, L, R, K, FL, FR>(left: &'a [L], right: &'a [R], left_key: FL,
+                                            right_key: FR, kind: JoinKind)
+                                            -> Vec<(Option<&'a L>, Option<&'a R>)>
+    where K: Ord, FL: Fn(&L) -> K, FR: Fn(&R) -> K
+{
+    let mut lefts: Vec<(K, &L)> = left.iter().map(|row| (left_key(row), row)).collect();
+    let mut rights: Vec<(K, &R)> = right.iter().map(|row| (right_key(row), row)).collect();
+    // Stable sorts keep rows with equal keys in their original order
+    lefts.sort_by(|a, b| a.0.cmp(&b.0));
+    rights.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < lefts.len() || j < rights.len() {
+        let ordering = if i == lefts.len() {
+            ::std::cmp::Ordering::Greater
+        } else if j == rights.len() {
+            ::std::cmp::Ordering::Less
         } else {
-            hash_map.insert(name, vec![nemesis]);
+            lefts[i].0.cmp(&rights[j].0)
+        };
+        match ordering {
+            ::std::cmp::Ordering::Less => {
+                if kind.keeps_unmatched_left() {
+                    result.push((Some(lefts[i].1), None));
+                }
+                i += 1;
+            }
+            ::std::cmp::Ordering::Greater => {
+                if kind.keeps_unmatched_right() {
+                    result.push((None, Some(rights[j].1)));
+                }
+                j += 1;
+            }
+            ::std::cmp::Ordering::Equal => {
+                // Runs of rows sharing the key on both sides
+                let mut i_end = i + 1;
+                while i_end < lefts.len() && lefts[i_end].0 == lefts[i].0 {
+                    i_end += 1;
+                }
+                let mut j_end = j + 1;
+                while j_end < rights.len() && rights[j_end].0 == rights[j].0 {
+                    j_end += 1;
+                }
+                for &(_, l) in &lefts[i..i_end] {
+                    match kind {
+                        JoinKind::Semi => result.push((Some(l), None)),
+                        JoinKind::Anti => {}
+                        _ => for &(_, r) in &rights[j..j_end] {
+                            result.push((Some(l), Some(r)));
+                        },
+                    }
+                }
+                i = i_end;
+                j = j_end;
+            }
         }
     }
+    result
+}
 
-    let mut result = vec![];
-    //join phase
-    for (age, name) in s {
-        if let Some(v) = hash_map.get(name) {
-           for nemesis in v {
-               result.push((age, name, *nemesis));
-           }
+// Rows the Grace hash join can write to its temporary files and read back
+pub trait Spill: Sized {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    // Reads the next row, `None` at the end of the file
+    fn read_from<B: BufRead>(reader: &mut B) -> io::Result<Option<Self>>;
+}
+
+// One line per row: the number of fields, then the fields, all separated by tabs. Backslashes,
+// tabs and newlines within fields are escaped.
+impl Spill for Vec<String> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(write!(writer, "{}", self.len()));
+        for field in self {
+            let escaped = field.replace("\\", "\\\\").replace("\t", "\\t").replace("\n", "\\n");
+            try!(write!(writer, "\t{}", escaped));
+        }
+        writeln!(writer, "")
+    }
+
+    fn read_from<B: BufRead>(reader: &mut B) -> io::Result<Option<Vec<String>>> {
+        let mut line = String::new();
+        if try!(reader.read_line(&mut line)) == 0 {
+            return Ok(None);
+        }
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "corrupt spill file");
+        let mut parts = line.trim_right_matches('\n').split('\t');
+        let count: usize = try!(parts.next().and_then(|n| n.parse().ok()).ok_or_else(invalid));
+        let mut fields = Vec::with_capacity(count);
+        for part in parts {
+            let mut field = String::with_capacity(part.len());
+            let mut chars = part.chars();
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    field.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('t') => field.push('\t'),
+                    Some('n') => field.push('\n'),
+                    Some('\\') => field.push('\\'),
+                    _ => return Err(invalid()),
+                }
+            }
+            fields.push(field);
+        }
+        if fields.len() != count {
+            return Err(invalid());
+        }
+        Ok(Some(fields))
+    }
+}
+
+// Reads back the rows of a partition file
+struct SpillReader<T> {
+    reader: BufReader<File>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Spill> Iterator for SpillReader<T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<io::Result<T>> {
+        match T::read_from(&mut self.reader) {
+            Ok(Some(row)) => Some(Ok(row)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+// The partition files of one level, removed when the join is done with them, whether it
+// succeeded or not
+struct SpillFiles {
+    paths: Vec<PathBuf>,
+}
+
+impl Drop for SpillFiles {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn partition_of<K: Hash>(key: &K, level: usize, partitions: usize) -> usize {
+    // Each level of partitioning hashes differently, otherwise the rows of a partition that is
+    // still too big would all end up in the same partition again
+    let mut hasher = SipHasher::new_with_keys(level as u64, 0x9e3779b97f4a7c15);
+    key.hash(&mut hasher);
+    (hasher.finish() % partitions as u64) as usize
+}
+
+pub struct GraceHashJoin {
+    // Number of build side rows held in memory at once
+    memory_budget: usize,
+    partitions: usize,
+    temp_dir: PathBuf,
+    // Partitions still too big after this many rounds are joined in memory anyway; they are
+    // mostly rows sharing a single key
+    max_depth: usize,
+}
+
+impl GraceHashJoin {
+    pub fn new(memory_budget: usize) -> GraceHashJoin {
+        GraceHashJoin {
+            memory_budget: memory_budget,
+            partitions: 16,
+            temp_dir: env::temp_dir(),
+            max_depth: 4,
+        }
+    }
+
+    pub fn partitions(mut self, partitions: usize) -> GraceHashJoin {
+        self.partitions = partitions;
+        self
+    }
+
+    pub fn temp_dir(mut self, dir: &Path) -> GraceHashJoin {
+        self.temp_dir = dir.to_path_buf();
+        self
+    }
+
+    // Joins two streams of rows, `right` being the build side. Joined rows are passed to `emit`
+    // as they are found, in no particular order.
+    pub fn join<L, R, K, IL, IR, FL, FR, F>(&self, left: IL, right: IR, left_key: FL,
+                                            right_key: FR, kind: JoinKind, mut emit: F)
+                                            -> io::Result<()>
+        where L: Spill, R: Spill, K: Hash + Eq,
+              IL: Iterator<Item=L>, IR: Iterator<Item=R>,
+              FL: Fn(&L) -> K, FR: Fn(&R) -> K,
+              F: FnMut(Option<&L>, Option<&R>)
+    {
+        self.join_level(left.map(Ok), right.map(Ok), &left_key, &right_key, kind, &mut emit, 0)
+    }
+
+    fn join_level<L, R, K, IL, IR, FL, FR, F>(&self, left: IL, mut right: IR, left_key: &FL,
+                                              right_key: &FR, kind: JoinKind, emit: &mut F,
+                                              level: usize) -> io::Result<()>
+        where L: Spill, R: Spill, K: Hash + Eq,
+              IL: Iterator<Item=io::Result<L>>, IR: Iterator<Item=io::Result<R>>,
+              FL: Fn(&L) -> K, FR: Fn(&R) -> K,
+              F: FnMut(Option<&L>, Option<&R>)
+    {
+        let mut build = Vec::new();
+        while build.len() < self.memory_budget || level >= self.max_depth {
+            match right.next() {
+                Some(row) => build.push(try!(row)),
+                None => return join_in_memory(left, &build, left_key, right_key, kind, emit),
+            }
+        }
+
+        // The build side doesn't fit: spill both sides and join each pair of partitions
+        let token = rand::random::<u32>();
+        let right_files = try!(self.partition(build.into_iter().map(Ok).chain(right), right_key,
+                                              level, &format!("{}-r", token)));
+        let left_files = try!(self.partition(left, left_key, level, &format!("{}-l", token)));
+        for (left_file, right_file) in left_files.paths.iter().zip(right_files.paths.iter()) {
+            let left = SpillReader { reader: BufReader::new(try!(File::open(left_file))),
+                                     phantom: PhantomData };
+            let right = SpillReader { reader: BufReader::new(try!(File::open(right_file))),
+                                      phantom: PhantomData };
+            try!(self.join_level(left, right, left_key, right_key, kind, emit, level + 1));
         }
+        Ok(())
     }
-    return result;
+
+    fn partition<T, K, I, FK>(&self, rows: I, key: &FK, level: usize, name: &str)
+                              -> io::Result<SpillFiles>
+        where T: Spill, K: Hash, I: Iterator<Item=io::Result<T>>, FK: Fn(&T) -> K
+    {
+        let mut files = SpillFiles { paths: Vec::with_capacity(self.partitions) };
+        let mut writers = Vec::with_capacity(self.partitions);
+        for n in 0..self.partitions {
+            let path = self.temp_dir.join(format!("grace-{}-{}-{}.tmp", name, level, n));
+            writers.push(BufWriter::new(try!(File::create(&path))));
+            files.paths.push(path);
+        }
+        for row in rows {
+            let row = try!(row);
+            let n = partition_of(&key(&row), level, self.partitions);
+            try!(row.write_to(&mut writers[n]));
+        }
+        for writer in writers.iter_mut() {
+            try!(writer.flush());
+        }
+        Ok(files)
+    }
+}
+
+fn hash_join<'a>(s: LeftTable<'a>, r: RightTable<'a>) -> ResultTable<'a> {
+    hash_join_by(&s, &r, |&(_, name)| name, |&(name, _)| name, JoinKind::Inner)
+        .into_iter()
+        .map(|(left, right)| {
+            let (&(age, name), &(_, nemesis)) = (left.unwrap(), right.unwrap());
+            (age, name, nemesis)
+        })
+        .collect()
 }
 
 #[cfg(not(test))]
@@ -42,10 +377,31 @@ pub fn main() {
               ("Alan", "Ghosts"),
               ("Alan", "Zombies"),
               ("Glory", "Buffy")];
-    let result = hash_join(table1, table2);
+    let result = hash_join(table1.clone(), table2.clone());
     for (age, name, nemesis) in result {
         println!("{}, {}, {}", age, name, nemesis);
     }
+
+    println!("\nFull outer join:");
+    let result = hash_join_by(&table1, &table2, |row| row.1, |row| row.0, JoinKind::FullOuter);
+    for (left, right) in result {
+        println!("{:?}, {:?}", left, right);
+    }
+
+    // The same join on CSV rows, with a budget of two rows to force spilling to disk
+    let csv = |text: &str| -> Vec<Vec<String>> {
+        text.lines().map(|line| line.split(',').map(|s| s.to_string()).collect()).collect()
+    };
+    let ages = csv("27,Jonah\n18,Alan\n28,Glory\n18,Popeye\n28,Alan");
+    let nemeses = csv("Jonah,Whales\nJonah,Spiders\nAlan,Ghosts\nAlan,Zombies\nGlory,Buffy");
+    println!("\nGrace hash join:");
+    GraceHashJoin::new(2).partitions(4)
+        .join(ages.into_iter(), nemeses.into_iter(), |row| row[1].clone(), |row| row[0].clone(),
+              JoinKind::Inner, |left, right| {
+                  let (left, right) = (left.unwrap(), right.unwrap());
+                  println!("{}, {}, {}", left[0], left[1], right[1]);
+              })
+        .unwrap();
 }
 
 #[test]
@@ -57,3 +413,135 @@ pub fn test() {
     let r = hash_join(t1, t2);
     assert!(r == vec![(0, "hello", "rust"), (0, "hello", "cargo")]);
 }
+
+#[cfg(test)]
+#[derive(Clone, PartialEq, Debug)]
+struct Employee { name: &'static str, department: u32 }
+
+#[cfg(test)]
+#[derive(Clone, PartialEq, Debug)]
+struct Department { id: u32, title: &'static str }
+
+#[cfg(test)]
+fn tables() -> (Vec<Employee>, Vec<Department>) {
+    (vec![Employee { name: "Ann", department: 1 },
+          Employee { name: "Bob", department: 2 },
+          Employee { name: "Cid", department: 1 },
+          Employee { name: "Dee", department: 9 }],
+     vec![Department { id: 1, title: "Sales" },
+          Department { id: 2, title: "R&D" },
+          Department { id: 3, title: "Legal" }])
+}
+
+// Joined rows as (employee, department) names, sorted to compare results of any join
+#[cfg(test)]
+fn names(rows: Vec<(Option<&Employee>, Option<&Department>)>)
+         -> Vec<(&'static str, &'static str)> {
+    let mut names: Vec<_> = rows.iter()
+        .map(|&(e, d)| (e.map_or("-", |e| e.name), d.map_or("-", |d| d.title)))
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_join_kinds() {
+    let (employees, departments) = tables();
+    let expected: Vec<(JoinKind, Vec<(&str, &str)>)> = vec![
+        (JoinKind::Inner, vec![("Ann", "Sales"), ("Bob", "R&D"), ("Cid", "Sales")]),
+        (JoinKind::Left, vec![("Ann", "Sales"), ("Bob", "R&D"), ("Cid", "Sales"),
+                              ("Dee", "-")]),
+        (JoinKind::Right, vec![("-", "Legal"), ("Ann", "Sales"), ("Bob", "R&D"),
+                               ("Cid", "Sales")]),
+        (JoinKind::FullOuter, vec![("-", "Legal"), ("Ann", "Sales"), ("Bob", "R&D"),
+                                   ("Cid", "Sales"), ("Dee", "-")]),
+        (JoinKind::Semi, vec![("Ann", "-"), ("Bob", "-"), ("Cid", "-")]),
+        (JoinKind::Anti, vec![("Dee", "-")]),
+    ];
+    for (kind, expected) in expected {
+        let hashed = hash_join_by(&employees, &departments, |e| e.department, |d| d.id, kind);
+        let merged = sort_merge_join(&employees, &departments, |e| e.department, |d| d.id,
+                                     kind);
+        assert_eq!(names(hashed), expected);
+        assert_eq!(names(merged), expected);
+    }
+}
+
+#[test]
+fn test_sort_merge_order() {
+    let left = vec![3, 1, 2, 1];
+    let right = vec![1, 3, 1];
+    let result: Vec<(i32, i32)> = sort_merge_join(&left, &right, |&x| x, |&x| x,
+                                                  JoinKind::Inner)
+        .into_iter().map(|(l, r)| (*l.unwrap(), *r.unwrap())).collect();
+    assert_eq!(result, vec![(1, 1), (1, 1), (1, 1), (1, 1), (3, 3)]);
+}
+
+#[test]
+fn test_spill_format() {
+    let row = vec!["a\tb".to_string(), "".to_string(), "c\\n\nd".to_string()];
+    let mut buffer = Vec::new();
+    row.write_to(&mut buffer).unwrap();
+    Vec::<String>::new().write_to(&mut buffer).unwrap();
+    let mut reader = BufReader::new(&buffer[..]);
+    assert_eq!(Vec::<String>::read_from(&mut reader).unwrap(), Some(row));
+    assert_eq!(Vec::<String>::read_from(&mut reader).unwrap(), Some(Vec::new()));
+    assert_eq!(Vec::<String>::read_from(&mut reader).unwrap(), None);
+    assert!(Vec::<String>::read_from(&mut BufReader::new(&b"2\ta\n"[..])).is_err());
+}
+
+#[cfg(test)]
+fn grace(budget: usize, kind: JoinKind, left: &[Vec<String>], right: &[Vec<String>])
+         -> Vec<(Option<Vec<String>>, Option<Vec<String>>)> {
+    let dir = env::temp_dir().join(format!("grace-test-{:?}-{}", kind, budget));
+    fs::create_dir_all(&dir).unwrap();
+    let mut result = Vec::new();
+    GraceHashJoin::new(budget).partitions(3).temp_dir(&dir)
+        .join(left.to_vec().into_iter(), right.to_vec().into_iter(),
+              |row| row[0].clone(), |row| row[0].clone(), kind,
+              |l, r| result.push((l.cloned(), r.cloned())))
+        .unwrap();
+    // Every temporary file has been removed
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir(&dir).unwrap();
+    result.sort();
+    result
+}
+
+#[test]
+fn test_grace_hash_join() {
+    let row = |key: usize, value: usize| vec![key.to_string(), value.to_string()];
+    let left: Vec<Vec<String>> = (0..200).map(|i| row(i % 50, i)).collect();
+    // Keys 0 to 59 with a hundred rows sharing key 7, more than a partition can hold
+    let right: Vec<Vec<String>> = (0..60).map(|i| row(i, i))
+                                         .chain((0..100).map(|i| row(7, 1000 + i)))
+                                         .collect();
+    for &kind in &[JoinKind::Inner, JoinKind::FullOuter, JoinKind::Semi, JoinKind::Anti] {
+        let mut expected: Vec<(Option<Vec<String>>, Option<Vec<String>>)> =
+            hash_join_by(&left, &right, |r| r[0].clone(), |r| r[0].clone(), kind)
+                .into_iter().map(|(l, r)| (l.cloned(), r.cloned())).collect();
+        expected.sort();
+        // Everything fits in memory
+        assert_eq!(grace(1000, kind, &left, &right), expected);
+        // Spills, and partitions again until the limit of depth
+        assert_eq!(grace(10, kind, &left, &right), expected);
+    }
+}
+
+#[test]
+fn test_grace_hash_join_errors() {
+    let dir = env::temp_dir().join("grace-test-errors");
+    fs::create_dir_all(&dir).unwrap();
+    let row = |i: usize| vec![i.to_string()];
+    // The probe side fails half way through, once every partition file exists
+    let left = (0..100).map(|i| if i < 50 { Ok(row(i)) } else {
+        Err(io::Error::new(io::ErrorKind::Other, "read failed"))
+    });
+    let right = (0..100).map(|i| Ok(row(i)));
+    let key = |row: &Vec<String>| row[0].clone();
+    let result = GraceHashJoin::new(10).partitions(3).temp_dir(&dir)
+        .join_level(left, right, &key, &key, JoinKind::Inner, &mut |_, _| {}, 0);
+    assert!(result.is_err());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir(&dir).unwrap();
+}
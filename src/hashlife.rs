@@ -0,0 +1,463 @@
+// Implements http://rosettacode.org/wiki/Conway%27s_Game_of_Life with Gosper's Hashlife
+//
+// The universe is a quadtree whose nodes are shared, so that every distinct square of cells is
+// stored once however often it appears. The future of the middle of each node is worked out
+// once and remembered, and nodes of level k jump 2^(k-2) generations at a time, which lets
+// regular patterns run for astronomical numbers of generations.
+//
+// Nothing is ever thrown away: memory grows with the number of distinct squares seen.
+//
+// The root stays centred on the cell at 0, 0 as it grows and shrinks, and coordinates are
+// i64s, so the universe reaches a little over 2^61 cells either way and runs fewer than 2^60
+// generations at a time.
+
+use std::collections::HashMap;
+
+struct Node {
+    // The node covers 2^level by 2^level cells; level 0 nodes are single cells
+    level: u8,
+    // Northwest, northeast, southwest and southeast quarters
+    children: [usize; 4],
+    population: u64,
+}
+
+// The ids of the dead and live cells
+const DEAD: usize = 0;
+const ALIVE: usize = 1;
+
+// The largest the root gets, so that its size and corners fit in an i64
+const MAX_LEVEL: u8 = 62;
+
+/// The error when a cell or a run of generations would need the universe to grow past its
+/// coordinates.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct OutOfRange;
+
+pub struct Hashlife {
+    birth: [bool; 9],
+    survival: [bool; 9],
+    nodes: Vec<Node>,
+    // The node made of each four children
+    table: HashMap<[usize; 4], usize>,
+    // The middle of a node some power of two generations on, by node and power
+    results: HashMap<(usize, u8), usize>,
+    // The empty node of each level
+    empty: Vec<usize>,
+    root: usize,
+    // The cell at the root's top left corner
+    origin: (i64, i64),
+    pub generation: u64,
+}
+
+impl Hashlife {
+    /// An empty universe for a Life-like rule: dead cells with one of the `birth` numbers of
+    /// live neighbours come alive, and live ones with one of the `survival` numbers stay so.
+    /// Birth on 0 isn't supported, as the empty space around everything would come alive.
+    pub fn new(birth: [bool; 9], survival: [bool; 9]) -> Hashlife {
+        assert!(!birth[0], "rules with birth on 0 can't be run on an empty universe");
+        let leaf = |population| Node { level: 0, children: [DEAD; 4], population: population };
+        let mut life = Hashlife {
+            birth: birth,
+            survival: survival,
+            nodes: vec![leaf(0), leaf(1)],
+            table: HashMap::new(),
+            results: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            origin: (-4, -4),
+            generation: 0,
+        };
+        life.root = life.empty(3);
+        life
+    }
+
+    /// Conway's rules, B3/S23
+    pub fn conway() -> Hashlife {
+        let (mut birth, mut survival) = ([false; 9], [false; 9]);
+        birth[3] = true;
+        survival[2] = true;
+        survival[3] = true;
+        Hashlife::new(birth, survival)
+    }
+
+    fn join(&mut self, nw: usize, ne: usize, sw: usize, se: usize) -> usize {
+        let children = [nw, ne, sw, se];
+        if let Some(&id) = self.table.get(&children) {
+            return id;
+        }
+        let population = children.iter().fold(0, |sum, &c| sum + self.nodes[c].population);
+        self.nodes.push(Node {
+            level: self.nodes[nw].level + 1,
+            children: children,
+            population: population,
+        });
+        let id = self.nodes.len() - 1;
+        self.table.insert(children, id);
+        id
+    }
+
+    fn empty(&mut self, level: u8) -> usize {
+        while self.empty.len() <= level as usize {
+            let e = *self.empty.last().unwrap();
+            let bigger = self.join(e, e, e, e);
+            self.empty.push(bigger);
+        }
+        self.empty[level as usize]
+    }
+
+    // The middle half of a node, one level down
+    fn centre(&mut self, id: usize) -> usize {
+        let c = self.nodes[id].children;
+        let (nw, ne, sw, se) = (self.nodes[c[0]].children, self.nodes[c[1]].children,
+                                self.nodes[c[2]].children, self.nodes[c[3]].children);
+        self.join(nw[3], ne[2], sw[1], se[0])
+    }
+
+    /// How many cells are alive.
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root].population
+    }
+
+    fn size(&self) -> i64 {
+        1 << self.nodes[self.root].level
+    }
+
+    fn contains(&self, x: i64, y: i64) -> bool {
+        let (ox, oy) = self.origin;
+        x >= ox && y >= oy && x < ox + self.size() && y < oy + self.size()
+    }
+
+    // Puts the root in the middle of a node twice its size
+    fn expand(&mut self) -> Result<(), OutOfRange> {
+        let level = self.nodes[self.root].level;
+        if level >= MAX_LEVEL {
+            return Err(OutOfRange);
+        }
+        let e = self.empty(level - 1);
+        let c = self.nodes[self.root].children;
+        let nw = self.join(e, e, e, c[0]);
+        let ne = self.join(e, e, c[1], e);
+        let sw = self.join(e, c[2], e, e);
+        let se = self.join(c[3], e, e, e);
+        self.root = self.join(nw, ne, sw, se);
+        let half = 1 << (level - 1);
+        self.origin = (self.origin.0 - half, self.origin.1 - half);
+        Ok(())
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> bool {
+        if !self.contains(x, y) {
+            return false;
+        }
+        let (mut x, mut y) = ((x - self.origin.0) as u64, (y - self.origin.1) as u64);
+        let mut id = self.root;
+        while self.nodes[id].level > 0 {
+            let half = 1 << (self.nodes[id].level - 1);
+            let quarter = if y >= half { 2 } else { 0 } + if x >= half { 1 } else { 0 };
+            id = self.nodes[id].children[quarter];
+            x %= half;
+            y %= half;
+        }
+        id == ALIVE
+    }
+
+    /// Sets a cell, failing if it's too far out for the universe to reach.
+    pub fn set(&mut self, x: i64, y: i64, alive: bool) -> Result<(), OutOfRange> {
+        while !self.contains(x, y) {
+            try!(self.expand());
+        }
+        let (root, origin) = (self.root, self.origin);
+        self.root = self.set_in(root, (x - origin.0) as u64, (y - origin.1) as u64, alive);
+        Ok(())
+    }
+
+    fn set_in(&mut self, id: usize, x: u64, y: u64, alive: bool) -> usize {
+        let level = self.nodes[id].level;
+        if level == 0 {
+            return if alive { ALIVE } else { DEAD };
+        }
+        let half = 1 << (level - 1);
+        let quarter = if y >= half { 2 } else { 0 } + if x >= half { 1 } else { 0 };
+        let mut c = self.nodes[id].children;
+        c[quarter] = self.set_in(c[quarter], x % half, y % half, alive);
+        self.join(c[0], c[1], c[2], c[3])
+    }
+
+    /// The coordinates of every live cell, row by row within each quarter of the universe.
+    pub fn cells(&self) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        self.collect(self.root, self.origin, &mut cells);
+        cells
+    }
+
+    fn collect(&self, id: usize, (x, y): (i64, i64), cells: &mut Vec<(i64, i64)>) {
+        let node = &self.nodes[id];
+        if node.population == 0 {
+            return;
+        }
+        if node.level == 0 {
+            cells.push((x, y));
+            return;
+        }
+        let half = 1 << (node.level - 1);
+        for (i, &child) in node.children.iter().enumerate() {
+            let corner = (x + if i % 2 == 1 { half } else { 0 }, y + if i >= 2 { half } else { 0 });
+            self.collect(child, corner, cells);
+        }
+    }
+
+    // The middle of a node of level k, 2^j generations on, for j from 0 to k - 2
+    fn successor(&mut self, id: usize, j: u8) -> usize {
+        let level = self.nodes[id].level;
+        if self.nodes[id].population == 0 {
+            return self.empty(level - 1);
+        }
+        if let Some(&result) = self.results.get(&(id, j)) {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.evolve_4x4(id)
+        } else {
+            let c = self.nodes[id].children;
+            let (nw, ne, sw, se) = (self.nodes[c[0]].children, self.nodes[c[1]].children,
+                                    self.nodes[c[2]].children, self.nodes[c[3]].children);
+            // Nine overlapping squares half the size, three by three
+            let squares = [
+                c[0],
+                self.join(nw[1], ne[0], nw[3], ne[2]),
+                c[1],
+                self.join(nw[2], nw[3], sw[0], sw[1]),
+                self.join(nw[3], ne[2], sw[1], se[0]),
+                self.join(ne[2], ne[3], se[0], se[1]),
+                c[2],
+                self.join(sw[1], se[0], sw[3], se[2]),
+                c[3],
+            ];
+            // Their middles, either half of the way on already or as they are now
+            let full_speed = j == level - 2;
+            let mut middles = [0; 9];
+            for i in 0..9 {
+                middles[i] = if full_speed {
+                    self.successor(squares[i], j - 1)
+                } else {
+                    self.centre(squares[i])
+                };
+            }
+            let rest = if full_speed { j - 1 } else { j };
+            let m = middles;
+            let quarters = [self.join(m[0], m[1], m[3], m[4]), self.join(m[1], m[2], m[4], m[5]),
+                            self.join(m[3], m[4], m[6], m[7]), self.join(m[4], m[5], m[7], m[8])];
+            let mut next = [0; 4];
+            for i in 0..4 {
+                next[i] = self.successor(quarters[i], rest);
+            }
+            self.join(next[0], next[1], next[2], next[3])
+        };
+        self.results.insert((id, j), result);
+        result
+    }
+
+    // The middle 2x2 cells of a 4x4 node one generation on
+    fn evolve_4x4(&mut self, id: usize) -> usize {
+        let mut cells = [[false; 4]; 4];
+        for (i, &child) in self.nodes[id].children.iter().enumerate() {
+            for (j, &cell) in self.nodes[child].children.iter().enumerate() {
+                cells[i / 2 * 2 + j / 2][i % 2 * 2 + j % 2] = cell == ALIVE;
+            }
+        }
+        let mut next = [DEAD; 4];
+        for i in 0..4 {
+            let (x, y) = (1 + i % 2, 1 + i / 2);
+            let mut neighbors = 0;
+            for yy in y - 1..y + 2 {
+                for xx in x - 1..x + 2 {
+                    if (xx, yy) != (x, y) && cells[yy][xx] {
+                        neighbors += 1;
+                    }
+                }
+            }
+            let alive = if cells[y][x] { self.survival[neighbors] } else { self.birth[neighbors] };
+            next[i] = if alive { ALIVE } else { DEAD };
+        }
+        self.join(next[0], next[1], next[2], next[3])
+    }
+
+    /// Runs 2^j generations in one go. It fails, leaving the cells as they were, when the
+    /// universe can't grow enough to hold the pattern however far it might spread.
+    pub fn step_pow2(&mut self, j: u8) -> Result<(), OutOfRange> {
+        // The root needs to be at least three levels up
+        if j > MAX_LEVEL - 3 {
+            return Err(OutOfRange);
+        }
+        let generation = try!(self.generation.checked_add(1 << j).ok_or(OutOfRange));
+        // Make room for the pattern to grow by up to 2^j cells each way and still be inside
+        // the middle of the root afterwards
+        loop {
+            let level = self.nodes[self.root].level;
+            if level >= j + 3 {
+                let root = self.root;
+                let middle = self.centre(root);
+                let inner = self.centre(middle);
+                if self.nodes[inner].population == self.population() {
+                    break;
+                }
+            }
+            try!(self.expand());
+        }
+        let level = self.nodes[self.root].level;
+        let quarter = 1 << (level - 2);
+        let root = self.root;
+        self.root = self.successor(root, j);
+        self.origin = (self.origin.0 + quarter, self.origin.1 + quarter);
+        self.generation = generation;
+        Ok(())
+    }
+
+    /// Runs any number of generations, as a sum of powers of two, smallest first. If a power
+    /// fails the ones before it have still been run, as `generation` shows.
+    pub fn advance(&mut self, generations: u64) -> Result<(), OutOfRange> {
+        for j in 0..64 {
+            if generations >> j & 1 == 1 {
+                try!(self.step_pow2(j));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(test))]
+#[allow(dead_code)]
+pub fn main() {
+    // A glider gun fires a glider every 30 generations, forever
+    let gun = ["........................O",
+               "......................O.O",
+               "............OO......OO............OO",
+               "...........O...O....OO............OO",
+               "OO........O.....O...OO",
+               "OO........O...O.OO....O.O",
+               "..........O.....O.......O",
+               "...........O...O",
+               "............OO"];
+    let mut life = Hashlife::conway();
+    for (y, row) in gun.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if c == 'O' {
+                life.set(x as i64, y as i64, true).unwrap();
+            }
+        }
+    }
+    for &power in &[10, 20, 30, 40] {
+        let target = 1u64 << power;
+        let (generation, nodes) = (life.generation, life.nodes.len());
+        match life.advance(target - generation) {
+            Ok(()) => println!("Generation 2^{}: {} cells alive, {} new nodes", power,
+                               life.population(), life.nodes.len() - nodes),
+            Err(OutOfRange) => println!("Generation 2^{} is out of reach", power),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Hashlife, OutOfRange};
+    use std::collections::HashSet;
+
+    fn with_cells(cells: &[(i64, i64)]) -> Hashlife {
+        let mut life = Hashlife::conway();
+        for &(x, y) in cells {
+            life.set(x, y, true).unwrap();
+        }
+        life
+    }
+
+    // One generation the slow way
+    fn naive_step(cells: &HashSet<(i64, i64)>) -> HashSet<(i64, i64)> {
+        let mut candidates = HashSet::new();
+        for &(x, y) in cells {
+            for dy in -1..2 {
+                for dx in -1..2 {
+                    candidates.insert((x + dx, y + dy));
+                }
+            }
+        }
+        candidates.into_iter().filter(|&(x, y)| {
+            let mut neighbors = 0;
+            for dy in -1..2 {
+                for dx in -1..2 {
+                    if (dx, dy) != (0, 0) && cells.contains(&(x + dx, y + dy)) {
+                        neighbors += 1;
+                    }
+                }
+            }
+            neighbors == 3 || (neighbors == 2 && cells.contains(&(x, y)))
+        }).collect()
+    }
+
+    fn sorted(mut cells: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn gliders_and_oscillators() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut life = with_cells(&glider);
+        assert_eq!(life.population(), 5);
+        assert!(life.get(2, 1) && !life.get(1, 1));
+        // Every four generations it moves one cell down and right
+        life.advance(4).unwrap();
+        let moved: Vec<(i64, i64)> = glider.iter().map(|&(x, y)| (x + 1, y + 1)).collect();
+        assert_eq!(sorted(life.cells()), sorted(moved));
+        life.advance(1 << 20).unwrap();
+        let far: Vec<(i64, i64)> = glider.iter().map(|&(x, y)| (x + 262145, y + 262145)).collect();
+        assert_eq!(sorted(life.cells()), sorted(far));
+        assert_eq!(life.generation, 4 + (1 << 20));
+
+        let mut blinker = with_cells(&[(-1, 0), (0, 0), (1, 0)]);
+        blinker.advance(1001).unwrap();
+        assert_eq!(sorted(blinker.cells()), vec![(0, -1), (0, 0), (0, 1)]);
+        blinker.set(0, 0, false).unwrap();
+        blinker.advance(1).unwrap();
+        assert_eq!(blinker.population(), 0);
+    }
+
+    #[test]
+    fn agrees_with_naive_stepping() {
+        // The R-pentomino keeps changing for over a thousand generations
+        let r_pentomino = [(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)];
+        let mut life = with_cells(&r_pentomino);
+        let mut cells: HashSet<(i64, i64)> = r_pentomino.iter().cloned().collect();
+        for &generations in &[1, 2, 5, 64, 128] {
+            life.advance(generations).unwrap();
+            for _ in 0..generations {
+                cells = naive_step(&cells);
+            }
+            assert_eq!(sorted(life.cells()), sorted(cells.iter().cloned().collect()));
+        }
+    }
+
+    #[test]
+    fn coordinate_limits() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut life = with_cells(&glider);
+        // Too many generations for the root to hold, and no cells change
+        assert_eq!(life.advance(1 << 62), Err(OutOfRange));
+        assert_eq!(life.step_pow2(255), Err(OutOfRange));
+        assert_eq!(sorted(life.cells()), sorted(glider.to_vec()));
+        assert_eq!(life.generation, 0);
+        // The most there is room for at once
+        life.advance(1 << 59).unwrap();
+        let far: Vec<(i64, i64)> = glider.iter().map(|&(x, y)| (x + (1 << 57), y + (1 << 57)))
+                                         .collect();
+        assert_eq!(sorted(life.cells()), sorted(far));
+        assert!(life.get(2 + (1 << 57), 1 + (1 << 57)));
+        // Cells past the largest root are out of reach, and read as dead
+        assert_eq!(life.set(1 << 60, -(1 << 60), true), Ok(()));
+        assert_eq!(life.set(1 << 61, 0, true), Err(OutOfRange));
+        assert_eq!(life.set(::std::i64::MIN, 0, true), Err(OutOfRange));
+        assert!(life.get(1 << 60, -(1 << 60)));
+        assert!(!life.get(::std::i64::MAX, ::std::i64::MIN));
+        assert_eq!(life.population(), 6);
+    }
+}
@@ -2,139 +2,373 @@
 //
 // Contributed by Gavin Baker <gavinb@antonym.org>
 // Adapted from the Go version
-use std::io::{BufReader, BufRead, BufWriter, Write, Read};
-use std::fs::File;
-use std::iter::repeat;
+#[cfg(not(test))]
+use std::io;
+use std::f64::consts::PI;
+use bitmap::{Color, Image};
+use bitmap::netpbm::{Netpbm, NetpbmError};
+#[cfg(not(test))]
+use bitmap::netpbm::Encoding;
+#[cfg(not(test))]
+use bitmap::png::{Png, ColorType, Compression};
+use bitmap::processing::GrayImage as ImageGray8;
+#[allow(dead_code)]
+mod bitmap;
+
+// Reads any PBM, PGM or PPM file, converting it to 8-bit grayscale
+fn load_pgm(filename: &str) -> Result<ImageGray8, NetpbmError> {
+    let image = try!(Netpbm::load(filename));
+
+    println!("Reading pgm file {}: {} x {}", filename, image.width, image.height);
+
+    Ok(ImageGray8 {
+        width: image.width,
+        height: image.height,
+        data: image.gray8(),
+    })
+}
 
-// Simple 8-bit grayscale image
+#[cfg(not(test))]
+fn save_pgm(img: &ImageGray8, filename: &str) -> io::Result<()> {
 
-struct ImageGray8 {
-    width: usize,
-    height: usize,
-    data: Vec<u8>,
-}
+    println!("Writing pgm file {}: {} x {}", filename, img.width, img.height);
 
-fn load_pgm(filename: &str) -> ImageGray8 {
+    Netpbm::from_gray8(img.width, img.height, &img.data).save(filename, Encoding::Raw)
+}
 
-    // Open file
-    let mut file = BufReader::new(File::open(filename).unwrap());
+#[cfg(not(test))]
+fn save_png(img: &ImageGray8, filename: &str) -> io::Result<()> {
 
-    // Read header
-    let mut magic_in = String::new();
-    let _ = file.read_line(&mut magic_in).unwrap();
-    let mut width_in = String::new();
-    let _ = file.read_line(&mut width_in).unwrap();
-    let mut height_in = String::new();
-    let _ = file.read_line(&mut height_in).unwrap();
-    let mut maxval_in = String::new();
-    let _ = file.read_line(&mut maxval_in).unwrap();
+    println!("Writing png file {}: {} x {}", filename, img.width, img.height);
 
-    assert_eq!(magic_in, "P5\n");
-    assert_eq!(maxval_in, "255\n");
+    let png = Png { width: img.width, height: img.height, color: ColorType::Gray,
+                    pixels: img.data.clone() };
+    png.save(filename, Compression::Fixed)
+}
 
-    // Parse header
+// Votes for the lines x cos(theta) + y sin(theta) = rho through the pixels that aren't white.
+// Theta runs over [0, pi) across the columns, and rho from rmax in the middle of the top row
+// down to -rmax at the bottom, where rmax is the length of the image's diagonal.
+struct Accumulator {
+    width: usize,
+    height: usize,
+    rho_step: f64,
+    theta_step: f64,
+    votes: Vec<u32>,
+}
 
-    let width = width_in.trim().parse::<usize>().unwrap();
-    let height: usize = height_in.trim().parse::<usize>().unwrap();
+// The coordinates of the pixels that aren't white
+fn ink(image: &ImageGray8) -> Vec<(usize, usize)> {
+    let mut pixels = Vec::new();
+    for y in 0..image.height {
+        for x in 0..image.width {
+            if image[(x, y)] != 255 {
+                pixels.push((x, y));
+            }
+        }
+    }
+    pixels
+}
 
-    println!("Reading pgm file {}: {} x {}", filename, width, height);
+fn accumulate(image: &ImageGray8, out_width: usize, out_height: usize) -> Accumulator {
 
-    // Create image and allocate buffer
+    // Transform extents
 
-    let mut img = ImageGray8 {
-        width: width,
-        height: height,
-        data: repeat(0u8).take(width*height).collect(),
+    let out_height = ((out_height/2) * 2) as usize;
+    let rmax = (image.width as f64).hypot(image.height as f64);
+    let mut accum = Accumulator {
+        width: out_width,
+        height: out_height,
+        rho_step: rmax / (out_height/2) as f64,
+        theta_step: PI / out_width as f64,
+        votes: vec![0; out_width * out_height],
     };
 
-    // Read image data
-    let len = img.data.len();
-    match file.read(&mut img.data) {
-        Ok(bytes_read) if bytes_read == len => println!("Read {} bytes", bytes_read),
-        Ok(bytes_read) =>
-            println!("Error: read {} bytes, expected {}", bytes_read, len),
-        Err(e) => println!("error reading: {}", e)
-    }
+    // Project each pixel into rho,theta space
+
+    for (x, y) in ink(image) {
+        for jtx in 0..out_width {
+            let th = accum.theta_step * (jtx as f64);
+            let r = (x as f64)*(th.cos()) + (y as f64)*(th.sin());
 
-    img
+            let iry = ((out_height/2) as isize - (r/accum.rho_step+0.5).floor() as isize) as usize;
+            // r close to -rmax rounds to the row just past the bottom one
+            let iry = iry.min(out_height - 1);
+            accum.votes[jtx + iry * out_width] += 1;
+        }
+    }
+    accum
 }
 
-fn save_pgm(img: &ImageGray8, filename: &str) {
+// Renders the accumulator, each vote darkening a white cell by one level
+fn hough(image: &ImageGray8, out_width: usize, out_height: usize) -> ImageGray8 {
+    let accum = accumulate(image, out_width, out_height);
+    ImageGray8 {
+        width: accum.width,
+        height: accum.height,
+        data: accum.votes.iter().map(|&v| 255 - v.min(255) as u8).collect(),
+    }
+}
 
-    // Open file
-    let mut file = BufWriter::new(File::create(filename).unwrap());
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Line {
+    rho: f64,
+    theta: f64,
+    votes: u32,
+}
 
-    // Write header
+// How far apart two cells are, in cells along whichever axis is further. Theta wraps around:
+// just past pi is just past 0 with rho negated, which mirrors the rows.
+fn cell_distance(accum: &Accumulator, (t1, r1): (usize, usize), (t2, r2): (usize, usize))
+                 -> usize {
+    let difference = |a: usize, b: usize| if a > b { a - b } else { b - a };
+    let direct = difference(t1, t2).max(difference(r1, r2));
+    let wrapped = (accum.width - difference(t1, t2)).max(difference(r1, accum.height - r2));
+    direct.min(wrapped)
+}
 
-    match writeln!(&mut file, "P5\n{}\n{}\n255", img.width, img.height) {
-        Err(e) => println!("Failed to write header: {}", e),
-        _ => {},
+// The strongest lines with at least `min_votes` votes, strongest first, by non-maximum
+// suppression: a cell only counts if no stronger one within `window` cells has been taken.
+fn find_lines(accum: &Accumulator, count: usize, min_votes: u32, window: usize) -> Vec<Line> {
+    let mut candidates: Vec<(u32, usize)> = accum.votes.iter().enumerate()
+        .filter(|&(_, &v)| v >= min_votes).map(|(i, &v)| (v, i)).collect();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut peaks: Vec<(usize, usize)> = Vec::new();
+    let mut lines = Vec::new();
+    for (votes, i) in candidates {
+        if lines.len() == count {
+            break;
+        }
+        let cell = (i % accum.width, i / accum.width);
+        if peaks.iter().all(|&peak| cell_distance(accum, peak, cell) > window) {
+            peaks.push(cell);
+            lines.push(Line {
+                rho: (accum.height as f64 / 2.0 - cell.1 as f64) * accum.rho_step,
+                theta: cell.0 as f64 * accum.theta_step,
+                votes: votes,
+            });
+        }
     }
+    lines
+}
 
-    println!("Writing pgm file {}: {} x {}", filename, img.width, img.height);
-
-    // Write binary image data
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Circle {
+    x: usize,
+    y: usize,
+    radius: usize,
+    // The share of the circle's pixels that are ink
+    coverage: f64,
+}
 
-    match file.write_all(&(img.data[..])) {
-        Err(e) => println!("Failed to image data: {}", e),
-        _ => {},
+// The distinct offsets of the pixels on a circle, by the midpoint algorithm
+fn circle_offsets(radius: usize) -> Vec<(isize, isize)> {
+    let (mut x, mut y) = (0, radius as isize);
+    let mut decision = 1 - y;
+    let mut offsets = Vec::new();
+    while x <= y {
+        offsets.extend([(x, y), (y, x), (-x, y), (-y, x), (x, -y), (y, -x), (-x, -y), (-y, -x)]
+                           .iter().cloned());
+        x += 1;
+        if decision < 0 {
+            decision += 2 * x + 1;
+        } else {
+            y -= 1;
+            decision += 2 * (x - y) + 1;
+        }
     }
+    offsets.sort();
+    offsets.dedup();
+    offsets
 }
 
-fn hough(image: &ImageGray8, out_width: usize, out_height: usize) -> ImageGray8 {
+// Circles with radii from min_radius to max_radius: every ink pixel votes for the centers of
+// the circles of each radius through it. A circle needs at least `min_coverage` of its pixels
+// inked, and suppresses any weaker one whose center and radius are both within `window`.
+fn hough_circles(image: &ImageGray8, min_radius: usize, max_radius: usize, count: usize,
+                 min_coverage: f64, window: usize) -> Vec<Circle> {
+    let (width, height) = (image.width as isize, image.height as isize);
+    let pixels = ink(image);
+    let mut candidates = Vec::new();
+    for radius in min_radius..max_radius + 1 {
+        let offsets = circle_offsets(radius);
+        let mut votes = vec![0u32; image.width * image.height];
+        for &(x, y) in &pixels {
+            for &(dx, dy) in &offsets {
+                let (cx, cy) = (x as isize - dx, y as isize - dy);
+                if cx >= 0 && cy >= 0 && cx < width && cy < height {
+                    votes[(cx + cy * width) as usize] += 1;
+                }
+            }
+        }
+        for (i, &v) in votes.iter().enumerate() {
+            let coverage = v as f64 / offsets.len() as f64;
+            if coverage >= min_coverage {
+                candidates.push(Circle { x: i % image.width, y: i / image.width,
+                                         radius: radius, coverage: coverage });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.coverage.partial_cmp(&a.coverage).unwrap());
 
-    let in_width = image.width;
-    let in_height = image.height;
+    let mut circles: Vec<Circle> = Vec::new();
+    for candidate in candidates {
+        if circles.len() == count {
+            break;
+        }
+        let near = |a: usize, b: usize| (a as isize - b as isize).abs() <= window as isize;
+        if !circles.iter().any(|c| {
+            near(c.x, candidate.x) && near(c.y, candidate.y) && near(c.radius, candidate.radius)
+        }) {
+            circles.push(candidate);
+        }
+    }
+    circles
+}
 
-    // Allocate accumulation buffer
+// The image in color with the lines in red and the circles in blue
+fn overlay(image: &ImageGray8, lines: &[Line], circles: &[Circle]) -> Image {
+    let mut output = Image::from_gray(image);
+    // Far enough along each line to leave the image from anywhere in it
+    let reach = (image.width as f64).hypot(image.height as f64);
+    for line in lines {
+        let (cos, sin) = (line.theta.cos(), line.theta.sin());
+        let (x0, y0) = (line.rho * cos, line.rho * sin);
+        output.draw_line_antialiased((x0 + reach * sin, y0 - reach * cos),
+                                     (x0 - reach * sin, y0 + reach * cos),
+                                     Color { red: 230, green: 0, blue: 0 });
+    }
+    for circle in circles {
+        output.draw_circle((circle.x as i64, circle.y as i64), circle.radius as i64,
+                           Color { red: 0, green: 0, blue: 230 });
+    }
+    output
+}
 
-    let out_height = ((out_height/2) * 2) as usize;
-    let mut accum = ImageGray8 {
-        width: out_width,
-        height: out_height,
-        data: repeat(255).take(out_width*out_height).collect(),
+#[cfg(not(test))]
+fn main() {
+    use std::env;
+    use bitmap::processing::canny;
+
+    // hough_transform [--canny] [image]: the transform counts every pixel that isn't white, so
+    // photographs need their edges found first
+    let args: Vec<String> = env::args().skip(1).collect();
+    let find_edges = args.iter().any(|arg| arg == "--canny");
+    let filename = args.iter().find(|arg| !arg.starts_with("--")).map(|arg| &arg[..])
+                       .unwrap_or("../src/resources/Pentagon.pgm");
+
+    let image = match load_pgm(filename) {
+        Ok(image) => image,
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        }
     };
+    let image = if find_edges { canny(&image, 1.4, 40.0, 100.0).inverted() } else { image };
 
-    // Transform extents
-
-    let rmax = (in_width as f64).hypot(in_height as f64);
-    let dr = rmax / (out_height/2) as f64;
-    let dth = std::f64::consts::PI / out_width as f64;
+    let accum = hough(&image, 460, 360);
 
-    // Process input image in raster order
+    if let Err(e) = save_pgm(&accum, "hough.pgm").and_then(|_| save_png(&accum, "hough.png")) {
+        println!("Error: {}", e);
+    }
 
-    for y in (0..in_height) {
-        for x in (0..in_width) {
-            let in_idx = y*in_width+x;
-            let col = image.data[in_idx];
-            if col == 255 {
-                continue;
-            }
+    let lines = find_lines(&accumulate(&image, 460, 360), 10, 100, 16);
+    for line in &lines {
+        println!("Line: rho {:.1}, theta {:.1} degrees, {} votes", line.rho,
+                 line.theta.to_degrees(), line.votes);
+    }
+    let circles = hough_circles(&image, 10, 60, 5, 0.6, 4);
+    for circle in &circles {
+        println!("Circle: center ({}, {}), radius {}, {:.0}% covered", circle.x, circle.y,
+                 circle.radius, circle.coverage * 100.0);
+    }
+    if let Err(e) = overlay(&image, &lines, &circles).write_png("hough-lines.png") {
+        println!("Error: {}", e);
+    }
+}
 
-            // Project into rho,theta space
+#[cfg(test)]
+mod test {
+    use super::{accumulate, find_lines, hough, hough_circles, load_pgm, overlay, ImageGray8};
+    use bitmap::Color;
+    use std::f64::consts::PI;
 
-            for jtx in (0..out_width) {
-                let th = dth * (jtx as f64);
-                let r = (x as f64)*(th.cos()) + (y as f64)*(th.sin());
+    fn blank(width: usize, height: usize) -> ImageGray8 {
+        ImageGray8 { width: width, height: height, data: vec![255; width * height] }
+    }
 
-                let iry = out_height/2 - (r/(dr as f64)+0.5).floor() as usize;
-                let out_idx = jtx + iry * out_width;
-                let col = accum.data[out_idx];
-                if col > 0 {
-                    accum.data[out_idx] = col - 1;
-                }
-            }
+    #[test]
+    fn pentagon() {
+        let image = load_pgm("src/resources/Pentagon.pgm").unwrap();
+        let lines = find_lines(&accumulate(&image, 460, 360), 10, 100, 16);
+        assert_eq!(lines.len(), 5);
+        // One line for each side, and the sides all point different ways
+        let mut thetas: Vec<f64> = lines.iter().map(|line| line.theta.to_degrees()).collect();
+        thetas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for pair in thetas.windows(2) {
+            assert!(pair[1] - pair[0] > 20.0);
         }
     }
-    accum
-}
 
-#[cfg(not(test))]
-fn main() {
-
-    let image = load_pgm("../src/resources/Pentagon.pgm");
+    #[test]
+    fn lines() {
+        // A horizontal line at y = 30 and a vertical one at x = 70
+        let mut image = blank(100, 80);
+        for x in 0..100 {
+            image.data[x + 30 * 100] = 0;
+        }
+        for y in 0..80 {
+            image.data[70 + y * 100] = 0;
+        }
+        let lines = find_lines(&accumulate(&image, 180, 256), 5, 50, 4);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].votes, 100);
+        assert!((lines[0].theta - PI / 2.0).abs() < 1e-9 && (lines[0].rho - 30.0).abs() < 1.0);
+        assert_eq!(lines[1].votes, 80);
+        assert!(lines[1].theta.abs() < 1e-9 && (lines[1].rho - 70.0).abs() < 1.0);
+
+        // The busiest cell is the darkest
+        let rendered = hough(&image, 180, 256);
+        assert_eq!((rendered.width, rendered.height), (180, 256));
+        assert_eq!(rendered.data.iter().cloned().min(), Some(255 - 100));
+        // The lines are drawn in red over the image
+        let drawn = overlay(&image, &lines, &[]);
+        assert!((28..33).any(|y| drawn[(10, y)].red > drawn[(10, y)].green));
+        assert!((68..73).any(|x| drawn[(x, 10)].red > drawn[(x, 10)].green));
+        assert_eq!(drawn[(10, 10)], Color { red: 255, green: 255, blue: 255 });
+    }
 
-    let accum = hough(&image, 460, 360);
+    #[test]
+    fn extreme_aspect_ratio() {
+        // Ink at the far end of a long thin image reaches the extremes of rho
+        let mut image = blank(1000, 1);
+        for x in 0..1000 {
+            image.data[x] = 0;
+        }
+        let accum = accumulate(&image, 460, 360);
+        assert_eq!(accum.votes.iter().fold(0, |sum, &v| sum + v), 1000 * 460);
+        let lines = find_lines(&accum, 1, 500, 4);
+        assert_eq!(lines[0].votes, 1000);
+        assert!((lines[0].theta - PI / 2.0).abs() < 1e-9 && lines[0].rho.abs() < 3.0);
+    }
 
-    save_pgm(&accum, "hough.pgm");
+    #[test]
+    fn circles() {
+        let mut image = blank(120, 100);
+        for &(cx, cy, r) in &[(40.0, 50.0, 25.0), (90.0, 30.0, 15.0)] {
+            for step in 0..720 {
+                let angle = step as f64 * PI / 360.0;
+                let (x, y) = (cx + r * angle.cos(), cy + r * angle.sin());
+                image.data[x.round() as usize + y.round() as usize * 120] = 0;
+            }
+        }
+        let mut circles = hough_circles(&image, 10, 30, 5, 0.8, 4);
+        assert_eq!(circles.len(), 2);
+        circles.sort_by(|a, b| a.x.cmp(&b.x));
+        assert_eq!((circles[0].x, circles[0].y, circles[0].radius), (40, 50, 25));
+        assert_eq!((circles[1].x, circles[1].y, circles[1].radius), (90, 30, 15));
+        let drawn = overlay(&image, &[], &circles);
+        assert_eq!(drawn[(65, 50)], Color { red: 0, green: 0, blue: 230 });
+    }
 }
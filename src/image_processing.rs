@@ -0,0 +1,453 @@
+// Implements http://rosettacode.org/wiki/Image_convolution
+// and its neighbours:
+// http://rosettacode.org/wiki/Canny_edge_detector
+// http://rosettacode.org/wiki/Bitmap/Histogram
+// http://rosettacode.org/wiki/Median_filter
+//
+// Everything works on 8-bit grayscale images; color images are processed a channel at a time
+// (see `Image::map_channels` in bitmap.rs). Pixels outside the image take the value of the
+// nearest edge pixel.
+
+use std::collections::VecDeque;
+use std::ops::{Index, IndexMut};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>
+}
+
+impl GrayImage {
+    pub fn new(width: usize, height: usize) -> GrayImage {
+        GrayImage { width: width, height: height, data: vec![0; width * height] }
+    }
+
+    pub fn inverted(&self) -> GrayImage {
+        GrayImage {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(|&v| 255 - v).collect(),
+        }
+    }
+
+    // The pixel at (x, y), or the nearest one inside the image
+    fn clamped(&self, x: isize, y: isize) -> u8 {
+        let x = x.max(0).min(self.width as isize - 1) as usize;
+        let y = y.max(0).min(self.height as isize - 1) as usize;
+        self.data[x + y * self.width]
+    }
+
+    fn to_plane(&self) -> Vec<f64> {
+        self.data.iter().map(|&v| v as f64).collect()
+    }
+
+    fn from_plane(width: usize, height: usize, plane: &[f64]) -> GrayImage {
+        GrayImage {
+            width: width,
+            height: height,
+            data: plane.iter().map(|&v| v.round().max(0.0).min(255.0) as u8).collect(),
+        }
+    }
+}
+
+impl Index<(usize, usize)> for GrayImage {
+    type Output = u8;
+
+    fn index(&self, (x, y): (usize, usize)) -> &u8 {
+        &self.data[x + y * self.width]
+    }
+}
+
+impl IndexMut<(usize, usize)> for GrayImage {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut u8 {
+        &mut self.data[x + y * self.width]
+    }
+}
+
+// Applies a kernel along rows, then another along columns. Kernels have odd lengths and are
+// centered on the pixel; they are applied as given, without flipping.
+fn convolve_plane(plane: &[f64], width: usize, height: usize, horizontal: &[f64],
+                  vertical: &[f64]) -> Vec<f64> {
+    assert!(horizontal.len() % 2 == 1 && vertical.len() % 2 == 1);
+    let clamp = |v: isize, limit: usize| v.max(0).min(limit as isize - 1) as usize;
+    let (rh, rv) = ((horizontal.len() / 2) as isize, (vertical.len() / 2) as isize);
+    let mut rows = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            rows[x + y * width] = horizontal.iter().enumerate().fold(0.0, |sum, (i, &k)| {
+                sum + k * plane[clamp(x as isize + i as isize - rh, width) + y * width]
+            });
+        }
+    }
+    let mut output = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            output[x + y * width] = vertical.iter().enumerate().fold(0.0, |sum, (i, &k)| {
+                sum + k * rows[x + clamp(y as isize + i as isize - rv, height) * width]
+            });
+        }
+    }
+    output
+}
+
+/// Convolves the image with a separable kernel: `horizontal` along the rows and `vertical`
+/// along the columns.
+pub fn convolve(image: &GrayImage, horizontal: &[f64], vertical: &[f64]) -> GrayImage {
+    let plane = convolve_plane(&image.to_plane(), image.width, image.height, horizontal,
+                               vertical);
+    GrayImage::from_plane(image.width, image.height, &plane)
+}
+
+/// The mean of the square of side 2 radius + 1 around each pixel.
+pub fn box_blur(image: &GrayImage, radius: usize) -> GrayImage {
+    let kernel = vec![1.0 / (2 * radius + 1) as f64; 2 * radius + 1];
+    convolve(image, &kernel, &kernel)
+}
+
+/// A normalized Gaussian of standard deviation sigma, out to three sigmas. A sigma of zero (or
+/// less) leaves the image as it is.
+pub fn gaussian_kernel(sigma: f64) -> Vec<f64> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+    let radius = (3.0 * sigma).ceil().max(0.0) as isize;
+    let weights: Vec<f64> = (-radius..radius + 1)
+        .map(|i| (-(i * i) as f64 / (2.0 * sigma * sigma)).exp()).collect();
+    let total = weights.iter().fold(0.0, |sum, &w| sum + w);
+    weights.iter().map(|&w| w / total).collect()
+}
+
+pub fn gaussian_blur(image: &GrayImage, sigma: f64) -> GrayImage {
+    let kernel = gaussian_kernel(sigma);
+    convolve(image, &kernel, &kernel)
+}
+
+// The horizontal and vertical derivatives by the Sobel operator
+fn sobel_gradients(plane: &[f64], width: usize, height: usize) -> (Vec<f64>, Vec<f64>) {
+    let (difference, smooth) = ([-1.0, 0.0, 1.0], [1.0, 2.0, 1.0]);
+    (convolve_plane(plane, width, height, &difference, &smooth),
+     convolve_plane(plane, width, height, &smooth, &difference))
+}
+
+/// The gradient magnitude by the Sobel operator, scaled so that the strongest edge is white.
+pub fn sobel(image: &GrayImage) -> GrayImage {
+    let (gx, gy) = sobel_gradients(&image.to_plane(), image.width, image.height);
+    let magnitude: Vec<f64> = gx.iter().zip(gy.iter()).map(|(x, y)| x.hypot(*y)).collect();
+    let max = magnitude.iter().fold(0.0, |max: f64, &m| max.max(m));
+    let scale = if max > 0.0 { 255.0 / max } else { 0.0 };
+    let scaled: Vec<f64> = magnitude.iter().map(|&m| m * scale).collect();
+    GrayImage::from_plane(image.width, image.height, &scaled)
+}
+
+/// Edges by Canny's method: smooth with a Gaussian of the given sigma, keep only the pixels
+/// where the Sobel gradient is strongest across the edge, and then keep those above `high`
+/// together with those above `low` connected to them. The thresholds are in units of the
+/// Sobel magnitude, which reaches 1020 across a black to white step. Edges are white on black.
+pub fn canny(image: &GrayImage, sigma: f64, low: f64, high: f64) -> GrayImage {
+    let (width, height) = (image.width, image.height);
+    let kernel = gaussian_kernel(sigma);
+    let smooth = convolve_plane(&image.to_plane(), width, height, &kernel, &kernel);
+    let (gx, gy) = sobel_gradients(&smooth, width, height);
+    let magnitude: Vec<f64> = gx.iter().zip(gy.iter()).map(|(x, y)| x.hypot(*y)).collect();
+    let at = |x: isize, y: isize| {
+        if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+            0.0
+        } else {
+            magnitude[x as usize + y as usize * width]
+        }
+    };
+
+    // Non-maximum suppression, with the gradient direction rounded to a multiple of 45 degrees
+    let mut strength = vec![0.0; magnitude.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = x + y * width;
+            if magnitude[i] < low {
+                continue;
+            }
+            let angle = gy[i].atan2(gx[i]).to_degrees();
+            let angle = if angle < 0.0 { angle + 180.0 } else { angle };
+            let (dx, dy) = if angle < 22.5 || angle >= 157.5 {
+                (1, 0)
+            } else if angle < 67.5 {
+                (1, 1)
+            } else if angle < 112.5 {
+                (0, 1)
+            } else {
+                (-1, 1)
+            };
+            let (x, y) = (x as isize, y as isize);
+            // Ties go to the first pixel along the gradient, so plateaus stay one pixel thick
+            if magnitude[i] > at(x - dx, y - dy) && magnitude[i] >= at(x + dx, y + dy) {
+                strength[i] = magnitude[i];
+            }
+        }
+    }
+
+    // Hysteresis: grow the strong edges through the weak ones
+    let mut edges = GrayImage::new(width, height);
+    let mut queue: VecDeque<usize> = (0..strength.len()).filter(|&i| strength[i] >= high)
+                                                       .collect();
+    for &i in &queue {
+        edges.data[i] = 255;
+    }
+    while let Some(i) = queue.pop_front() {
+        let (x, y) = ((i % width) as isize, (i / width) as isize);
+        for dy in -1..2 {
+            for dx in -1..2 {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                    continue;
+                }
+                let j = nx as usize + ny as usize * width;
+                if edges.data[j] == 0 && strength[j] >= low {
+                    edges.data[j] = 255;
+                    queue.push_back(j);
+                }
+            }
+        }
+    }
+    edges
+}
+
+pub fn histogram(image: &GrayImage) -> [usize; 256] {
+    let mut counts = [0; 256];
+    for &v in &image.data {
+        counts[v as usize] += 1;
+    }
+    counts
+}
+
+/// Otsu's threshold: the level that best splits the histogram in two, maximizing the variance
+/// between the classes. Pixels above it are the foreground.
+pub fn otsu_threshold(image: &GrayImage) -> u8 {
+    let counts = histogram(image);
+    let total = image.data.len() as f64;
+    let sum = (0..256).fold(0.0, |sum, v| sum + v as f64 * counts[v] as f64);
+    let (mut background, mut background_sum) = (0.0, 0.0);
+    let (mut best, mut best_variance) = (0, -1.0);
+    for t in 0..256 {
+        background += counts[t] as f64;
+        background_sum += t as f64 * counts[t] as f64;
+        let foreground = total - background;
+        if background == 0.0 || foreground == 0.0 {
+            continue;
+        }
+        let difference = background_sum / background - (sum - background_sum) / foreground;
+        let variance = background * foreground * difference * difference;
+        if variance > best_variance {
+            best = t;
+            best_variance = variance;
+        }
+    }
+    best as u8
+}
+
+/// White where the image is above the level, black elsewhere.
+pub fn threshold(image: &GrayImage, level: u8) -> GrayImage {
+    GrayImage {
+        width: image.width,
+        height: image.height,
+        data: image.data.iter().map(|&v| if v > level { 255 } else { 0 }).collect(),
+    }
+}
+
+/// Spreads the levels out so that their cumulative histogram is as close to a straight line as
+/// it can be, with the darkest level in the image becoming black.
+pub fn equalize_histogram(image: &GrayImage) -> GrayImage {
+    let counts = histogram(image);
+    let mut cumulative = [0; 256];
+    let mut running = 0;
+    for v in 0..256 {
+        running += counts[v];
+        cumulative[v] = running;
+    }
+    let darkest = match counts.iter().position(|&c| c > 0) {
+        Some(v) => cumulative[v],
+        None => return image.clone(),
+    };
+    let total = image.data.len();
+    if total == darkest {
+        return image.clone();
+    }
+    let mut output = image.clone();
+    for v in &mut output.data {
+        let scaled = (cumulative[*v as usize] - darkest) as f64 * 255.0 / (total - darkest) as f64;
+        *v = scaled.round() as u8;
+    }
+    output
+}
+
+/// The median of the square of side 2 radius + 1 around each pixel. A histogram of the window
+/// slides along each row, so the cost per pixel grows with the radius, not its square.
+pub fn median_filter(image: &GrayImage, radius: usize) -> GrayImage {
+    let r = radius as isize;
+    let half = ((2 * radius + 1) * (2 * radius + 1) + 1) / 2;
+    let mut output = GrayImage::new(image.width, image.height);
+    if image.width == 0 || image.height == 0 {
+        return output;
+    }
+    for y in 0..image.height as isize {
+        let mut counts = [0usize; 256];
+        for dy in -r..r + 1 {
+            for dx in -r..r + 1 {
+                counts[image.clamped(dx, y + dy) as usize] += 1;
+            }
+        }
+        for x in 0..image.width as isize {
+            if x > 0 {
+                for dy in -r..r + 1 {
+                    counts[image.clamped(x - r - 1, y + dy) as usize] -= 1;
+                    counts[image.clamped(x + r, y + dy) as usize] += 1;
+                }
+            }
+            let mut seen = 0;
+            let median = counts.iter().position(|&c| { seen += c; seen >= half }).unwrap();
+            output[(x as usize, y as usize)] = median as u8;
+        }
+    }
+    output
+}
+
+#[cfg(not(test))]
+#[allow(dead_code)]
+pub fn main() {
+    // A soft, noisy disc, and what each step makes of it
+    let size = 32;
+    let mut image = GrayImage::new(size, size);
+    for y in 0..size {
+        for x in 0..size {
+            let distance = ((x as f64 - 15.5).powi(2) + (y as f64 - 15.5).powi(2)).sqrt();
+            let noise = if (x * 7 + y * 13) % 17 == 0 { 120.0 } else { 0.0 };
+            image[(x, y)] = (200.0 / (1.0 + (distance - 10.0).exp()) + 30.0 + noise) as u8;
+        }
+    }
+    let print = |title: &str, image: &GrayImage| {
+        println!("{}:", title);
+        for row in image.data.chunks(image.width) {
+            let line: String = row.iter().map(|&v| [' ', '.', ':', 'o', '#'][v as usize * 5 / 256])
+                                  .collect();
+            println!("{}", line);
+        }
+    };
+    print("Input", &image);
+    let cleaned = median_filter(&image, 1);
+    print("Median filtered", &cleaned);
+    let level = otsu_threshold(&cleaned);
+    print(&format!("Otsu threshold ({})", level), &threshold(&cleaned, level));
+    print("Canny edges", &canny(&cleaned, 1.0, 100.0, 250.0));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GrayImage, convolve, box_blur, gaussian_kernel, gaussian_blur, sobel, canny,
+                histogram, otsu_threshold, threshold, equalize_histogram, median_filter};
+
+    fn from_fn<F: Fn(usize, usize) -> u8>(width: usize, height: usize, f: F) -> GrayImage {
+        let mut image = GrayImage::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                image[(x, y)] = f(x, y);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn convolution() {
+        let image = from_fn(7, 5, |x, y| (x * 30 + y * 7) as u8);
+        assert_eq!(convolve(&image, &[1.0], &[1.0]), image);
+        // Shifting by one pixel, with the edge repeated
+        let shifted = convolve(&image, &[0.0, 0.0, 1.0], &[1.0]);
+        assert_eq!(shifted[(0, 0)], image[(1, 0)]);
+        assert_eq!(shifted[(6, 0)], image[(6, 0)]);
+        // A linear ramp is unchanged by symmetric blurs
+        assert_eq!(box_blur(&image, 1)[(3, 2)], image[(3, 2)]);
+        assert_eq!(gaussian_blur(&image, 1.0)[(3, 2)], image[(3, 2)]);
+
+        let kernel = gaussian_kernel(1.5);
+        assert_eq!(kernel.len(), 11);
+        assert!((kernel.iter().fold(0.0, |sum, &w| sum + w) - 1.0).abs() < 1e-12);
+        assert!(kernel[5] > kernel[4] && kernel[4] == kernel[6]);
+        assert_eq!(gaussian_kernel(0.0), vec![1.0]);
+        assert_eq!(gaussian_blur(&image, 0.0), image);
+    }
+
+    #[test]
+    fn edges() {
+        let step = from_fn(10, 6, |x, _| if x < 5 { 0 } else { 200 });
+        let magnitude = sobel(&step);
+        for y in 0..6 {
+            assert_eq!(magnitude[(4, y)], 255);
+            assert_eq!(magnitude[(5, y)], 255);
+            assert_eq!(magnitude[(2, y)], 0);
+        }
+        // Without smoothing the step is still found
+        let unsmoothed = canny(&step, 0.0, 50.0, 150.0);
+        assert!((0..6).all(|y| unsmoothed[(4, y)] == 255 || unsmoothed[(5, y)] == 255));
+
+        // The outline of a square, one pixel thick
+        let square = from_fn(30, 30, |x, y| {
+            if x >= 10 && x < 20 && y >= 10 && y < 20 { 220 } else { 20 }
+        });
+        let edges = canny(&square, 1.0, 50.0, 150.0);
+        let on: Vec<(usize, usize)> = (0..900).filter(|&i| edges.data[i] == 255)
+                                             .map(|i| (i % 30, i / 30)).collect();
+        assert!(on.len() >= 36 && on.len() <= 44);
+        assert!(on.iter().all(|&(x, y)| {
+            let near = |v: usize| v >= 8 && v <= 21;
+            near(x) && near(y) && !(x >= 11 && x <= 18 && y >= 11 && y <= 18)
+        }));
+        assert_eq!(canny(&GrayImage::new(5, 5), 1.0, 10.0, 20.0), GrayImage::new(5, 5));
+    }
+
+    #[test]
+    fn thresholds_and_histograms() {
+        // Two noisy populations around 60 and 180
+        let image = from_fn(20, 20, |x, y| {
+            let jitter = ((x * 31 + y * 17) % 21) as u8;
+            if x < 12 { 50 + jitter } else { 170 + jitter }
+        });
+        let level = otsu_threshold(&image);
+        assert!(level >= 70 && level < 170);
+        let binary = threshold(&image, level);
+        assert_eq!(histogram(&binary)[255], 8 * 20);
+        assert_eq!(histogram(&binary)[0], 12 * 20);
+
+        let dull = from_fn(16, 16, |x, _| 100 + x as u8);
+        let equalized = equalize_histogram(&dull);
+        assert_eq!(equalized[(0, 0)], 0);
+        assert_eq!(equalized[(15, 0)], 255);
+        assert_eq!(equalized[(8, 0)], 136);
+        let flat = GrayImage { width: 2, height: 2, data: vec![9; 4] };
+        assert_eq!(equalize_histogram(&flat), flat);
+    }
+
+    #[test]
+    fn median() {
+        let mut image = from_fn(9, 9, |x, _| if x < 4 { 10 } else { 200 });
+        image[(1, 1)] = 255;
+        image[(6, 6)] = 0;
+        let filtered = median_filter(&image, 1);
+        // Isolated specks disappear and the edge stays sharp
+        assert_eq!(filtered, from_fn(9, 9, |x, _| if x < 4 { 10 } else { 200 }));
+        // Against a brute force median
+        let noisy = from_fn(12, 8, |x, y| ((x * 73 + y * 151) % 256) as u8);
+        let filtered = median_filter(&noisy, 2);
+        for y in 0..8 {
+            for x in 0..12 {
+                let mut window = Vec::new();
+                for dy in -2..3 {
+                    for dx in -2..3 {
+                        window.push(noisy.clamped(x as isize + dx, y as isize + dy));
+                    }
+                }
+                window.sort();
+                assert_eq!(filtered[(x, y)], window[12]);
+            }
+        }
+        assert_eq!(median_filter(&GrayImage::new(0, 4), 1), GrayImage::new(0, 4));
+        assert_eq!(median_filter(&GrayImage::new(4, 0), 1), GrayImage::new(4, 0));
+    }
+}
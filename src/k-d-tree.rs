@@ -5,6 +5,8 @@ extern crate rand;
 
 use rand::Rng;
 use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f32;
 #[cfg(not(test))]
 use time::get_time;
 use std::ops::Sub;
@@ -34,7 +36,7 @@ struct KDTreeNode {
     point: Point,
     dim: usize,
     // Construction could become faster if we use an arena allocator,
-    // but this is easier to use.
+    // but this is easier to use. KDTree below is the arena version.
     left: Option<Box<KDTreeNode>>,
     right: Option<Box<KDTreeNode>>
 }
@@ -151,6 +153,327 @@ impl KDTreeNode {
     }
 }
 
+// A node of KDTree. Children are indices into the arena rather than boxes.
+struct ArenaNode {
+    point: Point,
+    dim: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+    // Number of nodes in this subtree, deleted ones included
+    size: usize,
+    // Deleted nodes stay in place to guide searches until the next rebuild
+    deleted: bool
+}
+
+// A candidate of a k-nearest search, ordered by squared distance so that the
+// heap of candidates pops the farthest first.
+struct Candidate(f32, usize);
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Candidate) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+// A subtree is rebalanced when one of its children holds more than this
+// fraction of its nodes (a scapegoat tree).
+const ALPHA: f32 = 0.75;
+
+// A k-d tree stored in a flat arena, supporting insertion and deletion.
+//
+// Inserted points are added as leaves. When that makes the tree too deep, the
+// highest unbalanced subtree on the way down is rebuilt around its medians.
+// Deleted points are only marked; the whole tree is rebuilt once they
+// outnumber the live ones.
+struct KDTree {
+    nodes: Vec<ArenaNode>,
+    // Slots of nodes dropped by rebuilds, to be reused
+    free: Vec<usize>,
+    root: Option<usize>,
+    live: usize,
+    deleted: usize
+}
+
+impl KDTree {
+    pub fn new(mut points: Vec<Point>) -> KDTree {
+        let mut tree = KDTree { nodes: Vec::with_capacity(points.len()), free: Vec::new(),
+                                root: None, live: points.len(), deleted: 0 };
+        tree.root = tree.build(&mut points[..], 0);
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    // The number of levels, as a measure of balance
+    pub fn depth(&self) -> usize {
+        fn depth_of(tree: &KDTree, node: Option<usize>) -> usize {
+            node.map_or(0, |idx| {
+                let node = &tree.nodes[idx];
+                1 + ::std::cmp::max(depth_of(tree, node.left), depth_of(tree, node.right))
+            })
+        }
+        depth_of(self, self.root)
+    }
+
+    fn alloc(&mut self, node: ArenaNode) -> usize {
+        match self.free.pop() {
+            Some(idx) => { self.nodes[idx] = node; idx }
+            None => { self.nodes.push(node); self.nodes.len() - 1 }
+        }
+    }
+
+    // Builds a balanced subtree splitting on `dim` first, like KDTreeNode::new.
+    fn build(&mut self, points: &mut [Point], dim: usize) -> Option<usize> {
+        let points_len = points.len();
+        if points_len == 0 {
+            return None;
+        }
+        let pivot = quickselect_by(points, points_len/2,
+            &|a, b| a.coords[dim].partial_cmp(&b.coords[dim]).unwrap());
+        let next_dim = (dim + 1) % pivot.coords.len();
+        let left = self.build(&mut points[0..points_len/2], next_dim);
+        let right = self.build(&mut points[points_len/2+1..points_len], next_dim);
+        Some(self.alloc(ArenaNode { point: pivot, dim: dim, left: left, right: right,
+                                    size: points_len, deleted: false }))
+    }
+
+    // Frees the subtree and returns its live points
+    fn collect(&mut self, node: Option<usize>, points: &mut Vec<Point>) {
+        if let Some(idx) = node {
+            let (left, right) = (self.nodes[idx].left, self.nodes[idx].right);
+            self.collect(left, points);
+            self.collect(right, points);
+            if !self.nodes[idx].deleted {
+                points.push(self.nodes[idx].point.clone());
+            }
+            self.free.push(idx);
+        }
+    }
+
+    // Rebuilds the subtree balanced, dropping its deleted nodes. Returns the
+    // new root of the subtree and how many deleted nodes were dropped.
+    fn rebuild(&mut self, idx: usize) -> (Option<usize>, usize) {
+        let (dim, size) = (self.nodes[idx].dim, self.nodes[idx].size);
+        let mut points = Vec::with_capacity(size);
+        self.collect(Some(idx), &mut points);
+        let dropped = size - points.len();
+        self.deleted -= dropped;
+        (self.build(&mut points[..], dim), dropped)
+    }
+
+    pub fn insert(&mut self, point: Point) {
+        // Walk down to the leaf position, counting the new node in the size of
+        // every subtree on the way
+        let mut path = Vec::new();
+        let mut current = self.root;
+        while let Some(idx) = current {
+            path.push(idx);
+            self.nodes[idx].size += 1;
+            let node = &self.nodes[idx];
+            current = if point.coords[node.dim] < node.point.coords[node.dim] {
+                node.left
+            } else {
+                node.right
+            };
+        }
+
+        let dim = path.last().map_or(0, |&p| (self.nodes[p].dim + 1) % point.coords.len());
+        let goes_left = path.last().map(|&p| {
+            let parent = &self.nodes[p];
+            point.coords[parent.dim] < parent.point.coords[parent.dim]
+        });
+        let new = self.alloc(ArenaNode { point: point, dim: dim, left: None, right: None,
+                                         size: 1, deleted: false });
+        match (path.last(), goes_left) {
+            (Some(&p), Some(true)) => self.nodes[p].left = Some(new),
+            (Some(&p), _) => self.nodes[p].right = Some(new),
+            (None, _) => self.root = Some(new),
+        }
+        self.live += 1;
+
+        // A scapegoat tree is at most log base 1/ALPHA of its size deep
+        let size = self.nodes[self.root.unwrap()].size as f32;
+        if path.len() as f32 <= size.ln() / (1.0 / ALPHA).ln() {
+            return;
+        }
+        let mut child = new;
+        for i in (0..path.len()).rev() {
+            let idx = path[i];
+            if self.nodes[child].size as f32 > ALPHA * self.nodes[idx].size as f32 {
+                let (subtree, dropped) = self.rebuild(idx);
+                for &ancestor in &path[..i] {
+                    self.nodes[ancestor].size -= dropped;
+                }
+                match i.checked_sub(1).map(|p| path[p]) {
+                    Some(parent) if self.nodes[parent].left == Some(idx) => {
+                        self.nodes[parent].left = subtree
+                    }
+                    Some(parent) => self.nodes[parent].right = subtree,
+                    None => self.root = subtree,
+                }
+                return;
+            }
+            child = idx;
+        }
+    }
+
+    // Removes one copy of the point. Returns whether it was found.
+    pub fn remove(&mut self, point: &Point) -> bool {
+        let found = match self.root {
+            Some(root) => self.find(root, point),
+            None => None
+        };
+        match found {
+            Some(idx) => {
+                self.nodes[idx].deleted = true;
+                self.live -= 1;
+                self.deleted += 1;
+                if self.deleted > self.live {
+                    let root = self.root.unwrap();
+                    self.root = self.rebuild(root).0;
+                }
+                true
+            }
+            None => false
+        }
+    }
+
+    fn find(&self, idx: usize, point: &Point) -> Option<usize> {
+        let node = &self.nodes[idx];
+        if !node.deleted && node.point == *point {
+            return Some(idx);
+        }
+        // Points equal along the split may be on either side
+        let (a, b) = (point.coords[node.dim], node.point.coords[node.dim]);
+        let left = if a <= b { node.left.and_then(|l| self.find(l, point)) } else { None };
+        if left.is_some() {
+            return left;
+        }
+        if a >= b { node.right.and_then(|r| self.find(r, point)) } else { None }
+    }
+
+    // The k points nearest to the target, nearest first, with their squared
+    // distances. A heap holds the best k found so far; a subtree is skipped
+    // when its split is farther away than the worst of them.
+    pub fn k_nearest(&self, target: &Point, k: usize) -> Vec<(&Point, f32)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            if let Some(root) = self.root {
+                self.k_nearest_helper(root, target, k, &mut heap);
+            }
+        }
+        heap.into_sorted_vec().into_iter()
+            .map(|Candidate(dist_sq, idx)| (&self.nodes[idx].point, dist_sq))
+            .collect()
+    }
+
+    fn k_nearest_helper(&self, idx: usize, target: &Point, k: usize,
+                        heap: &mut BinaryHeap<Candidate>) {
+        let node = &self.nodes[idx];
+        let axis_dist = target.coords[node.dim] - node.point.coords[node.dim];
+        let (near, far) = if axis_dist < 0f32 { (node.left, node.right) }
+                          else { (node.right, node.left) };
+
+        if let Some(near) = near {
+            self.k_nearest_helper(near, target, k, heap);
+        }
+        if !node.deleted {
+            let dist_sq = (target - &node.point).norm_sq();
+            if heap.len() < k {
+                heap.push(Candidate(dist_sq, idx));
+            } else if dist_sq < heap.peek().unwrap().0 {
+                heap.pop();
+                heap.push(Candidate(dist_sq, idx));
+            }
+        }
+        if let Some(far) = far {
+            if heap.len() < k || axis_dist * axis_dist < heap.peek().unwrap().0 {
+                self.k_nearest_helper(far, target, k, heap);
+            }
+        }
+    }
+
+    pub fn nearest(&self, target: &Point) -> Option<(&Point, f32)> {
+        self.k_nearest(target, 1).pop()
+    }
+
+    // All points within the radius of the center
+    pub fn within_radius(&self, center: &Point, radius: f32) -> Vec<&Point> {
+        let mut result = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            if !node.deleted && (center - &node.point).norm_sq() <= radius * radius {
+                result.push(&node.point);
+            }
+            let axis_dist = center.coords[node.dim] - node.point.coords[node.dim];
+            if axis_dist <= radius {
+                stack.extend(node.left.into_iter());
+            }
+            if axis_dist >= -radius {
+                stack.extend(node.right.into_iter());
+            }
+        }
+        result
+    }
+
+    // All points inside the axis-aligned box between the corners min and max,
+    // bounds included
+    pub fn in_box(&self, min: &Point, max: &Point) -> Vec<&Point> {
+        let mut result = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let inside = node.point.coords.iter().zip(min.coords.iter().zip(max.coords.iter()))
+                .all(|(x, (lo, hi))| lo <= x && x <= hi);
+            if !node.deleted && inside {
+                result.push(&node.point);
+            }
+            let split = node.point.coords[node.dim];
+            if min.coords[node.dim] <= split {
+                stack.extend(node.left.into_iter());
+            }
+            if max.coords[node.dim] >= split {
+                stack.extend(node.right.into_iter());
+            }
+        }
+        result
+    }
+}
+
+// The k nearest points by checking all of them, for comparison
+fn brute_force_k_nearest<'a>(points: &'a [Point], target: &Point, k: usize)
+                             -> Vec<(&'a Point, f32)> {
+    let mut by_distance: Vec<(&Point, f32)> = points.iter()
+        .map(|p| (p, (target - p).norm_sq()))
+        .collect();
+    by_distance.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    by_distance.truncate(k);
+    by_distance
+}
+
+#[cfg(not(test))]
+fn elapsed_ms(start: time::Timespec) -> f32 {
+    let end = get_time();
+    ((end.sec - start.sec)*1000) as f32 + ((end.nsec - start.nsec) as f32)/1000000f32
+}
+
 #[cfg(not(test))]
 pub fn main() {
     // wordpress
@@ -215,6 +538,47 @@ pub fn main() {
              n_searches,
              ((end_search_time.sec - start_search_time.sec)*1000) as f32 +
              ((end_search_time.nsec - start_search_time.nsec) as f32)/1000000f32);
+
+    // benchmark the arena tree against brute force
+    let n_points = 20000;
+    let k = 10;
+    let points: Vec<Point> = (0..n_points).map(|_| make_random_point()).collect();
+    let start = get_time();
+    let mut arena_tree = KDTree::new(points.clone());
+    println!("\n20,000 3d points in an arena (Construction time: {}ms)", elapsed_ms(start));
+
+    let start = get_time();
+    let tree_results: Vec<Vec<(&Point, f32)>> = random_targets.iter()
+        .map(|target| arena_tree.k_nearest(target, k))
+        .collect();
+    println!("{} searches for the {} nearest: {} ms", n_searches, k, elapsed_ms(start));
+
+    let start = get_time();
+    let brute_results: Vec<Vec<(&Point, f32)>> = random_targets.iter()
+        .map(|target| brute_force_k_nearest(&points, target, k))
+        .collect();
+    println!("The same by brute force: {} ms", elapsed_ms(start));
+    let agree = tree_results.iter().zip(brute_results.iter()).all(|(a, b)| {
+        a.iter().zip(b.iter()).all(|(&(_, x), &(_, y))| x == y)
+    });
+    println!("Results agree: {}", agree);
+    drop(tree_results);
+
+    let start = get_time();
+    for target in &random_targets {
+        arena_tree.insert(target.clone());
+    }
+    for point in &points[..n_points/2] {
+        arena_tree.remove(point);
+    }
+    println!("Inserted {} and removed {} points in {} ms, leaving {} points {} levels deep",
+             n_searches, n_points/2, elapsed_ms(start), arena_tree.len(), arena_tree.depth());
+    let target = make_random_point();
+    println!("Nearest to {:?}: {:?}", target, arena_tree.nearest(&target));
+    println!("Within 50 of {:?}: {} points, in the box of side 100 around it: {} points",
+             target, arena_tree.within_radius(&target, 50f32).len(),
+             arena_tree.in_box(&(&target - &Point { coords: vec![50f32; 3] }),
+                               &(&target - &Point { coords: vec![-50f32; 3] })).len());
 }
 
 fn quickselect_by<T>(arr: &mut [T], position: usize, cmp: &Fn(&T, &T) -> Ordering) -> T
@@ -251,7 +615,8 @@ fn partition_by<T>(arr: &mut [T], pivot_index: usize, cmp: &Fn(&T, &T) -> Orderi
 
 #[cfg(test)]
 mod test {
-    use super::{Point, KDTreeNode};
+    use super::{Point, KDTreeNode, KDTree, brute_force_k_nearest};
+    use rand::{Rng, SeedableRng, XorShiftRng};
 
     #[test]
     fn wp() {
@@ -269,4 +634,94 @@ mod test {
         let (point, _) = wp_tree.find_nearest_neighbor(&wp_target);
         assert_eq!(*point, Point { coords: vec![8f32, 1f32] });
     }
+
+    fn random_points(n: usize, seed: u32) -> Vec<Point> {
+        let mut rng: XorShiftRng = SeedableRng::from_seed([seed, 2, 3, 4]);
+        (0..n).map(|_| Point { coords: (0..3).map(|_| rng.gen::<f32>() * 100f32).collect() })
+              .collect()
+    }
+
+    #[test]
+    fn arena_wp() {
+        let wp_points: Vec<Point> = [[2f32, 3f32], [5f32, 4f32], [9f32, 6f32],
+                                     [4f32, 7f32], [8f32, 1f32], [7f32, 2f32]]
+            .iter().map(|x| Point { coords: x.to_vec() }).collect();
+        let tree = KDTree::new(wp_points);
+        let target = Point { coords: vec![9f32, 2f32] };
+        assert_eq!(tree.nearest(&target), Some((&Point { coords: vec![8f32, 1f32] }, 2f32)));
+        let nearest: Vec<f32> = tree.k_nearest(&target, 3).iter().map(|&(_, d)| d).collect();
+        assert_eq!(nearest, vec![2f32, 4f32, 16f32]);
+        assert_eq!(tree.k_nearest(&target, 10).len(), 6);
+        assert!(tree.k_nearest(&target, 0).is_empty());
+        assert!(KDTree::new(Vec::new()).nearest(&target).is_none());
+    }
+
+    #[test]
+    fn k_nearest_matches_brute_force() {
+        let points = random_points(2000, 1);
+        let tree = KDTree::new(points.clone());
+        for target in &random_points(50, 2) {
+            let found: Vec<f32> = tree.k_nearest(target, 7).iter().map(|&(_, d)| d).collect();
+            let expected: Vec<f32> = brute_force_k_nearest(&points, target, 7)
+                .iter().map(|&(_, d)| d).collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    fn sorted(points: Vec<&Point>) -> Vec<Point> {
+        let mut points: Vec<Point> = points.into_iter().cloned().collect();
+        points.sort_by(|a, b| a.coords.partial_cmp(&b.coords).unwrap());
+        points
+    }
+
+    #[test]
+    fn range_queries() {
+        let points = random_points(1000, 3);
+        let tree = KDTree::new(points.clone());
+        let center = Point { coords: vec![50f32, 40f32, 60f32] };
+        let expected = points.iter().filter(|p| (&center - p).norm_sq() <= 400f32).collect();
+        assert_eq!(sorted(tree.within_radius(&center, 20f32)), sorted(expected));
+
+        let (min, max) = (Point { coords: vec![10f32, 20f32, 0f32] },
+                          Point { coords: vec![40f32, 90f32, 30f32] });
+        let expected: Vec<&Point> = points.iter().filter(|p| {
+            (0..3).all(|i| min.coords[i] <= p.coords[i] && p.coords[i] <= max.coords[i])
+        }).collect();
+        assert!(!expected.is_empty());
+        assert_eq!(sorted(tree.in_box(&min, &max)), sorted(expected));
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        // Inserting sorted points would make a list out of an unbalanced tree
+        let mut tree = KDTree::new(Vec::new());
+        let points: Vec<Point> = (0..1000)
+            .map(|i| Point { coords: vec![i as f32, (i % 7) as f32] })
+            .collect();
+        for point in &points {
+            tree.insert(point.clone());
+        }
+        assert_eq!(tree.len(), 1000);
+        assert!(tree.depth() <= 25);
+
+        for point in points.iter().filter(|p| p.coords[0] as usize % 3 != 0) {
+            assert!(tree.remove(point));
+        }
+        assert!(!tree.remove(&points[1]));
+        assert_eq!(tree.len(), 334);
+        // The deleted points were dropped by rebuilding
+        assert!(tree.nodes.len() - tree.free.len() < 1000);
+        let target = Point { coords: vec![500.2f32, 3f32] };
+        assert_eq!(tree.nearest(&target).unwrap().0.coords, vec![501f32, 4f32]);
+        let expected = points.iter()
+            .filter(|p| p.coords[0] as usize % 3 == 0 && (&target - p).norm_sq() <= 100f32)
+            .collect();
+        assert_eq!(sorted(tree.within_radius(&target, 10f32)), sorted(expected));
+
+        for point in points.iter().filter(|p| p.coords[0] as usize % 3 == 0) {
+            assert!(tree.remove(point));
+        }
+        assert_eq!(tree.len(), 0);
+        assert!(tree.nearest(&target).is_none());
+    }
 }
@@ -1,7 +1,10 @@
 // http://rosettacode.org/wiki/Knapsack_problem/0-1
 
 
-use std::cmp::max;
+use std::cmp::{max, min};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
 use std::iter::repeat;
 
 // This struct is used to store our items that we want in our knap-sack.
@@ -47,7 +50,8 @@ const ITEMS: &'static [Want<'static>] = &[
 // This is a bottom-up dynamic programming solution to the 0-1 knap-sack problem.
 //      maximize value
 //      subject to weights <= max_weight
-fn knap_01_dp<'a>(xs: &[Want<'a>], max_weight: usize) -> Vec<Want<'a>> {
+// It returns the indices of the chosen items, last to first.
+fn knap_01_dp<'a>(xs: &[Want<'a>], max_weight: usize) -> Vec<usize> {
 
     // Save this value, so we don't have to make repeated calls.
     let xs_len = xs.len();
@@ -86,8 +90,8 @@ fn knap_01_dp<'a>(xs: &[Want<'a>], max_weight: usize) -> Vec<Want<'a>> {
 
     // loop over the items
     for i in (0..xs_len) {
-        // loop over the weights
-        for w in (1..max_weight + 1) {
+        // loop over the weights, from 0 for the sake of weightless items
+        for w in (0..max_weight + 1) {
             // do we have room in our knapsack?
             if xs[i].weight > w {
                 // if we don't, then we'll say that the value doesn't change
@@ -116,7 +120,7 @@ fn knap_01_dp<'a>(xs: &[Want<'a>], max_weight: usize) -> Vec<Want<'a>> {
         // best_value with and without this item. If best_value added this
         // item then so should we.
         if best_value[i][left_weight] != best_value[i - 1][left_weight] {
-            result.push(xs[i - 1]);
+            result.push(i - 1);
             // we remove the weight of the object from the remaining weight
             // we can add to the bag
             left_weight -= xs[i - 1].weight;
@@ -126,31 +130,401 @@ fn knap_01_dp<'a>(xs: &[Want<'a>], max_weight: usize) -> Vec<Want<'a>> {
     return result;
 }
 
-#[cfg(not(test))]
-fn main () {
-    let xs = knap_01_dp(ITEMS, 400);
+// An item of an inventory, with the number of copies available; `None` for as many as fit.
+#[derive(Copy, Clone)]
+struct Stock<'a> {
+    want: Want<'a>,
+    quantity: Option<usize>
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    // The line number of a row with fewer than three fields
+    MissingField(usize),
+    // The line number of a row whose weight, value or quantity is not a number
+    BadNumber(usize)
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::MissingField(line) => write!(f, "missing field on line {}", line),
+            ParseError::BadNumber(line) => write!(f, "invalid number on line {}", line),
+        }
+    }
+}
+
+// Parses items from CSV rows of name, weight, value and optionally quantity, after a header line.
+// An empty quantity means unlimited. Fields are not quoted, so names cannot contain commas.
+fn parse_items<'a>(text: &'a str) -> Result<Vec<Stock<'a>>, ParseError> {
+    let mut items = Vec::new();
+    for (i, line) in text.lines().enumerate().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+        if fields.len() < 3 {
+            return Err(ParseError::MissingField(i + 1));
+        }
+        let number = |field: &str| field.parse::<usize>().map_err(|_| ParseError::BadNumber(i + 1));
+        let quantity = match fields.get(3) {
+            Some(&"") | None => None,
+            Some(field) => Some(try!(number(field))),
+        };
+        items.push(Stock {
+            want: Want {name: fields[0],
+                        weight: try!(number(fields[1])),
+                        value: try!(number(fields[2]))},
+            quantity: quantity
+        });
+    }
+    Ok(items)
+}
+
+// The items packed by a solver, with how many of each, and what is left of the capacity.
+struct Solution<'a> {
+    chosen: Vec<(Want<'a>, usize)>,
+    weight: usize,
+    value: usize,
+    remaining: usize
+}
+
+impl<'a> Solution<'a> {
+    fn new(chosen: Vec<(Want<'a>, usize)>, max_weight: usize) -> Solution<'a> {
+        let weight = chosen.iter().fold(0, |a, &(ref b, n)| a + b.weight * n);
+        let value = chosen.iter().fold(0, |a, &(ref b, n)| a + b.value * n);
+        Solution {chosen: chosen, weight: weight, value: value, remaining: max_weight - weight}
+    }
+}
+
+impl<'a> fmt::Display for Solution<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(ref item, n) in &self.chosen {
+            try!(writeln!(f, "Item: {}, Count: {}, Weight: {}, Value: {}",
+                          item.name, n, item.weight * n, item.value * n));
+        }
+        write!(f, "Total Weight: {}, Total Value: {}, Remaining Capacity: {}",
+               self.weight, self.value, self.remaining)
+    }
+}
+
+// The 0-1 DP solution, reported as a Solution.
+fn solve_01<'a>(xs: &[Want<'a>], max_weight: usize) -> Solution<'a> {
+    let mut chosen: Vec<(Want<'a>, usize)> = knap_01_dp(xs, max_weight)
+        .into_iter().map(|i| (xs[i], 1)).collect();
+    // knap_01_dp gives the items last to first
+    chosen.reverse();
+    Solution::new(chosen, max_weight)
+}
+
+// The bounded problem, where each item is available a limited number of times. Each item is split
+// into bundles of 1, 2, 4, ... copies plus a remainder; any count up to the quantity is a sum of
+// distinct bundles, so solving the 0-1 problem over the bundles solves the bounded problem.
+fn knap_bounded_dp<'a>(stock: &[Stock<'a>], max_weight: usize) -> Solution<'a> {
+    let mut bundles = Vec::new();
+    // for each bundle, the index of its item and how many copies it holds
+    let mut origins = Vec::new();
+    for (i, s) in stock.iter().enumerate() {
+        // an item can't be packed more often than it fits; an unlimited weightless one would
+        // fit forever, so it is packed once
+        let mut left = match (s.quantity, s.want.weight) {
+            (Some(quantity), 0) => quantity,
+            (Some(quantity), weight) => min(quantity, max_weight / weight),
+            (None, 0) => 1,
+            (None, weight) => max_weight / weight,
+        };
+        let mut size = 1;
+        while left > 0 {
+            let n = min(size, left);
+            bundles.push(Want {name: s.want.name,
+                               weight: s.want.weight * n,
+                               value: s.want.value * n});
+            origins.push((i, n));
+            left -= n;
+            size *= 2;
+        }
+    }
+
+    let mut counts: Vec<usize> = repeat(0).take(stock.len()).collect();
+    for bundle in knap_01_dp(&bundles, max_weight) {
+        let (i, n) = origins[bundle];
+        counts[i] += n;
+    }
+
+    let chosen = stock.iter().zip(counts.into_iter())
+        .filter(|&(_, n)| n > 0)
+        .map(|(s, n)| (s.want, n))
+        .collect();
+    Solution::new(chosen, max_weight)
+}
+
+// The unbounded problem, where any item can be packed any number of times. Weightless items would
+// make the value unbounded, so they are left out.
+fn knap_unbounded_dp<'a>(xs: &[Want<'a>], max_weight: usize) -> Solution<'a> {
+    // best_value[w] is the best value for a capacity of w, and last_item[w] the item packed last
+    // to reach it, if any
+    let mut best_value: Vec<usize> = repeat(0).take(max_weight + 1).collect();
+    let mut last_item: Vec<Option<usize>> = repeat(None).take(max_weight + 1).collect();
+    for w in 1..max_weight + 1 {
+        best_value[w] = best_value[w - 1];
+        for (i, x) in xs.iter().enumerate() {
+            if x.weight > 0 && x.weight <= w && best_value[w - x.weight] + x.value > best_value[w] {
+                best_value[w] = best_value[w - x.weight] + x.value;
+                last_item[w] = Some(i);
+            }
+        }
+    }
+
+    let mut counts: Vec<usize> = repeat(0).take(xs.len()).collect();
+    let mut w = max_weight;
+    while w > 0 {
+        match last_item[w] {
+            Some(i) => {
+                counts[i] += 1;
+                w -= xs[i].weight;
+            }
+            // the best value for w is also the best for w - 1
+            None => w -= 1,
+        }
+    }
+
+    let chosen = xs.iter().zip(counts.into_iter())
+        .filter(|&(_, n)| n > 0)
+        .map(|(x, n)| (*x, n))
+        .collect();
+    Solution::new(chosen, max_weight)
+}
+
+// Indices of the items by decreasing value per unit of weight, weightless items first.
+fn by_density(xs: &[Want]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..xs.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&xs[a], &xs[b]);
+        (b.value as f64 * a.weight as f64).partial_cmp(&(a.value as f64 * b.weight as f64)).unwrap()
+    });
+    order
+}
 
-    // Print the items. We have to reverse the order because we solved the
-    // problem backward.
-    for i in xs.iter().rev() {
-        println!("Item: {}, Weight: {}, Value: {}", i.name, i.weight, i.value);
+// The items packed by the fractional solver, with the fraction taken of each.
+struct FractionalSolution<'a> {
+    chosen: Vec<(Want<'a>, f64)>,
+    value: f64,
+    remaining: f64
+}
+
+// The fractional (continuous) problem, where any part of an item may be taken. Greedily taking the
+// densest items first is optimal; only the last item taken may be cut.
+fn knap_fractional<'a>(xs: &[Want<'a>], max_weight: usize) -> FractionalSolution<'a> {
+    let mut left = max_weight as f64;
+    let mut value = 0.0;
+    let mut chosen = Vec::new();
+    for i in by_density(xs) {
+        if xs[i].value == 0 {
+            continue;
+        }
+        let fraction = if xs[i].weight as f64 <= left { 1.0 } else { left / xs[i].weight as f64 };
+        if fraction <= 0.0 {
+            break;
+        }
+        left -= fraction * xs[i].weight as f64;
+        value += fraction * xs[i].value as f64;
+        chosen.push((xs[i], fraction));
+    }
+    FractionalSolution {chosen: chosen, value: value, remaining: left}
+}
+
+// Depth first search over the items in order of density, taking or leaving each one. A branch is
+// pruned when even the fractional solution of the remaining items cannot beat the best so far.
+fn branch_and_bound(xs: &[Want], order: &[usize], depth: usize, weight_left: usize, value: usize,
+                    taken: &mut Vec<bool>, best: &mut (usize, Vec<bool>)) {
+    if value > best.0 {
+        *best = (value, taken.clone());
+    }
+    if depth == order.len() {
+        return;
     }
 
-    // Print the sum of weights.
-    let weights = xs.iter().fold(0, |a, &b| a + b.weight);
-    println!("Total Weight: {}", weights);
+    // the upper bound: fill the rest of the capacity greedily, cutting the last item
+    let mut bound = value as f64;
+    let mut room = weight_left;
+    for &i in &order[depth..] {
+        if xs[i].weight <= room {
+            room -= xs[i].weight;
+            bound += xs[i].value as f64;
+        } else {
+            bound += xs[i].value as f64 * room as f64 / xs[i].weight as f64;
+            break;
+        }
+    }
+    // values are integers, so so is the best value under the bound
+    if (bound + 1e-9).floor() as usize <= best.0 {
+        return;
+    }
 
-    // Print the sum of the values.
-    let values = xs.iter().fold(0, |a, &b| a + b.value);
-    println!("Total Value: {}", values);
+    let i = order[depth];
+    if xs[i].weight <= weight_left {
+        taken[i] = true;
+        branch_and_bound(xs, order, depth + 1, weight_left - xs[i].weight, value + xs[i].value,
+                         taken, best);
+        taken[i] = false;
+    }
+    branch_and_bound(xs, order, depth + 1, weight_left, value, taken, best);
+}
+
+// The 0-1 problem by branch and bound. It needs no table indexed by weight, so it also works for
+// capacities far too large for knap_01_dp.
+fn knap_branch_and_bound<'a>(xs: &[Want<'a>], max_weight: usize) -> Solution<'a> {
+    let order = by_density(xs);
+    let mut taken: Vec<bool> = repeat(false).take(xs.len()).collect();
+    let mut best = (0, taken.clone());
+    branch_and_bound(xs, &order, 0, max_weight, 0, &mut taken, &mut best);
+    let chosen = xs.iter().zip(best.1.iter())
+        .filter(|&(_, &t)| t)
+        .map(|(x, _)| (*x, 1))
+        .collect();
+    Solution::new(chosen, max_weight)
+}
+
+// Runs every solver; the 0-1, unbounded and fractional ones ignore the quantities.
+#[cfg(not(test))]
+fn report(xs: &[Want], stock: &[Stock], max_weight: usize) {
+    println!("0-1:\n{}", solve_01(xs, max_weight));
+    println!("\nBranch and bound:\n{}", knap_branch_and_bound(xs, max_weight));
+    println!("\nBounded:\n{}", knap_bounded_dp(stock, max_weight));
+    println!("\nUnbounded:\n{}", knap_unbounded_dp(xs, max_weight));
+
+    let fractional = knap_fractional(xs, max_weight);
+    println!("\nFractional:");
+    for &(ref item, fraction) in &fractional.chosen {
+        println!("Item: {}, Fraction: {:.3}", item.name, fraction);
+    }
+    println!("Total Value: {:.3}, Remaining Capacity: {:.3}",
+             fractional.value, fractional.remaining);
+}
+
+#[cfg(not(test))]
+fn main () {
+    // knapsack_0-1 [items.csv [capacity]]: solves the task's problems, or those of the items in
+    // a CSV file as read by parse_items, for a capacity of 400 unless another is given
+    let args: Vec<String> = ::std::env::args().skip(1).collect();
+    let path = args.get(0).map_or("src/resources/knapsack_bounded.csv", |path| &path[..]);
+    let max_weight = match args.get(1).map(|arg| arg.parse::<usize>()) {
+        Some(Ok(max_weight)) => max_weight,
+        Some(Err(_)) => return println!("The capacity must be a number"),
+        None => 400,
+    };
+    let mut text = String::new();
+    if let Err(e) = File::open(path).and_then(|mut file| file.read_to_string(&mut text)) {
+        return println!("Can't read {}: {}", path, e);
+    }
+    let stock = match parse_items(&text) {
+        Ok(stock) => stock,
+        Err(e) => return println!("Can't read {}: {}", path, e),
+    };
 
+    if args.is_empty() {
+        // The task's items, with the quantities of the bounded variant
+        report(ITEMS, &stock, max_weight);
+    } else {
+        let xs: Vec<Want> = stock.iter().map(|s| s.want).collect();
+        report(&xs, &stock, max_weight);
+    }
 }
 
 #[test]
 fn test_dp_results() {
     let dp_results = knap_01_dp(ITEMS, 400);
-    let dp_weights= dp_results.iter().fold(0, |a, &b| a + b.weight);
-    let dp_values = dp_results.iter().fold(0, |a, &b| a + b.value);
+    let dp_weights= dp_results.iter().fold(0, |a, &i| a + ITEMS[i].weight);
+    let dp_values = dp_results.iter().fold(0, |a, &i| a + ITEMS[i].value);
     assert_eq!(dp_weights, 396);
     assert_eq!(dp_values, 1030);
 }
+
+#[test]
+fn test_solution_report() {
+    let solution = solve_01(ITEMS, 400);
+    assert_eq!(solution.chosen.len(), 12);
+    assert_eq!(solution.chosen[0].0.name, "map");
+    assert_eq!((solution.weight, solution.value, solution.remaining), (396, 1030, 4));
+}
+
+#[test]
+fn test_parse_items() {
+    let items = parse_items("name,weight,value,quantity\nrope, 12, 30, 2\ntent,40,90,\n").unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!((items[0].want.name, items[0].want.weight, items[0].quantity),
+               ("rope", 12, Some(2)));
+    assert_eq!((items[1].want.value, items[1].quantity), (90, None));
+    assert_eq!(parse_items("header\nrope,12").err(), Some(ParseError::MissingField(2)));
+    assert_eq!(parse_items("header\n\nrope,12,x").err(), Some(ParseError::BadNumber(3)));
+}
+
+#[test]
+fn test_bounded() {
+    let mut text = String::new();
+    File::open("src/resources/knapsack_bounded.csv")
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .unwrap();
+    let stock = parse_items(&text).unwrap();
+    let solution = knap_bounded_dp(&stock, 400);
+    assert_eq!((solution.weight, solution.value, solution.remaining), (396, 1010, 4));
+    for &(ref item, n) in &solution.chosen {
+        let s = stock.iter().find(|s| s.want.name == item.name).unwrap();
+        assert!(n <= s.quantity.unwrap());
+    }
+
+    // Two copies are split into two bundles of the same weight
+    let stock = parse_items("name,weight,value,quantity\na,3,5,2\nb,4,6,1").unwrap();
+    let solution = knap_bounded_dp(&stock, 10);
+    assert_eq!(solution.chosen.len(), 2);
+    assert_eq!((solution.chosen[0].0.name, solution.chosen[0].1), ("a", 2));
+    assert_eq!((solution.chosen[1].0.name, solution.chosen[1].1), ("b", 1));
+    assert_eq!((solution.weight, solution.value), (10, 16));
+
+    // Weightless items come in their full quantity
+    let stock = parse_items("name,weight,value,quantity\nfree,0,3,5\nb,4,6,1\nc,0,2,").unwrap();
+    let solution = knap_bounded_dp(&stock, 4);
+    let counts: Vec<usize> = solution.chosen.iter().map(|&(_, n)| n).collect();
+    assert_eq!(counts, vec![5, 1, 1]);
+    assert_eq!((solution.weight, solution.value), (4, 23));
+}
+
+#[test]
+fn test_unbounded() {
+    let xs = [Want {name: "a", weight: 1, value: 1},
+              Want {name: "b", weight: 3, value: 4},
+              Want {name: "c", weight: 4, value: 5},
+              Want {name: "d", weight: 5, value: 7}];
+    assert_eq!(knap_unbounded_dp(&xs, 7).value, 9);
+    let solution = knap_unbounded_dp(&xs, 10);
+    assert_eq!(solution.value, 14);
+    assert_eq!(solution.chosen.len(), 1);
+    assert_eq!((solution.chosen[0].0.name, solution.chosen[0].1), ("d", 2));
+}
+
+#[test]
+fn test_fractional() {
+    let xs = [Want {name: "a", weight: 10, value: 60},
+              Want {name: "b", weight: 20, value: 100},
+              Want {name: "c", weight: 30, value: 120}];
+    let solution = knap_fractional(&xs, 50);
+    assert!((solution.value - 240.0).abs() < 1e-9);
+    assert!(solution.remaining.abs() < 1e-9);
+    let fractions: Vec<f64> = solution.chosen.iter().map(|&(_, f)| f).collect();
+    assert_eq!(fractions[..2].to_vec(), vec![1.0, 1.0]);
+    assert!((fractions[2] - 2.0 / 3.0).abs() < 1e-9);
+    // the fractional value bounds the 0-1 value
+    assert!(knap_fractional(ITEMS, 400).value >= 1030.0);
+}
+
+#[test]
+fn test_branch_and_bound() {
+    let solution = knap_branch_and_bound(ITEMS, 400);
+    assert_eq!((solution.weight, solution.value), (396, 1030));
+    // weights too large for a table
+    let heavy: Vec<Want> = ITEMS.iter()
+        .map(|x| Want {name: x.name, weight: x.weight * 1000000, value: x.value})
+        .collect();
+    let solution = knap_branch_and_bound(&heavy, 400000000);
+    assert_eq!((solution.value, solution.remaining), (1030, 4000000));
+}
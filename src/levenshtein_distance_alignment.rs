@@ -1,74 +1,278 @@
 // http://rosettacode.org/wiki/Levenshtein_distance/Alignment
-use std::usize;
-use std::collections::LinkedList;
-use std::iter::repeat;
-
-// Returns the value of a 2D vector given a pair of indexes.
-// Returns the default value if indices are out of bounds.
-fn get_val(mat: &Vec<Vec<usize>>, r: usize, c: usize, default: usize) -> usize {
-    match mat.get(r) {
-        Some(col) => {
-            match col.get(c) { Some(v) => *v, None => default, }
-        }
-        None => default,
+//
+// Besides the alignment of the task, this computes the edit script turning one string into the
+// other with configurable operation costs, the Damerau-Levenshtein distance, and a banded
+// distance that gives up past a threshold. The latter drives a "did you mean" lookup over
+// unixdict.txt.
+use std::cmp;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Edit {
+    Match(char),
+    // Replaces the first character by the second
+    Substitute(char, char),
+    // Inserts a character of the second string
+    Insert(char),
+    // Deletes a character of the first string
+    Delete(char),
+    // Swaps two adjacent characters, the pair as it appears in the first string
+    Transpose(char, char),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Costs {
+    pub substitute: usize,
+    pub insert: usize,
+    pub delete: usize,
+    // Cost of swapping two adjacent characters, `None` to disallow it
+    pub transpose: Option<usize>,
+}
+
+impl Costs {
+    // The costs of the plain Levenshtein distance
+    pub fn unit() -> Costs {
+        Costs { substitute: 1, insert: 1, delete: 1, transpose: None }
     }
 }
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Alignment {
+    pub distance: usize,
+    // Both strings with '-' marking the gaps
+    pub top: String,
+    pub bottom: String,
+    pub script: Vec<Edit>,
+}
+
 // Implementation of the Needleman–Wunsch algorithm, with modification
 // to the scoring method to only allow positive ints.
 //
 // http://en.wikipedia.org/wiki/Needleman%E2%80%93Wunsch_algorithm
-fn levenshtein_distance(s1: &str, s2: &str) -> (usize, String, String) {
-    let l1 = s1.len() + 1;
-    let l2 = s2.len() + 1;
-
-    let mut mat: Vec<Vec<usize>> = repeat(
-		repeat(0).take(l2).collect()
-		).take(l1).collect();
-    for row in (0..l1) { mat[row][0] = row; }
-    for col in (0..l2) { mat[0][col] = col; }
-    for row in (1..l1) {
-        for col in (1..l2) {
-            mat[row][col] =
-                if s1.chars().nth(row - 1).unwrap() == s2.chars().nth(col - 1).unwrap() {
-                    mat[row - 1][col - 1]
-                } else {
-                    let vals =
-                        [mat[row - 1][col] + 1, mat[row][col - 1] + 1,
-                         mat[row - 1][col - 1] + 1];
-                    *vals.iter().min().unwrap()
+//
+// With a transposition cost this becomes the optimal string alignment distance, where no
+// substring is edited more than once.
+pub fn align(s1: &str, s2: &str, costs: &Costs) -> Alignment {
+    let a: Vec<char> = s1.chars().collect();
+    let b: Vec<char> = s2.chars().collect();
+    let (l1, l2) = (a.len() + 1, b.len() + 1);
+
+    let mut mat = vec![vec![0; l2]; l1];
+    for row in 1..l1 { mat[row][0] = row * costs.delete; }
+    for col in 1..l2 { mat[0][col] = col * costs.insert; }
+    for row in 1..l1 {
+        for col in 1..l2 {
+            let sub = if a[row - 1] == b[col - 1] { 0 } else { costs.substitute };
+            let mut best = cmp::min(mat[row - 1][col - 1] + sub,
+                                    cmp::min(mat[row][col - 1] + costs.insert,
+                                             mat[row - 1][col] + costs.delete));
+            if let Some(transpose) = costs.transpose {
+                if is_transposition(&a, &b, row, col) {
+                    best = cmp::min(best, mat[row - 2][col - 2] + transpose);
                 }
+            }
+            mat[row][col] = best;
         }
     }
-    let mut res1: LinkedList<char> = LinkedList::new();
-    let mut res2: LinkedList<char> = LinkedList::new();
-    let mut cur_row = l1 - 1;
-    let mut cur_col = l2 - 1;
-    while cur_row > 0 || cur_col > 0 {
-        let ins = get_val(&mat, cur_row, cur_col - 1, usize::MAX);
-        let del = get_val(&mat, cur_row - 1, cur_col, usize::MAX);
-        let sub = get_val(&mat, cur_row - 1, cur_col - 1, usize::MAX);
-        let min_val = [sub, ins, del];
-        let min_val = min_val.into_iter().min().unwrap();
-        if *min_val == sub {
-            cur_row -= 1;
-            cur_col -= 1;
-            res1.push_front(s1.chars().nth(cur_row).unwrap());
-            res2.push_front(s2.chars().nth(cur_col).unwrap());
-        } else if *min_val == ins {
-            cur_col -= 1;
-            res1.push_front('-');
-            res2.push_front(s1.chars().nth(cur_col).unwrap());
-        } else if *min_val == del {
-            cur_row -= 1;
-            res1.push_front(s1.chars().nth(cur_row).unwrap());
-            res2.push_front('-');
+
+    // Walk back from the bottom right corner, preferring substitutions, then insertions
+    let mut script = Vec::new();
+    let (mut row, mut col) = (l1 - 1, l2 - 1);
+    while row > 0 || col > 0 {
+        let here = mat[row][col];
+        if row > 0 && col > 0 {
+            let (x, y) = (a[row - 1], b[col - 1]);
+            let sub = if x == y { 0 } else { costs.substitute };
+            if mat[row - 1][col - 1] + sub == here {
+                script.push(if x == y { Edit::Match(x) } else { Edit::Substitute(x, y) });
+                row -= 1;
+                col -= 1;
+                continue;
+            }
+        }
+        if col > 0 && mat[row][col - 1] + costs.insert == here {
+            script.push(Edit::Insert(b[col - 1]));
+            col -= 1;
+        } else if row > 0 && mat[row - 1][col] + costs.delete == here {
+            script.push(Edit::Delete(a[row - 1]));
+            row -= 1;
+        } else {
+            // Only a transposition is left to explain this cell
+            script.push(Edit::Transpose(a[row - 2], a[row - 1]));
+            row -= 2;
+            col -= 2;
         }
     }
-    let aligned1: String = res1.into_iter().collect();
-    let aligned2: String = res2.into_iter().collect();
-    let lev_dist = mat[l1 - 1][l2 - 1];
+    script.reverse();
+
+    let mut top = String::new();
+    let mut bottom = String::new();
+    for edit in &script {
+        match *edit {
+            Edit::Match(c) => { top.push(c); bottom.push(c); }
+            Edit::Substitute(x, y) => { top.push(x); bottom.push(y); }
+            Edit::Insert(y) => { top.push('-'); bottom.push(y); }
+            Edit::Delete(x) => { top.push(x); bottom.push('-'); }
+            Edit::Transpose(x, y) => {
+                top.push(x); top.push(y);
+                bottom.push(y); bottom.push(x);
+            }
+        }
+    }
+
+    Alignment { distance: mat[l1 - 1][l2 - 1], top: top, bottom: bottom, script: script }
+}
+
+fn is_transposition(a: &[char], b: &[char], row: usize, col: usize) -> bool {
+    row > 1 && col > 1 && a[row - 1] == b[col - 2] && a[row - 2] == b[col - 1] &&
+        a[row - 1] != a[row - 2]
+}
+
+// Returns the distance and both aligned strings, as asked by the task
+fn levenshtein_distance(s1: &str, s2: &str) -> (usize, String, String) {
+    let alignment = align(s1, s2, &Costs::unit());
+    (alignment.distance, alignment.top, alignment.bottom)
+}
+
+// The unrestricted Damerau-Levenshtein distance, where characters may be edited again after a
+// transposition: "ca" -> "abc" takes two edits, against three for the optimal string alignment.
+//
+// http://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance
+pub fn damerau_levenshtein(s1: &str, s2: &str) -> usize {
+    let a: Vec<char> = s1.chars().collect();
+    let b: Vec<char> = s2.chars().collect();
+    let max = a.len() + b.len();
+    // Row of the last occurrence of each character in `a`
+    let mut last_row: HashMap<char, usize> = HashMap::new();
+
+    // The matrix has an extra row and column holding `max`, so that transpositions reaching
+    // before the start of the strings are never chosen
+    let mut d = vec![vec![0; b.len() + 2]; a.len() + 2];
+    d[0][0] = max;
+    for i in 0..a.len() + 1 {
+        d[i + 1][0] = max;
+        d[i + 1][1] = i;
+    }
+    for j in 0..b.len() + 1 {
+        d[0][j + 1] = max;
+        d[1][j + 1] = j;
+    }
 
-    return (lev_dist, aligned1, aligned2);
+    for i in 1..a.len() + 1 {
+        // Column of the last match on this row
+        let mut last_col = 0;
+        for j in 1..b.len() + 1 {
+            let k = *last_row.get(&b[j - 1]).unwrap_or(&0);
+            let l = last_col;
+            let cost = if a[i - 1] == b[j - 1] {
+                last_col = j;
+                0
+            } else {
+                1
+            };
+            d[i + 1][j + 1] = cmp::min(cmp::min(d[i][j] + cost, d[i + 1][j] + 1),
+                                       cmp::min(d[i][j + 1] + 1,
+                                                d[k][l] + (i - k - 1) + 1 + (j - l - 1)));
+        }
+        last_row.insert(a[i - 1], i);
+    }
+    d[a.len() + 1][b.len() + 1]
+}
+
+// Levenshtein distance if it is at most `max`, `None` otherwise. Only a band of 2 * max + 1
+// diagonals is computed, and the computation stops as soon as a whole row exceeds `max`.
+pub fn bounded_levenshtein(s1: &str, s2: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = s1.chars().collect();
+    let b: Vec<char> = s2.chars().collect();
+    let diff = if a.len() > b.len() { a.len() - b.len() } else { b.len() - a.len() };
+    if diff > max {
+        return None;
+    }
+
+    // Cells outside of the band count as "too far"
+    let far = max + 1;
+    let mut prev: Vec<usize> = (0..b.len() + 1).map(|j| cmp::min(j, far)).collect();
+    let mut cur = vec![far; b.len() + 1];
+    for i in 1..a.len() + 1 {
+        let lo = if i > max { i - max } else { 1 };
+        let hi = cmp::min(b.len(), i + max);
+        for c in cur.iter_mut() {
+            *c = far;
+        }
+        cur[0] = if i <= max { i } else { far };
+        let mut row_min = cur[0];
+        for j in lo..hi + 1 {
+            let sub = prev[j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let value = cmp::min(sub, cmp::min(prev[j] + 1, cur[j - 1] + 1));
+            cur[j] = cmp::min(value, far);
+            row_min = cmp::min(row_min, cur[j]);
+        }
+        if row_min > max {
+            return None;
+        }
+        ::std::mem::swap(&mut prev, &mut cur);
+    }
+    if prev[b.len()] <= max { Some(prev[b.len()]) } else { None }
+}
+
+// Word list indexed by length, so that only words of a close enough length get compared
+pub struct Dictionary {
+    by_length: Vec<Vec<String>>,
+}
+
+impl Dictionary {
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Dictionary> {
+        let mut by_length: Vec<Vec<String>> = Vec::new();
+        for line in reader.lines() {
+            let word = try!(line).trim().to_string();
+            let length = word.chars().count();
+            if length == 0 {
+                continue;
+            }
+            while by_length.len() <= length {
+                by_length.push(Vec::new());
+            }
+            by_length[length].push(word);
+        }
+        Ok(Dictionary { by_length: by_length })
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.by_length.get(word.chars().count())
+            .map_or(false, |words| words.iter().any(|w| w == word))
+    }
+
+    // Up to `limit` words at most `max_distance` edits away, closest first, ties in
+    // alphabetical order. A word of the dictionary is its own only suggestion.
+    pub fn suggest(&self, word: &str, max_distance: usize, limit: usize) -> Vec<(usize, &str)> {
+        if self.by_length.is_empty() {
+            return Vec::new();
+        }
+        let length = word.chars().count();
+        let lo = if length > max_distance { length - max_distance } else { 0 };
+        let hi = cmp::min(length + max_distance, self.by_length.len() - 1);
+        let mut found = Vec::new();
+        for l in lo..hi + 1 {
+            for candidate in &self.by_length[l] {
+                if let Some(d) = bounded_levenshtein(word, candidate, max_distance) {
+                    found.push((d, &candidate[..]));
+                }
+            }
+        }
+        if found.iter().any(|&(d, _)| d == 0) {
+            found.retain(|&(d, _)| d == 0);
+        }
+        found.sort();
+        found.truncate(limit);
+        found
+    }
+}
+
+fn load_unixdict() -> Dictionary {
+    let file = File::open("src/resources/unixdict.txt").unwrap();
+    Dictionary::from_reader(BufReader::new(file)).unwrap()
 }
 
 #[cfg(not(test))]
@@ -80,16 +284,109 @@ fn main() {
     println!("{}" , aligned1);
     println!("{}" , aligned2);
 
+    let alignment = align("kitten", "sitting", &Costs::unit());
+    println!("\nEdit script from kitten to sitting: {:?}", alignment.script);
+    println!("Damerau-Levenshtein distance of ca and abc: {}", damerau_levenshtein("ca", "abc"));
+
+    let dict = load_unixdict();
+    for word in &["rosseta", "algoritm", "recieve", "zzzzzz"] {
+        let suggestions: Vec<&str> = dict.suggest(word, 2, 5).iter().map(|s| s.1).collect();
+        if suggestions.is_empty() {
+            println!("{}: no suggestions", word);
+        } else {
+            println!("{}: did you mean {}?", word, suggestions.connect(", "));
+        }
+    }
 }
 
-#[test]
-fn test_lev_distance() {
-    let test_results =
-        vec![( "sunday" , "saturday" , (3, "s--unday", "sunurday"))  ,
-            ( "sitting" , "kitten" , (3, "sitting", "kitten-")) ,
-            ("test" , "test" , (0, "test", "test") )];
-    for (word1, word2, dist) in test_results {
-        let (d, s1, s2) = levenshtein_distance ( word1 , word2 );
-        assert_eq!( (d, &s1[..], &s2[..]) , dist);
+#[cfg(test)]
+mod test {
+    use super::{align, bounded_levenshtein, damerau_levenshtein, levenshtein_distance,
+                load_unixdict};
+    use super::{Costs, Dictionary, Edit};
+
+    #[test]
+    fn test_lev_distance() {
+        let test_results =
+            vec![( "sunday" , "saturday" , (3, "s--unday", "saturday"))  ,
+                ( "sitting" , "kitten" , (3, "sitting", "kitten-")) ,
+                ("test" , "test" , (0, "test", "test") )];
+        for (word1, word2, dist) in test_results {
+            let (d, s1, s2) = levenshtein_distance ( word1 , word2 );
+            assert_eq!( (d, &s1[..], &s2[..]) , dist);
+        }
+    }
+
+    #[test]
+    fn test_edit_script() {
+        let alignment = align("kitten", "sitting", &Costs::unit());
+        assert_eq!(alignment.script,
+                   vec![Edit::Substitute('k', 's'), Edit::Match('i'), Edit::Match('t'),
+                        Edit::Match('t'), Edit::Substitute('e', 'i'), Edit::Match('n'),
+                        Edit::Insert('g')]);
+        assert_eq!(align("", "ab", &Costs::unit()).script,
+                   vec![Edit::Insert('a'), Edit::Insert('b')]);
+        assert_eq!(align("ab", "", &Costs::unit()).script,
+                   vec![Edit::Delete('a'), Edit::Delete('b')]);
+    }
+
+    #[test]
+    fn test_weighted_costs() {
+        // Substituting costs more than deleting and inserting
+        let costs = Costs { substitute: 3, insert: 1, delete: 1, transpose: None };
+        let alignment = align("ab", "ac", &costs);
+        assert_eq!(alignment.distance, 2);
+        assert_eq!((&alignment.top[..], &alignment.bottom[..]), ("ab-", "a-c"));
+
+        let costs = Costs { substitute: 1, insert: 5, delete: 5, transpose: None };
+        assert_eq!(align("abc", "xyz", &costs).distance, 3);
+        assert_eq!(align("abc", "", &costs).distance, 15);
+    }
+
+    #[test]
+    fn test_transpositions() {
+        let costs = Costs { transpose: Some(1), .. Costs::unit() };
+        let alignment = align("form", "from", &costs);
+        assert_eq!(alignment.distance, 1);
+        assert_eq!(alignment.script,
+                   vec![Edit::Match('f'), Edit::Transpose('o', 'r'), Edit::Match('m')]);
+        assert_eq!(alignment.bottom, "from");
+        assert_eq!(align("form", "from", &Costs::unit()).distance, 2);
+
+        // Optimal string alignment can't edit a transposed pair again, Damerau-Levenshtein can
+        assert_eq!(align("ca", "abc", &costs).distance, 3);
+        assert_eq!(damerau_levenshtein("ca", "abc"), 2);
+        assert_eq!(damerau_levenshtein("form", "from"), 1);
+        assert_eq!(damerau_levenshtein("", "abc"), 3);
+        assert_eq!(damerau_levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_bounded() {
+        assert_eq!(bounded_levenshtein("kitten", "sitting", 3), Some(3));
+        assert_eq!(bounded_levenshtein("kitten", "sitting", 2), None);
+        assert_eq!(bounded_levenshtein("a", "abcd", 2), None);
+        assert_eq!(bounded_levenshtein("", "ab", 2), Some(2));
+        assert_eq!(bounded_levenshtein("same", "same", 0), Some(0));
+        for &(a, b) in &[("rosettacode", "raisethysword"), ("sunday", "saturday")] {
+            let d = levenshtein_distance(a, b).0;
+            assert_eq!(bounded_levenshtein(a, b, d), Some(d));
+            assert_eq!(bounded_levenshtein(a, b, d - 1), None);
+        }
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        let dict = load_unixdict();
+        assert!(dict.contains("rosetta"));
+        assert_eq!(dict.suggest("rosetta", 2, 5), vec![(0, "rosetta")]);
+        assert_eq!(dict.suggest("rossetta", 1, 5), vec![(1, "rosetta")]);
+        let suggestions = dict.suggest("algoritm", 2, 3);
+        assert_eq!(suggestions[0], (1, "algorithm"));
+        assert!(dict.suggest("zzzzzzzz", 1, 5).is_empty());
+
+        let empty = Dictionary::from_reader("".as_bytes()).unwrap();
+        assert!(!empty.contains("rosetta"));
+        assert!(empty.suggest("rosetta", 2, 5).is_empty());
     }
 }
@@ -1,26 +1,31 @@
 // Implements http://rosettacode.org/wiki/Longest_common_subsequence
+//
+// The subsequence is computed over slices of any comparable items. On top of it sits a line
+// based diff: Myers' O(ND) algorithm finds the shortest edit script, which is grouped into
+// unified diff hunks with context lines. Hunks can be printed, parsed back and applied.
+//
+// Texts are handled as sequences of lines; whether the last line ends with a newline is not
+// tracked, and patched texts always end with one.
+use std::cmp;
+use std::fmt;
 
 /// Returns the longest common subsequence of a and b.
-fn longest_common_subsequence(a: &str, b: &str) -> String {
-    let a_chars: Vec<_> = a.chars().collect();
-    let b_chars: Vec<_> = b.chars().collect();
+pub fn lcs<T: Eq + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
 
-    let mut lengths = vec![vec![0; b_chars.len() + 1]; a_chars.len() + 1];
-
-    for (i, a_char) in a_chars.iter().enumerate() {
-        for (j, b_char) in b_chars.iter().enumerate() {
-            if a_char == b_char {
+    for (i, a_item) in a.iter().enumerate() {
+        for (j, b_item) in b.iter().enumerate() {
+            if a_item == b_item {
                 lengths[i + 1][j + 1] = lengths[i][j] + 1;
             } else {
-                lengths[i + 1][j + 1] = std::cmp::max(
-                        lengths[i + 1][j], lengths[i][j + 1]);
+                lengths[i + 1][j + 1] = cmp::max(lengths[i + 1][j], lengths[i][j + 1]);
             }
         }
     }
 
     let mut lcs = Vec::new();
-    let mut i = a_chars.len();
-    let mut j = b_chars.len();
+    let mut i = a.len();
+    let mut j = b.len();
 
     while i != 0 && j != 0 {
         if lengths[i][j] == lengths[i - 1][j] {
@@ -28,15 +33,329 @@ fn longest_common_subsequence(a: &str, b: &str) -> String {
         } else if lengths[i][j] == lengths[i][j - 1] {
             j -= 1;
         } else {
-            lcs.push(a_chars[i - 1]);
+            lcs.push(a[i - 1].clone());
             i -= 1;
             j -= 1;
         }
     }
 
-    lcs.into_iter().rev().collect()
+    lcs.reverse();
+    lcs
+}
+
+/// Returns the longest common subsequence of the characters of a and b.
+fn longest_common_subsequence(a: &str, b: &str) -> String {
+    let a_chars: Vec<_> = a.chars().collect();
+    let b_chars: Vec<_> = b.chars().collect();
+    lcs(&a_chars, &b_chars).into_iter().collect()
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Change {
+    // Indices of an item common to the old and the new sequence
+    Equal(usize, usize),
+    // Index of an item only in the old sequence
+    Delete(usize),
+    // Index of an item only in the new sequence
+    Insert(usize),
+}
+
+// Shortest edit script between two sequences, following Eugene W. Myers, "An O(ND) Difference
+// Algorithm and Its Variations". Deletions come before insertions when both are possible.
+//
+// The forward pass records, for every edit distance d, the furthest reaching path on each
+// diagonal k = x - y; walking those records backwards from the end recovers the script.
+pub fn diff<T: Eq>(a: &[T], b: &[T]) -> Vec<Change> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    // Diagonal k is stored at index k + offset
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..(max as isize + 1) {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let i = (k + offset) as usize;
+            // Either move down from diagonal k + 1 (an insertion) or right from k - 1 (a deletion)
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..trace.len()).rev() {
+        let v = &trace[d];
+        let d = d as isize;
+        let k = x - y;
+        let i = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[i - 1] < v[i + 1]) { k + 1 } else { k - 1 };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            changes.push(Change::Equal(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                changes.push(Change::Insert(prev_y as usize));
+            } else {
+                changes.push(Change::Delete(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    changes.reverse();
+    changes
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Line {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+// A group of nearby changes. Starts are 1-based line numbers, as printed in the `@@` header;
+// a hunk with no lines on one side has the number of the line preceding it instead.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<Line>,
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "@@ -{},{} +{},{} @@",
+                      self.old_start, self.old_len, self.new_start, self.new_len));
+        for line in &self.lines {
+            try!(match *line {
+                Line::Context(ref s) => writeln!(f, " {}", s),
+                Line::Removed(ref s) => writeln!(f, "-{}", s),
+                Line::Added(ref s) => writeln!(f, "+{}", s),
+            });
+        }
+        Ok(())
+    }
+}
+
+// Diffs two texts line by line, keeping up to `context` unchanged lines around each change.
+// Changes separated by at most twice that many lines end up in the same hunk.
+pub fn unified_diff(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let changes = diff(&a, &b);
+
+    // Positions in both texts before each change
+    let mut positions = Vec::with_capacity(changes.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for change in &changes {
+        positions.push((old_pos, new_pos));
+        match *change {
+            Change::Equal(..) => { old_pos += 1; new_pos += 1; }
+            Change::Delete(_) => old_pos += 1,
+            Change::Insert(_) => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    let edits: Vec<usize> = (0..changes.len())
+        .filter(|&i| match changes[i] { Change::Equal(..) => false, _ => true })
+        .collect();
+
+    let mut hunks = Vec::new();
+    let mut e = 0;
+    while e < edits.len() {
+        // Extend the group while the next edit is close enough
+        let mut last = e;
+        while last + 1 < edits.len() && edits[last + 1] - edits[last] - 1 <= 2 * context {
+            last += 1;
+        }
+        let start = edits[e] - cmp::min(edits[e], context);
+        let end = cmp::min(changes.len(), edits[last] + context + 1);
+
+        let lines = changes[start..end].iter().map(|change| match *change {
+            Change::Equal(i, _) => Line::Context(a[i].to_string()),
+            Change::Delete(i) => Line::Removed(a[i].to_string()),
+            Change::Insert(j) => Line::Added(b[j].to_string()),
+        }).collect();
+        let (old_from, new_from) = positions[start];
+        let (old_to, new_to) = positions[end];
+        let (old_len, new_len) = (old_to - old_from, new_to - new_from);
+        hunks.push(Hunk {
+            old_start: if old_len == 0 { old_from } else { old_from + 1 },
+            old_len: old_len,
+            new_start: if new_len == 0 { new_from } else { new_from + 1 },
+            new_len: new_len,
+            lines: lines,
+        });
+        e = last + 1;
+    }
+    hunks
+}
+
+// Formats hunks as a unified diff with `---` and `+++` file headers. Equal texts give an empty
+// string, which makes this handy to show mismatching fixtures in test failures.
+pub fn format_unified(old_name: &str, new_name: &str, hunks: &[Hunk]) -> String {
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for hunk in hunks {
+        out.push_str(&hunk.to_string());
+    }
+    out
 }
 
+#[derive(PartialEq, Debug)]
+pub enum PatchError {
+    // The patch text is malformed at the given line
+    Parse(usize),
+    // The lines the given hunk (counting from 1) expects are not in the text
+    Mismatch(usize),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchError::Parse(line) => write!(f, "malformed patch at line {}", line),
+            PatchError::Mismatch(hunk) => write!(f, "hunk #{} does not apply", hunk),
+        }
+    }
+}
+
+// Parses "-12,3" or "+12" into a start and a length, which defaults to 1. Lines count from 1,
+// so only an empty range may start at 0.
+fn parse_range(range: &str, sign: char) -> Option<(usize, usize)> {
+    if !range.starts_with(sign) {
+        return None;
+    }
+    let mut parts = range[1..].split(',');
+    let start = parts.next().and_then(|s| s.parse().ok());
+    let len = match parts.next() {
+        Some(s) => s.parse().ok(),
+        None => Some(1),
+    };
+    match (start, len, parts.next()) {
+        (Some(0), Some(len), None) if len > 0 => None,
+        (Some(start), Some(len), None) => Some((start, len)),
+        _ => None,
+    }
+}
+
+// Reads the hunks of a unified diff. File headers and "\ No newline at end of file" markers are
+// skipped.
+pub fn parse_unified(patch: &str) -> Result<Vec<Hunk>, PatchError> {
+    let mut hunks = Vec::new();
+    let mut lines = patch.lines().enumerate();
+    while let Some((n, line)) = lines.next() {
+        if line.starts_with("---") || line.starts_with("+++") || line.starts_with('\\') {
+            continue;
+        }
+        if !line.starts_with("@@ ") {
+            return Err(PatchError::Parse(n + 1));
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let ranges = if fields.len() >= 4 && fields[3] == "@@" {
+            parse_range(fields[1], '-').and_then(|old| parse_range(fields[2], '+')
+                                                           .map(|new| (old, new)))
+        } else {
+            None
+        };
+        let ((old_start, old_len), (new_start, new_len)) = match ranges {
+            Some(ranges) => ranges,
+            None => return Err(PatchError::Parse(n + 1)),
+        };
+
+        let mut hunk = Hunk { old_start: old_start, old_len: old_len,
+                              new_start: new_start, new_len: new_len, lines: Vec::new() };
+        let (mut old_seen, mut new_seen) = (0, 0);
+        while old_seen < old_len || new_seen < new_len {
+            let (n, line) = match lines.next() {
+                Some(line) => line,
+                None => return Err(PatchError::Parse(patch.lines().count())),
+            };
+            // Some tools drop the space of empty context lines
+            let (marker, text) = if line.is_empty() {
+                (b' ', "")
+            } else {
+                (line.as_bytes()[0], &line[1..])
+            };
+            let text = text.to_string();
+            match marker {
+                b' ' => {
+                    old_seen += 1;
+                    new_seen += 1;
+                    hunk.lines.push(Line::Context(text));
+                }
+                b'-' => { old_seen += 1; hunk.lines.push(Line::Removed(text)); }
+                b'+' => { new_seen += 1; hunk.lines.push(Line::Added(text)); }
+                b'\\' => {}
+                _ => return Err(PatchError::Parse(n + 1)),
+            }
+            if old_seen > old_len || new_seen > new_len {
+                return Err(PatchError::Parse(n + 1));
+            }
+        }
+        hunks.push(hunk);
+    }
+    Ok(hunks)
+}
+
+// Applies hunks to a text. Every hunk must match exactly at the line it names.
+pub fn apply(old: &str, hunks: &[Hunk]) -> Result<String, PatchError> {
+    let lines: Vec<&str> = old.lines().collect();
+    let mut out: Vec<&str> = Vec::new();
+    let mut cursor = 0;
+    for (number, hunk) in hunks.iter().enumerate() {
+        let at = match (hunk.old_len, hunk.old_start) {
+            (0, start) => start,
+            (_, 0) => return Err(PatchError::Mismatch(number + 1)),
+            (_, start) => start - 1,
+        };
+        let expected: Vec<&str> = hunk.lines.iter().filter_map(|line| match *line {
+            Line::Context(ref s) | Line::Removed(ref s) => Some(&s[..]),
+            Line::Added(_) => None,
+        }).collect();
+        if at < cursor || at + expected.len() > lines.len() ||
+                lines[at..at + expected.len()] != expected[..] {
+            return Err(PatchError::Mismatch(number + 1));
+        }
+        out.extend(lines[cursor..at].iter().cloned());
+        out.extend(hunk.lines.iter().filter_map(|line| match *line {
+            Line::Context(ref s) | Line::Added(ref s) => Some(&s[..]),
+            Line::Removed(_) => None,
+        }));
+        cursor = at + expected.len();
+    }
+    out.extend(lines[cursor..].iter().cloned());
+
+    let mut patched = out.connect("\n");
+    if !patched.is_empty() {
+        patched.push('\n');
+    }
+    Ok(patched)
+}
 
 // Demonstration code
 #[cfg(not(test))]
@@ -48,6 +367,11 @@ fn main() {
     println!("{}", longest_common_subsequence("abc", ""));
     println!("{}", longest_common_subsequence("abcdefg", "abc"));
     println!("{}", longest_common_subsequence("aaaa", "aaaaaa"));
+    println!("{:?}", lcs(&[1, 2, 3, 4, 1], &[3, 4, 1, 2, 1, 3]));
+
+    let old = "fn main() {\n    println!(\"Hello\");\n}\n";
+    let new = "// Greets\nfn main() {\n    println!(\"Hello, world!\");\n}\n";
+    print!("\n{}", format_unified("a/hello.rs", "b/hello.rs", &unified_diff(old, new, 3)));
 }
 
 #[test]
@@ -67,3 +391,82 @@ fn test_unicode() {
         "самолетов была отмечена в Японском", "отмечена в"),
         "отмечена в");
 }
+
+#[test]
+fn test_generic_lcs() {
+    assert_eq!(lcs(&[1, 2, 3, 4, 1], &[3, 4, 1, 2, 1, 3]), vec![1, 2, 3]);
+    assert_eq!(lcs(&["a", "b", "c"], &["b", "c", "d"]), vec!["b", "c"]);
+    assert_eq!(lcs::<u8>(&[], &[1]), vec![]);
+}
+
+#[test]
+fn test_myers() {
+    // The example of Myers' paper
+    let a: Vec<char> = "ABCABBA".chars().collect();
+    let b: Vec<char> = "CBABAC".chars().collect();
+    let changes = diff(&a, &b);
+    let equal = changes.iter().filter(|c| match **c { Change::Equal(..) => true, _ => false })
+                              .count();
+    assert_eq!(changes.len() - equal, 5);
+    assert_eq!(equal, lcs(&a, &b).len());
+    assert_eq!(diff(&[1, 2], &[1, 2]), vec![Change::Equal(0, 0), Change::Equal(1, 1)]);
+    assert_eq!(diff(&[1], &[2]), vec![Change::Delete(0), Change::Insert(0)]);
+    assert_eq!(diff::<u8>(&[], &[]), vec![]);
+}
+
+#[test]
+fn test_unified_diff() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+    let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    let expected = "--- old\n+++ new\n\
+                    @@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n\
+                    @@ -9,1 +9,2 @@\n i\n+j\n";
+    assert_eq!(format_unified("old", "new", &unified_diff(old, new, 1)), expected);
+
+    // Seven unchanged lines apart, the changes share a hunk once there are four lines of context
+    assert_eq!(unified_diff(old, new, 3).len(), 2);
+    let hunks = unified_diff(old, new, 4);
+    assert_eq!(hunks.len(), 1);
+    assert_eq!((hunks[0].old_start, hunks[0].old_len), (1, 9));
+    assert_eq!((hunks[0].new_start, hunks[0].new_len), (1, 10));
+
+    assert_eq!(format_unified("old", "new", &unified_diff(old, old, 3)), "");
+}
+
+#[test]
+fn test_empty_sides() {
+    let hunks = unified_diff("", "x\ny\n", 3);
+    assert_eq!(hunks, vec![Hunk { old_start: 0, old_len: 0, new_start: 1, new_len: 2,
+                                  lines: vec![Line::Added("x".to_string()),
+                                              Line::Added("y".to_string())] }]);
+    assert_eq!(apply("", &hunks).unwrap(), "x\ny\n");
+    let hunks = unified_diff("x\ny\n", "", 3);
+    assert_eq!(apply("x\ny\n", &hunks).unwrap(), "");
+}
+
+#[test]
+fn test_round_trip() {
+    let old = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+    let new = "zero\none\ntwo\n3\nfour\nfive\nsix\nseven\nnine\nten\neleven\n";
+    for context in 0..4 {
+        let patch = format_unified("a", "b", &unified_diff(old, new, context));
+        let hunks = parse_unified(&patch).unwrap();
+        assert_eq!(hunks, unified_diff(old, new, context));
+        assert_eq!(apply(old, &hunks).unwrap(), new);
+    }
+}
+
+#[test]
+fn test_patch_errors() {
+    let hunks = unified_diff("a\nb\n", "a\nc\n", 1);
+    assert_eq!(apply("a\nx\n", &hunks), Err(PatchError::Mismatch(1)));
+    assert_eq!(parse_unified("@@ -1 +1 @@\n-a\n"), Err(PatchError::Parse(2)));
+    assert_eq!(parse_unified("@@ -1,x +1 @@\n"), Err(PatchError::Parse(1)));
+    assert_eq!(parse_unified("@@ -1 +1 @@\n-a\n+b\n+c\n"), Err(PatchError::Parse(4)));
+    assert_eq!(parse_unified("garbage\n"), Err(PatchError::Parse(1)));
+    assert_eq!(parse_unified("@@ -0,3 +1,3 @@\n a\n b\n c\n"), Err(PatchError::Parse(1)));
+    let hunk = Hunk { old_start: 0, old_len: 1, new_start: 1, new_len: 1,
+                      lines: vec![Line::Context("a".to_string())] };
+    assert_eq!(apply("a\n", &[hunk]), Err(PatchError::Mismatch(1)));
+    assert_eq!(parse_unified("@@ -1 +1 @@\n-a\n+b\n").unwrap().len(), 1);
+}
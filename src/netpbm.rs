@@ -0,0 +1,500 @@
+// Implements http://rosettacode.org/wiki/Bitmap/Read_a_PPM_file
+//
+// A reader and writer for the whole Netpbm family: PBM (P1, P4), PGM (P2, P5) and PPM (P3, P6),
+// each in its plain (ASCII) and raw (binary) form. Headers may contain comments and any
+// whitespace, maxval can be anything from 1 to 65535 (raw samples take two bytes, big endian,
+// from 256 up), and malformed input is reported as an error rather than a panic.
+//
+// Images are kept as the file has them: a list of samples, one per pixel for PBM and PGM and
+// three per pixel for PPM. In PBM files 1 means black.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write, BufWriter};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Kind {
+    Bitmap,
+    Graymap,
+    Pixmap
+}
+
+impl Kind {
+    pub fn channels(self) -> usize {
+        match self {
+            Kind::Pixmap => 3,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Encoding {
+    Plain,
+    Raw
+}
+
+#[derive(Debug)]
+pub enum NetpbmError {
+    Io(io::Error),
+    BadMagic,
+    // The byte offset of something that should have been a number
+    Syntax(usize),
+    BadMaxval(u32),
+    // The index of a sample larger than maxval
+    BadSample(usize),
+    TooLarge,
+    UnexpectedEof,
+}
+
+impl From<io::Error> for NetpbmError {
+    fn from(err: io::Error) -> NetpbmError {
+        NetpbmError::Io(err)
+    }
+}
+
+impl fmt::Display for NetpbmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NetpbmError::Io(ref err) => write!(f, "{}", err),
+            NetpbmError::BadMagic => write!(f, "not a Netpbm file"),
+            NetpbmError::Syntax(offset) => write!(f, "expected a number at byte {}", offset),
+            NetpbmError::BadMaxval(maxval) => write!(f, "maxval {} is out of range", maxval),
+            NetpbmError::BadSample(i) => write!(f, "sample {} is larger than maxval", i),
+            NetpbmError::TooLarge => write!(f, "image dimensions are too large"),
+            NetpbmError::UnexpectedEof => write!(f, "unexpected end of file"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Netpbm {
+    pub kind: Kind,
+    pub width: usize,
+    pub height: usize,
+    pub maxval: u16,
+    pub samples: Vec<u16>
+}
+
+// A cursor over the file's bytes
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    // Skips whitespace and comments, which run from '#' to the end of the line
+    fn skip_space(&mut self) {
+        while self.pos < self.bytes.len() {
+            match self.bytes[self.pos] {
+                b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c' => self.pos += 1,
+                b'#' => {
+                    while self.pos < self.bytes.len() && self.bytes[self.pos] != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    // A decimal number, saturating just above the largest u32
+    fn number(&mut self) -> Result<u64, NetpbmError> {
+        self.skip_space();
+        if self.pos == self.bytes.len() {
+            return Err(NetpbmError::UnexpectedEof);
+        }
+        let start = self.pos;
+        let mut value = 0u64;
+        while self.pos < self.bytes.len() && (self.bytes[self.pos] as char).is_digit(10) {
+            value = (value * 10 + (self.bytes[self.pos] - b'0') as u64).min(1 << 32);
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(NetpbmError::Syntax(start));
+        }
+        Ok(value)
+    }
+
+    // One digit of a plain PBM raster, where the digits need not be separated
+    fn bit(&mut self) -> Result<u16, NetpbmError> {
+        self.skip_space();
+        match self.bytes.get(self.pos) {
+            Some(&b'0') => { self.pos += 1; Ok(0) }
+            Some(&b'1') => { self.pos += 1; Ok(1) }
+            Some(_) => Err(NetpbmError::Syntax(self.pos)),
+            None => Err(NetpbmError::UnexpectedEof),
+        }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], NetpbmError> {
+        if self.bytes.len() - self.pos < n {
+            return Err(NetpbmError::UnexpectedEof);
+        }
+        self.pos += n;
+        Ok(&self.bytes[self.pos - n..self.pos])
+    }
+}
+
+// Writes the tokens separated by spaces, in lines of at most 70 characters
+fn write_plain<W: Write, I: Iterator<Item=String>>(writer: &mut W, tokens: I) -> io::Result<()> {
+    let mut line = 0;
+    for token in tokens {
+        if line > 0 && line + 1 + token.len() > 70 {
+            try!(writeln!(writer, ""));
+            line = 0;
+        }
+        if line > 0 {
+            try!(write!(writer, " "));
+            line += 1;
+        }
+        try!(write!(writer, "{}", token));
+        line += token.len();
+    }
+    writeln!(writer, "")
+}
+
+// Scales a sample from 0..maxval to 0..255, rounding
+fn to_u8(sample: u16, maxval: u16) -> u8 {
+    ((sample as u32 * 255 + maxval as u32 / 2) / maxval as u32) as u8
+}
+
+impl Netpbm {
+    /// A black image.
+    pub fn new(kind: Kind, width: usize, height: usize, maxval: u16) -> Netpbm {
+        let (maxval, black) = if kind == Kind::Bitmap { (1, 1) } else { (maxval, 0) };
+        Netpbm {
+            kind: kind,
+            width: width,
+            height: height,
+            maxval: maxval,
+            samples: vec![black; width * height * kind.channels()],
+        }
+    }
+
+    /// An 8-bit graymap from one brightness per pixel, row by row.
+    pub fn from_gray8(width: usize, height: usize, data: &[u8]) -> Netpbm {
+        assert_eq!(data.len(), width * height);
+        Netpbm {
+            kind: Kind::Graymap,
+            width: width,
+            height: height,
+            maxval: 255,
+            samples: data.iter().map(|&v| v as u16).collect(),
+        }
+    }
+
+    /// Parses the first image in a Netpbm file.
+    pub fn parse(bytes: &[u8]) -> Result<Netpbm, NetpbmError> {
+        if bytes.len() < 2 || bytes[0] != b'P' {
+            return Err(NetpbmError::BadMagic);
+        }
+        let (kind, encoding) = match bytes[1] {
+            b'1' => (Kind::Bitmap, Encoding::Plain),
+            b'2' => (Kind::Graymap, Encoding::Plain),
+            b'3' => (Kind::Pixmap, Encoding::Plain),
+            b'4' => (Kind::Bitmap, Encoding::Raw),
+            b'5' => (Kind::Graymap, Encoding::Raw),
+            b'6' => (Kind::Pixmap, Encoding::Raw),
+            _ => return Err(NetpbmError::BadMagic),
+        };
+        let mut parser = Parser { bytes: bytes, pos: 2 };
+
+        let width = try!(parser.number());
+        let height = try!(parser.number());
+        let maxval = if kind == Kind::Bitmap { 1 } else { try!(parser.number()) };
+        if maxval == 0 || maxval > 65535 {
+            return Err(NetpbmError::BadMaxval(maxval.min(u32::max_value() as u64) as u32));
+        }
+        let count = (width as usize).checked_mul(height as usize)
+                                    .and_then(|n| n.checked_mul(kind.channels()));
+        let count = match count {
+            Some(n) if width <= u32::max_value() as u64 && height <= u32::max_value() as u64 => n,
+            _ => return Err(NetpbmError::TooLarge),
+        };
+        let (width, height, maxval) = (width as usize, height as usize, maxval as u16);
+
+        let mut samples = Vec::new();
+        match encoding {
+            Encoding::Plain => {
+                // Don't trust the header with the allocation: every sample takes a byte
+                samples.reserve(count.min(bytes.len()));
+                for i in 0..count {
+                    let sample = if kind == Kind::Bitmap {
+                        try!(parser.bit()) as u64
+                    } else {
+                        try!(parser.number())
+                    };
+                    if sample > maxval as u64 {
+                        return Err(NetpbmError::BadSample(i));
+                    }
+                    samples.push(sample as u16);
+                }
+            }
+            Encoding::Raw => {
+                // A single whitespace character separates the header from the raster, though
+                // a comment may come first
+                if parser.bytes.get(parser.pos) == Some(&b'#') {
+                    while parser.pos < bytes.len() && bytes[parser.pos] != b'\n' {
+                        parser.pos += 1;
+                    }
+                }
+                match parser.bytes.get(parser.pos) {
+                    Some(&c) if (c as char).is_whitespace() => parser.pos += 1,
+                    Some(_) => return Err(NetpbmError::Syntax(parser.pos)),
+                    None => return Err(NetpbmError::UnexpectedEof),
+                }
+                if kind == Kind::Bitmap {
+                    // Eight pixels a byte, most significant bit first, each row padded
+                    let row_bytes = (width + 7) / 8;
+                    let raster = try!(parser.take(try!(row_bytes.checked_mul(height)
+                                                                .ok_or(NetpbmError::TooLarge))));
+                    samples.reserve(count);
+                    for row in raster.chunks(row_bytes.max(1)).take(height) {
+                        for x in 0..width {
+                            samples.push((row[x / 8] >> (7 - x % 8) & 1) as u16);
+                        }
+                    }
+                } else {
+                    let size = if maxval < 256 { 1 } else { 2 };
+                    let raster = try!(parser.take(try!(count.checked_mul(size)
+                                                            .ok_or(NetpbmError::TooLarge))));
+                    samples.reserve(count);
+                    for (i, bytes) in raster.chunks(size).enumerate() {
+                        let sample = bytes.iter().fold(0, |value, &b| value << 8 | b as u16);
+                        if sample > maxval {
+                            return Err(NetpbmError::BadSample(i));
+                        }
+                        samples.push(sample);
+                    }
+                }
+            }
+        }
+
+        Ok(Netpbm { kind: kind, width: width, height: height, maxval: maxval, samples: samples })
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Netpbm, NetpbmError> {
+        let mut bytes = Vec::new();
+        try!(reader.read_to_end(&mut bytes));
+        Netpbm::parse(&bytes)
+    }
+
+    pub fn load(filename: &str) -> Result<Netpbm, NetpbmError> {
+        let mut file = try!(File::open(filename));
+        Netpbm::read(&mut file)
+    }
+
+    /// Writes the image. Samples must not be larger than maxval; for bitmaps any nonzero
+    /// sample is black.
+    pub fn write<W: Write>(&self, writer: &mut W, encoding: Encoding) -> io::Result<()> {
+        let magic = match (self.kind, encoding) {
+            (Kind::Bitmap, Encoding::Plain) => 1,
+            (Kind::Graymap, Encoding::Plain) => 2,
+            (Kind::Pixmap, Encoding::Plain) => 3,
+            (Kind::Bitmap, Encoding::Raw) => 4,
+            (Kind::Graymap, Encoding::Raw) => 5,
+            (Kind::Pixmap, Encoding::Raw) => 6,
+        };
+        try!(write!(writer, "P{}\n{} {}", magic, self.width, self.height));
+        if self.kind != Kind::Bitmap {
+            try!(write!(writer, " {}", self.maxval));
+        }
+        try!(writeln!(writer, ""));
+
+        match (self.kind, encoding) {
+            (Kind::Bitmap, Encoding::Plain) => {
+                write_plain(writer, self.samples.iter()
+                                        .map(|&s| (if s != 0 { "1" } else { "0" }).to_string()))
+            }
+            (_, Encoding::Plain) => write_plain(writer, self.samples.iter().map(|s| s.to_string())),
+            (Kind::Bitmap, Encoding::Raw) => {
+                let mut raster = Vec::with_capacity((self.width + 7) / 8 * self.height);
+                for row in self.samples.chunks(self.width.max(1)).take(self.height) {
+                    for byte in row.chunks(8) {
+                        raster.push(byte.iter().enumerate().fold(0u8, |b, (i, &s)| {
+                            if s != 0 { b | 0x80 >> i } else { b }
+                        }));
+                    }
+                }
+                writer.write_all(&raster)
+            }
+            (_, Encoding::Raw) => {
+                let raster: Vec<u8> = if self.maxval < 256 {
+                    self.samples.iter().map(|&s| s as u8).collect()
+                } else {
+                    self.samples.iter().flat_map(|&s| vec![(s >> 8) as u8, s as u8]).collect()
+                };
+                writer.write_all(&raster)
+            }
+        }
+    }
+
+    pub fn save(&self, filename: &str, encoding: Encoding) -> io::Result<()> {
+        let mut writer = BufWriter::new(try!(File::create(filename)));
+        try!(self.write(&mut writer, encoding));
+        writer.flush()
+    }
+
+    /// The color of every pixel, row by row, scaled to 8 bits.
+    pub fn rgb8(&self) -> Vec<(u8, u8, u8)> {
+        match self.kind {
+            Kind::Pixmap => self.samples.chunks(3).map(|rgb| {
+                (to_u8(rgb[0], self.maxval), to_u8(rgb[1], self.maxval), to_u8(rgb[2], self.maxval))
+            }).collect(),
+            _ => self.gray8().into_iter().map(|v| (v, v, v)).collect(),
+        }
+    }
+
+    /// The brightness of every pixel, row by row, scaled to 8 bits. Colors are weighted by
+    /// their luminance (ITU-R BT.709).
+    pub fn gray8(&self) -> Vec<u8> {
+        match self.kind {
+            Kind::Bitmap => self.samples.iter().map(|&s| if s != 0 { 0 } else { 255 }).collect(),
+            Kind::Graymap => self.samples.iter().map(|&s| to_u8(s, self.maxval)).collect(),
+            Kind::Pixmap => self.rgb8().into_iter().map(|(r, g, b)| {
+                (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64).round() as u8
+            }).collect(),
+        }
+    }
+}
+
+#[cfg(not(test))]
+#[allow(dead_code)]
+pub fn main() {
+    use std::env;
+
+    // Converts any Netpbm file to a plain graymap
+    let args: Vec<String> = env::args().collect();
+    let input = args.get(1).map(|s| &s[..]).unwrap_or("src/resources/Pentagon.pgm");
+    let output = args.get(2).map(|s| &s[..]).unwrap_or("grayscale.pgm");
+    let image = match Netpbm::load(input) {
+        Ok(image) => image,
+        Err(e) => {
+            println!("Can't read {}: {}", input, e);
+            return;
+        }
+    };
+    println!("{}: {:?}, {} x {}, maxval {}", input, image.kind, image.width, image.height,
+             image.maxval);
+    let gray = Netpbm::from_gray8(image.width, image.height, &image.gray8());
+    match gray.save(output, Encoding::Plain) {
+        Ok(()) => println!("Wrote {}", output),
+        Err(e) => println!("Can't write {}: {}", output, e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Netpbm, NetpbmError, Kind, Encoding};
+
+    fn round_trip(image: &Netpbm, encoding: Encoding) -> Netpbm {
+        let mut bytes = Vec::new();
+        image.write(&mut bytes, encoding).unwrap();
+        Netpbm::parse(&bytes).unwrap()
+    }
+
+    #[test]
+    fn all_formats_round_trip() {
+        for &(kind, maxval) in &[(Kind::Bitmap, 1), (Kind::Graymap, 255), (Kind::Graymap, 1000),
+                                 (Kind::Pixmap, 15), (Kind::Pixmap, 65535)] {
+            // Widths that don't fill whole bytes, for the raw bitmaps
+            let mut image = Netpbm::new(kind, 11, 3, maxval);
+            for (i, sample) in image.samples.iter_mut().enumerate() {
+                *sample = (i as u64 * 7919 % (maxval as u64 + 1)) as u16;
+            }
+            for &encoding in &[Encoding::Plain, Encoding::Raw] {
+                assert_eq!(round_trip(&image, encoding), image);
+            }
+        }
+    }
+
+    #[test]
+    fn written_forms() {
+        let mut bitmap = Netpbm::new(Kind::Bitmap, 10, 2, 1);
+        bitmap.samples[0] = 0;
+        bitmap.samples[19] = 0;
+        let mut bytes = Vec::new();
+        bitmap.write(&mut bytes, Encoding::Raw).unwrap();
+        assert_eq!(bytes, b"P4\n10 2\n\x7f\xc0\xff\x80".to_vec());
+
+        let graymap = Netpbm::from_gray8(3, 1, &[0, 128, 255]);
+        let mut bytes = Vec::new();
+        graymap.write(&mut bytes, Encoding::Plain).unwrap();
+        assert_eq!(bytes, b"P2\n3 1 255\n0 128 255\n".to_vec());
+
+        let mut deep = Netpbm::new(Kind::Graymap, 1, 1, 65535);
+        deep.samples[0] = 0x1234;
+        let mut bytes = Vec::new();
+        deep.write(&mut bytes, Encoding::Raw).unwrap();
+        assert_eq!(bytes, b"P5\n1 1 65535\n\x12\x34".to_vec());
+
+        // Plain lines stay within 70 characters
+        let wide = Netpbm::new(Kind::Pixmap, 100, 1, 65535);
+        let mut bytes = Vec::new();
+        wide.write(&mut bytes, Encoding::Plain).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.lines().all(|line| line.len() <= 70));
+        assert_eq!(Netpbm::parse(text.as_bytes()).unwrap(), wide);
+    }
+
+    #[test]
+    fn comments_and_whitespace() {
+        let text = b"P2 # a comment\n#another\n  3\t# width\n\n2 \r\n# maxval next\n9\n\
+                     0 1 2\n# in the raster\n3 4\n5\n";
+        let image = Netpbm::parse(text).unwrap();
+        assert_eq!((image.width, image.height, image.maxval), (3, 2, 9));
+        assert_eq!(image.samples, vec![0, 1, 2, 3, 4, 5]);
+
+        // Plain bitmaps may run their digits together
+        let image = Netpbm::parse(b"P1\n5 2\n10110\n0 1 0 0 1").unwrap();
+        assert_eq!(image.samples, vec![1, 0, 1, 1, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(image.gray8(), vec![0, 255, 0, 0, 255, 255, 0, 255, 255, 0]);
+
+        // A comment may sit right before the single whitespace ending a raw header
+        let image = Netpbm::parse(b"P5 2 1 255# comment\n\x01\x02").unwrap();
+        assert_eq!(image.samples, vec![1, 2]);
+    }
+
+    #[test]
+    fn conversions() {
+        let image = Netpbm::parse(b"P3 2 1 15 15 0 0 0 15 0").unwrap();
+        assert_eq!(image.rgb8(), vec![(255, 0, 0), (0, 255, 0)]);
+        assert_eq!(image.gray8(), vec![54, 182]);
+        let image = Netpbm::parse(b"P2 2 1 1000 500 1000").unwrap();
+        assert_eq!(image.gray8(), vec![128, 255]);
+    }
+
+    #[test]
+    fn errors() {
+        fn error(bytes: &[u8]) -> NetpbmError {
+            Netpbm::parse(bytes).unwrap_err()
+        }
+        match error(b"") { NetpbmError::BadMagic => {}, e => panic!("{:?}", e) }
+        match error(b"P7\n1 1\n255\n") { NetpbmError::BadMagic => {}, e => panic!("{:?}", e) }
+        match error(b"P2\n1 x\n255\n0") { NetpbmError::Syntax(5) => {}, e => panic!("{:?}", e) }
+        match error(b"P2\n1 1\n0\n0") { NetpbmError::BadMaxval(0) => {}, e => panic!("{:?}", e) }
+        match error(b"P5 1 1 70000\n\0\0") {
+            NetpbmError::BadMaxval(70000) => {},
+            e => panic!("{:?}", e),
+        }
+        match error(b"P2 2 1 7 3 8") { NetpbmError::BadSample(1) => {}, e => panic!("{:?}", e) }
+        match error(b"P5 2 1 7\n\x03\x08") {
+            NetpbmError::BadSample(1) => {},
+            e => panic!("{:?}", e),
+        }
+        match error(b"P1 2 2 1 0 2") { NetpbmError::Syntax(11) => {}, e => panic!("{:?}", e) }
+        match error(b"P6\n2 2\n255\n\0\0\0") {
+            NetpbmError::UnexpectedEof => {},
+            e => panic!("{:?}", e),
+        }
+        match error(b"P3 1 1 255 0 0") {
+            NetpbmError::UnexpectedEof => {},
+            e => panic!("{:?}", e),
+        }
+        match error(b"P5 99999999999 99999999999 255\n") {
+            NetpbmError::TooLarge => {},
+            e => panic!("{:?}", e),
+        }
+    }
+}
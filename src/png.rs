@@ -0,0 +1,834 @@
+// PNG reading and writing, for images that ordinary tools can open. Every chunk is checked with
+// the CRC from http://rosettacode.org/wiki/CRC-32, and the zlib streams inside come from a
+// DEFLATE implemented here: stored blocks or fixed Huffman codes with LZ77 matching for output,
+// and all three block types for input.
+//
+// Only 8 bits per sample are supported, without interlacing or palettes: grayscale, grayscale
+// with alpha, RGB and RGBA.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write, BufWriter};
+use self::crc_32::crc;
+
+#[path = "crc_32.rs"]
+mod crc_32;
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ColorType {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba
+}
+
+impl ColorType {
+    pub fn channels(self) -> usize {
+        match self {
+            ColorType::Gray => 1,
+            ColorType::GrayAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    // The color type field of the header
+    fn code(self) -> u8 {
+        match self {
+            ColorType::Gray => 0,
+            ColorType::Rgb => 2,
+            ColorType::GrayAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Compression {
+    Stored,
+    Fixed
+}
+
+#[derive(Debug)]
+pub enum PngError {
+    Io(io::Error),
+    BadSignature,
+    // The type of a chunk whose CRC doesn't match
+    BadCrc(String),
+    Unsupported(&'static str),
+    Corrupt(&'static str),
+    UnexpectedEof,
+}
+
+impl From<io::Error> for PngError {
+    fn from(err: io::Error) -> PngError {
+        PngError::Io(err)
+    }
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PngError::Io(ref err) => write!(f, "{}", err),
+            PngError::BadSignature => write!(f, "not a PNG file"),
+            PngError::BadCrc(ref chunk) => write!(f, "bad CRC in {} chunk", chunk),
+            PngError::Unsupported(what) => write!(f, "unsupported: {}", what),
+            PngError::Corrupt(what) => write!(f, "corrupt data: {}", what),
+            PngError::UnexpectedEof => write!(f, "unexpected end of file"),
+        }
+    }
+}
+
+// DEFLATE's lengths and distances: the smallest of each code, and its number of extra bits
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43,
+                                51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4,
+                                4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257,
+                                  385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289,
+                                  16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9,
+                                  9, 10, 10, 11, 11, 12, 12, 13, 13];
+// The order in which a dynamic block lists the code lengths of its code length code
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2,
+                                        14, 1, 15];
+
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 128;
+
+// Writes bits least significant first, as DEFLATE packs them
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter { bytes: Vec::new(), buffer: 0, count: 0 }
+    }
+
+    fn bits(&mut self, value: u32, n: u32) {
+        self.buffer |= value << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes go most significant bit first
+    fn code(&mut self, code: u32, n: u32) {
+        let reversed = (0..n).fold(0, |r, i| r << 1 | (code >> i & 1));
+        self.bits(reversed, n);
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+}
+
+// The fixed Huffman code of a literal/length symbol, and its length
+fn fixed_literal_code(symbol: u16) -> (u32, u32) {
+    let symbol = symbol as u32;
+    match symbol {
+        0...143 => (0x30 + symbol, 8),
+        144...255 => (0x190 + symbol - 144, 9),
+        256...279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    }
+}
+
+// The code and extra bits for a length or distance, from its table
+fn base_code(value: usize, base: &[u16], extra: &[u8]) -> (usize, u32, u32) {
+    let code = base.iter().rposition(|&b| b as usize <= value).unwrap();
+    (code, (value - base[code] as usize) as u32, extra[code] as u32)
+}
+
+/// Compresses with DEFLATE, in uncompressed blocks.
+pub fn deflate_stored(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    let mut blocks = data.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        writer.bits(1, 3);
+        writer.align();
+        writer.bytes.extend([0, 0, 0xff, 0xff].iter().cloned());
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        writer.bits(if last { 1 } else { 0 }, 3);
+        writer.align();
+        let len = block.len() as u16;
+        writer.bytes.extend([len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]
+                                .iter().cloned());
+        writer.bytes.extend(block.iter().cloned());
+    }
+    writer.bytes
+}
+
+/// Compresses with DEFLATE, in a single block with the fixed Huffman codes. Repeats are found
+/// by hash chains over the last 32K, following up to 128 earlier positions for each match.
+pub fn deflate_fixed(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    writer.bits(1 | 1 << 1, 3);
+
+    // The most recent position of each three byte hash, and the one before each position
+    let mut head = vec![usize::max_value(); 1 << 15];
+    let mut prev = vec![usize::max_value(); data.len()];
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7fff
+    };
+    let insert = |i: usize, head: &mut Vec<usize>, prev: &mut Vec<usize>| {
+        if i + 2 < data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_distance) = (0, 0);
+        if i + 2 < data.len() {
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::max_value() && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let limit = (data.len() - i).min(MAX_MATCH);
+                let len = (0..limit).take_while(|&k| data[candidate + k] == data[i + k]).count();
+                if len > best_len {
+                    best_len = len;
+                    best_distance = i - candidate;
+                    if len == limit {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_len >= 3 {
+            let (code, extra, extra_bits) = base_code(best_len, &LENGTH_BASE, &LENGTH_EXTRA);
+            let (symbol, n) = fixed_literal_code(257 + code as u16);
+            writer.code(symbol, n);
+            writer.bits(extra, extra_bits);
+            let (code, extra, extra_bits) = base_code(best_distance, &DISTANCE_BASE,
+                                                      &DISTANCE_EXTRA);
+            writer.code(code as u32, 5);
+            writer.bits(extra, extra_bits);
+            for k in i..i + best_len {
+                insert(k, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            let (symbol, n) = fixed_literal_code(data[i] as u16);
+            writer.code(symbol, n);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+
+    let (symbol, n) = fixed_literal_code(256);
+    writer.code(symbol, n);
+    writer.align();
+    writer.bytes
+}
+
+// Reads bits least significant first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n: u32) -> Result<u32, PngError> {
+        while self.count < n {
+            let byte = match self.data.get(self.pos) {
+                Some(&byte) => byte,
+                None => return Err(PngError::UnexpectedEof),
+            };
+            self.buffer |= (byte as u32) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+// A canonical Huffman code, as the number of codes of each length and the symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, PngError> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        // Reject codes with more codes of some length than there is room for
+        let mut left = 1i32;
+        for len in 1..16 {
+            left = left * 2 - counts[len] as i32;
+            if left < 0 {
+                return Err(PngError::Corrupt("over-subscribed Huffman code"));
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; offsets[15] as usize + counts[15] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts: counts, symbols: symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, PngError> {
+        // Codes of each length follow on from the codes one bit shorter
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= try!(reader.bits(1)) as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(PngError::Corrupt("invalid Huffman code"))
+    }
+}
+
+// Reads the code lengths of a dynamic block and builds its two codes
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), PngError> {
+    let literals = try!(reader.bits(5)) as usize + 257;
+    let distances = try!(reader.bits(5)) as usize + 1;
+    let code_lengths = try!(reader.bits(4)) as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err(PngError::Corrupt("too many codes"));
+    }
+    let mut lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[i] = try!(reader.bits(3)) as u8;
+    }
+    let length_code = try!(Huffman::new(&lengths));
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let symbol = try!(length_code.decode(reader));
+        let (value, repeat) = match symbol {
+            0...15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(&last) => (last, 3 + try!(reader.bits(2)) as usize),
+                None => return Err(PngError::Corrupt("repeat with no previous length")),
+            },
+            17 => (0, 3 + try!(reader.bits(3)) as usize),
+            _ => (0, 11 + try!(reader.bits(7)) as usize),
+        };
+        if lengths.len() + repeat > literals + distances {
+            return Err(PngError::Corrupt("too many code lengths"));
+        }
+        lengths.extend(::std::iter::repeat(value).take(repeat));
+    }
+    if lengths[256] == 0 {
+        return Err(PngError::Corrupt("no end of block code"));
+    }
+    Ok((try!(Huffman::new(&lengths[..literals])), try!(Huffman::new(&lengths[literals..]))))
+}
+
+/// Decompresses a DEFLATE stream with stored, fixed and dynamic Huffman blocks. Returns the
+/// data and the number of bytes of input used. A stream that would produce more than `limit`
+/// bytes is refused as soon as it gets there, so a few bytes can't demand gigabytes.
+pub fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), PngError> {
+    let mut reader = BitReader { data: data, pos: 0, buffer: 0, count: 0 };
+    let mut output = Vec::new();
+    loop {
+        let last = try!(reader.bits(1)) == 1;
+        match try!(reader.bits(2)) {
+            0 => {
+                reader.align();
+                let pos = reader.pos;
+                if data.len() - pos < 4 {
+                    return Err(PngError::UnexpectedEof);
+                }
+                let len = data[pos] as usize | (data[pos + 1] as usize) << 8;
+                let nlen = data[pos + 2] as usize | (data[pos + 3] as usize) << 8;
+                if len != !nlen & 0xffff {
+                    return Err(PngError::Corrupt("stored block length"));
+                }
+                if data.len() - pos - 4 < len {
+                    return Err(PngError::UnexpectedEof);
+                }
+                if limit - output.len() < len {
+                    return Err(PngError::Corrupt("more data than expected"));
+                }
+                output.extend(data[pos + 4..pos + 4 + len].iter().cloned());
+                reader.pos = pos + 4 + len;
+            }
+            btype @ 1...2 => {
+                let (literal_code, distance_code) = if btype == 1 {
+                    let mut lengths = [0u8; 288 + 30];
+                    for (symbol, len) in lengths.iter_mut().enumerate() {
+                        *len = match symbol {
+                            0...143 => 8,
+                            144...255 => 9,
+                            256...279 => 7,
+                            280...287 => 8,
+                            _ => 5,
+                        };
+                    }
+                    (try!(Huffman::new(&lengths[..288])), try!(Huffman::new(&lengths[288..])))
+                } else {
+                    try!(dynamic_codes(&mut reader))
+                };
+                loop {
+                    let symbol = try!(literal_code.decode(&mut reader)) as usize;
+                    if symbol < 256 {
+                        if output.len() == limit {
+                            return Err(PngError::Corrupt("more data than expected"));
+                        }
+                        output.push(symbol as u8);
+                        continue;
+                    } else if symbol == 256 {
+                        break;
+                    } else if symbol > 285 {
+                        return Err(PngError::Corrupt("bad length code"));
+                    }
+                    let code = symbol - 257;
+                    let len = LENGTH_BASE[code] as usize
+                        + try!(reader.bits(LENGTH_EXTRA[code] as u32)) as usize;
+                    let code = try!(distance_code.decode(&mut reader)) as usize;
+                    if code >= 30 {
+                        return Err(PngError::Corrupt("bad distance code"));
+                    }
+                    let distance = DISTANCE_BASE[code] as usize
+                        + try!(reader.bits(DISTANCE_EXTRA[code] as u32)) as usize;
+                    if distance > output.len() {
+                        return Err(PngError::Corrupt("distance too far back"));
+                    }
+                    if limit - output.len() < len {
+                        return Err(PngError::Corrupt("more data than expected"));
+                    }
+                    // The copy may overlap what it is producing
+                    let start = output.len() - distance;
+                    for k in 0..len {
+                        let byte = output[start + k];
+                        output.push(byte);
+                    }
+                }
+            }
+            _ => return Err(PngError::Corrupt("bad block type")),
+        }
+        if last {
+            return Ok((output, reader.pos));
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // Postpone the modulo for as long as the sums can't overflow
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+fn be32(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).fold(0, |value, &b| value << 8 | b as u32)
+}
+
+fn push_be32(output: &mut Vec<u8>, value: u32) {
+    output.extend([(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+                      .iter().cloned());
+}
+
+/// Wraps DEFLATE output in a zlib stream.
+pub fn zlib_compress(data: &[u8], compression: Compression) -> Vec<u8> {
+    // Deflate with a 32K window, and a check value making the header a multiple of 31
+    let mut output = vec![0x78, 0x01];
+    output.extend(match compression {
+        Compression::Stored => deflate_stored(data),
+        Compression::Fixed => deflate_fixed(data),
+    });
+    push_be32(&mut output, adler32(data));
+    output
+}
+
+/// Unwraps a zlib stream of at most `limit` bytes of data.
+pub fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, PngError> {
+    if data.len() < 2 {
+        return Err(PngError::UnexpectedEof);
+    }
+    // Deflate, a window of at most 32K, and a header that is a multiple of 31
+    let header = data[0] as u32 * 256 + data[1] as u32;
+    if data[0] & 0x0f != 8 || data[0] >> 4 > 7 || header % 31 != 0 {
+        return Err(PngError::Corrupt("zlib header"));
+    }
+    if data[1] & 0x20 != 0 {
+        return Err(PngError::Unsupported("zlib preset dictionary"));
+    }
+    let (output, used) = try!(inflate(&data[2..], limit));
+    if data.len() - 2 - used < 4 {
+        Err(PngError::UnexpectedEof)
+    } else if be32(&data[2 + used..]) != adler32(&output) {
+        Err(PngError::Corrupt("zlib checksum"))
+    } else {
+        Ok(output)
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+// Filters one row in place of its type byte's filter. Left and upper left neighbours outside
+// the image are zero, as is the row above the first.
+fn filter_row(filter: u8, row: &[u8], above: &[u8], bpp: usize, output: &mut Vec<u8>) {
+    output.push(filter);
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let c = if i >= bpp { above[i - bpp] } else { 0 };
+        let b = above[i];
+        output.push(match filter {
+            0 => row[i],
+            1 => row[i].wrapping_sub(a),
+            2 => row[i].wrapping_sub(b),
+            3 => row[i].wrapping_sub(((a as u16 + b as u16) / 2) as u8),
+            _ => row[i].wrapping_sub(paeth(a, b, c)),
+        });
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Png {
+    pub width: usize,
+    pub height: usize,
+    pub color: ColorType,
+    /// The samples of each pixel, row by row
+    pub pixels: Vec<u8>
+}
+
+fn write_chunk(output: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    push_be32(output, data.len() as u32);
+    let start = output.len();
+    output.extend(kind.iter().cloned());
+    output.extend(data.iter().cloned());
+    let check = crc(&output[start..]);
+    push_be32(output, check);
+}
+
+impl Png {
+    /// The PNG file. Each row gets whichever filter leaves it with the smallest sum of
+    /// absolute values, taking the filtered bytes as signed, as the PNG specification suggests.
+    pub fn encode(&self, compression: Compression) -> Vec<u8> {
+        assert_eq!(self.pixels.len(), self.width * self.height * self.color.channels());
+        let bpp = self.color.channels();
+        let stride = self.width * bpp;
+        let mut filtered = Vec::with_capacity((stride + 1) * self.height);
+        let zeros = vec![0; stride];
+        let mut candidate = Vec::with_capacity(stride + 1);
+        for y in 0..self.height {
+            let row = &self.pixels[y * stride..(y + 1) * stride];
+            let above = if y > 0 { &self.pixels[(y - 1) * stride..y * stride] } else { &zeros[..] };
+            let mut best: Option<(u64, Vec<u8>)> = None;
+            for filter in 0..5 {
+                candidate.clear();
+                filter_row(filter, row, above, bpp, &mut candidate);
+                let cost = candidate[1..].iter().fold(0, |sum, &v| {
+                    sum + (v as i8 as i64).abs() as u64
+                });
+                if best.as_ref().map_or(true, |&(best_cost, _)| cost < best_cost) {
+                    best = Some((cost, candidate.clone()));
+                }
+            }
+            filtered.extend(best.unwrap().1);
+        }
+
+        let mut output = SIGNATURE.to_vec();
+        let mut header = Vec::new();
+        push_be32(&mut header, self.width as u32);
+        push_be32(&mut header, self.height as u32);
+        // Bit depth, color type, compression, filter method and no interlacing
+        header.extend([8, self.color.code(), 0, 0, 0].iter().cloned());
+        write_chunk(&mut output, b"IHDR", &header);
+        write_chunk(&mut output, b"IDAT", &zlib_compress(&filtered, compression));
+        write_chunk(&mut output, b"IEND", &[]);
+        output
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Png, PngError> {
+        if bytes.len() < 8 || bytes[..8] != SIGNATURE[..] {
+            return Err(PngError::BadSignature);
+        }
+        let mut pos = 8;
+        let mut header = None;
+        let mut compressed = Vec::new();
+        loop {
+            if bytes.len() - pos < 12 {
+                return Err(PngError::UnexpectedEof);
+            }
+            let len = be32(&bytes[pos..]) as usize;
+            if bytes.len() - pos - 12 < len {
+                return Err(PngError::UnexpectedEof);
+            }
+            let kind = &bytes[pos + 4..pos + 8];
+            let data = &bytes[pos + 8..pos + 8 + len];
+            if crc(&bytes[pos + 4..pos + 8 + len]) != be32(&bytes[pos + 8 + len..]) {
+                return Err(PngError::BadCrc(String::from_utf8_lossy(kind).into_owned()));
+            }
+            pos += 12 + len;
+            match kind {
+                b"IHDR" => {
+                    if len != 13 {
+                        return Err(PngError::Corrupt("header length"));
+                    }
+                    if data[8] != 8 {
+                        return Err(PngError::Unsupported("bit depths other than 8"));
+                    }
+                    let color = match data[9] {
+                        0 => ColorType::Gray,
+                        2 => ColorType::Rgb,
+                        4 => ColorType::GrayAlpha,
+                        6 => ColorType::Rgba,
+                        3 => return Err(PngError::Unsupported("palettes")),
+                        _ => return Err(PngError::Corrupt("color type")),
+                    };
+                    if data[10] != 0 || data[11] != 0 {
+                        return Err(PngError::Corrupt("compression or filter method"));
+                    }
+                    if data[12] != 0 {
+                        return Err(PngError::Unsupported("interlacing"));
+                    }
+                    header = Some((be32(&data[0..]) as usize, be32(&data[4..]) as usize, color));
+                }
+                b"IDAT" => compressed.extend(data.iter().cloned()),
+                b"IEND" => break,
+                // Ancillary chunks have a lowercase first letter and may be ignored
+                _ if kind[0] & 0x20 != 0 => {}
+                _ => return Err(PngError::Unsupported("critical chunk")),
+            }
+        }
+        let (width, height, color) = match header {
+            Some(header) => header,
+            None => return Err(PngError::Corrupt("missing header")),
+        };
+
+        let bpp = color.channels();
+        let stride = width * bpp;
+        let size = match stride.checked_add(1).and_then(|n| n.checked_mul(height)) {
+            Some(size) => size,
+            None => return Err(PngError::Unsupported("images this large")),
+        };
+        let filtered = try!(zlib_decompress(&compressed, size));
+        if filtered.len() != size {
+            return Err(PngError::Corrupt("image data length"));
+        }
+        let mut pixels = Vec::with_capacity(stride * height);
+        for (y, line) in filtered.chunks(stride + 1).enumerate() {
+            let row_start = y * stride;
+            for i in 0..stride {
+                let a = if i >= bpp { pixels[row_start + i - bpp] } else { 0 };
+                let b = if y > 0 { pixels[row_start - stride + i] } else { 0 };
+                let c = if y > 0 && i >= bpp { pixels[row_start - stride + i - bpp] } else { 0 };
+                let x = line[1 + i];
+                pixels.push(match line[0] {
+                    0 => x,
+                    1 => x.wrapping_add(a),
+                    2 => x.wrapping_add(b),
+                    3 => x.wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                    4 => x.wrapping_add(paeth(a, b, c)),
+                    _ => return Err(PngError::Corrupt("filter type")),
+                });
+            }
+        }
+        Ok(Png { width: width, height: height, color: color, pixels: pixels })
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Png, PngError> {
+        let mut bytes = Vec::new();
+        try!(reader.read_to_end(&mut bytes));
+        Png::decode(&bytes)
+    }
+
+    pub fn load(filename: &str) -> Result<Png, PngError> {
+        let mut file = try!(File::open(filename));
+        Png::read(&mut file)
+    }
+
+    pub fn save(&self, filename: &str, compression: Compression) -> io::Result<()> {
+        let mut writer = BufWriter::new(try!(File::create(filename)));
+        try!(writer.write_all(&self.encode(compression)));
+        writer.flush()
+    }
+}
+
+#[cfg(not(test))]
+#[allow(dead_code)]
+pub fn main() {
+    // A color wheel with a transparent background
+    let size = 128;
+    let mut pixels = Vec::with_capacity(size * size * 4);
+    for y in 0..size {
+        for x in 0..size {
+            let (dx, dy) = (x as f64 - 63.5, y as f64 - 63.5);
+            let hue = (dy.atan2(dx) / ::std::f64::consts::PI + 1.0) * 3.0;
+            let channel = |offset: f64| {
+                let d = ((hue - offset + 6.0) % 6.0 - 3.0).abs();
+                ((d - 1.0).max(0.0).min(1.0) * 255.0) as u8
+            };
+            let alpha = if dx.hypot(dy) < 60.0 { 255 } else { 0 };
+            pixels.extend([channel(0.0), channel(2.0), channel(4.0), alpha].iter().cloned());
+        }
+    }
+    let png = Png { width: size, height: size, color: ColorType::Rgba, pixels: pixels };
+    for &(compression, filename) in &[(Compression::Stored, "wheel-stored.png"),
+                                      (Compression::Fixed, "wheel.png")] {
+        match png.save(filename, compression) {
+            Ok(()) => println!("Wrote {}: {} bytes", filename, png.encode(compression).len()),
+            Err(e) => println!("Can't write {}: {}", filename, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Png, PngError, ColorType, Compression};
+    use super::{deflate_stored, deflate_fixed, inflate, zlib_compress, zlib_decompress, adler32};
+    use std::fs::File;
+    use std::io::Read;
+
+    fn sample_data() -> Vec<u8> {
+        let mut data = Vec::new();
+        File::open("src/resources/unixdict.txt").unwrap().read_to_end(&mut data).unwrap();
+        data.truncate(100000);
+        // Some incompressible bytes too
+        data.extend((0..5000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8));
+        data
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        for data in vec![Vec::new(), b"a".to_vec(), b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_vec(),
+                         sample_data()] {
+            let (stored, used) = inflate(&deflate_stored(&data), data.len()).unwrap();
+            assert_eq!(stored, data);
+            assert_eq!(used, deflate_stored(&data).len());
+            let compressed = deflate_fixed(&data);
+            assert_eq!(inflate(&compressed, data.len()).unwrap().0, data);
+            let zlib = zlib_compress(&data, Compression::Fixed);
+            assert_eq!(zlib_decompress(&zlib, data.len()).unwrap(), data);
+        }
+        let data = sample_data();
+        assert!(deflate_fixed(&data).len() < data.len() / 2);
+
+        // Output past the limit is refused, however little input it takes
+        assert!(inflate(&deflate_stored(&data), data.len() - 1).is_err());
+        let zeros = vec![0; 1 << 20];
+        let bomb = zlib_compress(&zeros, Compression::Fixed);
+        assert!(bomb.len() < zeros.len() / 100);
+        match zlib_decompress(&bomb, 1000) {
+            Err(PngError::Corrupt("more data than expected")) => {}
+            other => panic!("{:?}", other.map(|output| output.len())),
+        }
+    }
+
+    #[test]
+    fn inflate_zlib_output() {
+        // The first 200 bytes of unixdict.txt, compressed by zlib into a dynamic Huffman block
+        let compressed = b"\x78\xda\x25\x8c\x4b\x0e\x02\x31\x0c\x43\xf7\x39\x08\x6c\x19\xfe\
+                           \x1c\xc7\x69\x2b\x75\x04\xb4\xa8\xed\x20\x71\x7b\x9c\x74\xf1\x9e\
+                           \x63\xc9\xca\x72\x18\x59\x96\x3e\xe4\x58\xa2\x9c\x5a\x94\x33\xfb\
+                           \x85\x5c\xc9\x8d\xdc\xc9\x83\x40\xb0\x7b\x93\x8f\x60\xdf\x05\x80\
+                           \x61\x47\xcb\x9b\x47\x2d\xf4\x26\x50\x18\xd3\xe1\xe9\xb6\x81\xe2\
+                           \x55\x4b\xb2\x2c\xd1\xa6\x8a\xee\xad\x67\xf3\x48\xd3\x8d\xa1\xf6\
+                           \x56\x35\xb9\x7e\xe6\x3a\xa6\x3d\x5a\xfa\xae\x73\x1f\x48\x5c\x83\
+                           \x97\x3f\x02\xa9\x40\x5b";
+        assert_eq!(zlib_decompress(compressed, 200).unwrap(), &sample_data()[..200]);
+
+        let mut corrupt = compressed.to_vec();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        match zlib_decompress(&corrupt, 200) {
+            Err(PngError::Corrupt("zlib checksum")) => {}
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn png_round_trip() {
+        for &color in &[ColorType::Gray, ColorType::GrayAlpha, ColorType::Rgb, ColorType::Rgba] {
+            let (width, height) = (37, 23);
+            let pixels = (0..width * height * color.channels())
+                .map(|i| ((i * i) % 251 + i / 97) as u8).collect();
+            let png = Png { width: width, height: height, color: color, pixels: pixels };
+            for &compression in &[Compression::Stored, Compression::Fixed] {
+                assert_eq!(Png::decode(&png.encode(compression)).unwrap(), png);
+            }
+        }
+    }
+
+    #[test]
+    fn png_errors() {
+        let png = Png { width: 2, height: 2, color: ColorType::Gray, pixels: vec![1, 2, 3, 4] };
+        let bytes = png.encode(Compression::Fixed);
+        match Png::decode(&bytes[1..]) {
+            Err(PngError::BadSignature) => {}
+            other => panic!("{:?}", other),
+        }
+        let mut corrupt = bytes.clone();
+        corrupt[20] ^= 1;
+        match Png::decode(&corrupt) {
+            Err(PngError::BadCrc(ref chunk)) if chunk == "IHDR" => {}
+            other => panic!("{:?}", other),
+        }
+        match Png::decode(&bytes[..bytes.len() - 12]) {
+            Err(PngError::UnexpectedEof) => {}
+            other => panic!("{:?}", other),
+        }
+        let mut interlaced = bytes.clone();
+        interlaced[28] = 1;
+        let check = super::crc(&interlaced[12..29]);
+        for i in 0..4 {
+            interlaced[29 + i] = (check >> (24 - 8 * i)) as u8;
+        }
+        match Png::decode(&interlaced) {
+            Err(PngError::Unsupported("interlacing")) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -1,19 +1,383 @@
 // http://rosettacode.org/wiki/Strip_comments_from_a_string
+//
+// Beyond the task itself this is a small comment stripper for several languages. A `Profile`
+// describes the comment and string syntax of a language, and a `Stripper` walks the input as a
+// state machine so that comment markers inside string and char literals are left alone. The
+// state is kept between calls, so input can be fed line by line or as a whole buffer.
+use std::fmt;
+use std::io::{self, BufRead, Write};
 
+use self::State::*;
+
+pub struct Profile {
+    // Markers starting a comment that runs to the end of the line
+    pub line_comments: &'static [&'static str],
+    // Opening and closing markers of block comments
+    pub block_comment: Option<(&'static str, &'static str)>,
+    // Whether block comments nest, as they do in Rust
+    pub nested_blocks: bool,
+    // String delimiters, each with a flag telling if a backslash escapes the next character
+    pub quotes: &'static [(char, bool)],
+    // A doubled delimiter stands for the delimiter itself, as in SQL's 'it''s'
+    pub doubled_quotes: bool,
+    // Rust style char literals, where a lone `'` may also start a lifetime
+    pub char_literals: bool,
+    // A backslash outside of strings escapes the next character, as in shell
+    pub escape_outside: bool,
+    // Line comments only start at the beginning of a word, so that `a#b` is no comment in shell
+    pub comments_start_words: bool,
+}
+
+pub const SHELL: Profile = Profile {
+    line_comments: &["#"],
+    block_comment: None,
+    nested_blocks: false,
+    quotes: &[('"', true), ('\'', false)],
+    doubled_quotes: false,
+    char_literals: false,
+    escape_outside: true,
+    comments_start_words: true,
+};
+
+pub const INI: Profile = Profile {
+    line_comments: &["#", ";"],
+    block_comment: None,
+    nested_blocks: false,
+    quotes: &[('"', true)],
+    doubled_quotes: false,
+    char_literals: false,
+    escape_outside: false,
+    comments_start_words: false,
+};
+
+pub const C: Profile = Profile {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_blocks: false,
+    quotes: &[('"', true), ('\'', true)],
+    doubled_quotes: false,
+    char_literals: false,
+    escape_outside: false,
+    comments_start_words: false,
+};
+
+pub const RUST: Profile = Profile {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_blocks: true,
+    quotes: &[('"', true)],
+    doubled_quotes: false,
+    char_literals: true,
+    escape_outside: false,
+    comments_start_words: false,
+};
+
+pub const SQL: Profile = Profile {
+    line_comments: &["--"],
+    block_comment: Some(("/*", "*/")),
+    nested_blocks: false,
+    quotes: &[('\'', false), ('"', false)],
+    doubled_quotes: true,
+    char_literals: false,
+    escape_outside: false,
+    comments_start_words: false,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    // A block comment opened on the given line was never closed
+    UnterminatedComment(usize),
+    // A string opened on the given line was never closed
+    UnterminatedString(usize),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::UnterminatedComment(line) =>
+                write!(f, "block comment starting on line {} is not closed", line),
+            Error::UnterminatedString(line) =>
+                write!(f, "string starting on line {} is not closed", line),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum State {
+    Code,
+    LineComment,
+    // Nesting depth, and whether a newline has been kept from the comment
+    Block(usize, bool),
+    // Delimiter, and whether backslash escapes are allowed
+    Str(char, bool),
+}
+
+pub struct Stripper<'a> {
+    profile: &'a Profile,
+    state: State,
+    // Current line, and the line where the open comment or string started
+    line: usize,
+    opened_at: usize,
+    // Whether the last character was unescaped whitespace, or there was none
+    word_start: bool,
+}
+
+impl<'a> Stripper<'a> {
+    pub fn new(profile: &'a Profile) -> Stripper<'a> {
+        Stripper { profile: profile, state: Code, line: 1, opened_at: 0, word_start: true }
+    }
+
+    // Strips the comments of `input`, appending what remains to `out`. Newlines inside block
+    // comments are kept so that line numbers don't change, a block comment on a single line is
+    // replaced by a space so that it cannot glue two tokens together.
+    pub fn feed(&mut self, input: &str, out: &mut String) {
+        let mut rest = input;
+        while let Some(c) = rest.chars().next() {
+            let taken = match self.state {
+                Code => self.code(rest, c, out),
+                LineComment => {
+                    if c == '\n' {
+                        self.state = Code;
+                        out.push('\n');
+                    }
+                    c.len_utf8()
+                }
+                Block(depth, kept_newline) => self.block(rest, c, depth, kept_newline, out),
+                Str(quote, escapes) => {
+                    let mut taken = c.len_utf8();
+                    if escapes && c == '\\' {
+                        taken += rest[1..].chars().next().map_or(0, |n| n.len_utf8());
+                    } else if c == quote {
+                        if self.profile.doubled_quotes && rest[1..].starts_with(quote) {
+                            taken += 1;
+                        } else {
+                            self.state = Code;
+                        }
+                    }
+                    out.push_str(&rest[..taken]);
+                    taken
+                }
+            };
+            self.line += rest[..taken].matches('\n').count();
+            self.word_start = taken == c.len_utf8() && c.is_whitespace();
+            rest = &rest[taken..];
+        }
+    }
+
+    fn code(&mut self, rest: &str, c: char, out: &mut String) -> usize {
+        let profile = self.profile;
+        if !profile.comments_start_words || self.word_start {
+            if let Some(marker) = profile.line_comments.iter().find(|m| rest.starts_with(**m)) {
+                self.state = LineComment;
+                return marker.len();
+            }
+        }
+        if let Some((open, _)) = profile.block_comment {
+            if rest.starts_with(open) {
+                self.state = Block(1, false);
+                self.opened_at = self.line;
+                return open.len();
+            }
+        }
+
+        let mut taken = c.len_utf8();
+        if profile.escape_outside && c == '\\' {
+            taken += rest[1..].chars().next().map_or(0, |n| n.len_utf8());
+        } else if let Some(&(quote, escapes)) = profile.quotes.iter().find(|q| q.0 == c) {
+            self.state = Str(quote, escapes);
+            self.opened_at = self.line;
+        } else if profile.char_literals && c == '\'' {
+            taken = char_literal_len(rest).unwrap_or(taken);
+        }
+        out.push_str(&rest[..taken]);
+        taken
+    }
+
+    fn block(&mut self, rest: &str, c: char, depth: usize, kept_newline: bool,
+             out: &mut String) -> usize {
+        let (open, close) = self.profile.block_comment.unwrap();
+        if self.profile.nested_blocks && rest.starts_with(open) {
+            self.state = Block(depth + 1, kept_newline);
+            open.len()
+        } else if rest.starts_with(close) {
+            if depth == 1 {
+                self.state = Code;
+                if !kept_newline {
+                    out.push(' ');
+                }
+            } else {
+                self.state = Block(depth - 1, kept_newline);
+            }
+            close.len()
+        } else {
+            if c == '\n' {
+                out.push('\n');
+                self.state = Block(depth, true);
+            }
+            c.len_utf8()
+        }
+    }
+
+    // Strips a single line given without its line terminator, as returned by `BufRead::lines`.
+    pub fn strip_line(&mut self, line: &str) -> String {
+        let mut out = String::with_capacity(line.len());
+        self.feed(line, &mut out);
+        // The line terminator ends line comments, and is part of a multi-line string or comment
+        match self.state {
+            LineComment => self.state = Code,
+            Block(depth, _) => self.state = Block(depth, true),
+            _ => {}
+        }
+        self.line += 1;
+        self.word_start = true;
+        out
+    }
+
+    // Checks that no block comment or string is left open at the end of the input.
+    pub fn finish(&self) -> Result<(), Error> {
+        match self.state {
+            Block(..) => Err(Error::UnterminatedComment(self.opened_at)),
+            Str(..) => Err(Error::UnterminatedString(self.opened_at)),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Length of a Rust char literal at the start of `s`, or `None` if the quote starts a lifetime or
+// a label instead.
+fn char_literal_len(s: &str) -> Option<usize> {
+    let mut chars = s.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '\\')) => {
+            // Escapes run up to the next quote: '\n', '\x7f', '\u{1F600}'
+            chars.skip(1).take(10).find(|&(_, c)| c == '\'').map(|(i, _)| i + 1)
+        }
+        Some((_, '\'')) | None => None,
+        Some(_) => match chars.next() {
+            Some((i, '\'')) => Some(i + 1),
+            _ => None,
+        },
+    }
+}
+
+// Strips all the comments of a whole buffer.
+pub fn strip(text: &str, profile: &Profile) -> Result<String, Error> {
+    let mut stripper = Stripper::new(profile);
+    let mut out = String::with_capacity(text.len());
+    stripper.feed(text, &mut out);
+    try!(stripper.finish());
+    Ok(out)
+}
+
+// Strips a file line by line, without holding it in memory.
+pub fn strip_lines<R: BufRead, W: Write>(reader: R, writer: &mut W, profile: &Profile)
+                                          -> Result<(), Error> {
+    let mut stripper = Stripper::new(profile);
+    for line in reader.lines() {
+        let line = try!(line);
+        try!(writeln!(writer, "{}", stripper.strip_line(&line)));
+    }
+    stripper.finish()
+}
+
+// The original task: strips `#` and `;` comments, ignoring markers inside double quotes, and
+// trims the remaining whitespace.
 fn strip_comments(str: &str) -> &str {
-    let markers = ['#', ';'];
-    str.find(&markers[..]).map_or(str, |i| &str[..i]).trim()
+    let mut stripper = Stripper::new(&INI);
+    let stripped = stripper.strip_line(str);
+    // Line comments only ever cut off a suffix
+    str[..stripped.len()].trim()
 }
 
-#[test]
-fn test_strip_comments() {
-    let inputs = ["apples, pears # and bananas",
-                  "apples, pears ; and bananas",
-                  "  apples, pears "];
-    let output = "apples, pears";
+#[cfg(test)]
+mod test {
+    use super::{strip, strip_comments, strip_lines, Error, Stripper};
+    use super::{C, INI, RUST, SHELL, SQL};
 
-    for &input in &inputs {
-        assert_eq!(strip_comments(input), output)
+    #[test]
+    fn test_strip_comments() {
+        let inputs = ["apples, pears # and bananas",
+                      "apples, pears ; and bananas",
+                      "  apples, pears "];
+        let output = "apples, pears";
+
+        for &input in &inputs {
+            assert_eq!(strip_comments(input), output)
+        }
+    }
+
+    #[test]
+    fn test_markers_in_strings() {
+        assert_eq!(strip_comments("name = \"a # b\" ; comment"), "name = \"a # b\"");
+        assert_eq!(strip("echo \"#1\" '#2' \\#3 # done\n", &SHELL).unwrap(),
+                   "echo \"#1\" '#2' \\#3 \n");
+        assert_eq!(strip("x = \"a\\\" // b\"; // c\n", &C).unwrap(), "x = \"a\\\" // b\"; \n");
+        assert_eq!(strip("SELECT 'it''s -- here' -- there\n", &SQL).unwrap(),
+                   "SELECT 'it''s -- here' \n");
+    }
+
+    #[test]
+    fn test_shell_words() {
+        assert_eq!(strip("echo a#b \"c\"#d\\ #e #f\n#g\n", &SHELL).unwrap(),
+                   "echo a#b \"c\"#d\\ #e \n\n");
+        let mut stripper = Stripper::new(&SHELL);
+        assert_eq!(stripper.strip_line("x=1"), "x=1");
+        assert_eq!(stripper.strip_line("# y"), "");
+        // Other languages don't care
+        assert_eq!(strip("a#b", &INI).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_block_comments() {
+        assert_eq!(strip("a/* x */b\nc /* 1\n2 */ d", &C).unwrap(), "a b\nc \n d");
+        // C block comments don't nest, the first `*/` closes the comment
+        assert_eq!(strip("a /* /* */ b */", &C).unwrap(), "a   b */");
+        assert_eq!(strip("a /* /* */ b */ c", &RUST).unwrap(), "a   c");
+        assert_eq!(strip("s = \"/* no */\";", &RUST).unwrap(), "s = \"/* no */\";");
+    }
+
+    #[test]
+    fn test_char_literals() {
+        assert_eq!(strip("let c = '\"'; // quote\n", &RUST).unwrap(), "let c = '\"'; \n");
+        assert_eq!(strip("let c = '\\''; // x\n", &RUST).unwrap(), "let c = '\\''; \n");
+        assert_eq!(strip("fn f<'a>(s: &'a str) {} // x", &RUST).unwrap(),
+                   "fn f<'a>(s: &'a str) {} ");
+        assert_eq!(strip("c = '/'; // x", &C).unwrap(), "c = '/'; ");
+    }
+
+    #[test]
+    fn test_line_by_line() {
+        let input = "a = 1 ; one\n/* not a comment */ = 2\n\"multi\nline ; string\" ; x\n";
+        let mut out = Vec::new();
+        strip_lines(input.as_bytes(), &mut out, &INI).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "a = 1 \n/* not a comment */ = 2\n\"multi\nline ; string\" \n");
+
+        let mut stripper = Stripper::new(&RUST);
+        assert_eq!(stripper.strip_line("a /* b"), "a ");
+        assert_eq!(stripper.strip_line("c */ d // e"), " d ");
+        assert_eq!(stripper.strip_line("f"), "f");
+        assert!(stripper.finish().is_ok());
+    }
+
+    #[test]
+    fn test_unterminated() {
+        match strip("a\n/* b\nc", &C) {
+            Err(Error::UnterminatedComment(2)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match strip("a = 'b", &SQL) {
+            Err(Error::UnterminatedString(1)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
     }
 }
 
@@ -26,4 +390,11 @@ fn main() {
     for &input in &inputs {
         println!("Input: {}\nStripped: {}", input, strip_comments(input))
     }
+
+    let source = "/* A /* nested */ comment */\n\
+                  fn main() {\n    \
+                      let s = \"// not a comment\"; // a comment\n    \
+                      let c = '\"';\n\
+                  }\n";
+    println!("\nInput:\n{}\nStripped:\n{}", source, strip(source, &RUST).unwrap());
 }
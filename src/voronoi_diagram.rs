@@ -0,0 +1,326 @@
+// Implements http://rosettacode.org/wiki/Voronoi_diagram
+//
+// The Voronoi diagram is built as the dual of the Delaunay triangulation, which comes from the
+// Bowyer-Watson algorithm: insert the points one at a time into a triangulation of a huge
+// "super triangle", removing every triangle whose circumcircle contains the new point and
+// connecting the point to the boundary of the hole. The Voronoi cell of a site is then the part
+// of the plane on its side of the perpendicular bisector with each of its Delaunay neighbours.
+extern crate rand;
+
+use std::collections::HashSet;
+use bitmap::{Color, Image};
+use geometry::{Point, Location, in_circle, clip_half_plane, point_in_polygon};
+#[allow(dead_code)]
+mod bitmap;
+#[allow(dead_code)]
+mod geometry;
+
+pub struct Triangulation {
+    /// The input points; duplicates are kept here but take no part in the triangulation
+    pub points: Vec<Point>,
+    /// Counterclockwise triangles, as indices into `points`
+    pub triangles: Vec<[usize; 3]>
+}
+
+impl Triangulation {
+    /// The Delaunay triangulation of the points, by Bowyer-Watson. Each insertion scans every
+    /// triangle, so this takes quadratic time; it is meant for up to a few thousand points.
+    ///
+    /// The super triangle is finite, so hull edges between points that are very nearly
+    /// collinear, relative to their spread, may be missing.
+    pub fn new(points: &[Point]) -> Triangulation {
+        let n = points.len();
+        let mut vertices = points.to_vec();
+        let mut triangles = Vec::new();
+        if n > 0 {
+            let (mut min, mut max) = (points[0], points[0]);
+            for p in points {
+                min = Point::new(min.x.min(p.x), min.y.min(p.y));
+                max = Point::new(max.x.max(p.x), max.y.max(p.y));
+            }
+            let center = (min + max) * 0.5;
+            let size = (max.x - min.x).max(max.y - min.y).max(1.0) * 1e4;
+            vertices.push(center + Point::new(-2.0 * size, -size));
+            vertices.push(center + Point::new(2.0 * size, -size));
+            vertices.push(center + Point::new(0.0, 2.0 * size));
+            triangles.push([n, n + 1, n + 2]);
+        }
+
+        for i in 0..n {
+            let p = vertices[i];
+            let (bad, good): (Vec<[usize; 3]>, Vec<[usize; 3]>) =
+                triangles.into_iter().partition(|t| {
+                    in_circle(vertices[t[0]], vertices[t[1]], vertices[t[2]], p)
+                        == Location::Inside
+                });
+            triangles = good;
+            // The boundary of the hole is made of the edges that only one bad triangle has
+            let edges: HashSet<(usize, usize)> =
+                bad.iter().flat_map(|t| vec![(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+                   .collect();
+            for &(a, b) in &edges {
+                if !edges.contains(&(b, a)) {
+                    triangles.push([a, b, i]);
+                }
+            }
+        }
+
+        triangles.retain(|t| t.iter().all(|&v| v < n));
+        Triangulation { points: points.to_vec(), triangles: triangles }
+    }
+
+    /// Each edge of the triangulation once, as a pair of point indices in increasing order.
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges: Vec<(usize, usize)> = self.triangles.iter()
+            .flat_map(|t| vec![(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+            .map(|(a, b)| if a < b { (a, b) } else { (b, a) })
+            .collect();
+        edges.sort();
+        edges.dedup();
+        edges
+    }
+
+    /// The Delaunay neighbours of every point.
+    pub fn neighbors(&self) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::new(); self.points.len()];
+        for (a, b) in self.edges() {
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+        }
+        neighbors
+    }
+
+    /// The center of the circle through the corners of a triangle: a vertex of the Voronoi
+    /// diagram.
+    pub fn circumcenter(&self, triangle: [usize; 3]) -> Point {
+        let a = self.points[triangle[0]];
+        let (b, c) = (self.points[triangle[1]] - a, self.points[triangle[2]] - a);
+        let d = 2.0 * b.cross(c);
+        a + Point::new(c.y * b.norm_sqr() - b.y * c.norm_sqr(),
+                       b.x * c.norm_sqr() - c.x * b.norm_sqr()) * (1.0 / d)
+    }
+
+    /// The Voronoi cell of every point, clipped to the box from min to max, as counterclockwise
+    /// polygons. Duplicated points get an empty cell.
+    pub fn voronoi_cells(&self, min: Point, max: Point) -> Vec<Vec<Point>> {
+        let bounds = vec![min, Point::new(max.x, min.y), max, Point::new(min.x, max.y)];
+        let neighbors = if self.triangles.is_empty() {
+            // Fewer than three distinct points, or all on a line: without triangles to tell who
+            // the neighbours are, every other point is one
+            self.points.iter().map(|&site| {
+                (0..self.points.len()).filter(|&j| self.points[j] != site).collect()
+            }).collect()
+        } else {
+            self.neighbors()
+        };
+        self.points.iter().enumerate().map(|(i, &site)| {
+            if self.points[..i].contains(&site) {
+                return Vec::new();
+            }
+            neighbors[i].iter().fold(bounds.clone(), |cell, &j| {
+                // Keep the side of the bisector towards the site
+                let v = self.points[j] - site;
+                let middle = site + v * 0.5;
+                clip_half_plane(&cell, middle, middle + Point::new(-v.y, v.x))
+            })
+        }).collect()
+    }
+}
+
+// Marks a site with a small square
+fn draw_site(image: &mut Image, site: Point, color: Color) {
+    for dy in -1..2 {
+        for dx in -1..2 {
+            image.plot(site.x.round() as i64 + dx, site.y.round() as i64 + dy, color);
+        }
+    }
+}
+
+/// Draws the triangulation of points given in pixel coordinates.
+pub fn render_triangulation(triangulation: &Triangulation, width: usize, height: usize)
+                            -> Image {
+    let mut image = Image::new(width, height);
+    image.fill(Color { red: 255, green: 255, blue: 255 });
+    for (a, b) in triangulation.edges() {
+        let (a, b) = (triangulation.points[a], triangulation.points[b]);
+        image.draw_line_antialiased((a.x, a.y), (b.x, b.y), Color { red: 0, green: 0, blue: 160 });
+    }
+    for &site in &triangulation.points {
+        draw_site(&mut image, site, Color { red: 200, green: 0, blue: 0 });
+    }
+    image
+}
+
+/// Fills the Voronoi cell of each site, given in pixel coordinates, with its color, and marks
+/// the sites in black. Pixels are colored by where their centers fall.
+pub fn render_voronoi(triangulation: &Triangulation, colors: &[Color], width: usize,
+                      height: usize) -> Image {
+    let mut image = Image::new(width, height);
+    let cells = triangulation.voronoi_cells(Point::new(0.0, 0.0),
+                                            Point::new(width as f64, height as f64));
+    for (cell, &color) in cells.iter().zip(colors.iter()) {
+        if cell.is_empty() {
+            continue;
+        }
+        let (mut min, mut max) = (cell[0], cell[0]);
+        for p in cell {
+            min = Point::new(min.x.min(p.x), min.y.min(p.y));
+            max = Point::new(max.x.max(p.x), max.y.max(p.y));
+        }
+        let (x_end, y_end) = ((max.x.ceil() as usize).min(width),
+                              (max.y.ceil() as usize).min(height));
+        for y in min.y.floor() as usize..y_end {
+            for x in min.x.floor() as usize..x_end {
+                let center = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                if point_in_polygon(center, cell) != Location::Outside {
+                    image[(x, y)] = color;
+                }
+            }
+        }
+    }
+    for &site in &triangulation.points {
+        draw_site(&mut image, site, Color { red: 0, green: 0, blue: 0 });
+    }
+    image
+}
+
+#[cfg(not(test))]
+pub fn main() {
+    use rand::Rng;
+
+    let (width, height) = (400, 300);
+    let mut rng = rand::thread_rng();
+    let sites: Vec<Point> = (0..40).map(|_| {
+        Point::new(rng.gen_range(0.0, width as f64), rng.gen_range(0.0, height as f64))
+    }).collect();
+    let colors: Vec<Color> = sites.iter().map(|_| {
+        Color { red: rng.gen(), green: rng.gen(), blue: rng.gen() }
+    }).collect();
+
+    let triangulation = Triangulation::new(&sites);
+    println!("{} sites, {} triangles, {} edges", sites.len(), triangulation.triangles.len(),
+             triangulation.edges().len());
+    render_triangulation(&triangulation, width, height).write_ppm("delaunay.ppm").unwrap();
+    render_voronoi(&triangulation, &colors, width, height).write_ppm("voronoi.ppm").unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Triangulation, render_voronoi};
+    use bitmap::Color;
+    use geometry::{Point, Location, in_circle, convex_hull, polygon_area};
+    use rand::{Rng, SeedableRng, StdRng};
+
+    fn random_points(n: usize, seed: usize) -> Vec<Point> {
+        let mut rng: StdRng = SeedableRng::from_seed(&[seed][..]);
+        (0..n).map(|_| Point::new(rng.gen_range(0.0, 100.0), rng.gen_range(0.0, 100.0)))
+              .collect()
+    }
+
+    fn triangle_area(t: &Triangulation, triangle: [usize; 3]) -> f64 {
+        let (a, b, c) = (t.points[triangle[0]], t.points[triangle[1]], t.points[triangle[2]]);
+        (b - a).cross(c - a) / 2.0
+    }
+
+    #[test]
+    fn empty_circumcircles() {
+        let points = random_points(200, 1);
+        let t = Triangulation::new(&points);
+        for &triangle in &t.triangles {
+            assert!(triangle_area(&t, triangle) > 0.0);
+            let (a, b, c) = (points[triangle[0]], points[triangle[1]], points[triangle[2]]);
+            for &p in &points {
+                assert!(in_circle(a, b, c, p) != Location::Inside);
+            }
+        }
+        // The triangles tile the convex hull
+        let hull = convex_hull(&points);
+        let area = t.triangles.iter().fold(0.0, |sum, &triangle| sum + triangle_area(&t, triangle));
+        assert!((area - polygon_area(&hull)).abs() < 1e-6);
+        assert_eq!(t.triangles.len(), 2 * points.len() - 2 - hull.len());
+    }
+
+    #[test]
+    fn cocircular_and_duplicate_points() {
+        // A grid is full of four points on a circle; any diagonal will do
+        let mut grid = Vec::new();
+        for y in 0..5 {
+            for x in 0..5 {
+                grid.push(Point::new(x as f64, y as f64));
+            }
+        }
+        grid.push(Point::new(2.0, 2.0));
+        let t = Triangulation::new(&grid);
+        assert_eq!(t.triangles.len(), 32);
+        assert!(t.triangles.iter().all(|t| !t.contains(&25)));
+        let area = t.triangles.iter().fold(0.0, |sum, &triangle| sum + triangle_area(&t, triangle));
+        assert_eq!(area, 16.0);
+
+        assert!(Triangulation::new(&[]).triangles.is_empty());
+        assert!(Triangulation::new(&grid[..2]).triangles.is_empty());
+    }
+
+    #[test]
+    fn voronoi_cells() {
+        let points = random_points(50, 2);
+        let t = Triangulation::new(&points);
+        let (min, max) = (Point::new(0.0, 0.0), Point::new(100.0, 100.0));
+        let cells = t.voronoi_cells(min, max);
+        let total = cells.iter().fold(0.0, |sum, cell| sum + polygon_area(cell));
+        assert!((total - 10000.0).abs() < 1e-6);
+        let (center, far) = (t.circumcenter(t.triangles[0]), points[t.triangles[0][0]]);
+        for &i in &t.triangles[0] {
+            assert!(((points[i] - center).norm() - (far - center).norm()).abs() < 1e-9);
+        }
+
+        // Every pixel gets the color of its nearest site
+        let colors: Vec<Color> = (0..points.len())
+            .map(|i| Color { red: i as u8, green: 1, blue: 0 }).collect();
+        let image = render_voronoi(&t, &colors, 100, 100);
+        let mut checked = 0;
+        for y in 0..100 {
+            for x in 0..100 {
+                let center = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                let mut distances: Vec<(f64, usize)> = points.iter().enumerate()
+                    .map(|(i, &p)| ((p - center).norm(), i)).collect();
+                distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                // Skip the sites' own markers and pixels too close to a cell boundary to call
+                if distances[0].0 < 3.0 || distances[1].0 - distances[0].0 < 1e-9 {
+                    continue;
+                }
+                assert_eq!(image[(x, y)], colors[distances[0].1]);
+                checked += 1;
+            }
+        }
+        assert!(checked > 8000);
+    }
+
+    #[test]
+    fn degenerate_voronoi_cells() {
+        let (min, max) = (Point::new(0.0, 0.0), Point::new(100.0, 100.0));
+        let one = Triangulation::new(&[Point::new(10.0, 10.0)]).voronoi_cells(min, max);
+        assert_eq!(polygon_area(&one[0]), 10000.0);
+
+        // Two sites split the box along their bisector
+        let two = Triangulation::new(&[Point::new(20.0, 50.0), Point::new(60.0, 50.0),
+                                       Point::new(20.0, 50.0)]);
+        let cells = two.voronoi_cells(min, max);
+        assert!((polygon_area(&cells[0]) - 4000.0).abs() < 1e-9);
+        assert!((polygon_area(&cells[1]) - 6000.0).abs() < 1e-9);
+        assert!(cells[2].is_empty());
+
+        // Collinear sites make strips, each cut by both of its neighbours
+        let line = Triangulation::new(&[Point::new(10.0, 10.0), Point::new(50.0, 50.0),
+                                        Point::new(30.0, 30.0)]);
+        assert!(line.triangles.is_empty());
+        let cells = line.voronoi_cells(min, max);
+        let total = cells.iter().fold(0.0, |sum, cell| sum + polygon_area(cell));
+        assert!((total - 10000.0).abs() < 1e-6);
+        let colors = [Color { red: 1, green: 0, blue: 0 }, Color { red: 2, green: 0, blue: 0 },
+                      Color { red: 3, green: 0, blue: 0 }];
+        let image = render_voronoi(&line, &colors, 100, 100);
+        assert_eq!(image[(0, 0)], colors[0]);
+        assert_eq!(image[(99, 99)], colors[1]);
+        assert_eq!(image[(45, 15)], colors[2]);
+    }
+}
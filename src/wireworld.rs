@@ -1,6 +1,7 @@
 // Implements http://rosettacode.org/wiki/Wireworld
 
 use std::mem;
+pub mod cellular_automaton;
 
 // Use VT100 cursor control sequences to animate in-place
 #[cfg(not(test))] const ANIMATE: bool = true;
@@ -53,6 +54,105 @@ fn next_world(input: &[Cell], output: &mut [Cell], w: usize, h: usize) {
 
 #[cfg(not(test))]
 fn main() {
+    use std::env;
+    use std::thread::sleep_ms;
+    use cellular_automaton::{Automaton, Pattern, SparseGrid, Wireworld, HEAD};
+    use cellular_automaton::bitmap::gif::GifWriter;
+
+    // wireworld [--frames N | --gif N | --steps N] [pattern]: runs a pattern in the Wireworld
+    // text format or RLE, printing it, writing the first N generations as PPM files or as an
+    // animated GIF, or running N generations and reporting on them. Only the parts with
+    // electrons in them are worked on, so circuits of millions of cells are fine.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (mut frames, mut gif_frames, mut steps, mut filename) = (None, None, None, None);
+    let mut i = 0;
+    while i < args.len() {
+        if (args[i] == "--frames" || args[i] == "--gif" || args[i] == "--steps") &&
+           i + 1 < args.len() {
+            let count = args[i + 1].parse::<usize>().ok();
+            match &args[i][..] {
+                "--frames" => frames = count,
+                "--gif" => gif_frames = count,
+                _ => steps = count,
+            }
+            i += 1;
+        } else {
+            filename = Some(args[i].clone());
+        }
+        i += 1;
+    }
+    let filename = match filename {
+        Some(filename) => filename,
+        None => return example(),
+    };
+    let pattern = match Pattern::load(&filename) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            println!("Can't read {}: {}", filename, e);
+            return;
+        }
+    };
+    let (width, height) = (pattern.grid.width, pattern.grid.height);
+    let mut wires = SparseGrid::from_grid(Wireworld, &pattern.grid);
+
+    if let Some(frames) = frames {
+        for _ in 0..frames {
+            let name = format!("wireworld{:04}.ppm", wires.generation);
+            if let Err(e) = wires.render(0, 0, width, height, 8).write_ppm(&name) {
+                println!("Can't write {}: {}", name, e);
+                return;
+            }
+            wires.step();
+        }
+        println!("Wrote {} frames", frames);
+        // Looking for a cycle keeps every generation, so only try on small circuits
+        if width * height <= 1 << 16 {
+            match Automaton::new(Wireworld, pattern.grid).find_cycle(10000) {
+                Some(cycle) => println!("It repeats every {} generations", cycle.period),
+                None => println!("It doesn't repeat within 10000 generations"),
+            }
+        }
+        return;
+    }
+    if let Some(frames) = gif_frames {
+        let name = "wireworld.gif";
+        let result = GifWriter::create(name, width * 8, height * 8, 10).and_then(|mut gif| {
+            for _ in 0..frames {
+                try!(wires.render(0, 0, width, height, 8).write_gif_frame(&mut gif));
+                wires.step();
+            }
+            gif.finish()
+        });
+        match result {
+            Ok(_) => println!("Wrote {} frames to {}", frames, name),
+            Err(e) => println!("Can't write {}: {}", name, e),
+        }
+        return;
+    }
+    if let Some(steps) = steps {
+        for _ in 0..steps {
+            wires.step();
+        }
+        let heads = wires.window(0, 0, width, height).cells.iter().filter(|&&c| c == HEAD)
+                         .count();
+        let (chunks, active) = wires.chunks();
+        println!("After {} generations: {} electrons, {} of {} chunks active", steps, heads,
+                 active, chunks);
+        return;
+    }
+    loop {
+        print!("{}", wires.window(0, 0, width, height).to_wireworld());
+        wires.step();
+        if ANIMATE {
+            print!("\x1b[{}A", height);
+            sleep_ms(100);
+        }
+    }
+}
+
+// The example from the task, animated forever
+#[cfg(not(test))]
+fn example() {
     use std::thread::sleep_ms;
     let (w, h) = (14usize, 7usize);
     let mut world: Vec<Cell> = "
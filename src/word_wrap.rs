@@ -2,8 +2,15 @@
 
 // Using the minimum length greedy algorithm
 // http://en.wikipedia.org/wiki/Word_wrap#Minimum_length
+//
+// `wrap` and `fill` additionally offer the minimum raggedness algorithm, a dynamic programming
+// simplification of Knuth and Plass' line breaking
+// http://en.wikipedia.org/wiki/Word_wrap#Minimum_raggedness
+// They measure text by its display width, keep paragraph breaks and indentation, and can break
+// words through a `Hyphenator`.
 
 // Implemented as a lazy String iterator, returning a wrapped line each time
+use std::cmp;
 use std::mem::swap;
 use std::str::SplitWhitespace;
 
@@ -14,7 +21,7 @@ pub struct WordWrap<'a> {
 }
 
 impl<'a> WordWrap<'a> {
-    fn new(text: &'a str, line_length: usize) -> WordWrap {
+    pub fn new(text: &'a str, line_length: usize) -> WordWrap {
         WordWrap {
             words : text.split_whitespace(),
             line_length : line_length,
@@ -31,12 +38,13 @@ impl<'a> Iterator for WordWrap<'a> {
         let mut this_line = String::new();
         swap(&mut self.next_line, &mut this_line);
 
-        let mut space_left = self.line_length - this_line.chars().count();
+        // A word wider than the line was carried over, it goes on a line of its own
+        let mut space_left = self.line_length.saturating_sub(display_width(&this_line));
         const SPACE_WIDTH: usize = 1;
 
         // Loop, adding words until we run out of words or hit the line length
         while let Some(word) = self.words.next() {
-            let word_length = word.chars().count();
+            let word_length = display_width(word);
 
             // If not the first word for this line
             if space_left != self.line_length {
@@ -54,14 +62,323 @@ impl<'a> Iterator for WordWrap<'a> {
 
             // Add word to this line
             this_line.push_str(word);
-            space_left -= word_length;
+            space_left -= cmp::min(space_left, word_length);
         }
 
         if this_line.is_empty() { None } else { Some(this_line) }
     }
 }
 
+// Number of terminal columns a string occupies: combining marks and zero width characters take no
+// room, East Asian wide and fullwidth characters take two.
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).fold(0, |acc, w| acc + w)
+}
+
+fn char_width(c: char) -> usize {
+    let cp = c as u32;
+    if cp == 0 || is_zero_width(cp) {
+        0
+    } else if is_wide(cp) {
+        2
+    } else {
+        1
+    }
+}
+
+fn is_zero_width(cp: u32) -> bool {
+    const RANGES: [(u32, u32); 13] = [
+        (0x00AD, 0x00AD), // soft hyphen, only visible at a line break
+        (0x0300, 0x036F), // Combining Diacritical Marks
+        (0x0483, 0x0489), // Cyrillic combining marks
+        (0x0591, 0x05BD), // Hebrew points
+        (0x0610, 0x061A), // Arabic marks
+        (0x064B, 0x065F),
+        (0x1AB0, 0x1AFF), // Combining Diacritical Marks Extended
+        (0x1DC0, 0x1DFF), // Combining Diacritical Marks Supplement
+        (0x200B, 0x200F), // zero width space, joiners and direction marks
+        (0x20D0, 0x20FF), // Combining Diacritical Marks for Symbols
+        (0xFE00, 0xFE0F), // Variation Selectors
+        (0xFE20, 0xFE2F), // Combining Half Marks
+        (0xFEFF, 0xFEFF), // zero width no-break space
+    ];
+    RANGES.iter().any(|&(lo, hi)| lo <= cp && cp <= hi)
+}
+
+fn is_wide(cp: u32) -> bool {
+    const RANGES: [(u32, u32); 14] = [
+        (0x1100, 0x115F), // Hangul Jamo
+        (0x2E80, 0x303E), // CJK Radicals .. CJK Symbols and Punctuation
+        (0x3041, 0x33FF), // Hiragana, Katakana, Bopomofo, CJK compatibility
+        (0x3400, 0x4DBF), // CJK Unified Ideographs Extension A
+        (0x4E00, 0x9FFF), // CJK Unified Ideographs
+        (0xA000, 0xA4CF), // Yi
+        (0xAC00, 0xD7A3), // Hangul Syllables
+        (0xF900, 0xFAFF), // CJK Compatibility Ideographs
+        (0xFE30, 0xFE4F), // CJK Compatibility Forms
+        (0xFF00, 0xFF60), // Fullwidth Forms
+        (0xFFE0, 0xFFE6),
+        (0x1F300, 0x1F64F), // pictographs and emoticons
+        (0x1F900, 0x1F9FF),
+        (0x20000, 0x3FFFD), // CJK Unified Ideographs Extension B and beyond
+    ];
+    RANGES.iter().any(|&(lo, hi)| lo <= cp && cp <= hi)
+}
+
+const SOFT_HYPHEN: char = '\u{AD}';
+
+// Tells where a word may be broken across lines. Split points are byte offsets into the word;
+// when a line ends at one, a '-' is appended unless the word already has one there.
+// Soft hyphens (U+00AD) in the text are always used as split points.
+pub trait Hyphenator {
+    fn split_points(&self, word: &str) -> Vec<usize>;
+}
+
+// Never breaks words
+pub struct NoHyphenation;
+
+impl Hyphenator for NoHyphenation {
+    fn split_points(&self, _: &str) -> Vec<usize> {
+        Vec::new()
+    }
+}
+
+// Breaks words after the hyphens they already contain, as in "well-|known"
+pub struct HyphenSplitter;
+
+impl Hyphenator for HyphenSplitter {
+    fn split_points(&self, word: &str) -> Vec<usize> {
+        word.char_indices()
+            .filter(|&(i, c)| c == '-' && i > 0 && i + 1 < word.len())
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
+}
+
+// Any closure returning split points can be used, e.g. a dictionary lookup
+impl<F: Fn(&str) -> Vec<usize>> Hyphenator for F {
+    fn split_points(&self, word: &str) -> Vec<usize> {
+        self(word)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Mode {
+    // Fills each line as much as possible
+    Greedy,
+    // Minimizes the sum of the squared free space at the end of each line but the last
+    MinRaggedness,
+}
+
+pub struct Options<'a> {
+    pub width: usize,
+    pub mode: Mode,
+    pub hyphenator: &'a Hyphenator,
+}
+
+impl<'a> Options<'a> {
+    pub fn new(width: usize) -> Options<'a> {
+        Options { width: width, mode: Mode::Greedy, hyphenator: &NoHyphenation }
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Options<'a> {
+        self.mode = mode;
+        self
+    }
+
+    pub fn hyphenator(mut self, hyphenator: &'a Hyphenator) -> Options<'a> {
+        self.hyphenator = hyphenator;
+        self
+    }
+}
+
+// A piece of a word, the unit the line breakers work on
+struct Fragment {
+    text: String,
+    width: usize,
+    // Whether a new word starts here, i.e. a space separates it from the previous fragment
+    starts_word: bool,
+}
+
+fn fragments(words: &[&str], hyphenator: &Hyphenator) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
+    for word in words {
+        // Soft hyphens are removed from the text and remembered as split points
+        let mut points = Vec::new();
+        let mut plain = String::with_capacity(word.len());
+        for c in word.chars() {
+            if c == SOFT_HYPHEN {
+                points.push(plain.len());
+            } else {
+                plain.push(c);
+            }
+        }
+        points.extend(hyphenator.split_points(&plain).into_iter());
+        points.push(plain.len());
+        points.sort();
+        points.dedup();
+
+        let mut start = 0;
+        for &end in points.iter().filter(|&&p| p > 0 && p <= plain.len()) {
+            let text = plain[start..end].to_string();
+            fragments.push(Fragment {
+                width: display_width(&text),
+                text: text,
+                starts_word: start == 0,
+            });
+            start = end;
+        }
+    }
+    fragments
+}
+
+// Lays out words as lines and returns the fragment index each line starts at. The first line
+// may have a different width, to make room for an indentation of its own.
+fn break_lines(fragments: &[Fragment], first_width: usize, width: usize, mode: Mode)
+               -> Vec<usize> {
+    let n = fragments.len();
+    // Prefix sums of the fragment widths and of the spaces between them
+    let mut widths = vec![0; n + 1];
+    let mut spaces = vec![0; n + 1];
+    for (i, f) in fragments.iter().enumerate() {
+        widths[i + 1] = widths[i] + f.width;
+        spaces[i + 1] = spaces[i] + if f.starts_word && i > 0 { 1 } else { 0 };
+    }
+    // Width of a line made of fragments i..j, including a hyphen if it ends inside a word
+    let line_width = |i: usize, j: usize| {
+        let hyphen = j < n && !fragments[j].starts_word && !fragments[j - 1].text.ends_with('-');
+        widths[j] - widths[i] + spaces[j] - spaces[i + 1] + if hyphen { 1 } else { 0 }
+    };
+    let available = |i: usize| if i == 0 { first_width } else { width };
+
+    let mut starts = Vec::new();
+    match mode {
+        Mode::Greedy => {
+            let mut i = 0;
+            while i < n {
+                starts.push(i);
+                let mut j = i + 1;
+                while j < n && line_width(i, j + 1) <= available(i) {
+                    j += 1;
+                }
+                i = j;
+            }
+        }
+        Mode::MinRaggedness => {
+            const INFINITY: u64 = ::std::u64::MAX;
+            const HYPHEN_PENALTY: u64 = 10;
+            // best[j] is the cost of the best layout of the first j fragments, the last line of
+            // which starts at fragment previous[j]
+            let mut best = vec![INFINITY; n + 1];
+            let mut previous = vec![0; n + 1];
+            best[0] = 0;
+            for j in 1..n + 1 {
+                for i in (0..j).rev() {
+                    let (w, width) = (line_width(i, j), available(i));
+                    // A single fragment wider than the line has to overflow
+                    if w > width && i + 1 < j {
+                        // Only the first line can be wider, so it has to be checked anyway
+                        if i > 0 && available(0) > width {
+                            continue;
+                        }
+                        break;
+                    }
+                    let mut cost = if w > width {
+                        1000 * (w - width) as u64
+                    } else if j == n {
+                        0
+                    } else {
+                        let slack = (width - w) as u64;
+                        slack * slack
+                    };
+                    if j < n && !fragments[j].starts_word {
+                        cost += HYPHEN_PENALTY;
+                    }
+                    if best[i] != INFINITY && best[i] + cost < best[j] {
+                        best[j] = best[i] + cost;
+                        previous[j] = i;
+                    }
+                }
+            }
+            let mut j = n;
+            while j > 0 {
+                starts.push(previous[j]);
+                j = previous[j];
+            }
+            starts.reverse();
+        }
+    }
+    starts
+}
+
+fn render_line(fragments: &[Fragment], i: usize, j: usize, indent: &str) -> String {
+    let mut line = indent.to_string();
+    for k in i..j {
+        if k > i && fragments[k].starts_word {
+            line.push(' ');
+        }
+        line.push_str(&fragments[k].text);
+    }
+    if j < fragments.len() && !fragments[j].starts_word && !line.ends_with('-') {
+        line.push('-');
+    }
+    line
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_left().len()]
+}
+
+// Wraps a single paragraph. The first line keeps the indentation of the first input line, the
+// following lines that of the second input line, which allows hanging indents.
+fn wrap_paragraph(lines: &[&str], options: &Options, out: &mut Vec<String>) {
+    let first_indent = leading_whitespace(lines[0]);
+    let rest_indent = if lines.len() > 1 { leading_whitespace(lines[1]) } else { first_indent };
+    let words: Vec<&str> = lines.iter().flat_map(|l| l.split_whitespace()).collect();
+    let fragments = fragments(&words, options.hyphenator);
+
+    // Lines get narrower by their indentation, but are never left without room
+    let available = |indent: &str| {
+        cmp::max(1, options.width - cmp::min(options.width, display_width(indent)))
+    };
+    let starts = break_lines(&fragments, available(first_indent), available(rest_indent),
+                             options.mode);
+    for (k, &i) in starts.iter().enumerate() {
+        let j = if k + 1 < starts.len() { starts[k + 1] } else { fragments.len() };
+        let indent = if k == 0 { first_indent } else { rest_indent };
+        out.push(render_line(&fragments, i, j, indent));
+    }
+}
+
+// Wraps text into lines of at most `options.width` columns. Paragraphs are separated by blank
+// lines, which are kept as they are.
+pub fn wrap(text: &str, options: &Options) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut paragraph = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !paragraph.is_empty() {
+                wrap_paragraph(&paragraph, options, &mut out);
+                paragraph.clear();
+            }
+            out.push(String::new());
+        } else {
+            paragraph.push(line);
+        }
+    }
+    if !paragraph.is_empty() {
+        wrap_paragraph(&paragraph, options, &mut out);
+    }
+    out
+}
+
+// Like `wrap`, but joins the lines
+pub fn fill(text: &str, options: &Options) -> String {
+    wrap(text, options).connect("\n")
+}
+
 #[cfg(not(test))]
+#[allow(dead_code)]
 fn main () {
     let text =
         "In olden times when wishing still helped one, there lived a king \
@@ -81,6 +398,19 @@ fn main () {
         }
         println!("");
     }
+
+    let options = Options::new(72).mode(Mode::MinRaggedness);
+    println!("Text wrapped at 72 with minimum raggedness\n{}\n", fill(text, &options));
+
+    // The indentation of the second line gives a hanging indent
+    let help = "Usage: word_wrap [OPTIONS]\n\n  \
+                --width <N> Wrap the text at N columns instead of\n      \
+                the default of 80. Lines are measured by their display width, so \
+                wide characters count twice.";
+    println!("{}", fill(help, &Options::new(50)));
+
+    let compounds = "A well-known, long-winded and often-quoted up-to-date example";
+    println!("\n{}", fill(compounds, &Options::new(20).hyphenator(&HyphenSplitter)));
 }
 
 #[test]
@@ -106,6 +436,15 @@ fn test_wrap_second_word() {
     assert_eq!(w.next().unwrap(), "world");
 }
 
+#[test]
+fn test_carried_overlong_word() {
+    let mut w = WordWrap::new("a abcdefg b", 4);
+    assert_eq!(w.next().unwrap(), "a");
+    assert_eq!(w.next().unwrap(), "abcdefg");
+    assert_eq!(w.next().unwrap(), "b");
+    assert_eq!(w.next(), None);
+}
+
 #[test]
 fn test_wrap_punctuation() {
     let mut w = WordWrap::new("Hello, world", 6);
@@ -131,3 +470,49 @@ fn test_unicode() {
     assert_eq!(w.next().unwrap(), "Proviseur");
     assert_eq!(w.next().unwrap(), "entra");
 }
+
+#[test]
+fn test_wide_characters() {
+    let mut w = WordWrap::new("東京 大阪 京都", 10);
+    assert_eq!(w.next().unwrap(), "東京 大阪");
+    assert_eq!(w.next().unwrap(), "京都");
+}
+
+#[test]
+fn test_min_raggedness() {
+    let text = "aaa bb cc ddddd";
+    let greedy = Options::new(6);
+    assert_eq!(wrap(text, &greedy), vec!["aaa bb", "cc", "ddddd"]);
+    let optimal = Options::new(6).mode(Mode::MinRaggedness);
+    assert_eq!(wrap(text, &optimal), vec!["aaa", "bb cc", "ddddd"]);
+}
+
+#[test]
+fn test_overlong_word() {
+    let options = Options::new(4).mode(Mode::MinRaggedness);
+    assert_eq!(wrap("a abcdefg b", &options), vec!["a", "abcdefg", "b"]);
+    assert_eq!(wrap("a abcdefg b", &Options::new(4)), vec!["a", "abcdefg", "b"]);
+}
+
+#[test]
+fn test_paragraphs_and_indentation() {
+    let text = "  first para\n  graph here\n\n- item that\n  hangs\n";
+    assert_eq!(wrap(text, &Options::new(12)),
+               vec!["  first para", "  graph here", "", "- item that", "  hangs"]);
+    assert_eq!(wrap(text, &Options::new(9)),
+               vec!["  first", "  para", "  graph", "  here", "", "- item", "  that", "  hangs"]);
+}
+
+#[test]
+fn test_hyphenation() {
+    let options = Options::new(8).hyphenator(&HyphenSplitter);
+    assert_eq!(wrap("a well-known fact", &options), vec!["a well-", "known", "fact"]);
+
+    // Soft hyphens are only shown at line breaks
+    assert_eq!(wrap("hy\u{AD}phen\u{AD}ation", &Options::new(8)), vec!["hyphen-", "ation"]);
+    assert_eq!(wrap("hy\u{AD}phen", &Options::new(8)), vec!["hyphen"]);
+
+    let syllables = |word: &str| if word == "rosetta" { vec![2, 5] } else { Vec::new() };
+    let options = Options::new(6).hyphenator(&syllables);
+    assert_eq!(wrap("the rosetta code", &options), vec!["the", "roset-", "ta", "code"]);
+}
@@ -1,34 +1,12 @@
 // Implements http://rosettacode.org/wiki/Write_ppm_file
 extern crate rand;
 
-use std::fs::File;
-use std::io::{Error, Write};
-use bitmap::Image;
+#[allow(dead_code)]
 mod bitmap;
 
-trait PPMWritable {
-    fn write_ppm(&self, filename: &str) -> Result<(), Error>;
-}
-
-impl PPMWritable for Image {
-    fn write_ppm(&self, filename: &str) -> Result<(), Error> {
-        let mut writer = try!{File::create(filename)};
-        //let mut writer = BufWriter::new(file);
-        try!(writeln!(&mut writer, "P6"));
-        try!(write!(&mut writer, "{} {} {}\n", self.width, self.height, 255));
-        for color in &(self.data) {
-            for channel in &[color.red, color.green, color.blue] {
-                let ch = *channel as u8;
-                try!(writer.write(&[ch]));
-            }
-        }
-        Ok(())
-    }
-}
-
 #[cfg(not(test))]
 pub fn main() {
-    use bitmap::Color;
+    use bitmap::{Color, Image};
 
     // write a PPM image, the left side of which is red, and the right side
     // of which is blue
@@ -40,6 +18,8 @@ pub fn main() {
         }
     }
     image.write_ppm("./test_image.ppm").unwrap();
+    // The same image in a form most viewers can open
+    image.write_png("./test_image.png").unwrap();
 }
 
 #[cfg(test)]
@@ -73,12 +53,12 @@ mod test {
         let _ = reader.read_line(&mut line);
         assert_eq!(line, "2 1 255\n");
         let mut bytes = reader.bytes();
-        assert_eq!(bytes.next().unwrap().unwrap(), 49);
-        assert_eq!(bytes.next().unwrap().unwrap(), 50);
-        assert_eq!(bytes.next().unwrap().unwrap(), 51);
-        assert_eq!(bytes.next().unwrap().unwrap(), 52);
-        assert_eq!(bytes.next().unwrap().unwrap(), 53);
-        assert_eq!(bytes.next().unwrap().unwrap(), 54);
+        assert_eq!(bytes.next().unwrap().unwrap(), 1);
+        assert_eq!(bytes.next().unwrap().unwrap(), 2);
+        assert_eq!(bytes.next().unwrap().unwrap(), 3);
+        assert_eq!(bytes.next().unwrap().unwrap(), 4);
+        assert_eq!(bytes.next().unwrap().unwrap(), 5);
+        assert_eq!(bytes.next().unwrap().unwrap(), 6);
         assert!(bytes.next().is_none());
     }
 }